@@ -0,0 +1,6 @@
+fn main() {
+    // The gRPC service of `serve --grpc`, generated with `protoc`
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/scannrs.proto")
+        .expect("proto/scannrs.proto should compile with protoc");
+}
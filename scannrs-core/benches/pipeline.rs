@@ -0,0 +1,209 @@
+//! The stages every scanned page goes through, on synthetic data so no scanner is needed
+//!
+//! Run with `cargo bench -p scannrs-core`, criterion compares against the previous run.
+
+use std::hint::black_box;
+use std::io::Cursor;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::Throughput;
+use image::DynamicImage;
+use image::Luma;
+use image::RgbImage;
+use scannrs_core::backend::FrameFormat;
+use scannrs_core::backend::FrameParameters;
+use scannrs_core::decode::decode_frame;
+use scannrs_core::output::write_document;
+use scannrs_core::output::write_lines;
+use scannrs_core::output::Format;
+use scannrs_core::output::Page;
+use scannrs_core::postprocess::Autocrop;
+use scannrs_core::postprocess::Deskew;
+use scannrs_core::postprocess::PostProcessing;
+use scannrs_core::postprocess::PostProcessor;
+use scannrs_core::postprocess::SkipBlank;
+
+/// A4 at 150 dpi
+const WIDTH: u32 = 1240;
+const HEIGHT: u32 = 1754;
+const DPI: f32 = 150.0;
+
+/// A page of text tilted by a degree and a half, lying on a gray background
+fn page() -> DynamicImage {
+    let (sin, cos) = 1.5_f32.to_radians().sin_cos();
+    let (center_x, center_y) = (WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0);
+
+    let page = image::ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| {
+        let (dx, dy) = (x as f32 - center_x, y as f32 - center_y);
+        let (x, y) = (
+            dx * cos + dy * sin + center_x,
+            dy * cos - dx * sin + center_y,
+        );
+
+        let on_paper =
+            (60.0..WIDTH as f32 - 60.0).contains(&x) && (60.0..HEIGHT as f32 - 60.0).contains(&y);
+        let in_text = (200.0..WIDTH as f32 - 200.0).contains(&x)
+            && (200.0..HEIGHT as f32 - 300.0).contains(&y);
+        // Lines of 12 pixels with 18 pixels between them, made of words and the gaps between them
+        let is_ink =
+            in_text && y as u32 % 30 < 12 && x as u32 % 97 < 80 && (x as u32 ^ y as u32) % 3 != 0;
+
+        Luma([if is_ink {
+            20
+        } else if on_paper {
+            245
+        } else {
+            120
+        }])
+    });
+
+    DynamicImage::ImageLuma8(page)
+}
+
+/// Raw frame data as a scanner sends it, with deterministic but irregular samples
+fn frame(params: &FrameParameters) -> Vec<u8> {
+    let size = params.bytes_per_line * params.lines.unwrap_or_default();
+    (0..size)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 7) as u8)
+        .collect()
+}
+
+fn decoding(c: &mut Criterion) {
+    let width = WIDTH as usize;
+    let lines = Some(HEIGHT as usize);
+    let frames = [
+        (
+            "padded-gray8",
+            FrameParameters {
+                format: FrameFormat::Gray,
+                last_frame: true,
+                bytes_per_line: width + 4,
+                pixels_per_line: width,
+                lines,
+                depth: 8,
+            },
+        ),
+        (
+            "lineart",
+            FrameParameters {
+                format: FrameFormat::Gray,
+                last_frame: true,
+                bytes_per_line: width.div_ceil(8),
+                pixels_per_line: width,
+                lines,
+                depth: 1,
+            },
+        ),
+        (
+            "rgb16",
+            FrameParameters {
+                format: FrameFormat::Rgb,
+                last_frame: true,
+                bytes_per_line: width * 6,
+                pixels_per_line: width,
+                lines,
+                depth: 16,
+            },
+        ),
+    ];
+
+    let mut group = c.benchmark_group("decode");
+    for (name, params) in frames {
+        let data = frame(&params);
+        group.throughput(Throughput::Bytes(data.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &data, |b, data| {
+            b.iter(|| decode_frame(&params, black_box(data)).expect("the frame is complete"))
+        });
+    }
+    group.finish();
+}
+
+fn post_processing(c: &mut Criterion) {
+    let page = page();
+    let steps: [(&str, &dyn PostProcessor); 3] = [
+        ("skip-blank", &SkipBlank),
+        ("deskew", &Deskew),
+        ("autocrop", &Autocrop),
+    ];
+
+    let mut group = c.benchmark_group("postprocess");
+    group.sample_size(20);
+    for (name, step) in steps {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || page.clone(),
+                |page| step.process(page),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    let all = PostProcessing {
+        skip_blank: true,
+        deskew: true,
+        autocrop: true,
+        ..PostProcessing::default()
+    };
+    group.bench_function("all", |b| {
+        b.iter_batched(
+            || page.clone(),
+            |page| all.apply(page),
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn encoding(c: &mut Criterion) {
+    let gray = page();
+    let color = DynamicImage::ImageRgb8(RgbImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        let luma = gray.as_luma8().map_or(255, |gray| gray[(x, y)].0[0]);
+        image::Rgb([
+            luma,
+            luma.saturating_add((x % 32) as u8),
+            luma.saturating_sub((y % 32) as u8),
+        ])
+    }));
+
+    let mut group = c.benchmark_group("encode");
+    group.sample_size(20);
+    for (colors, image) in [("gray", &gray), ("color", &color)] {
+        let pages = [Page {
+            image: image.clone(),
+            dpi: DPI,
+        }];
+        for format in [Format::Jpeg, Format::Png, Format::Tiff, Format::Pdf] {
+            group.bench_function(BenchmarkId::new(format!("{format:?}"), colors), |b| {
+                b.iter(|| {
+                    let mut document = Cursor::new(Vec::new());
+                    write_document(&mut document, format, black_box(&pages))
+                        .expect("the page can be encoded");
+                    document
+                })
+            });
+        }
+
+        let lines = (0..HEIGHT)
+            .map(|y| image.crop_imm(0, y, WIDTH, 1))
+            .collect::<Vec<_>>();
+        for format in [Format::Jpeg, Format::Tiff] {
+            group.bench_function(BenchmarkId::new(format!("{format:?}-lines"), colors), |b| {
+                b.iter(|| {
+                    let mut document = Cursor::new(Vec::new());
+                    let mut lines = lines.iter().cloned().map(Ok::<_, miette::Report>);
+                    write_lines(&mut document, format, DPI, Some(HEIGHT), &mut lines)
+                        .expect("the lines can be encoded");
+                    document
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decoding, post_processing, encoding);
+criterion_main!(benches);
@@ -0,0 +1,656 @@
+//! Scanning over eSCL (also known as AirScan), the driverless HTTP protocol of most network scanners made since 2014
+//!
+//! Scanners are named by the URL of their eSCL root prefixed with `escl:`, like `escl:http://192.168.1.20/eSCL`. The
+//! capabilities the scanner describes are mapped onto options named like those of SANE, which are sent along with
+//! the scan job once a scan starts. Pages arrive as image files and are handed out as a single frame each.
+
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use image::DynamicImage;
+use miette::Context;
+use miette::IntoDiagnostic;
+use roxmltree::Document;
+use roxmltree::Node;
+use url::Url;
+
+use super::network;
+use super::page::PageFrame;
+use super::page::PageMode;
+use super::Constraint;
+use super::FrameParameters;
+use super::OptionDescriptor;
+use super::OptionValue;
+use super::ScanBackend;
+use super::ScanDevice;
+use super::Unit;
+use super::ValueType;
+use crate::device::DeviceInfo;
+use crate::device::NetworkInfo;
+use crate::Error;
+
+/// The prefix of the names of eSCL scanners
+pub const PREFIX: &str = "escl:";
+
+/// Regions are given in 300ths of an inch
+const UNITS_PER_INCH: f64 = 300.0;
+
+/// How long to wait for a page that is not ready yet, and how often
+const RETRY: (Duration, usize) = (Duration::from_millis(500), 120);
+
+/// The document formats to ask for, the first one the scanner supports is used
+const FORMATS: [&str; 2] = ["image/png", "image/jpeg"];
+
+/// Talks eSCL to the scanners it is given or finds on the network
+#[derive(Clone, Debug)]
+pub struct EsclBackend {
+    agent: ureq::Agent,
+    /// The URLs of the known scanners, which [`ScanBackend::devices`] lists
+    scanners: Vec<Url>,
+}
+
+impl Default for EsclBackend {
+    fn default() -> Self {
+        EsclBackend {
+            agent: network::agent(),
+            scanners: Vec::new(),
+        }
+    }
+}
+
+impl EsclBackend {
+    /// Also list the scanner with the given eSCL root, like `http://192.168.1.20/eSCL`
+    pub fn scanner(mut self, url: Url) -> EsclBackend {
+        self.scanners.push(url);
+        self
+    }
+
+    fn capabilities(&self, root: &Url) -> miette::Result<Capabilities> {
+        let xml = self
+            .agent
+            .request_url("GET", &endpoint(root, "ScannerCapabilities")?)
+            .call()
+            .map_err(Error::from)?
+            .into_string()
+            .into_diagnostic()?;
+
+        Capabilities::parse(&xml)
+            .with_context(|| format!("The scanner at {root} sent invalid capabilities"))
+    }
+
+    fn describe(&self, root: &Url) -> Option<DeviceInfo> {
+        let capabilities = self.capabilities(root).ok()?;
+        Some(DeviceInfo {
+            name: format!("{PREFIX}{root}"),
+            vendor: capabilities.make,
+            model: capabilities.model,
+            type_: String::from("eSCL network scanner"),
+            network: Some(NetworkInfo::new("eSCL", true)),
+        })
+    }
+}
+
+impl ScanBackend for EsclBackend {
+    fn version(&self) -> String {
+        String::from("eSCL")
+    }
+
+    /// Scanners that do not answer are left out, as network scanners may be switched off
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        Ok(network::probe_all(&self.scanners, |root| {
+            self.describe(root)
+        }))
+    }
+
+    fn devices_streamed(&self, found: &mut dyn FnMut(Vec<DeviceInfo>)) -> miette::Result<()> {
+        network::probe(
+            &self.scanners,
+            |root| self.describe(root),
+            &mut |_, device| found(vec![device]),
+        );
+        Ok(())
+    }
+
+    fn as_sync(&self) -> Option<&(dyn ScanBackend + Sync)> {
+        Some(self)
+    }
+
+    fn claims(&self, name: &str) -> bool {
+        name.starts_with(PREFIX)
+    }
+
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        let not_found = || Error::CouldNotFindScanner {
+            name: name.to_string(),
+        };
+        let root = name
+            .strip_prefix(PREFIX)
+            .and_then(|url| Url::parse(url).ok())
+            .ok_or_else(not_found)?;
+
+        let capabilities = self
+            .capabilities(&root)
+            .with_context(|| format!("While trying to open a connection with scanner {name}"))?;
+        let source = capabilities
+            .sources
+            .first()
+            .ok_or(Error::Unsupported)
+            .context("The scanner offers neither a flatbed nor a document feeder")?;
+        let settings = Settings::defaults(source, 0);
+
+        Ok(Box::new(EsclDevice {
+            name: name.to_string(),
+            agent: self.agent.clone(),
+            root,
+            capabilities,
+            settings,
+            job: None,
+            frame: None,
+        }))
+    }
+}
+
+/// Where pages can be scanned from and how
+#[derive(Clone, Debug)]
+struct Source {
+    /// The value of the `source` option, named like the sources of SANE
+    name: &'static str,
+    /// The name of the source in scan settings
+    input: &'static str,
+    duplex: bool,
+    /// The largest region in 300ths of an inch
+    max_size: (u32, u32),
+    modes: Vec<PageMode>,
+    resolutions: Constraint,
+    formats: Vec<String>,
+}
+
+impl Source {
+    fn parse(name: &'static str, input: &'static str, duplex: bool, caps: Node<'_, '_>) -> Source {
+        let number = |name| text(caps, name).and_then(|text| text.parse().ok());
+        let mut modes = descendants(caps, "ColorMode")
+            .filter_map(|mode| match mode {
+                "BlackAndWhite1" => Some(PageMode::Lineart),
+                "Grayscale8" => Some(PageMode::Gray),
+                "RGB24" => Some(PageMode::Color),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        modes.sort_by_key(|mode| PageMode::ALL.iter().position(|m| m == mode));
+        modes.dedup();
+
+        let mut resolutions = descendants(caps, "XResolution")
+            .filter_map(|dpi| dpi.parse::<f64>().ok())
+            .collect::<Vec<_>>();
+        resolutions.sort_by(f64::total_cmp);
+        resolutions.dedup();
+        let range = caps
+            .descendants()
+            .find(|node| node.tag_name().name() == "XResolutionRange")
+            .and_then(|range| {
+                let number = |name| text(range, name)?.parse::<f64>().ok();
+                Some(Constraint::Range {
+                    min: number("Min")?,
+                    max: number("Max")?,
+                    step: number("Step").filter(|step| *step > 1.0),
+                })
+            });
+
+        Source {
+            name,
+            input,
+            duplex,
+            max_size: (
+                number("MaxWidth").unwrap_or(2550),
+                number("MaxHeight").unwrap_or(3300),
+            ),
+            modes,
+            resolutions: match range {
+                Some(range) if resolutions.is_empty() => range,
+                _ => Constraint::Numbers(resolutions),
+            },
+            formats: descendants(caps, "DocumentFormat")
+                .chain(descendants(caps, "DocumentFormatExt"))
+                .map(String::from)
+                .collect(),
+        }
+    }
+
+    /// The size of the largest region in millimeters
+    fn max_mm(&self) -> (f64, f64) {
+        let mm = |units: u32| f64::from(units) / UNITS_PER_INCH * 25.4;
+        (mm(self.max_size.0), mm(self.max_size.1))
+    }
+
+    fn allows_resolution(&self, dpi: i32) -> bool {
+        let dpi = f64::from(dpi);
+        match &self.resolutions {
+            Constraint::Numbers(resolutions) => resolutions.contains(&dpi),
+            Constraint::Range { min, max, .. } => (*min..=*max).contains(&dpi),
+            _ => true,
+        }
+    }
+}
+
+/// What the scanner describes in its `ScannerCapabilities`
+#[derive(Clone, Debug)]
+struct Capabilities {
+    make: String,
+    model: String,
+    sources: Vec<Source>,
+}
+
+impl Capabilities {
+    fn parse(xml: &str) -> miette::Result<Capabilities> {
+        let document = Document::parse(xml).into_diagnostic()?;
+        let root = document.root_element();
+        let make_and_model = text(root, "MakeAndModel").unwrap_or_default();
+        let (make, model) = make_and_model
+            .split_once(' ')
+            .unwrap_or((make_and_model, make_and_model));
+
+        let mut sources = Vec::new();
+        if let Some(caps) =
+            child(root, "Platen").and_then(|platen| child(platen, "PlatenInputCaps"))
+        {
+            sources.push(Source::parse("Flatbed", "Platen", false, caps));
+        }
+        if let Some(adf) = child(root, "Adf") {
+            if let Some(caps) = child(adf, "AdfSimplexInputCaps") {
+                sources.push(Source::parse("ADF", "Feeder", false, caps));
+            }
+            if let Some(caps) = child(adf, "AdfDuplexInputCaps") {
+                sources.push(Source::parse("ADF Duplex", "Feeder", true, caps));
+            }
+        }
+
+        Ok(Capabilities {
+            make: make.to_string(),
+            model: model.to_string(),
+            sources,
+        })
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+}
+
+fn text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)?.text().map(str::trim)
+}
+
+/// The texts of all elements below the node with the name
+fn descendants<'a>(node: Node<'a, 'a>, name: &'a str) -> impl Iterator<Item = &'a str> {
+    node.descendants()
+        .filter(move |node| node.tag_name().name() == name)
+        .filter_map(|node| node.text().map(str::trim))
+}
+
+/// The settings the options change, sent along with the scan job
+#[derive(Clone, Debug)]
+struct Settings {
+    /// The index into the sources of the capabilities
+    source: usize,
+    mode: PageMode,
+    resolution: i32,
+    /// Left, top, right and bottom in millimeters
+    area: [f64; 4],
+}
+
+impl Settings {
+    /// The whole area in color at 300 dpi, or whatever comes closest for the source
+    fn defaults(source: &Source, index: usize) -> Settings {
+        let (width, height) = source.max_mm();
+        let resolution = match &source.resolutions {
+            Constraint::Numbers(resolutions) => resolutions
+                .iter()
+                .min_by_key(|dpi| (**dpi - 300.0).abs() as i64)
+                .map_or(300, |dpi| *dpi as i32),
+            Constraint::Range { min, max, .. } => 300f64.clamp(*min, *max) as i32,
+            _ => 300,
+        };
+
+        Settings {
+            source: index,
+            mode: source.modes.last().copied().unwrap_or(PageMode::Color),
+            resolution,
+            area: [0.0, 0.0, width, height],
+        }
+    }
+}
+
+struct EsclDevice {
+    name: String,
+    agent: ureq::Agent,
+    root: Url,
+    capabilities: Capabilities,
+    settings: Settings,
+    /// The URL of the running scan job, a job of a feeder runs until the feeder is empty
+    job: Option<Url>,
+    frame: Option<PageFrame>,
+}
+
+impl EsclDevice {
+    fn source(&self) -> &Source {
+        &self.capabilities.sources[self.settings.source]
+    }
+
+    fn not_found(&self, option: &OptionDescriptor) -> miette::Report {
+        Error::OptionNotFound {
+            name: self.name.clone(),
+            option: option.name.clone(),
+        }
+        .into()
+    }
+
+    fn scan_settings(&self) -> String {
+        let source = self.source();
+        let settings = &self.settings;
+        let units = |mm: f64| (mm / 25.4 * UNITS_PER_INCH).round() as u32;
+        let [left, top, right, bottom] = settings.area;
+        let mode = match settings.mode {
+            PageMode::Lineart => "BlackAndWhite1",
+            PageMode::Gray => "Grayscale8",
+            PageMode::Color => "RGB24",
+        };
+        let format = FORMATS
+            .into_iter()
+            .find(|format| source.formats.iter().any(|f| f == format))
+            .unwrap_or(FORMATS[1]);
+        let duplex = if source.input == "Feeder" {
+            format!("<scan:Duplex>{}</scan:Duplex>", source.duplex)
+        } else {
+            String::new()
+        };
+
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<scan:ScanSettings xmlns:scan="http://schemas.hp.com/imaging/escl/2011/05/03" xmlns:pwg="http://www.pwg.org/schemas/2010/12/sm">
+  <pwg:Version>2.0</pwg:Version>
+  <pwg:ScanRegions>
+    <pwg:ScanRegion>
+      <pwg:ContentRegionUnits>escl:ThreeHundredthsOfInches</pwg:ContentRegionUnits>
+      <pwg:XOffset>{}</pwg:XOffset>
+      <pwg:YOffset>{}</pwg:YOffset>
+      <pwg:Width>{}</pwg:Width>
+      <pwg:Height>{}</pwg:Height>
+    </pwg:ScanRegion>
+  </pwg:ScanRegions>
+  <pwg:InputSource>{}</pwg:InputSource>
+  <scan:ColorMode>{mode}</scan:ColorMode>
+  <scan:XResolution>{}</scan:XResolution>
+  <scan:YResolution>{}</scan:YResolution>
+  <pwg:DocumentFormat>{format}</pwg:DocumentFormat>
+  {duplex}
+</scan:ScanSettings>
+"#,
+            units(left),
+            units(top),
+            units(right - left).max(1),
+            units(bottom - top).max(1),
+            source.input,
+            settings.resolution,
+            settings.resolution,
+        )
+    }
+
+    /// Create a scan job, returning its URL
+    fn create_job(&self) -> miette::Result<Url> {
+        let jobs = endpoint(&self.root, "ScanJobs")?;
+        let response = self
+            .agent
+            .request_url("POST", &jobs)
+            .set("Content-Type", "text/xml")
+            .send_string(&self.scan_settings())
+            .map_err(Error::from)
+            .with_context(|| format!("While starting a scan on {}", self.name))?;
+
+        let location = response
+            .header("Location")
+            .ok_or(Error::Unsupported)
+            .context("The scanner did not tell where the scan job is")?;
+        jobs.join(location).into_diagnostic()
+    }
+
+    /// The next page of the job, `None` once the job has no more pages
+    fn next_document(&self, job: &Url) -> miette::Result<Option<DynamicImage>> {
+        let url = endpoint(job, "NextDocument")?;
+
+        for _ in 0..RETRY.1 {
+            match self.agent.request_url("GET", &url).call() {
+                Ok(response) => {
+                    let mut data = Vec::new();
+                    response
+                        .into_reader()
+                        .read_to_end(&mut data)
+                        .into_diagnostic()?;
+                    let page = image::load_from_memory(&data)
+                        .into_diagnostic()
+                        .context("The scanner sent a page that could not be decoded")?;
+                    return Ok(Some(page));
+                }
+                Err(ureq::Error::Status(404 | 410, _)) => return Ok(None),
+                // The scanner is still warming up or moving the paper
+                Err(ureq::Error::Status(503, _)) => thread::sleep(RETRY.0),
+                Err(error) => return Err(Error::from(error).into()),
+            }
+        }
+
+        Err(Error::DeviceBusy.into())
+    }
+
+    fn finish_job(&mut self) {
+        if let Some(job) = self.job.take() {
+            // The job is gone on most scanners once its last page was read, so failing to delete it is expected
+            let _ = self.agent.request_url("DELETE", &job).call();
+        }
+    }
+}
+
+impl ScanDevice for EsclDevice {
+    fn options(&self) -> miette::Result<Vec<OptionDescriptor>> {
+        let source = self.source();
+        let (width, height) = source.max_mm();
+        let mm = |name, title, max| {
+            let constraint = Constraint::Range {
+                min: 0.0,
+                max,
+                step: None,
+            };
+            option(name, title, ValueType::Fixed, Unit::Mm, constraint)
+        };
+
+        Ok(vec![
+            group("Scan Mode"),
+            option(
+                "source",
+                "Scan source",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(
+                    self.capabilities
+                        .sources
+                        .iter()
+                        .map(|source| source.name.to_string())
+                        .collect(),
+                ),
+            ),
+            option(
+                "mode",
+                "Scan mode",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(
+                    source
+                        .modes
+                        .iter()
+                        .map(|mode| mode.name().to_string())
+                        .collect(),
+                ),
+            ),
+            option(
+                "resolution",
+                "Scan resolution",
+                ValueType::Int,
+                Unit::Dpi,
+                source.resolutions.clone(),
+            ),
+            group("Geometry"),
+            mm("tl-x", "Top-left x", width),
+            mm("tl-y", "Top-left y", height),
+            mm("br-x", "Bottom-right x", width),
+            mm("br-y", "Bottom-right y", height),
+        ])
+    }
+
+    fn get_option(&self, option: &OptionDescriptor) -> miette::Result<OptionValue> {
+        let settings = &self.settings;
+        let area = |idx: usize| OptionValue::Fixed(settings.area[idx]);
+
+        Ok(match option.name.as_str() {
+            "source" => OptionValue::String(self.source().name.to_string()),
+            "mode" => OptionValue::String(settings.mode.name().to_string()),
+            "resolution" => OptionValue::Int(settings.resolution),
+            "tl-x" => area(0),
+            "tl-y" => area(1),
+            "br-x" => area(2),
+            "br-y" => area(3),
+            _ => return Err(self.not_found(option)),
+        })
+    }
+
+    fn set_option(&mut self, option: &OptionDescriptor, value: OptionValue) -> miette::Result<()> {
+        let invalid = || invalid_value(option, &value);
+        let (width, height) = self.source().max_mm();
+
+        match (option.name.as_str(), &value) {
+            ("source", OptionValue::String(name)) => {
+                let index = self
+                    .capabilities
+                    .sources
+                    .iter()
+                    .position(|source| source.name == name)
+                    .ok_or_else(invalid)?;
+                // The other settings may not fit the new source, like SANE backends start over with its defaults
+                self.settings = Settings::defaults(&self.capabilities.sources[index], index);
+            }
+            ("mode", OptionValue::String(name)) => {
+                self.settings.mode = PageMode::from_name(name)
+                    .filter(|mode| self.source().modes.contains(mode))
+                    .ok_or_else(invalid)?;
+            }
+            ("resolution", OptionValue::Int(dpi)) if self.source().allows_resolution(*dpi) => {
+                self.settings.resolution = *dpi
+            }
+            ("tl-x" | "tl-y" | "br-x" | "br-y", OptionValue::Fixed(mm)) => {
+                let (idx, max) = match option.name.as_str() {
+                    "tl-x" => (0, width),
+                    "tl-y" => (1, height),
+                    "br-x" => (2, width),
+                    _ => (3, height),
+                };
+                if !(0.0..=max).contains(mm) {
+                    return Err(invalid());
+                }
+                self.settings.area[idx] = *mm;
+            }
+            ("source" | "mode" | "resolution" | "tl-x" | "tl-y" | "br-x" | "br-y", _) => {
+                return Err(invalid())
+            }
+            _ => return Err(self.not_found(option)),
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> miette::Result<FrameParameters> {
+        let feeder = self.source().input == "Feeder";
+        let job = match self.job.clone() {
+            Some(job) => job,
+            None => {
+                let job = self.create_job()?;
+                self.job = Some(job.clone());
+                job
+            }
+        };
+
+        let page = self.next_document(&job);
+        // A flatbed job holds a single page, the next scan needs a new job
+        if !feeder || !matches!(page, Ok(Some(_))) {
+            self.finish_job();
+        }
+        let page = page?.ok_or(Error::FeederEmpty)?;
+
+        let (frame, params) = PageFrame::new(&page, self.settings.mode);
+        self.frame = Some(frame);
+        Ok(params)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> miette::Result<Option<usize>> {
+        let frame = self.frame.as_mut().ok_or(Error::ScanCancelled)?;
+
+        let read = frame.read(buffer);
+        if read.is_none() {
+            self.frame = None;
+        }
+        Ok(read)
+    }
+
+    fn cancel(&mut self) {
+        self.frame = None;
+        self.finish_job();
+    }
+}
+
+impl Drop for EsclDevice {
+    fn drop(&mut self) {
+        self.finish_job();
+    }
+}
+
+/// The URL of a resource below the eSCL root or a job
+fn endpoint(base: &Url, name: &str) -> miette::Result<Url> {
+    let mut base = base.clone();
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+    base.join(name).into_diagnostic()
+}
+
+fn invalid_value(option: &OptionDescriptor, value: &OptionValue) -> miette::Report {
+    Error::InvalidValue {
+        option: option.name.clone(),
+        value: format!("{value:?}"),
+    }
+    .into()
+}
+
+fn option(
+    name: &str,
+    title: &str,
+    type_: ValueType,
+    unit: Unit,
+    constraint: Constraint,
+) -> OptionDescriptor {
+    OptionDescriptor {
+        name: name.to_string(),
+        title: title.to_string(),
+        description: String::new(),
+        type_,
+        unit,
+        constraint,
+        active: true,
+        settable: !matches!(type_, ValueType::Group),
+        hardware: false,
+        automatic: false,
+        advanced: false,
+        emulated: false,
+    }
+}
+
+fn group(title: &str) -> OptionDescriptor {
+    option("", title, ValueType::Group, Unit::None, Constraint::None)
+}
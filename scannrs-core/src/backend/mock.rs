@@ -0,0 +1,610 @@
+//! Scanners that do not exist, producing the same synthetic pages on every scan
+//!
+//! The frames are laid out like those of real scanners, with the same bit depths, three-pass scans, line padding and
+//! hand-scanners, so that everything after the backend can be tested without hardware or libsane. The options mimic
+//! those of the SANE `test` backend.
+
+use std::collections::VecDeque;
+
+use super::Constraint;
+use super::FrameFormat;
+use super::FrameParameters;
+use super::OptionDescriptor;
+use super::OptionValue;
+use super::ScanBackend;
+use super::ScanDevice;
+use super::Unit;
+use super::ValueType;
+use crate::device::DeviceInfo;
+use crate::Error;
+
+/// The size of the scan bed in millimeters, that of a letter-sized page
+const BED: (f64, f64) = (215.9, 279.4);
+
+/// What the mock scanner puts on its pages
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Picture {
+    /// Red rises from left to right, green from top to bottom and blue along the diagonal
+    Gradient,
+    /// Vertical bars in white, yellow, cyan, green, magenta, red, blue and black
+    ColorPattern,
+    /// A uniformly white page, like an empty sheet
+    White,
+}
+
+impl Picture {
+    const ALL: [Picture; 3] = [Picture::Gradient, Picture::ColorPattern, Picture::White];
+
+    /// The value of the `test-picture` option showing this picture
+    pub fn name(self) -> &'static str {
+        match self {
+            Picture::Gradient => "Gradient",
+            Picture::ColorPattern => "Color pattern",
+            Picture::White => "Solid white",
+        }
+    }
+
+    /// The color at the given pixel, with channels from 0 to 1
+    fn color(self, x: usize, y: usize, width: usize, height: usize) -> [f64; 3] {
+        let fraction =
+            |value: usize, size: usize| value as f64 / size.saturating_sub(1).max(1) as f64;
+
+        match self {
+            Picture::Gradient => [
+                fraction(x, width),
+                fraction(y, height),
+                fraction(x + y, width + height - 1),
+            ],
+            Picture::ColorPattern => {
+                const BARS: [[f64; 3]; 8] = [
+                    [1.0, 1.0, 1.0],
+                    [1.0, 1.0, 0.0],
+                    [0.0, 1.0, 1.0],
+                    [0.0, 1.0, 0.0],
+                    [1.0, 0.0, 1.0],
+                    [1.0, 0.0, 0.0],
+                    [0.0, 0.0, 1.0],
+                    [0.0, 0.0, 0.0],
+                ];
+                BARS[x * BARS.len() / width]
+            }
+            Picture::White => [1.0; 3],
+        }
+    }
+}
+
+/// A point at which a mock scanner fails
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Failure {
+    Open,
+    Start,
+    /// Once the given amount of bytes of a frame has been read
+    Read {
+        after: usize,
+    },
+}
+
+impl Failure {
+    fn error(self) -> miette::Report {
+        let stage = match self {
+            Failure::Open => "to open",
+            Failure::Start => "to start the scan",
+            Failure::Read { .. } => "while reading",
+        };
+        Error::MockFailure { stage }.into()
+    }
+}
+
+/// The settings of a mock scanner, which its options change
+#[derive(Clone, Debug)]
+struct Settings {
+    color: bool,
+    depth: i32,
+    resolution: i32,
+    /// Left, top, right and bottom in millimeters
+    area: [f64; 4],
+    three_pass: bool,
+    hand_scanner: bool,
+    picture: Picture,
+}
+
+/// The description of a mock scanner
+///
+/// By default it scans 8-bit color gradients of the whole bed at 75 dpi, in chunks of 4 KiB.
+#[derive(Clone, Debug)]
+pub struct MockScanner {
+    name: String,
+    settings: Settings,
+    padding: usize,
+    chunk_size: usize,
+    pages: Option<usize>,
+    failure: Option<Failure>,
+}
+
+impl MockScanner {
+    pub fn new(name: impl Into<String>) -> MockScanner {
+        MockScanner {
+            name: name.into(),
+            settings: Settings {
+                color: true,
+                depth: 8,
+                resolution: 75,
+                area: [0.0, 0.0, BED.0, BED.1],
+                three_pass: false,
+                hand_scanner: false,
+                picture: Picture::Gradient,
+            },
+            padding: 0,
+            chunk_size: 4096,
+            pages: None,
+            failure: None,
+        }
+    }
+
+    pub fn picture(mut self, picture: Picture) -> MockScanner {
+        self.settings.picture = picture;
+        self
+    }
+
+    /// Scan in gray instead of color
+    pub fn gray(mut self) -> MockScanner {
+        self.settings.color = false;
+        self
+    }
+
+    /// Bits per sample, one of 1, 8 and 16
+    pub fn depth(mut self, depth: i32) -> MockScanner {
+        self.settings.depth = depth;
+        self
+    }
+
+    pub fn resolution(mut self, dpi: i32) -> MockScanner {
+        self.settings.resolution = dpi;
+        self
+    }
+
+    /// Send a frame per color instead of a single color frame
+    pub fn three_pass(mut self) -> MockScanner {
+        self.settings.three_pass = true;
+        self
+    }
+
+    /// Do not tell the amount of lines before the scan
+    pub fn hand_scanner(mut self) -> MockScanner {
+        self.settings.hand_scanner = true;
+        self
+    }
+
+    /// Add unused bytes at the end of every line
+    pub fn padding(mut self, bytes: usize) -> MockScanner {
+        self.padding = bytes;
+        self
+    }
+
+    /// The most bytes handed out by a single read
+    pub fn chunk_size(mut self, bytes: usize) -> MockScanner {
+        self.chunk_size = bytes.max(1);
+        self
+    }
+
+    /// Act like a document feeder holding the given amount of pages, instead of a flatbed
+    pub fn pages(mut self, pages: usize) -> MockScanner {
+        self.pages = Some(pages);
+        self
+    }
+
+    pub fn fail_at(mut self, failure: Failure) -> MockScanner {
+        self.failure = Some(failure);
+        self
+    }
+}
+
+/// A backend offering the given mock scanners
+#[derive(Clone, Debug)]
+pub struct MockBackend {
+    scanners: Vec<MockScanner>,
+}
+
+impl MockBackend {
+    pub fn new(scanners: impl IntoIterator<Item = MockScanner>) -> MockBackend {
+        MockBackend {
+            scanners: scanners.into_iter().collect(),
+        }
+    }
+}
+
+impl Default for MockBackend {
+    /// A single scanner named `mock:0` with the default settings
+    fn default() -> Self {
+        MockBackend::new([MockScanner::new("mock:0")])
+    }
+}
+
+impl ScanBackend for MockBackend {
+    fn version(&self) -> String {
+        format!("mock {}", env!("CARGO_PKG_VERSION"))
+    }
+
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        Ok(self
+            .scanners
+            .iter()
+            .map(|scanner| DeviceInfo {
+                name: scanner.name.clone(),
+                vendor: String::from("scannrs"),
+                model: String::from("Mock scanner"),
+                type_: String::from("virtual device"),
+                network: None,
+            })
+            .collect())
+    }
+
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        let scanner = self
+            .scanners
+            .iter()
+            .find(|scanner| scanner.name == name)
+            .ok_or_else(|| Error::CouldNotFindScanner {
+                name: name.to_string(),
+            })?;
+
+        if scanner.failure == Some(Failure::Open) {
+            return Err(Failure::Open.error());
+        }
+
+        Ok(Box::new(MockDevice {
+            settings: scanner.settings.clone(),
+            pages_left: scanner.pages,
+            scanner: scanner.clone(),
+            frames: VecDeque::new(),
+            frame: None,
+        }))
+    }
+}
+
+/// The frame being read
+struct Frame {
+    data: Vec<u8>,
+    read: usize,
+}
+
+struct MockDevice {
+    scanner: MockScanner,
+    settings: Settings,
+    /// `None` for flatbeds, which scan a page every time
+    pages_left: Option<usize>,
+    /// The frames still to come for the current page
+    frames: VecDeque<FrameFormat>,
+    frame: Option<Frame>,
+}
+
+impl MockDevice {
+    /// The size of the scanned area in pixels
+    fn size(&self) -> (usize, usize) {
+        let [left, top, right, bottom] = self.settings.area;
+        let pixels =
+            |mm: f64| ((mm / 25.4 * f64::from(self.settings.resolution)).round() as usize).max(1);
+        (pixels(right - left), pixels(bottom - top))
+    }
+
+    fn not_found(&self, option: &OptionDescriptor) -> miette::Report {
+        Error::OptionNotFound {
+            name: self.scanner.name.clone(),
+            option: option.name.clone(),
+        }
+        .into()
+    }
+}
+
+fn invalid_value(option: &OptionDescriptor, value: &OptionValue) -> miette::Report {
+    Error::InvalidValue {
+        option: option.name.clone(),
+        value: format!("{value:?}"),
+    }
+    .into()
+}
+
+fn option(
+    name: &str,
+    title: &str,
+    type_: ValueType,
+    unit: Unit,
+    constraint: Constraint,
+) -> OptionDescriptor {
+    OptionDescriptor {
+        name: name.to_string(),
+        title: title.to_string(),
+        description: String::new(),
+        type_,
+        unit,
+        constraint,
+        active: true,
+        settable: !matches!(type_, ValueType::Group),
+        hardware: false,
+        automatic: false,
+        advanced: false,
+        emulated: false,
+    }
+}
+
+fn group(title: &str) -> OptionDescriptor {
+    option("", title, ValueType::Group, Unit::None, Constraint::None)
+}
+
+fn range(min: f64, max: f64) -> Constraint {
+    Constraint::Range {
+        min,
+        max,
+        step: None,
+    }
+}
+
+impl ScanDevice for MockDevice {
+    fn options(&self) -> miette::Result<Vec<OptionDescriptor>> {
+        let mut three_pass = option(
+            "three-pass",
+            "Three-pass simulation",
+            ValueType::Bool,
+            Unit::None,
+            Constraint::None,
+        );
+        three_pass.active = self.settings.color;
+
+        Ok(vec![
+            group("Scan Mode"),
+            option(
+                "mode",
+                "Scan mode",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(vec![String::from("Gray"), String::from("Color")]),
+            ),
+            option(
+                "depth",
+                "Bit depth",
+                ValueType::Int,
+                Unit::Bit,
+                Constraint::Numbers(vec![1.0, 8.0, 16.0]),
+            ),
+            option(
+                "resolution",
+                "Scan resolution",
+                ValueType::Int,
+                Unit::Dpi,
+                range(25.0, 1200.0),
+            ),
+            group("Geometry"),
+            option(
+                "tl-x",
+                "Top-left x",
+                ValueType::Fixed,
+                Unit::Mm,
+                range(0.0, BED.0),
+            ),
+            option(
+                "tl-y",
+                "Top-left y",
+                ValueType::Fixed,
+                Unit::Mm,
+                range(0.0, BED.1),
+            ),
+            option(
+                "br-x",
+                "Bottom-right x",
+                ValueType::Fixed,
+                Unit::Mm,
+                range(0.0, BED.0),
+            ),
+            option(
+                "br-y",
+                "Bottom-right y",
+                ValueType::Fixed,
+                Unit::Mm,
+                range(0.0, BED.1),
+            ),
+            group("Test"),
+            three_pass,
+            option(
+                "hand-scanner",
+                "Hand-scanner simulation",
+                ValueType::Bool,
+                Unit::None,
+                Constraint::None,
+            ),
+            option(
+                "test-picture",
+                "Test picture",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(Picture::ALL.map(|p| p.name().to_string()).to_vec()),
+            ),
+        ])
+    }
+
+    fn get_option(&self, option: &OptionDescriptor) -> miette::Result<OptionValue> {
+        let settings = &self.settings;
+        let area = |idx: usize| OptionValue::Fixed(settings.area[idx]);
+
+        Ok(match option.name.as_str() {
+            "mode" => {
+                OptionValue::String(String::from(if settings.color { "Color" } else { "Gray" }))
+            }
+            "depth" => OptionValue::Int(settings.depth),
+            "resolution" => OptionValue::Int(settings.resolution),
+            "tl-x" => area(0),
+            "tl-y" => area(1),
+            "br-x" => area(2),
+            "br-y" => area(3),
+            "three-pass" => OptionValue::Bool(settings.three_pass),
+            "hand-scanner" => OptionValue::Bool(settings.hand_scanner),
+            "test-picture" => OptionValue::String(settings.picture.name().to_string()),
+            _ => return Err(self.not_found(option)),
+        })
+    }
+
+    fn set_option(&mut self, option: &OptionDescriptor, value: OptionValue) -> miette::Result<()> {
+        let invalid = || invalid_value(option, &value);
+        let settings = &mut self.settings;
+
+        match (option.name.as_str(), &value) {
+            ("mode", OptionValue::String(mode)) if mode == "Color" => settings.color = true,
+            ("mode", OptionValue::String(mode)) if mode == "Gray" => settings.color = false,
+            ("depth", OptionValue::Int(depth @ (1 | 8 | 16))) => settings.depth = *depth,
+            ("resolution", OptionValue::Int(dpi @ 25..=1200)) => settings.resolution = *dpi,
+            ("tl-x" | "tl-y" | "br-x" | "br-y", OptionValue::Fixed(mm)) => {
+                let (idx, max) = match option.name.as_str() {
+                    "tl-x" => (0, BED.0),
+                    "tl-y" => (1, BED.1),
+                    "br-x" => (2, BED.0),
+                    _ => (3, BED.1),
+                };
+                if !(0.0..=max).contains(mm) {
+                    return Err(invalid());
+                }
+                settings.area[idx] = *mm;
+            }
+            ("three-pass", OptionValue::Bool(three_pass)) => settings.three_pass = *three_pass,
+            ("hand-scanner", OptionValue::Bool(hand_scanner)) => {
+                settings.hand_scanner = *hand_scanner
+            }
+            ("test-picture", OptionValue::String(name)) => {
+                settings.picture = Picture::ALL
+                    .into_iter()
+                    .find(|picture| picture.name() == name)
+                    .ok_or_else(invalid)?;
+            }
+            (
+                "mode" | "depth" | "resolution" | "tl-x" | "tl-y" | "br-x" | "br-y" | "three-pass"
+                | "hand-scanner" | "test-picture",
+                _,
+            ) => return Err(invalid()),
+            _ => return Err(self.not_found(option)),
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> miette::Result<FrameParameters> {
+        if self.scanner.failure == Some(Failure::Start) {
+            return Err(Failure::Start.error());
+        }
+
+        if self.frames.is_empty() {
+            if let Some(pages) = &mut self.pages_left {
+                *pages = pages.checked_sub(1).ok_or(Error::FeederEmpty)?;
+            }
+
+            self.frames = match (self.settings.color, self.settings.three_pass) {
+                (false, _) => [FrameFormat::Gray].into(),
+                (true, false) => [FrameFormat::Rgb].into(),
+                (true, true) => [FrameFormat::Red, FrameFormat::Green, FrameFormat::Blue].into(),
+            };
+        }
+
+        let format = self.frames.pop_front().ok_or(Error::MissingColorPlane)?;
+        let (width, height) = self.size();
+        let channels = if format == FrameFormat::Rgb { 3 } else { 1 };
+        let depth = self.settings.depth as usize;
+        let bytes_per_line = (width * channels * depth).div_ceil(8) + self.scanner.padding;
+
+        let data = render(&self.settings, format, width, height, self.scanner.padding);
+        self.frame = Some(Frame { data, read: 0 });
+
+        Ok(FrameParameters {
+            format,
+            last_frame: self.frames.is_empty(),
+            bytes_per_line,
+            pixels_per_line: width,
+            lines: (!self.settings.hand_scanner).then_some(height),
+            depth,
+        })
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> miette::Result<Option<usize>> {
+        let frame = self.frame.as_mut().ok_or(Error::ScanCancelled)?;
+
+        let mut end = frame.data.len();
+        if let Some(Failure::Read { after }) = self.scanner.failure {
+            if frame.read >= after {
+                return Err(Failure::Read { after }.error());
+            }
+            end = end.min(after);
+        }
+
+        if frame.read == frame.data.len() {
+            self.frame = None;
+            return Ok(None);
+        }
+
+        let len = (end - frame.read)
+            .min(self.scanner.chunk_size)
+            .min(buffer.len());
+        buffer[..len].copy_from_slice(&frame.data[frame.read..frame.read + len]);
+        frame.read += len;
+
+        Ok(Some(len))
+    }
+
+    fn cancel(&mut self) {
+        self.frame = None;
+        self.frames.clear();
+    }
+}
+
+/// Lay out the picture as a scanner sends it: line by line, samples packed by the depth and padded at the end
+fn render(
+    settings: &Settings,
+    format: FrameFormat,
+    width: usize,
+    height: usize,
+    padding: usize,
+) -> Vec<u8> {
+    let depth = settings.depth as usize;
+    let mut data = Vec::new();
+
+    for y in 0..height {
+        let samples = (0..width).flat_map(|x| {
+            let [red, green, blue] = settings.picture.color(x, y, width, height);
+            match format {
+                FrameFormat::Gray => vec![0.299 * red + 0.587 * green + 0.114 * blue],
+                FrameFormat::Rgb => vec![red, green, blue],
+                FrameFormat::Red => vec![red],
+                FrameFormat::Green => vec![green],
+                FrameFormat::Blue => vec![blue],
+            }
+        });
+
+        match depth {
+            1 => {
+                let mut line = Vec::new();
+                for (idx, sample) in samples.enumerate() {
+                    if idx % 8 == 0 {
+                        line.push(0);
+                    }
+                    // Set bits are black in gray frames and full intensity in color frames
+                    let set = if format == FrameFormat::Gray {
+                        sample < 0.5
+                    } else {
+                        sample >= 0.5
+                    };
+                    if set {
+                        if let Some(byte) = line.last_mut() {
+                            *byte |= 0x80 >> (idx % 8);
+                        }
+                    }
+                }
+                data.extend(line);
+            }
+            16 => {
+                data.extend(samples.flat_map(|sample| {
+                    ((sample * f64::from(u16::MAX)).round() as u16).to_ne_bytes()
+                }))
+            }
+            _ => data.extend(samples.map(|sample| (sample * f64::from(u8::MAX)).round() as u8)),
+        }
+
+        data.extend(std::iter::repeat(0).take(padding));
+    }
+
+    data
+}
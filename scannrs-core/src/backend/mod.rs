@@ -0,0 +1,306 @@
+//! The interface to scanners, implemented by every way of talking to them
+//!
+//! [`sane::SaneBackend`] drives scanners through SANE, `escl::EsclBackend` and `wsd::WsdBackend` network scanners over
+//! eSCL and WS-Scan, `wia::WiaBackend` through Windows Image Acquisition on Windows and [`mock::MockBackend`] makes
+//! up scanners for tests. Everything else in this crate only goes through [`ScanBackend`] and [`ScanDevice`], so that
+//! other backends can be added. [`Backends`] combines several backends into one.
+
+use std::sync::mpsc;
+
+use crate::device::DeviceInfo;
+use crate::Error;
+
+#[cfg(feature = "escl")]
+pub mod escl;
+pub mod mock;
+#[cfg(any(feature = "escl", feature = "wsd"))]
+mod network;
+#[cfg(any(feature = "escl", feature = "wsd", all(windows, feature = "wia")))]
+mod page;
+#[cfg(feature = "sane")]
+pub mod sane;
+#[cfg(all(windows, feature = "wia"))]
+pub mod wia;
+#[cfg(feature = "wsd")]
+pub mod wsd;
+
+/// A way of finding and opening scanners
+pub trait ScanBackend {
+    /// The name of the backend and its version, like `SANE 1.0.32`
+    fn version(&self) -> String;
+
+    /// Find the scanners that are available, which can take several seconds for network scanners
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>>;
+
+    /// Find the scanners like [`ScanBackend::devices`], handing them to `found` in groups as soon as they are known
+    ///
+    /// Backends probing several hosts hand over every scanner once it answered, instead of waiting for the slowest.
+    fn devices_streamed(&self, found: &mut dyn FnMut(Vec<DeviceInfo>)) -> miette::Result<()> {
+        found(self.devices()?);
+        Ok(())
+    }
+
+    /// This backend, if it can be used from several threads at once
+    ///
+    /// [`Backends`] probes such backends on threads of their own, next to the others.
+    fn as_sync(&self) -> Option<&(dyn ScanBackend + Sync)> {
+        None
+    }
+
+    /// The backend opening the scanner with the given name, if it can be used from several threads at once
+    ///
+    /// Such scanners can be used next to the scanners of the other backends, even if this backend as a whole cannot.
+    fn as_sync_for(&self, _name: &str) -> Option<&(dyn ScanBackend + Sync)> {
+        self.as_sync()
+    }
+
+    /// Open the scanner with the given name, as listed by [`ScanBackend::devices`]
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>>;
+
+    /// Whether the name belongs to this backend by its form alone, like the URLs of eSCL scanners
+    ///
+    /// [`Backends`] opens such scanners without asking the other backends.
+    fn claims(&self, _name: &str) -> bool {
+        false
+    }
+}
+
+/// Several backends as one, listing the scanners of all of them
+///
+/// A scanner is opened by the backend claiming its name, or else by the first backend that can open it. If none can,
+/// the error of the first backend is reported, so the backends are best given with the main one first. Scanners are
+/// listed by all backends at once where they allow it, see [`ScanBackend::as_sync`].
+pub struct Backends {
+    backends: Vec<Box<dyn ScanBackend>>,
+}
+
+impl Backends {
+    pub fn new(backends: impl IntoIterator<Item = Box<dyn ScanBackend>>) -> Backends {
+        Backends {
+            backends: backends.into_iter().collect(),
+        }
+    }
+}
+
+impl ScanBackend for Backends {
+    fn version(&self) -> String {
+        self.backends
+            .iter()
+            .map(|backend| backend.version())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        let mut devices = Vec::new();
+        self.devices_streamed(&mut |found| devices.extend(found))?;
+        Ok(devices)
+    }
+
+    /// The backends that can be used from other threads probe while the others are asked in order, their scanners
+    /// are handed over after those of the others
+    fn devices_streamed(&self, found: &mut dyn FnMut(Vec<DeviceInfo>)) -> miette::Result<()> {
+        std::thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for backend in self.backends.iter().filter_map(|backend| backend.as_sync()) {
+                let sender = sender.clone();
+                scope.spawn(move || {
+                    let res = backend.devices_streamed(&mut |devices| {
+                        let _ = sender.send(Ok(devices));
+                    });
+                    if let Err(error) = res {
+                        let _ = sender.send(Err(error));
+                    }
+                });
+            }
+            drop(sender);
+
+            for backend in &self.backends {
+                if backend.as_sync().is_none() {
+                    backend.devices_streamed(found)?;
+                }
+            }
+            for devices in receiver {
+                found(devices?);
+            }
+
+            Ok(())
+        })
+    }
+
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        if let Some(backend) = self.backends.iter().find(|backend| backend.claims(name)) {
+            return backend.open(name);
+        }
+
+        let mut error = None;
+        for backend in &self.backends {
+            match backend.open(name) {
+                Ok(device) => return Ok(device),
+                Err(e) => error = error.or(Some(e)),
+            }
+        }
+        Err(error.unwrap_or_else(|| {
+            Error::CouldNotFindScanner {
+                name: name.to_string(),
+            }
+            .into()
+        }))
+    }
+
+    fn claims(&self, name: &str) -> bool {
+        self.backends.iter().any(|backend| backend.claims(name))
+    }
+
+    /// Only scanners claimed by a backend are known to be opened by it, see [`Backends::open`]
+    fn as_sync_for(&self, name: &str) -> Option<&(dyn ScanBackend + Sync)> {
+        self.backends
+            .iter()
+            .find(|backend| backend.claims(name))
+            .and_then(|backend| backend.as_sync_for(name))
+    }
+}
+
+/// An opened scanner
+pub trait ScanDevice {
+    /// Describe the options of the scanner, in the order it lists them
+    ///
+    /// Which options are active can change whenever an option is set.
+    fn options(&self) -> miette::Result<Vec<OptionDescriptor>>;
+
+    /// Read the current value of an active option
+    fn get_option(&self, option: &OptionDescriptor) -> miette::Result<OptionValue>;
+
+    /// Set an option, setting a button to [`OptionValue::Button`] presses it
+    fn set_option(&mut self, option: &OptionDescriptor, value: OptionValue) -> miette::Result<()>;
+
+    /// Start the scan of the next frame
+    fn start(&mut self) -> miette::Result<FrameParameters>;
+
+    /// Read the next chunk of the current frame into `buffer`, `None` once the frame is complete
+    fn read(&mut self, buffer: &mut [u8]) -> miette::Result<Option<usize>>;
+
+    /// Stop the running scan
+    fn cancel(&mut self);
+}
+
+/// The kind of value an option holds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+    Bool,
+    Int,
+    /// A number with a fractional part
+    Fixed,
+    String,
+    /// An action that is triggered by setting the option, it holds no value
+    Button,
+    /// The start of a group of options, it holds no value
+    Group,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    None,
+    Pixel,
+    Bit,
+    Mm,
+    Dpi,
+    Percent,
+    Microsecond,
+}
+
+impl Unit {
+    /// How the unit is written after a value, `None` for unitless options
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Unit::None => None,
+            Unit::Pixel => Some("pixels"),
+            Unit::Bit => Some("bits"),
+            Unit::Mm => Some("mm"),
+            Unit::Dpi => Some("dpi"),
+            Unit::Percent => Some("%"),
+            Unit::Microsecond => Some("µs"),
+        }
+    }
+}
+
+/// The values an option accepts, numbers are given as they are for `Fixed` options
+#[derive(Clone, Debug, PartialEq)]
+pub enum Constraint {
+    None,
+    Range {
+        min: f64,
+        max: f64,
+        step: Option<f64>,
+    },
+    Numbers(Vec<f64>),
+    Strings(Vec<String>),
+}
+
+/// The description of an option of a scanner
+#[derive(Clone, Debug, PartialEq)]
+pub struct OptionDescriptor {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub type_: ValueType,
+    pub unit: Unit,
+    pub constraint: Constraint,
+    pub active: bool,
+    /// Can be set by software
+    pub settable: bool,
+    /// Reflects the state of the hardware, like a loaded document or a pressed button
+    pub hardware: bool,
+    /// The scanner can pick the value by itself
+    pub automatic: bool,
+    pub advanced: bool,
+    /// Provided by the backend instead of the scanner itself
+    pub emulated: bool,
+}
+
+impl OptionDescriptor {
+    /// Whether the option holds a value that can be read
+    pub fn has_value(&self) -> bool {
+        !matches!(self.type_, ValueType::Group | ValueType::Button)
+    }
+}
+
+/// The value of an option
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionValue {
+    Bool(bool),
+    Int(i32),
+    Fixed(f64),
+    String(String),
+    /// Pressing a button, buttons and groups hold no other value
+    Button,
+}
+
+/// How the data of a frame is laid out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameFormat {
+    Gray,
+    /// Interleaved red, green and blue samples
+    Rgb,
+    /// A single color of a three-pass scan
+    Red,
+    Green,
+    Blue,
+}
+
+/// The layout of the frame a scan produces
+///
+/// Whatever the backend, the data of frames is laid out as in the SANE standard.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameParameters {
+    pub format: FrameFormat,
+    /// Whether no frames follow, three-pass scans send a frame per color
+    pub last_frame: bool,
+    /// Including any padding at the end of the line
+    pub bytes_per_line: usize,
+    pub pixels_per_line: usize,
+    /// `None` for hand-scanners, which do not know the amount of lines in advance
+    pub lines: Option<usize>,
+    /// Bits per sample
+    pub depth: usize,
+}
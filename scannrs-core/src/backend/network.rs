@@ -0,0 +1,67 @@
+//! What the backends for network scanners share
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::device::DeviceInfo;
+use crate::Error;
+
+/// An HTTP client for talking to scanners, which may take a while to send a page but should connect quickly
+pub(crate) fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(5))
+        .timeout_read(Duration::from_secs(60))
+        .build()
+}
+
+/// Ask every host on a thread of its own, handing each scanner to `found` with the index of its host once it answered
+///
+/// Hosts that do not answer are left out, as network scanners may be switched off.
+pub(crate) fn probe<T: Sync>(
+    hosts: &[T],
+    describe: impl Fn(&T) -> Option<DeviceInfo> + Sync,
+    found: &mut dyn FnMut(usize, DeviceInfo),
+) {
+    let describe = &describe;
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        for (index, host) in hosts.iter().enumerate() {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                if let Some(device) = describe(host) {
+                    let _ = sender.send((index, device));
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, device) in receiver {
+            found(index, device);
+        }
+    });
+}
+
+/// Probe the hosts like [`probe`], listing the scanners in the order of their hosts
+pub(crate) fn probe_all<T: Sync>(
+    hosts: &[T],
+    describe: impl Fn(&T) -> Option<DeviceInfo> + Sync,
+) -> Vec<DeviceInfo> {
+    let mut devices = Vec::new();
+    probe(hosts, describe, &mut |index, device| {
+        devices.push((index, device))
+    });
+    devices.sort_by_key(|(index, _)| *index);
+    devices.into_iter().map(|(_, device)| device).collect()
+}
+
+impl From<ureq::Error> for Error {
+    fn from(error: ureq::Error) -> Self {
+        match &error {
+            ureq::Error::Status(401 | 403, _) => Error::AccessDenied,
+            ureq::Error::Status(409 | 503, _) => Error::DeviceBusy,
+            _ => Error::Http {
+                error: Box::new(error),
+            },
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Frames for backends whose scanners send whole pages as image files instead of raw lines
+
+use image::DynamicImage;
+use image::GrayImage;
+
+use super::FrameFormat;
+use super::FrameParameters;
+
+/// The modes of scanners sending image files, with their names as in SANE
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PageMode {
+    Lineart,
+    Gray,
+    Color,
+}
+
+impl PageMode {
+    pub(crate) const ALL: [PageMode; 3] = [PageMode::Lineart, PageMode::Gray, PageMode::Color];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            PageMode::Lineart => "Lineart",
+            PageMode::Gray => "Gray",
+            PageMode::Color => "Color",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<PageMode> {
+        PageMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+/// A page laid out as a single frame like SANE sends it, handed out in chunks
+pub(crate) struct PageFrame {
+    data: Vec<u8>,
+    read: usize,
+}
+
+impl PageFrame {
+    pub(crate) fn new(page: &DynamicImage, mode: PageMode) -> (PageFrame, FrameParameters) {
+        let (width, height) = (page.width() as usize, page.height() as usize);
+        let (format, depth, data) = match mode {
+            PageMode::Lineart => (FrameFormat::Gray, 1, lineart(&page.to_luma8())),
+            PageMode::Gray => (FrameFormat::Gray, 8, page.to_luma8().into_raw()),
+            PageMode::Color => (FrameFormat::Rgb, 8, page.to_rgb8().into_raw()),
+        };
+        let channels = if format == FrameFormat::Rgb { 3 } else { 1 };
+
+        let params = FrameParameters {
+            format,
+            last_frame: true,
+            bytes_per_line: (width * channels * depth).div_ceil(8),
+            pixels_per_line: width,
+            lines: Some(height),
+            depth,
+        };
+        (PageFrame { data, read: 0 }, params)
+    }
+
+    /// Copy the next chunk into `buffer`, `None` once the whole page was read
+    pub(crate) fn read(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        let chunk = &self.data[self.read..];
+        if chunk.is_empty() {
+            return None;
+        }
+
+        let length = chunk.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&chunk[..length]);
+        self.read += length;
+        Some(length)
+    }
+}
+
+/// Pack a gray page into lines of bits, with set bits being black as in SANE
+fn lineart(page: &GrayImage) -> Vec<u8> {
+    page.rows()
+        .flat_map(|row| {
+            let row = row.collect::<Vec<_>>();
+            row.chunks(8)
+                .map(|pixels| {
+                    pixels
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, pixel)| pixel.0[0] < 128)
+                        .fold(0u8, |byte, (idx, _)| byte | 0x80 >> idx)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
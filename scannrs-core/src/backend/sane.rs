@@ -0,0 +1,309 @@
+//! Scanning through SANE, which supports most scanners on Linux and other Unix systems
+
+use std::ffi::CString;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use sane_scan::DeviceHandle;
+use sane_scan::DeviceOption;
+use sane_scan::DeviceOptionValue;
+use sane_scan::Frame;
+use sane_scan::OptionCapability;
+use sane_scan::OptionConstraint;
+use sane_scan::OptionUnit;
+use sane_scan::Sane;
+
+use super::Constraint;
+use super::FrameFormat;
+use super::FrameParameters;
+use super::OptionDescriptor;
+use super::OptionValue;
+use super::ScanBackend;
+use super::ScanDevice;
+use super::Unit;
+use super::ValueType;
+use crate::device::DeviceInfo;
+use crate::Error;
+
+/// Convert a number to SANE's fixed point representation with 16 fractional bits
+pub fn to_fixed(value: f64) -> i32 {
+    (value * f64::from(1 << 16)).round() as i32
+}
+
+/// Convert SANE's fixed point representation with 16 fractional bits to a number
+pub fn from_fixed(value: i32) -> f64 {
+    f64::from(value) / f64::from(1 << 16)
+}
+
+/// The SANE library, initialized once per process
+///
+/// SANE is not thread-safe, so all devices have to be used from the thread the backend lives on.
+pub struct SaneBackend {
+    sane: Sane,
+}
+
+impl SaneBackend {
+    pub fn init() -> miette::Result<SaneBackend> {
+        let sane = Sane::init_1_0().map_err(Error::from)?;
+        Ok(SaneBackend { sane })
+    }
+}
+
+impl ScanBackend for SaneBackend {
+    fn version(&self) -> String {
+        let version_code = self.sane.version_code();
+        format!(
+            "SANE {}.{}.{}",
+            (version_code >> 24) & 0xff,
+            (version_code >> 16) & 0xff,
+            version_code & 0xffff
+        )
+    }
+
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        Ok(self
+            .sane
+            .get_devices()
+            .map_err(Error::from)?
+            .iter()
+            .map(|device| DeviceInfo {
+                name: device.name.to_string_lossy().to_string(),
+                vendor: device.vendor.to_string_lossy().to_string(),
+                model: device.model.to_string_lossy().to_string(),
+                type_: device.type_.to_string_lossy().to_string(),
+                network: None,
+            })
+            .collect())
+    }
+
+    /// The name is first handed to SANE directly, as enumerating all devices can take many seconds with network
+    /// backends. Only if that fails are the devices enumerated, to give a proper error when the scanner does not
+    /// exist.
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        let device = |handle| -> Box<dyn ScanDevice> {
+            Box::new(SaneDevice {
+                name: name.to_string(),
+                handle,
+            })
+        };
+
+        if let Ok(device_name) = CString::new(name) {
+            let sane_device = sane_scan::Device {
+                name: device_name,
+                vendor: CString::default(),
+                model: CString::default(),
+                type_: CString::default(),
+            };
+
+            if let Ok(handle) = sane_device.open() {
+                return Ok(device(handle));
+            }
+        }
+
+        match self
+            .sane
+            .get_devices()
+            .map_err(Error::from)?
+            .into_iter()
+            .find_map(|d| (d.name.as_bytes() == name.as_bytes()).then(|| d.open()))
+        {
+            Some(handle) => handle.map(device).map_err(Error::from).with_context(|| {
+                format!("While trying to open a connection with scanner {}", name)
+            }),
+            None => Err(Error::CouldNotFindScanner {
+                name: name.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+struct SaneDevice {
+    name: String,
+    handle: DeviceHandle,
+}
+
+impl SaneDevice {
+    /// Look up the SANE option of a descriptor by its name
+    fn sane_option(&self, option: &OptionDescriptor) -> miette::Result<DeviceOption> {
+        self.handle
+            .get_options()
+            .map_err(Error::from)?
+            .into_iter()
+            .find(|o| o.name.as_bytes() == option.name.as_bytes())
+            .ok_or_else(|| Error::OptionNotFound {
+                name: self.name.clone(),
+                option: option.name.clone(),
+            })
+            .map_err(miette::Report::from)
+    }
+}
+
+impl ScanDevice for SaneDevice {
+    fn options(&self) -> miette::Result<Vec<OptionDescriptor>> {
+        Ok(self
+            .handle
+            .get_options()
+            .map_err(Error::from)?
+            .iter()
+            .map(descriptor)
+            .collect())
+    }
+
+    fn get_option(&self, option: &OptionDescriptor) -> miette::Result<OptionValue> {
+        let sane_option = self.sane_option(option)?;
+        let value = self.handle.get_option(&sane_option).map_err(Error::from)?;
+
+        Ok(match value {
+            DeviceOptionValue::Bool(value) => OptionValue::Bool(value),
+            DeviceOptionValue::Int(value) => OptionValue::Int(value),
+            DeviceOptionValue::Fixed(value) => OptionValue::Fixed(from_fixed(value)),
+            DeviceOptionValue::String(value) => {
+                OptionValue::String(value.to_string_lossy().to_string())
+            }
+            _ => OptionValue::Button,
+        })
+    }
+
+    fn set_option(&mut self, option: &OptionDescriptor, value: OptionValue) -> miette::Result<()> {
+        let sane_option = self.sane_option(option)?;
+        let value = match value {
+            OptionValue::Bool(value) => DeviceOptionValue::Bool(value),
+            OptionValue::Int(value) => DeviceOptionValue::Int(value),
+            OptionValue::Fixed(value) => DeviceOptionValue::Fixed(to_fixed(value)),
+            OptionValue::String(value) => DeviceOptionValue::String(
+                CString::new(value).into_diagnostic().with_context(|| {
+                    format!(
+                        "The value given for '{}' contains a NUL (\\0) byte, which is invalid",
+                        option.name
+                    )
+                })?,
+            ),
+            OptionValue::Button => DeviceOptionValue::Button,
+        };
+
+        self.handle
+            .set_option(&sane_option, value)
+            .map_err(Error::from)?;
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> miette::Result<FrameParameters> {
+        let params = self.handle.start_scan().map_err(Error::from)?;
+
+        Ok(FrameParameters {
+            format: match params.format {
+                Frame::Gray => FrameFormat::Gray,
+                Frame::Rgb => FrameFormat::Rgb,
+                Frame::Red => FrameFormat::Red,
+                Frame::Green => FrameFormat::Green,
+                Frame::Blue => FrameFormat::Blue,
+            },
+            last_frame: params.last_frame,
+            bytes_per_line: params.bytes_per_line as usize,
+            pixels_per_line: params.pixels_per_line as usize,
+            lines: (params.lines > 0).then_some(params.lines as usize),
+            depth: params.depth as usize,
+        })
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> miette::Result<Option<usize>> {
+        self.handle
+            .read(buffer)
+            .map_err(Error::from)
+            .map_err(miette::Report::from)
+    }
+
+    fn cancel(&mut self) {
+        self.handle.cancel();
+    }
+}
+
+fn descriptor(option: &DeviceOption) -> OptionDescriptor {
+    let number = |value: i32| match option.type_ {
+        sane_scan::ValueType::Fixed => from_fixed(value),
+        _ => f64::from(value),
+    };
+
+    OptionDescriptor {
+        name: option.name.to_string_lossy().to_string(),
+        title: option.title.to_string_lossy().to_string(),
+        description: option.desc.to_string_lossy().to_string(),
+        type_: match option.type_ {
+            sane_scan::ValueType::Bool => ValueType::Bool,
+            sane_scan::ValueType::Int => ValueType::Int,
+            sane_scan::ValueType::Fixed => ValueType::Fixed,
+            sane_scan::ValueType::String => ValueType::String,
+            sane_scan::ValueType::Button => ValueType::Button,
+            sane_scan::ValueType::Group => ValueType::Group,
+        },
+        unit: match option.unit {
+            OptionUnit::None => Unit::None,
+            OptionUnit::Pixel => Unit::Pixel,
+            OptionUnit::Bit => Unit::Bit,
+            OptionUnit::Mm => Unit::Mm,
+            OptionUnit::Dpi => Unit::Dpi,
+            OptionUnit::Percent => Unit::Percent,
+            OptionUnit::Microsecond => Unit::Microsecond,
+        },
+        constraint: match &option.constraint {
+            OptionConstraint::None => Constraint::None,
+            OptionConstraint::Range { range, quant } => Constraint::Range {
+                min: number(range.start),
+                max: number(range.end),
+                step: (*quant != 0).then(|| number(*quant)),
+            },
+            OptionConstraint::WordList(words) => {
+                Constraint::Numbers(words.iter().map(|word| number(*word)).collect())
+            }
+            OptionConstraint::StringList(strings) => Constraint::Strings(
+                strings
+                    .iter()
+                    .map(|string| string.to_string_lossy().to_string())
+                    .collect(),
+            ),
+        },
+        active: !option.cap.contains(OptionCapability::INACTIVE),
+        settable: option.cap.contains(OptionCapability::SOFT_SELECT),
+        hardware: option.cap.contains(OptionCapability::HARD_SELECT),
+        automatic: option.cap.contains(OptionCapability::AUTOMATIC),
+        advanced: option.cap.contains(OptionCapability::ADVANCED),
+        emulated: option.cap.contains(OptionCapability::EMULATED),
+    }
+}
+
+/// The statuses SANE reports that have their own [`Error`], by words of their description
+///
+/// `sane-scan` only hands out the description of a status, so the status is recognized by it. Descriptions are
+/// compared without case and punctuation, which matches both the names of the statuses and the texts of
+/// `sane_strstatus`.
+const STATUSES: [(&str, fn() -> Error); 13] = [
+    ("busy", || Error::DeviceBusy),
+    ("denied", || Error::AccessDenied),
+    ("jammed", || Error::FeederJammed),
+    ("nodocs", || Error::FeederEmpty),
+    ("outofdocuments", || Error::FeederEmpty),
+    ("cover", || Error::CoverOpen),
+    ("ioerror", || Error::DeviceIo),
+    ("deviceio", || Error::DeviceIo),
+    ("cancelled", || Error::ScanCancelled),
+    ("nomem", || Error::OutOfMemory),
+    ("outofmemory", || Error::OutOfMemory),
+    ("unsupported", || Error::Unsupported),
+    ("notsupported", || Error::Unsupported),
+];
+
+impl From<sane_scan::Error> for Error {
+    fn from(error: sane_scan::Error) -> Self {
+        let description = error
+            .to_string()
+            .to_lowercase()
+            .replace(|c: char| !c.is_alphanumeric(), "");
+
+        STATUSES
+            .iter()
+            .find(|(words, _)| description.contains(words))
+            .map_or(Error::Sane { error }, |(_, error)| error())
+    }
+}
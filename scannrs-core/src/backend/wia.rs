@@ -0,0 +1,626 @@
+//! Scanning through Windows Image Acquisition (WIA 2.0), the scanner interface built into Windows
+//!
+//! WIA transfers whole pages as image files instead of raw lines, so a page is downloaded when its scan starts and
+//! then handed out as a single frame laid out like the frames of SANE. The options mimic the well-known options of
+//! SANE, like `mode`, `resolution` and the geometry in millimeters, and are only written to the scanner when a scan
+//! starts.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use windows::core::implement;
+use windows::core::Interface;
+use windows::core::BSTR;
+use windows::core::HRESULT;
+use windows::core::PROPVARIANT;
+use windows::Win32::Devices::ImageAcquisition::IEnumWIA_DEV_INFO;
+use windows::Win32::Devices::ImageAcquisition::IEnumWiaItem2;
+use windows::Win32::Devices::ImageAcquisition::IWiaDevMgr2;
+use windows::Win32::Devices::ImageAcquisition::IWiaItem2;
+use windows::Win32::Devices::ImageAcquisition::IWiaPropertyStorage;
+use windows::Win32::Devices::ImageAcquisition::IWiaTransfer;
+use windows::Win32::Devices::ImageAcquisition::IWiaTransferCallback;
+use windows::Win32::Devices::ImageAcquisition::IWiaTransferCallback_Impl;
+use windows::Win32::Devices::ImageAcquisition::WiaDevMgr2;
+use windows::Win32::Devices::ImageAcquisition::WiaTransferParams;
+use windows::Win32::System::Com::CoCreateInstance;
+use windows::Win32::System::Com::CoInitializeEx;
+use windows::Win32::System::Com::IStream;
+use windows::Win32::System::Com::StructuredStorage::PROPSPEC;
+use windows::Win32::System::Com::StructuredStorage::PROPSPEC_0;
+use windows::Win32::System::Com::StructuredStorage::PRSPEC_PROPID;
+use windows::Win32::System::Com::CLSCTX_LOCAL_SERVER;
+use windows::Win32::System::Com::COINIT_MULTITHREADED;
+use windows::Win32::System::Com::STREAM_SEEK_SET;
+use windows::Win32::UI::Shell::SHCreateMemStream;
+
+use super::page::PageFrame;
+use super::page::PageMode;
+use super::Constraint;
+use super::FrameParameters;
+use super::OptionDescriptor;
+use super::OptionValue;
+use super::ScanBackend;
+use super::ScanDevice;
+use super::Unit;
+use super::ValueType;
+use crate::device::DeviceInfo;
+use crate::Error;
+
+// The properties of devices and items, from `wiadef.h`
+const WIA_DIP_DEV_ID: u32 = 2;
+const WIA_DIP_VEND_DESC: u32 = 3;
+const WIA_DIP_DEV_TYPE: u32 = 5;
+const WIA_DIP_DEV_NAME: u32 = 7;
+const WIA_DPS_HORIZONTAL_BED_SIZE: u32 = 3074;
+const WIA_DPS_VERTICAL_BED_SIZE: u32 = 3075;
+const WIA_DPS_PAGES: u32 = 3096;
+const WIA_IPA_ITEM_NAME: u32 = 4098;
+const WIA_IPA_DATATYPE: u32 = 4103;
+const WIA_IPA_DEPTH: u32 = 4104;
+const WIA_IPS_XRES: u32 = 6147;
+const WIA_IPS_YRES: u32 = 6148;
+const WIA_IPS_XPOS: u32 = 6149;
+const WIA_IPS_YPOS: u32 = 6150;
+const WIA_IPS_XEXTENT: u32 = 6151;
+const WIA_IPS_YEXTENT: u32 = 6152;
+const WIA_IPS_BRIGHTNESS: u32 = 6154;
+const WIA_IPS_CONTRAST: u32 = 6155;
+
+const WIA_DATA_THRESHOLD: i32 = 0;
+const WIA_DATA_GRAYSCALE: i32 = 2;
+const WIA_DATA_COLOR: i32 = 3;
+
+/// `StiDeviceTypeScanner`, in the upper word of `WIA_DIP_DEV_TYPE`
+const STI_DEVICE_TYPE_SCANNER: i32 = 1;
+
+/// Enumerate local and network devices
+const WIA_DEVINFO_ENUM_ALL: i32 = 0;
+
+/// The resolutions offered through the `resolution` option, which nearly all WIA drivers support
+const RESOLUTIONS: [f64; 7] = [75.0, 100.0, 150.0, 200.0, 300.0, 600.0, 1200.0];
+
+/// The size of the scan bed assumed when the driver does not tell it, that of a letter-sized page
+const DEFAULT_BED: (f64, f64) = (215.9, 279.4);
+
+/// The WIA device manager, with COM initialized for the thread it is created on
+///
+/// Like SANE, devices have to be used from the thread the backend lives on.
+pub struct WiaBackend {
+    manager: IWiaDevMgr2,
+}
+
+impl WiaBackend {
+    pub fn init() -> miette::Result<WiaBackend> {
+        // Fails if COM was already initialized differently on this thread, which works as well
+        let _ = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) };
+
+        let manager = unsafe { CoCreateInstance(&WiaDevMgr2, None, CLSCTX_LOCAL_SERVER) }
+            .map_err(Error::from)
+            .context("While connecting to the Windows Image Acquisition service")?;
+        Ok(WiaBackend { manager })
+    }
+
+    /// The properties of all scanners, skipping cameras and other imaging devices
+    fn scanners(&self) -> miette::Result<Vec<IWiaPropertyStorage>> {
+        let devices: IEnumWIA_DEV_INFO =
+            unsafe { self.manager.EnumDeviceInfo(WIA_DEVINFO_ENUM_ALL) }.map_err(Error::from)?;
+
+        let mut scanners = Vec::new();
+        loop {
+            let mut device = [None];
+            let mut fetched = 0;
+            unsafe { devices.Next(1, device.as_mut_ptr(), &mut fetched) }
+                .ok()
+                .map_err(Error::from)?;
+
+            let Some(device) = device[0].take().filter(|_| fetched == 1) else {
+                break;
+            };
+            if read_int(&device, WIA_DIP_DEV_TYPE)? >> 16 == STI_DEVICE_TYPE_SCANNER {
+                scanners.push(device);
+            }
+        }
+
+        Ok(scanners)
+    }
+}
+
+impl ScanBackend for WiaBackend {
+    fn version(&self) -> String {
+        String::from("WIA 2.0")
+    }
+
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        self.scanners()?
+            .iter()
+            .map(|device| {
+                Ok(DeviceInfo {
+                    name: read_string(device, WIA_DIP_DEV_ID)?,
+                    vendor: read_string(device, WIA_DIP_VEND_DESC)?,
+                    model: read_string(device, WIA_DIP_DEV_NAME)?,
+                    type_: String::from("scanner"),
+                    network: None,
+                })
+            })
+            .collect()
+    }
+
+    /// Scanners are opened by their WIA device id, as listed by [`ScanBackend::devices`]
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        let root = unsafe { self.manager.CreateDevice(0, &BSTR::from(name)) }.map_err(|_| {
+            Error::CouldNotFindScanner {
+                name: name.to_string(),
+            }
+        })?;
+        let properties = root.cast::<IWiaPropertyStorage>().map_err(Error::from)?;
+
+        let thousandths = |property| {
+            read_int(&properties, property)
+                .ok()
+                .filter(|size| *size > 0)
+                .map(|size| f64::from(size) * 25.4 / 1000.0)
+        };
+        let bed = (
+            thousandths(WIA_DPS_HORIZONTAL_BED_SIZE).unwrap_or(DEFAULT_BED.0),
+            thousandths(WIA_DPS_VERTICAL_BED_SIZE).unwrap_or(DEFAULT_BED.1),
+        );
+
+        let sources = children(&root)?
+            .into_iter()
+            .map(|item| {
+                let properties = item.cast::<IWiaPropertyStorage>().map_err(Error::from)?;
+                Ok((read_string(&properties, WIA_IPA_ITEM_NAME)?, item))
+            })
+            .collect::<miette::Result<Vec<_>>>()
+            .with_context(|| format!("While reading the sources of scanner {name}"))?;
+        if sources.is_empty() {
+            return Err(Error::Unsupported.into());
+        }
+
+        Ok(Box::new(WiaDevice {
+            name: name.to_string(),
+            bed,
+            sources,
+            settings: Settings {
+                source: 0,
+                mode: PageMode::Color,
+                resolution: 300,
+                area: [0.0, 0.0, bed.0, bed.1],
+                brightness: 0,
+                contrast: 0,
+            },
+            pages: VecDeque::new(),
+            frame: None,
+        }))
+    }
+}
+
+/// The settings the options change, written to the item of the source when a scan starts
+#[derive(Clone, Debug)]
+struct Settings {
+    /// The index into the sources
+    source: usize,
+    mode: PageMode,
+    resolution: i32,
+    /// Left, top, right and bottom in millimeters
+    area: [f64; 4],
+    /// From -100 to 100 percent
+    brightness: i32,
+    contrast: i32,
+}
+
+struct WiaDevice {
+    name: String,
+    /// The size of the scan bed in millimeters
+    bed: (f64, f64),
+    /// The items pages can be scanned from by their name, like `Flatbed` and `Feeder`
+    sources: Vec<(String, IWiaItem2)>,
+    settings: Settings,
+    /// Pages of the last transfer that have not been scanned yet, a feeder transfers all of its pages at once
+    pages: VecDeque<image::DynamicImage>,
+    frame: Option<PageFrame>,
+}
+
+impl WiaDevice {
+    fn not_found(&self, option: &OptionDescriptor) -> miette::Report {
+        Error::OptionNotFound {
+            name: self.name.clone(),
+            option: option.name.clone(),
+        }
+        .into()
+    }
+
+    fn is_feeder(&self) -> bool {
+        self.sources[self.settings.source]
+            .0
+            .to_lowercase()
+            .contains("feeder")
+    }
+
+    /// Write the settings to the item of the source and transfer its pages
+    fn download(&mut self) -> miette::Result<()> {
+        let settings = &self.settings;
+        let item = &self.sources[settings.source].1;
+        let properties = item.cast::<IWiaPropertyStorage>().map_err(Error::from)?;
+
+        let pixels = |mm: f64| (mm / 25.4 * f64::from(settings.resolution)).round() as i32;
+        let [left, top, right, bottom] = settings.area;
+        let (data_type, depth) = match settings.mode {
+            PageMode::Lineart => (WIA_DATA_THRESHOLD, 1),
+            PageMode::Gray => (WIA_DATA_GRAYSCALE, 8),
+            PageMode::Color => (WIA_DATA_COLOR, 24),
+        };
+
+        // The extent is checked against the resolution, so the resolution has to be written first
+        write_ints(
+            &properties,
+            &[
+                (WIA_IPA_DATATYPE, data_type),
+                (WIA_IPA_DEPTH, depth),
+                (WIA_IPS_XRES, settings.resolution),
+                (WIA_IPS_YRES, settings.resolution),
+            ],
+        )?;
+        write_ints(
+            &properties,
+            &[
+                (WIA_IPS_XPOS, pixels(left)),
+                (WIA_IPS_YPOS, pixels(top)),
+                (WIA_IPS_XEXTENT, pixels(right - left).max(1)),
+                (WIA_IPS_YEXTENT, pixels(bottom - top).max(1)),
+                (WIA_IPS_BRIGHTNESS, settings.brightness * 10),
+                (WIA_IPS_CONTRAST, settings.contrast * 10),
+            ],
+        )?;
+        if self.is_feeder() {
+            // Zero scans all pages in the feeder
+            write_ints(&properties, &[(WIA_DPS_PAGES, 0)])?;
+        }
+
+        let transfer = item.cast::<IWiaTransfer>().map_err(Error::from)?;
+        let streams = Rc::new(RefCell::new(Vec::new()));
+        let callback: IWiaTransferCallback = TransferCallback {
+            streams: streams.clone(),
+        }
+        .into();
+        unsafe { transfer.Download(0, &callback) }.map_err(Error::from)?;
+
+        drop(callback);
+        for stream in streams.take() {
+            let data = read_stream(&stream)?;
+            let page = image::load_from_memory(&data)
+                .into_diagnostic()
+                .context("The scanner sent a page that could not be decoded")?;
+            self.pages.push_back(page);
+        }
+
+        if self.pages.is_empty() {
+            return Err(Error::FeederEmpty.into());
+        }
+        Ok(())
+    }
+}
+
+fn invalid_value(option: &OptionDescriptor, value: &OptionValue) -> miette::Report {
+    Error::InvalidValue {
+        option: option.name.clone(),
+        value: format!("{value:?}"),
+    }
+    .into()
+}
+
+fn option(
+    name: &str,
+    title: &str,
+    type_: ValueType,
+    unit: Unit,
+    constraint: Constraint,
+) -> OptionDescriptor {
+    OptionDescriptor {
+        name: name.to_string(),
+        title: title.to_string(),
+        description: String::new(),
+        type_,
+        unit,
+        constraint,
+        active: true,
+        settable: !matches!(type_, ValueType::Group),
+        hardware: false,
+        automatic: false,
+        advanced: false,
+        emulated: false,
+    }
+}
+
+fn group(title: &str) -> OptionDescriptor {
+    option("", title, ValueType::Group, Unit::None, Constraint::None)
+}
+
+fn range(min: f64, max: f64) -> Constraint {
+    Constraint::Range {
+        min,
+        max,
+        step: None,
+    }
+}
+
+impl ScanDevice for WiaDevice {
+    fn options(&self) -> miette::Result<Vec<OptionDescriptor>> {
+        let mm =
+            |name, title, max| option(name, title, ValueType::Fixed, Unit::Mm, range(0.0, max));
+
+        Ok(vec![
+            group("Scan Mode"),
+            option(
+                "source",
+                "Scan source",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(self.sources.iter().map(|(name, _)| name.clone()).collect()),
+            ),
+            option(
+                "mode",
+                "Scan mode",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(PageMode::ALL.map(|mode| mode.name().to_string()).to_vec()),
+            ),
+            option(
+                "resolution",
+                "Scan resolution",
+                ValueType::Int,
+                Unit::Dpi,
+                Constraint::Numbers(RESOLUTIONS.to_vec()),
+            ),
+            group("Geometry"),
+            mm("tl-x", "Top-left x", self.bed.0),
+            mm("tl-y", "Top-left y", self.bed.1),
+            mm("br-x", "Bottom-right x", self.bed.0),
+            mm("br-y", "Bottom-right y", self.bed.1),
+            group("Enhancement"),
+            option(
+                "brightness",
+                "Brightness",
+                ValueType::Int,
+                Unit::Percent,
+                range(-100.0, 100.0),
+            ),
+            option(
+                "contrast",
+                "Contrast",
+                ValueType::Int,
+                Unit::Percent,
+                range(-100.0, 100.0),
+            ),
+        ])
+    }
+
+    fn get_option(&self, option: &OptionDescriptor) -> miette::Result<OptionValue> {
+        let settings = &self.settings;
+        let area = |idx: usize| OptionValue::Fixed(settings.area[idx]);
+
+        Ok(match option.name.as_str() {
+            "source" => OptionValue::String(self.sources[settings.source].0.clone()),
+            "mode" => OptionValue::String(settings.mode.name().to_string()),
+            "resolution" => OptionValue::Int(settings.resolution),
+            "tl-x" => area(0),
+            "tl-y" => area(1),
+            "br-x" => area(2),
+            "br-y" => area(3),
+            "brightness" => OptionValue::Int(settings.brightness),
+            "contrast" => OptionValue::Int(settings.contrast),
+            _ => return Err(self.not_found(option)),
+        })
+    }
+
+    fn set_option(&mut self, option: &OptionDescriptor, value: OptionValue) -> miette::Result<()> {
+        let invalid = || invalid_value(option, &value);
+        let bed = self.bed;
+
+        match (option.name.as_str(), &value) {
+            ("source", OptionValue::String(name)) => {
+                self.settings.source = self
+                    .sources
+                    .iter()
+                    .position(|(source, _)| source == name)
+                    .ok_or_else(invalid)?;
+            }
+            ("mode", OptionValue::String(name)) => {
+                self.settings.mode = PageMode::from_name(name).ok_or_else(invalid)?;
+            }
+            ("resolution", OptionValue::Int(dpi)) if RESOLUTIONS.contains(&f64::from(*dpi)) => {
+                self.settings.resolution = *dpi
+            }
+            ("tl-x" | "tl-y" | "br-x" | "br-y", OptionValue::Fixed(mm)) => {
+                let (idx, max) = match option.name.as_str() {
+                    "tl-x" => (0, bed.0),
+                    "tl-y" => (1, bed.1),
+                    "br-x" => (2, bed.0),
+                    _ => (3, bed.1),
+                };
+                if !(0.0..=max).contains(mm) {
+                    return Err(invalid());
+                }
+                self.settings.area[idx] = *mm;
+            }
+            ("brightness", OptionValue::Int(percent @ -100..=100)) => {
+                self.settings.brightness = *percent
+            }
+            ("contrast", OptionValue::Int(percent @ -100..=100)) => {
+                self.settings.contrast = *percent
+            }
+            (
+                "source" | "mode" | "resolution" | "tl-x" | "tl-y" | "br-x" | "br-y" | "brightness"
+                | "contrast",
+                _,
+            ) => return Err(invalid()),
+            _ => return Err(self.not_found(option)),
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> miette::Result<FrameParameters> {
+        if self.pages.is_empty() {
+            self.download()?;
+        }
+        let page = self.pages.pop_front().ok_or(Error::FeederEmpty)?;
+
+        let (frame, params) = PageFrame::new(&page, self.settings.mode);
+        self.frame = Some(frame);
+        Ok(params)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> miette::Result<Option<usize>> {
+        let frame = self.frame.as_mut().ok_or(Error::ScanCancelled)?;
+
+        let read = frame.read(buffer);
+        if read.is_none() {
+            self.frame = None;
+        }
+        Ok(read)
+    }
+
+    fn cancel(&mut self) {
+        self.frame = None;
+        self.pages.clear();
+    }
+}
+
+/// Hands WIA a stream in memory for every page it transfers
+#[implement(IWiaTransferCallback)]
+struct TransferCallback {
+    streams: Rc<RefCell<Vec<IStream>>>,
+}
+
+impl IWiaTransferCallback_Impl for TransferCallback_Impl {
+    fn TransferCallback(
+        &self,
+        _flags: i32,
+        _params: *const WiaTransferParams,
+    ) -> windows::core::Result<()> {
+        Ok(())
+    }
+
+    fn GetNextStream(
+        &self,
+        _flags: i32,
+        _item_name: &BSTR,
+        _full_item_name: &BSTR,
+    ) -> windows::core::Result<IStream> {
+        let stream =
+            unsafe { SHCreateMemStream(None) }.ok_or_else(windows::core::Error::from_win32)?;
+        self.streams.borrow_mut().push(stream.clone());
+        Ok(stream)
+    }
+}
+
+fn children(item: &IWiaItem2) -> miette::Result<Vec<IWiaItem2>> {
+    let items: IEnumWiaItem2 = unsafe { item.EnumChildItems(None) }.map_err(Error::from)?;
+
+    let mut children = Vec::new();
+    loop {
+        let mut child = [None];
+        let mut fetched = 0;
+        unsafe { items.Next(1, child.as_mut_ptr(), &mut fetched) }
+            .ok()
+            .map_err(Error::from)?;
+
+        match child[0].take().filter(|_| fetched == 1) {
+            Some(child) => children.push(child),
+            None => break,
+        }
+    }
+
+    Ok(children)
+}
+
+fn read_stream(stream: &IStream) -> miette::Result<Vec<u8>> {
+    unsafe { stream.Seek(0, STREAM_SEEK_SET, None) }.map_err(Error::from)?;
+
+    let mut data = Vec::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let mut read = 0;
+        unsafe {
+            stream.Read(
+                buffer.as_mut_ptr().cast(),
+                buffer.len() as u32,
+                Some(&mut read),
+            )
+        }
+        .ok()
+        .map_err(Error::from)?;
+
+        if read == 0 {
+            return Ok(data);
+        }
+        data.extend_from_slice(&buffer[..read as usize]);
+    }
+}
+
+fn property(id: u32) -> PROPSPEC {
+    PROPSPEC {
+        ulKind: PRSPEC_PROPID,
+        Anonymous: PROPSPEC_0 { propid: id },
+    }
+}
+
+fn read(properties: &IWiaPropertyStorage, id: u32) -> miette::Result<PROPVARIANT> {
+    let mut value = [PROPVARIANT::default()];
+    unsafe { properties.ReadMultiple(1, &property(id), value.as_mut_ptr()) }
+        .map_err(Error::from)?;
+    let [value] = value;
+    Ok(value)
+}
+
+fn read_int(properties: &IWiaPropertyStorage, id: u32) -> miette::Result<i32> {
+    i32::try_from(&read(properties, id)?)
+        .map_err(Error::from)
+        .map_err(miette::Report::from)
+}
+
+fn read_string(properties: &IWiaPropertyStorage, id: u32) -> miette::Result<String> {
+    Ok(BSTR::try_from(&read(properties, id)?)
+        .map_err(Error::from)?
+        .to_string())
+}
+
+fn write_ints(properties: &IWiaPropertyStorage, values: &[(u32, i32)]) -> miette::Result<()> {
+    let (ids, values): (Vec<_>, Vec<_>) = values
+        .iter()
+        .map(|(id, value)| (property(*id), PROPVARIANT::from(*value)))
+        .unzip();
+
+    unsafe { properties.WriteMultiple(ids.len() as u32, ids.as_ptr(), values.as_ptr(), 2) }
+        .map_err(Error::from)
+        .context("The scanner did not accept the options")
+}
+
+/// The WIA errors that have their own [`Error`], from `wiadef.h`
+const STATUSES: [(u32, fn() -> Error); 9] = [
+    (0x8021_0002, || Error::FeederJammed),
+    (0x8021_0003, || Error::FeederEmpty),
+    (0x8021_0005, || Error::DeviceIo),
+    (0x8021_0006, || Error::DeviceBusy),
+    (0x8021_000A, || Error::DeviceIo),
+    (0x8021_000D, || Error::DeviceBusy),
+    (0x8021_0016, || Error::CoverOpen),
+    (0x8007_0005, || Error::AccessDenied),
+    (0x8007_000E, || Error::OutOfMemory),
+];
+
+impl From<windows::core::Error> for Error {
+    fn from(error: windows::core::Error) -> Self {
+        let HRESULT(code) = error.code();
+
+        STATUSES
+            .iter()
+            .find(|(status, _)| *status == code as u32)
+            .map_or(Error::Wia { error }, |(_, error)| error())
+    }
+}
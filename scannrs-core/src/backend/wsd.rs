@@ -0,0 +1,905 @@
+//! Scanning over WS-Scan (WSD), the SOAP protocol of Windows that many office printers speak instead of eSCL
+//!
+//! Scanners are found by a WS-Discovery probe sent to the local network, and named by the URL of their scan service
+//! prefixed with `wsd:`, like `wsd:http://192.168.1.30:8018/wsd/scan`. As with eSCL, the configuration the scanner
+//! describes is mapped onto options named like those of SANE, and pages arrive as image files.
+
+use std::net::UdpSocket;
+use std::time::Duration;
+use std::time::Instant;
+
+use image::DynamicImage;
+use miette::Context;
+use miette::IntoDiagnostic;
+use roxmltree::Document;
+use roxmltree::Node;
+use url::Url;
+
+use super::network;
+use super::page::PageFrame;
+use super::page::PageMode;
+use super::Constraint;
+use super::FrameParameters;
+use super::OptionDescriptor;
+use super::OptionValue;
+use super::ScanBackend;
+use super::ScanDevice;
+use super::Unit;
+use super::ValueType;
+use crate::device::DeviceInfo;
+use crate::device::NetworkInfo;
+use crate::Error;
+
+/// The prefix of the names of WSD scanners
+pub const PREFIX: &str = "wsd:";
+
+/// Sizes are given in thousandths of an inch
+const UNITS_PER_INCH: f64 = 1000.0;
+
+/// Where WS-Discovery probes are sent to
+const DISCOVERY_ADDRESS: &str = "239.255.255.250:3702";
+
+/// How long to wait for scanners to answer a probe
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+const SOAP: &str = "http://www.w3.org/2003/05/soap-envelope";
+const ADDRESSING: &str = "http://schemas.xmlsoap.org/ws/2004/08/addressing";
+const DISCOVERY: &str = "http://schemas.xmlsoap.org/ws/2005/04/discovery";
+const SCAN: &str = "http://schemas.microsoft.com/windows/2006/08/wdp/scan";
+
+/// The document formats to ask for, the first one the scanner supports is used
+const FORMATS: [&str; 2] = ["png", "jfif"];
+
+/// Talks WS-Scan to the scanners it is given or finds on the network
+#[derive(Clone, Debug)]
+pub struct WsdBackend {
+    agent: ureq::Agent,
+    /// The URLs of the scan services of known scanners, which [`ScanBackend::devices`] lists next to the ones found
+    scanners: Vec<Url>,
+    /// Send a WS-Discovery probe to find scanners
+    discover: bool,
+}
+
+impl Default for WsdBackend {
+    fn default() -> Self {
+        WsdBackend {
+            agent: network::agent(),
+            scanners: Vec::new(),
+            discover: true,
+        }
+    }
+}
+
+impl WsdBackend {
+    /// Also list the scanner with the given scan service, like `http://192.168.1.30:8018/wsd/scan`
+    pub fn scanner(mut self, url: Url) -> WsdBackend {
+        self.scanners.push(url);
+        self
+    }
+
+    /// Only list the scanners given to [`WsdBackend::scanner`], without probing the network
+    pub fn without_discovery(mut self) -> WsdBackend {
+        self.discover = false;
+        self
+    }
+
+    fn call(&self, url: &Url, action: &str, body: &str) -> miette::Result<String> {
+        call(&self.agent, url, action, body)?
+            .into_string()
+            .into_diagnostic()
+    }
+
+    /// Find the scan services on the local network
+    fn discover(&self) -> miette::Result<Vec<Url>> {
+        let socket = UdpSocket::bind("0.0.0.0:0").into_diagnostic()?;
+        socket.set_multicast_ttl_v4(1).into_diagnostic()?;
+        let probe = envelope(
+            "urn:schemas-xmlsoap-org:ws:2005:04:discovery",
+            &format!("{DISCOVERY}/Probe"),
+            &format!(
+                r#"<wsd:Probe xmlns:wsd="{DISCOVERY}"><wsd:Types xmlns:wscn="{SCAN}">wscn:ScanDeviceType</wsd:Types></wsd:Probe>"#
+            ),
+        );
+        socket
+            .send_to(probe.as_bytes(), DISCOVERY_ADDRESS)
+            .into_diagnostic()
+            .context("While probing the network for WSD scanners")?;
+
+        let mut devices = Vec::new();
+        let deadline = Instant::now() + DISCOVERY_TIMEOUT;
+        let mut buffer = vec![0; 64 * 1024];
+        while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+            socket
+                .set_read_timeout(Some(left.max(Duration::from_millis(1))))
+                .into_diagnostic()?;
+            let Ok((length, _)) = socket.recv_from(&mut buffer) else {
+                break;
+            };
+            let Ok(xml) = std::str::from_utf8(&buffer[..length]) else {
+                continue;
+            };
+            if let Ok(document) = Document::parse(xml) {
+                devices.extend(probe_matches(&document));
+            }
+        }
+        devices.sort_by(|a, b| a.0.cmp(&b.0));
+        devices.dedup_by(|a, b| a.0 == b.0);
+
+        // Devices that do not answer the request for their metadata are left out
+        Ok(devices
+            .into_iter()
+            .filter_map(|(address, urls)| {
+                urls.iter()
+                    .find_map(|url| self.scan_service(&address, url).ok())
+            })
+            .collect())
+    }
+
+    /// Ask a device for its metadata to find its scan service
+    fn scan_service(&self, address: &str, device: &Url) -> miette::Result<Url> {
+        let xml = send(
+            &self.agent,
+            device,
+            address,
+            "http://schemas.xmlsoap.org/ws/2004/09/transfer/Get",
+            "",
+        )
+        .map(|response| response.into_string())
+        .and_then(|xml| xml.into_diagnostic())
+        .with_context(|| format!("While asking {address} for its metadata"))?;
+        let document = Document::parse(&xml).into_diagnostic()?;
+
+        document
+            .descendants()
+            .filter(|node| node.tag_name().name() == "Hosted")
+            .find(|hosted| {
+                texts(*hosted, "Types").any(|types| types.contains("ScannerServiceType"))
+            })
+            .and_then(|hosted| texts(hosted, "Address").next())
+            .and_then(|address| Url::parse(address).ok())
+            .ok_or(Error::Unsupported)
+            .context("The device does not offer a scan service")
+    }
+
+    /// The scan services of the known scanners and of those answering the probe
+    fn services(&self) -> miette::Result<Vec<Url>> {
+        let mut services = self.scanners.clone();
+        if self.discover {
+            services.extend(self.discover()?);
+        }
+        Ok(services)
+    }
+
+    fn describe(&self, service: &Url) -> Option<DeviceInfo> {
+        let xml = self.scanner_elements(service, "ScannerDescription").ok()?;
+        let document = Document::parse(&xml).ok()?;
+        let root = document.root_element();
+        let text = |name| texts(root, name).next().unwrap_or_default().to_string();
+        Some(DeviceInfo {
+            name: format!("{PREFIX}{service}"),
+            vendor: text("Manufacturer"),
+            model: text("Model"),
+            type_: String::from("WSD network scanner"),
+            network: Some(NetworkInfo::new("WS-Scan", true)),
+        })
+    }
+
+    fn scanner_elements(&self, service: &Url, element: &str) -> miette::Result<String> {
+        self.call(
+            service,
+            &format!("{SCAN}/GetScannerElements"),
+            &format!(
+                r#"<wscn:GetScannerElementsRequest xmlns:wscn="{SCAN}"><wscn:RequestedElements><wscn:Name>wscn:{element}</wscn:Name></wscn:RequestedElements></wscn:GetScannerElementsRequest>"#
+            ),
+        )
+    }
+}
+
+impl ScanBackend for WsdBackend {
+    fn version(&self) -> String {
+        String::from("WS-Scan")
+    }
+
+    /// Probing the network takes a few seconds, scanners that do not answer are left out
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        Ok(network::probe_all(&self.services()?, |service| {
+            self.describe(service)
+        }))
+    }
+
+    fn devices_streamed(&self, found: &mut dyn FnMut(Vec<DeviceInfo>)) -> miette::Result<()> {
+        network::probe(
+            &self.services()?,
+            |service| self.describe(service),
+            &mut |_, device| found(vec![device]),
+        );
+        Ok(())
+    }
+
+    fn as_sync(&self) -> Option<&(dyn ScanBackend + Sync)> {
+        Some(self)
+    }
+
+    fn claims(&self, name: &str) -> bool {
+        name.starts_with(PREFIX)
+    }
+
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        let service = name
+            .strip_prefix(PREFIX)
+            .and_then(|url| Url::parse(url).ok())
+            .ok_or_else(|| Error::CouldNotFindScanner {
+                name: name.to_string(),
+            })?;
+
+        let configuration = self
+            .scanner_elements(&service, "ScannerConfiguration")
+            .and_then(|xml| Configuration::parse(&xml))
+            .with_context(|| format!("While trying to open a connection with scanner {name}"))?;
+        let source = configuration
+            .sources
+            .first()
+            .ok_or(Error::Unsupported)
+            .context("The scanner offers neither a flatbed nor a document feeder")?;
+        let settings = Settings::defaults(source, 0);
+
+        Ok(Box::new(WsdDevice {
+            name: name.to_string(),
+            agent: self.agent.clone(),
+            service,
+            configuration,
+            settings,
+            job: None,
+            frame: None,
+        }))
+    }
+}
+
+/// The endpoint addresses and metadata URLs of the devices answering a probe
+fn probe_matches(document: &Document<'_>) -> Vec<(String, Vec<Url>)> {
+    document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "ProbeMatch")
+        .filter_map(|probe_match| {
+            let address = texts(probe_match, "Address").next()?.to_string();
+            let urls = texts(probe_match, "XAddrs")
+                .flat_map(str::split_whitespace)
+                .filter_map(|url| Url::parse(url).ok())
+                .collect();
+            Some((address, urls))
+        })
+        .collect()
+}
+
+/// Where pages can be scanned from and how
+#[derive(Clone, Debug)]
+struct Source {
+    /// The value of the `source` option, named like the sources of SANE
+    name: &'static str,
+    /// The name of the source in scan tickets
+    input: &'static str,
+    duplex: bool,
+    /// The largest region in thousandths of an inch
+    max_size: (u32, u32),
+    modes: Vec<PageMode>,
+    resolutions: Vec<f64>,
+}
+
+impl Source {
+    /// Read the capabilities of a source, whose elements are named after it like `PlatenColor` or `ADFColor`
+    fn parse(name: &'static str, input: &'static str, duplex: bool, caps: Node<'_, '_>) -> Source {
+        let element = |suffix: &str| {
+            caps.descendants()
+                .find(|node| node.tag_name().name().ends_with(suffix))
+        };
+        let size =
+            |name| element("MaximumSize").and_then(|size| texts(size, name).next()?.parse().ok());
+
+        let mut modes = element("Color")
+            .map(|colors| {
+                texts(colors, "ColorEntry")
+                    .filter_map(|mode| match mode {
+                        "BlackAndWhite1" => Some(PageMode::Lineart),
+                        "Grayscale8" => Some(PageMode::Gray),
+                        "RGB24" => Some(PageMode::Color),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        modes.sort_by_key(|mode| PageMode::ALL.iter().position(|m| m == mode));
+        modes.dedup();
+
+        let mut resolutions = element("Resolutions")
+            .and_then(|resolutions| {
+                resolutions
+                    .descendants()
+                    .find(|node| node.tag_name().name() == "Widths")
+            })
+            .map(|widths| {
+                texts(widths, "Width")
+                    .filter_map(|dpi| dpi.parse::<f64>().ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        resolutions.sort_by(f64::total_cmp);
+        resolutions.dedup();
+
+        Source {
+            name,
+            input,
+            duplex,
+            max_size: (
+                size("Width").unwrap_or(8500),
+                size("Height").unwrap_or(11000),
+            ),
+            modes,
+            resolutions,
+        }
+    }
+
+    /// The size of the largest region in millimeters
+    fn max_mm(&self) -> (f64, f64) {
+        let mm = |units: u32| f64::from(units) / UNITS_PER_INCH * 25.4;
+        (mm(self.max_size.0), mm(self.max_size.1))
+    }
+}
+
+/// What the scanner describes in its `ScannerConfiguration`
+#[derive(Clone, Debug)]
+struct Configuration {
+    sources: Vec<Source>,
+    formats: Vec<String>,
+}
+
+impl Configuration {
+    fn parse(xml: &str) -> miette::Result<Configuration> {
+        let document = Document::parse(xml).into_diagnostic()?;
+        let element = |name: &str| {
+            document
+                .descendants()
+                .find(|node| node.tag_name().name() == name)
+        };
+
+        let mut sources = Vec::new();
+        if let Some(platen) = element("Platen") {
+            sources.push(Source::parse("Flatbed", "Platen", false, platen));
+        }
+        if let Some(front) = element("ADFFront") {
+            sources.push(Source::parse("ADF", "ADF", false, front));
+            let duplex = element("ADFSupportsDuplex")
+                .and_then(|duplex| duplex.text())
+                .is_some_and(|duplex| matches!(duplex.trim(), "1" | "true"));
+            if duplex {
+                sources.push(Source::parse("ADF Duplex", "ADFDuplex", true, front));
+            }
+        }
+
+        Ok(Configuration {
+            sources,
+            formats: document
+                .descendants()
+                .filter(|node| node.tag_name().name() == "FormatValue")
+                .filter_map(|node| node.text().map(|text| text.trim().to_string()))
+                .collect(),
+        })
+    }
+}
+
+/// The texts of all elements below the node with the name
+fn texts<'a>(node: Node<'a, 'a>, name: &'a str) -> impl Iterator<Item = &'a str> {
+    node.descendants()
+        .filter(move |node| node.tag_name().name() == name)
+        .filter_map(|node| node.text().map(str::trim))
+}
+
+/// The settings the options change, sent along with the scan job
+#[derive(Clone, Debug)]
+struct Settings {
+    /// The index into the sources of the configuration
+    source: usize,
+    mode: PageMode,
+    resolution: i32,
+    /// Left, top, right and bottom in millimeters
+    area: [f64; 4],
+}
+
+impl Settings {
+    /// The whole area in color at 300 dpi, or whatever comes closest for the source
+    fn defaults(source: &Source, index: usize) -> Settings {
+        let (width, height) = source.max_mm();
+        Settings {
+            source: index,
+            mode: source.modes.last().copied().unwrap_or(PageMode::Color),
+            resolution: source
+                .resolutions
+                .iter()
+                .min_by_key(|dpi| (**dpi - 300.0).abs() as i64)
+                .map_or(300, |dpi| *dpi as i32),
+            area: [0.0, 0.0, width, height],
+        }
+    }
+}
+
+/// A scan job the scanner created, which is needed to fetch its pages
+#[derive(Clone, Debug)]
+struct Job {
+    id: String,
+    token: String,
+}
+
+struct WsdDevice {
+    name: String,
+    agent: ureq::Agent,
+    service: Url,
+    configuration: Configuration,
+    settings: Settings,
+    /// The running scan job, a job of a feeder runs until the feeder is empty
+    job: Option<Job>,
+    frame: Option<PageFrame>,
+}
+
+impl WsdDevice {
+    fn source(&self) -> &Source {
+        &self.configuration.sources[self.settings.source]
+    }
+
+    fn not_found(&self, option: &OptionDescriptor) -> miette::Report {
+        Error::OptionNotFound {
+            name: self.name.clone(),
+            option: option.name.clone(),
+        }
+        .into()
+    }
+
+    fn format(&self) -> &'static str {
+        FORMATS
+            .into_iter()
+            .find(|format| self.configuration.formats.iter().any(|f| f == format))
+            .unwrap_or(FORMATS[1])
+    }
+
+    fn scan_ticket(&self) -> String {
+        let source = self.source();
+        let settings = &self.settings;
+        let units = |mm: f64| (mm / 25.4 * UNITS_PER_INCH).round() as u32;
+        let [left, top, right, bottom] = settings.area;
+        let mode = match settings.mode {
+            PageMode::Lineart => "BlackAndWhite1",
+            PageMode::Gray => "Grayscale8",
+            PageMode::Color => "RGB24",
+        };
+        let side = |name: &str| {
+            format!(
+                "<wscn:{name}><wscn:ScanRegion><wscn:ScanRegionXOffset>{}</wscn:ScanRegionXOffset>\
+                 <wscn:ScanRegionYOffset>{}</wscn:ScanRegionYOffset><wscn:ScanRegionWidth>{}</wscn:ScanRegionWidth>\
+                 <wscn:ScanRegionHeight>{}</wscn:ScanRegionHeight></wscn:ScanRegion>\
+                 <wscn:ColorProcessing>{mode}</wscn:ColorProcessing><wscn:Resolution><wscn:Width>{}</wscn:Width>\
+                 <wscn:Height>{}</wscn:Height></wscn:Resolution></wscn:{name}>",
+                units(left),
+                units(top),
+                units(right - left).max(1),
+                units(bottom - top).max(1),
+                settings.resolution,
+                settings.resolution,
+            )
+        };
+        let sides = if source.duplex {
+            side("MediaFront") + &side("MediaBack")
+        } else {
+            side("MediaFront")
+        };
+        // Zero transfers all pages of the feeder
+        let images = if source.input == "Platen" { 1 } else { 0 };
+
+        format!(
+            r#"<wscn:CreateScanJobRequest xmlns:wscn="{SCAN}"><wscn:ScanTicket><wscn:JobDescription><wscn:JobName>scannrs</wscn:JobName><wscn:JobOriginatingUserName>scannrs</wscn:JobOriginatingUserName></wscn:JobDescription><wscn:DocumentParameters><wscn:Format>{}</wscn:Format><wscn:ImagesToTransfer>{images}</wscn:ImagesToTransfer><wscn:InputSource>{}</wscn:InputSource><wscn:MediaSides>{sides}</wscn:MediaSides></wscn:DocumentParameters></wscn:ScanTicket></wscn:CreateScanJobRequest>"#,
+            self.format(),
+            source.input,
+        )
+    }
+
+    fn create_job(&self) -> miette::Result<Job> {
+        let xml = call(
+            &self.agent,
+            &self.service,
+            &format!("{SCAN}/CreateScanJob"),
+            &self.scan_ticket(),
+        )?
+        .into_string()
+        .into_diagnostic()
+        .with_context(|| format!("While starting a scan on {}", self.name))?;
+        let document = Document::parse(&xml).into_diagnostic()?;
+        let root = document.root_element();
+
+        let text = |name| texts(root, name).next().map(String::from);
+        text("JobId")
+            .zip(text("JobToken"))
+            .map(|(id, token)| Job { id, token })
+            .ok_or(Error::Unsupported)
+            .context("The scanner did not tell which job it created")
+    }
+
+    /// The next page of the job, `None` once the job has no more pages
+    fn retrieve_image(&self, job: &Job) -> miette::Result<Option<DynamicImage>> {
+        let request = format!(
+            r#"<wscn:RetrieveImageRequest xmlns:wscn="{SCAN}"><wscn:JobId>{}</wscn:JobId><wscn:JobToken>{}</wscn:JobToken><wscn:DocumentDescription><wscn:DocumentName>scannrs</wscn:DocumentName></wscn:DocumentDescription></wscn:RetrieveImageRequest>"#,
+            job.id, job.token,
+        );
+        let response = match call(
+            &self.agent,
+            &self.service,
+            &format!("{SCAN}/RetrieveImage"),
+            &request,
+        ) {
+            Ok(response) => response,
+            Err(error) if is_fault(&error, "NoImagesAvailable") => return Ok(None),
+            Err(error) => return Err(error),
+        };
+
+        let content_type = response.content_type().to_string();
+        let boundary = response
+            .header("Content-Type")
+            .and_then(|header| {
+                header
+                    .split(';')
+                    .find_map(|param| param.trim().strip_prefix("boundary="))
+            })
+            .map(|boundary| boundary.trim_matches('"').to_string());
+        let mut data = Vec::new();
+        std::io::Read::read_to_end(&mut response.into_reader(), &mut data).into_diagnostic()?;
+
+        let image = match boundary {
+            Some(boundary) if content_type.starts_with("multipart/") => {
+                attachment(&data, &boundary)
+                    .ok_or(Error::Unsupported)
+                    .context("The scanner sent no image along with its answer")?
+            }
+            _ => data.as_slice(),
+        };
+        image::load_from_memory(image)
+            .into_diagnostic()
+            .context("The scanner sent a page that could not be decoded")
+            .map(Some)
+    }
+
+    fn finish_job(&mut self) {
+        if let Some(job) = self.job.take() {
+            // The job is gone once its last page was read, so failing to cancel it is expected
+            let _ = call(
+                &self.agent,
+                &self.service,
+                &format!("{SCAN}/CancelJob"),
+                &format!(
+                    r#"<wscn:CancelJobRequest xmlns:wscn="{SCAN}"><wscn:JobId>{}</wscn:JobId></wscn:CancelJobRequest>"#,
+                    job.id
+                ),
+            );
+        }
+    }
+}
+
+impl ScanDevice for WsdDevice {
+    fn options(&self) -> miette::Result<Vec<OptionDescriptor>> {
+        let source = self.source();
+        let (width, height) = source.max_mm();
+        let mm = |name, title, max| {
+            let constraint = Constraint::Range {
+                min: 0.0,
+                max,
+                step: None,
+            };
+            option(name, title, ValueType::Fixed, Unit::Mm, constraint)
+        };
+
+        Ok(vec![
+            group("Scan Mode"),
+            option(
+                "source",
+                "Scan source",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(
+                    self.configuration
+                        .sources
+                        .iter()
+                        .map(|source| source.name.to_string())
+                        .collect(),
+                ),
+            ),
+            option(
+                "mode",
+                "Scan mode",
+                ValueType::String,
+                Unit::None,
+                Constraint::Strings(
+                    source
+                        .modes
+                        .iter()
+                        .map(|mode| mode.name().to_string())
+                        .collect(),
+                ),
+            ),
+            option(
+                "resolution",
+                "Scan resolution",
+                ValueType::Int,
+                Unit::Dpi,
+                Constraint::Numbers(source.resolutions.clone()),
+            ),
+            group("Geometry"),
+            mm("tl-x", "Top-left x", width),
+            mm("tl-y", "Top-left y", height),
+            mm("br-x", "Bottom-right x", width),
+            mm("br-y", "Bottom-right y", height),
+        ])
+    }
+
+    fn get_option(&self, option: &OptionDescriptor) -> miette::Result<OptionValue> {
+        let settings = &self.settings;
+        let area = |idx: usize| OptionValue::Fixed(settings.area[idx]);
+
+        Ok(match option.name.as_str() {
+            "source" => OptionValue::String(self.source().name.to_string()),
+            "mode" => OptionValue::String(settings.mode.name().to_string()),
+            "resolution" => OptionValue::Int(settings.resolution),
+            "tl-x" => area(0),
+            "tl-y" => area(1),
+            "br-x" => area(2),
+            "br-y" => area(3),
+            _ => return Err(self.not_found(option)),
+        })
+    }
+
+    fn set_option(&mut self, option: &OptionDescriptor, value: OptionValue) -> miette::Result<()> {
+        let invalid = || invalid_value(option, &value);
+        let (width, height) = self.source().max_mm();
+
+        match (option.name.as_str(), &value) {
+            ("source", OptionValue::String(name)) => {
+                let index = self
+                    .configuration
+                    .sources
+                    .iter()
+                    .position(|source| source.name == name)
+                    .ok_or_else(invalid)?;
+                // The other settings may not fit the new source, like SANE backends start over with its defaults
+                self.settings = Settings::defaults(&self.configuration.sources[index], index);
+            }
+            ("mode", OptionValue::String(name)) => {
+                self.settings.mode = PageMode::from_name(name)
+                    .filter(|mode| self.source().modes.contains(mode))
+                    .ok_or_else(invalid)?;
+            }
+            ("resolution", OptionValue::Int(dpi))
+                if self.source().resolutions.contains(&f64::from(*dpi)) =>
+            {
+                self.settings.resolution = *dpi
+            }
+            ("tl-x" | "tl-y" | "br-x" | "br-y", OptionValue::Fixed(mm)) => {
+                let (idx, max) = match option.name.as_str() {
+                    "tl-x" => (0, width),
+                    "tl-y" => (1, height),
+                    "br-x" => (2, width),
+                    _ => (3, height),
+                };
+                if !(0.0..=max).contains(mm) {
+                    return Err(invalid());
+                }
+                self.settings.area[idx] = *mm;
+            }
+            ("source" | "mode" | "resolution" | "tl-x" | "tl-y" | "br-x" | "br-y", _) => {
+                return Err(invalid())
+            }
+            _ => return Err(self.not_found(option)),
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self) -> miette::Result<FrameParameters> {
+        let platen = self.source().input == "Platen";
+        let job = match self.job.clone() {
+            Some(job) => job,
+            None => {
+                let job = self.create_job()?;
+                self.job = Some(job.clone());
+                job
+            }
+        };
+
+        let page = self.retrieve_image(&job);
+        // A flatbed job holds a single page, the next scan needs a new job
+        if platen || !matches!(page, Ok(Some(_))) {
+            self.finish_job();
+        }
+        let page = page?.ok_or(Error::FeederEmpty)?;
+
+        let (frame, params) = PageFrame::new(&page, self.settings.mode);
+        self.frame = Some(frame);
+        Ok(params)
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> miette::Result<Option<usize>> {
+        let frame = self.frame.as_mut().ok_or(Error::ScanCancelled)?;
+
+        let read = frame.read(buffer);
+        if read.is_none() {
+            self.frame = None;
+        }
+        Ok(read)
+    }
+
+    fn cancel(&mut self) {
+        self.frame = None;
+        self.finish_job();
+    }
+}
+
+impl Drop for WsdDevice {
+    fn drop(&mut self) {
+        self.finish_job();
+    }
+}
+
+/// A SOAP message with the addressing headers WS-Scan needs
+fn envelope(to: &str, action: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<soap:Envelope xmlns:soap="{SOAP}" xmlns:wsa="{ADDRESSING}">
+  <soap:Header>
+    <wsa:To>{to}</wsa:To>
+    <wsa:Action>{action}</wsa:Action>
+    <wsa:MessageID>urn:uuid:{}</wsa:MessageID>
+    <wsa:ReplyTo><wsa:Address>{ADDRESSING}/role/anonymous</wsa:Address></wsa:ReplyTo>
+  </soap:Header>
+  <soap:Body>{body}</soap:Body>
+</soap:Envelope>
+"#,
+        uuid::Uuid::new_v4()
+    )
+}
+
+/// Send a SOAP request to a service, see [`send`]
+fn call(
+    agent: &ureq::Agent,
+    url: &Url,
+    action: &str,
+    body: &str,
+) -> miette::Result<ureq::Response> {
+    send(agent, url, url.as_str(), action, body)
+}
+
+/// Send a SOAP request addressed to `to`, faults the scanner answers with become [`Error::WsdFault`] or one of the
+/// errors they stand for
+fn send(
+    agent: &ureq::Agent,
+    url: &Url,
+    to: &str,
+    action: &str,
+    body: &str,
+) -> miette::Result<ureq::Response> {
+    let result = agent
+        .request_url("POST", url)
+        .set("Content-Type", "application/soap+xml; charset=utf-8")
+        .send_string(&envelope(to, action, body));
+
+    let error = match result {
+        Ok(response) => return Ok(response),
+        Err(ureq::Error::Status(status, response)) => {
+            let xml = response.into_string().unwrap_or_default();
+            match Document::parse(&xml)
+                .ok()
+                .and_then(|document| fault(&document))
+            {
+                Some(fault) => Error::from_fault(fault),
+                None => match status {
+                    401 | 403 => Error::AccessDenied,
+                    409 | 503 => Error::DeviceBusy,
+                    _ => Error::WsdFault {
+                        fault: format!("HTTP status {status}"),
+                    },
+                },
+            }
+        }
+        Err(error) => Error::from(error),
+    };
+    Err(error.into())
+}
+
+/// The most specific code of a SOAP fault, like `wscn:ClientErrorNoImagesAvailable`
+fn fault(document: &Document<'_>) -> Option<String> {
+    document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "Value")
+        .filter_map(|node| node.text())
+        .last()
+        .map(|code| code.trim().to_string())
+}
+
+fn is_fault(error: &miette::Report, code: &str) -> bool {
+    matches!(error.downcast_ref::<Error>(), Some(Error::WsdFault { fault }) if fault.ends_with(code))
+}
+
+/// The faults WS-Scan reports that have their own [`Error`], by the end of their code
+const FAULTS: [(&str, fn() -> Error); 6] = [
+    ("NotAcceptingJobs", || Error::DeviceBusy),
+    ("TemporaryError", || Error::DeviceBusy),
+    ("NotAuthorized", || Error::AccessDenied),
+    ("FormatNotSupported", || Error::Unsupported),
+    ("InvalidArgs", || Error::Unsupported),
+    ("JobIdNotFound", || Error::ScanCancelled),
+];
+
+impl Error {
+    fn from_fault(fault: String) -> Error {
+        FAULTS
+            .iter()
+            .find(|(code, _)| fault.ends_with(code))
+            .map_or(Error::WsdFault { fault }, |(_, error)| error())
+    }
+}
+
+/// The image sent along with an MTOM answer, which is the part after the SOAP message
+fn attachment<'a>(data: &'a [u8], boundary: &str) -> Option<&'a [u8]> {
+    let delimiter = format!("--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    };
+
+    let mut parts = Vec::new();
+    let mut rest = data;
+    while let Some(start) = find(rest, delimiter) {
+        rest = &rest[start + delimiter.len()..];
+        let end = find(rest, delimiter).unwrap_or(rest.len());
+        parts.push(&rest[..end]);
+    }
+
+    parts
+        .into_iter()
+        .filter_map(|part| {
+            let body = find(part, b"\r\n\r\n")? + 4;
+            let headers = String::from_utf8_lossy(&part[..body]).to_lowercase();
+            let content = part[body..].strip_suffix(b"\r\n").unwrap_or(&part[body..]);
+            (!headers.contains("xml")).then_some(content)
+        })
+        .find(|content| !content.is_empty())
+}
+
+fn invalid_value(option: &OptionDescriptor, value: &OptionValue) -> miette::Report {
+    Error::InvalidValue {
+        option: option.name.clone(),
+        value: format!("{value:?}"),
+    }
+    .into()
+}
+
+fn option(
+    name: &str,
+    title: &str,
+    type_: ValueType,
+    unit: Unit,
+    constraint: Constraint,
+) -> OptionDescriptor {
+    OptionDescriptor {
+        name: name.to_string(),
+        title: title.to_string(),
+        description: String::new(),
+        type_,
+        unit,
+        constraint,
+        active: true,
+        settable: !matches!(type_, ValueType::Group),
+        hardware: false,
+        automatic: false,
+        advanced: false,
+        emulated: false,
+    }
+}
+
+fn group(title: &str) -> OptionDescriptor {
+    option("", title, ValueType::Group, Unit::None, Constraint::None)
+}
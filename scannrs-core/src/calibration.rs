@@ -0,0 +1,120 @@
+//! Evening out differences in sensor sensitivity and illumination across the scan head
+
+use image::DynamicImage;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Per-column gain factors computed from a scan of a white target
+///
+/// Applying them evens out differences in sensor sensitivity and illumination across the scan head.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Calibration {
+    width: u32,
+    channels: u8,
+    /// `width * channels` gains, interleaved per column like the pixel data
+    gains: Vec<f32>,
+}
+
+impl Calibration {
+    /// Compute the calibration from an image of a uniformly white target
+    pub fn from_white_scan(img: &DynamicImage) -> Calibration {
+        match img {
+            DynamicImage::ImageLuma8(img) => Calibration::from_samples(img.width(), 1, img),
+            img => {
+                let img = img.to_rgb8();
+                Calibration::from_samples(img.width(), 3, &img)
+            }
+        }
+    }
+
+    fn from_samples(width: u32, channels: u8, samples: &[u8]) -> Calibration {
+        let row_len = width as usize * channels as usize;
+        let mut sums = vec![0u64; row_len];
+        let mut rows = 0u64;
+
+        for row in samples.chunks_exact(row_len.max(1)) {
+            for (sum, sample) in sums.iter_mut().zip(row) {
+                *sum += u64::from(*sample);
+            }
+            rows += 1;
+        }
+
+        let gains = sums
+            .into_iter()
+            .map(|sum| {
+                let average = sum as f32 / rows.max(1) as f32;
+                if average < 1.0 {
+                    1.0
+                } else {
+                    255.0 / average
+                }
+            })
+            .collect();
+
+        Calibration {
+            width,
+            channels,
+            gains,
+        }
+    }
+
+    /// Apply the gains to an image, scans with a different width are mapped proportionally onto the calibration
+    ///
+    /// A gray calibration evens out all colors alike, a color one evens out gray scans by the average of its colors.
+    /// Samples without a gain, as with a damaged calibration file, are left as they are.
+    pub fn apply(&self, img: &mut DynamicImage) {
+        let width = img.width() as usize;
+        let scale8 = |sample: u8, gain: f32| (f32::from(sample) * gain).round().min(255.0) as u8;
+        let scale16 =
+            |sample: u16, gain: f32| (f32::from(sample) * gain).round().min(65535.0) as u16;
+        match img {
+            DynamicImage::ImageLuma8(img) => self.apply_to(width, 1, &mut **img, scale8),
+            DynamicImage::ImageRgb8(img) => self.apply_to(width, 3, &mut **img, scale8),
+            DynamicImage::ImageLuma16(img) => self.apply_to(width, 1, &mut **img, scale16),
+            DynamicImage::ImageRgb16(img) => self.apply_to(width, 3, &mut **img, scale16),
+            img => tracing::warn!(
+                "Scans of {:?} cannot be calibrated, the scan is left as it is",
+                img.color()
+            ),
+        }
+    }
+
+    fn apply_to<T: Copy>(
+        &self,
+        width: usize,
+        channels: usize,
+        samples: &mut [T],
+        scale: impl Fn(T, f32) -> T,
+    ) {
+        if width == 0 {
+            return;
+        }
+
+        let calibration_width = self.width as usize;
+        for row in samples.chunks_exact_mut(width * channels) {
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                let column = x * calibration_width / width;
+                for (c, sample) in pixel.iter_mut().enumerate() {
+                    if let Some(gain) = self.gain(column, c, channels) {
+                        *sample = scale(*sample, gain);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The gain for a channel of a column, in a scan with the given number of channels
+    fn gain(&self, column: usize, channel: usize, channels: usize) -> Option<f32> {
+        let own = self.channels as usize;
+        if own == channels {
+            self.gains.get(column * own + channel).copied()
+        } else if own == 1 {
+            self.gains.get(column).copied()
+        } else if channels == 1 {
+            let gains = self.gains.get(column * own..(column + 1) * own)?;
+            Some(gains.iter().sum::<f32>() / own as f32)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use image::DynamicImage;
+use image::ImageBuffer;
+use image::Luma;
+use image::Rgb;
+
+use crate::backend::FrameFormat;
+use crate::backend::FrameParameters;
+use crate::Error;
+
+/// Decode the raw data of a single frame into an image
+///
+/// Padding at the end of each line is dropped, 1-bit data is unpacked (with set bits being black for gray frames)
+/// and 16-bit samples are read in the native byte order of the host, as required by the SANE standard.
+/// Single-color frames of a three-pass scan are decoded as grayscale images.
+pub fn decode_frame(params: &FrameParameters, data: &[u8]) -> miette::Result<DynamicImage> {
+    let channels = match params.format {
+        FrameFormat::Rgb => 3,
+        FrameFormat::Gray | FrameFormat::Red | FrameFormat::Green | FrameFormat::Blue => 1,
+    };
+    let width = params.pixels_per_line;
+    let depth = params.depth;
+    let bytes_per_line = params.bytes_per_line;
+    let samples_per_line = width * channels;
+    let needed_bytes = (samples_per_line * depth).div_ceil(8);
+
+    let invalid_size = || Error::InvalidImageSize {
+        width: width as u32,
+        height: params.lines.unwrap_or_default() as u32,
+        buffer_size: data.len(),
+        pixel_size: depth as u32,
+    };
+
+    if bytes_per_line == 0 || bytes_per_line < needed_bytes {
+        return Err(invalid_size().into());
+    }
+
+    // Hand-scanners do not know the amount of lines in advance, so always go by what was actually read
+    let lines = (data.len() / bytes_per_line) as u32;
+    let rows = data
+        .chunks_exact(bytes_per_line)
+        .map(|row| &row[..needed_bytes]);
+
+    let img = match (channels, depth) {
+        (_, 1) => {
+            let set = if channels == 1 { 0 } else { u8::MAX };
+            let samples = rows
+                .flat_map(|row| {
+                    (0..samples_per_line).map(move |i| {
+                        if row[i / 8] & (0x80 >> (i % 8)) != 0 {
+                            set
+                        } else {
+                            !set
+                        }
+                    })
+                })
+                .collect::<Vec<u8>>();
+
+            from_samples_8(channels, width as u32, lines, samples)
+        }
+        (_, 8) => {
+            let samples = rows.flatten().copied().collect::<Vec<u8>>();
+
+            from_samples_8(channels, width as u32, lines, samples)
+        }
+        (_, 16) => {
+            let samples = rows
+                .flat_map(|row| {
+                    row.chunks_exact(2)
+                        .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+                })
+                .collect::<Vec<u16>>();
+
+            if channels == 1 {
+                ImageBuffer::<Luma<u16>, _>::from_raw(width as u32, lines, samples)
+                    .map(DynamicImage::from)
+            } else {
+                ImageBuffer::<Rgb<u16>, _>::from_raw(width as u32, lines, samples)
+                    .map(DynamicImage::from)
+            }
+        }
+        _ => return Err(Error::UnsupportedBitDepth { depth }.into()),
+    };
+
+    img.ok_or_else(invalid_size).map_err(miette::Report::from)
+}
+
+fn from_samples_8(
+    channels: usize,
+    width: u32,
+    lines: u32,
+    samples: Vec<u8>,
+) -> Option<DynamicImage> {
+    if channels == 1 {
+        image::GrayImage::from_raw(width, lines, samples).map(DynamicImage::from)
+    } else {
+        image::RgbImage::from_raw(width, lines, samples).map(DynamicImage::from)
+    }
+}
+
+/// Combine the separately scanned planes of a three-pass scan into a single color image
+pub fn merge_planes(red: &DynamicImage, green: &DynamicImage, blue: &DynamicImage) -> DynamicImage {
+    let width = red.width().min(green.width()).min(blue.width());
+    let height = red.height().min(green.height()).min(blue.height());
+
+    if matches!(red, DynamicImage::ImageLuma16(_)) {
+        let [red, green, blue] = [red, green, blue].map(DynamicImage::to_luma16);
+
+        ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb([red[(x, y)].0[0], green[(x, y)].0[0], blue[(x, y)].0[0]])
+        })
+        .into()
+    } else {
+        let [red, green, blue] = [red, green, blue].map(DynamicImage::to_luma8);
+
+        image::RgbImage::from_fn(width, height, |x, y| {
+            Rgb([red[(x, y)].0[0], green[(x, y)].0[0], blue[(x, y)].0[0]])
+        })
+        .into()
+    }
+}
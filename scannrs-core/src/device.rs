@@ -0,0 +1,154 @@
+//! Describing scanners and their options for people and machine-readable output
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::backend::Constraint;
+use crate::backend::OptionDescriptor;
+use crate::backend::OptionValue;
+use crate::backend::ValueType;
+
+/// A serializable description of a scanner
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub vendor: String,
+    pub model: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// How the scanner is reached, for scanners on the network
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkInfo>,
+}
+
+/// How a scanner on the network is reached
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NetworkInfo {
+    /// The protocol the scanner speaks, like `eSCL`
+    pub protocol: String,
+    /// Whether scannrs can scan with the scanner itself, without SANE having to be configured for it
+    pub native: bool,
+}
+
+impl NetworkInfo {
+    pub fn new(protocol: &str, native: bool) -> NetworkInfo {
+        NetworkInfo {
+            protocol: protocol.to_string(),
+            native,
+        }
+    }
+}
+
+/// A serializable description of a single option a scanner exposes
+#[derive(Serialize, Debug, Clone)]
+pub struct OptionInfo {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<ValueInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<ConstraintInfo>,
+    pub active: bool,
+    pub settable: bool,
+    /// Reflects the state of the hardware, like a loaded document or a pressed button, instead of a setting
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub sensor: bool,
+}
+
+impl OptionInfo {
+    /// Also include the current value of the option
+    pub fn with_value(mut self, value: &OptionValue) -> Self {
+        self.value = ValueInfo::from_value(value);
+        self
+    }
+}
+
+impl From<&OptionDescriptor> for OptionInfo {
+    fn from(option: &OptionDescriptor) -> Self {
+        let number = |value: f64| match option.type_ {
+            ValueType::Fixed => ValueInfo::Float(value),
+            _ => ValueInfo::Int(value as i32),
+        };
+
+        let constraint = match &option.constraint {
+            Constraint::None => None,
+            Constraint::Range { min, max, step } => Some(ConstraintInfo::Range {
+                min: number(*min),
+                max: number(*max),
+                step: step.map(number),
+            }),
+            Constraint::Numbers(numbers) => Some(ConstraintInfo::List {
+                values: numbers.iter().map(|value| number(*value)).collect(),
+            }),
+            Constraint::Strings(strings) => Some(ConstraintInfo::List {
+                values: strings.iter().cloned().map(ValueInfo::String).collect(),
+            }),
+        };
+
+        OptionInfo {
+            name: option.name.clone(),
+            title: option.title.clone(),
+            description: option.description.clone(),
+            type_: format!("{:?}", option.type_),
+            unit: option.unit.name(),
+            value: None,
+            constraint,
+            active: option.active,
+            settable: option.settable,
+            sensor: option.hardware && !option.settable,
+        }
+    }
+}
+
+/// The value of an option, as it appears in machine-readable output
+#[derive(Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum ValueInfo {
+    Bool(bool),
+    Int(i32),
+    Float(f64),
+    String(String),
+}
+
+impl ValueInfo {
+    pub fn from_value(value: &OptionValue) -> Option<ValueInfo> {
+        Some(match value {
+            OptionValue::Bool(value) => ValueInfo::Bool(*value),
+            OptionValue::Int(value) => ValueInfo::Int(*value),
+            OptionValue::Fixed(value) => ValueInfo::Float(*value),
+            OptionValue::String(value) => ValueInfo::String(value.clone()),
+            OptionValue::Button => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for ValueInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueInfo::Bool(value) => value.fmt(f),
+            ValueInfo::Int(value) => value.fmt(f),
+            ValueInfo::Float(value) => value.fmt(f),
+            ValueInfo::String(value) => value.fmt(f),
+        }
+    }
+}
+
+/// The values an option accepts
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ConstraintInfo {
+    Range {
+        min: ValueInfo,
+        max: ValueInfo,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        step: Option<ValueInfo>,
+    },
+    List {
+        values: Vec<ValueInfo>,
+    },
+}
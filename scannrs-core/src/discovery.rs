@@ -0,0 +1,132 @@
+//! Finding scanners on the network through mDNS/DNS-SD, also called zeroconf or Bonjour
+//!
+//! Scanners announce themselves as `_uscan._tcp` and `_uscans._tcp` when they speak eSCL, or as `_scanner._tcp`
+//! when they speak a protocol of their vendor. Only eSCL scanners can be driven by scannrs itself, with the `escl`
+//! feature, the others need SANE to be set up for them.
+
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use mdns_sd::ServiceDaemon;
+use mdns_sd::ServiceEvent;
+use mdns_sd::ServiceInfo;
+
+use crate::device::DeviceInfo;
+use crate::device::NetworkInfo;
+use crate::Error;
+
+/// How long to listen for answers by default, scanners on the same network usually answer within a second
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often to look for answers while listening
+const POLL: Duration = Duration::from_millis(50);
+
+/// The services announced by scanners, with the URL scheme to reach them and whether they speak eSCL
+const SERVICES: [(&str, &str, bool); 3] = [
+    ("_uscan._tcp.local.", "http", true),
+    ("_uscans._tcp.local.", "https", true),
+    ("_scanner._tcp.local.", "http", false),
+];
+
+/// Look for scanners on the network for `timeout`, each listed once even if it answers on several interfaces
+///
+/// Scanners scannrs can drive are named like [`crate::backend::ScanBackend::open`] expects, the others by their
+/// address.
+pub fn discover(timeout: Duration) -> miette::Result<Vec<DeviceInfo>> {
+    let daemon = ServiceDaemon::new().map_err(Error::from)?;
+    let receivers = SERVICES
+        .iter()
+        .map(|(service, scheme, escl)| {
+            let receiver = daemon.browse(service).map_err(Error::from)?;
+            Ok((receiver, *scheme, *escl))
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    let mut scanners = BTreeMap::new();
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        for (receiver, scheme, escl) in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    let scanner = describe(&info, scheme, *escl);
+                    scanners.entry(scanner.name.clone()).or_insert(scanner);
+                }
+            }
+        }
+        thread::sleep(POLL);
+    }
+
+    // Only fails when the daemon already stopped on its own
+    let _ = daemon.shutdown();
+
+    Ok(scanners.into_values().collect())
+}
+
+impl From<mdns_sd::Error> for Error {
+    fn from(error: mdns_sd::Error) -> Self {
+        Error::Discovery { error }
+    }
+}
+
+fn describe(info: &ServiceInfo, scheme: &str, escl: bool) -> DeviceInfo {
+    let property = |key| info.get_property_val_str(key).unwrap_or_default();
+
+    let host = address(info).map_or_else(
+        || info.get_hostname().trim_end_matches('.').to_string(),
+        |address| match address {
+            IpAddr::V4(address) => address.to_string(),
+            IpAddr::V6(address) => format!("[{address}]"),
+        },
+    );
+    let address = format!("{scheme}://{host}:{}", info.get_port());
+
+    // `ty` holds the make and model together, some scanners also send them on their own
+    let make_and_model = property("ty");
+    let (vendor, model) = match (property("mfg"), property("mdl")) {
+        ("", _) | (_, "") => make_and_model
+            .split_once(' ')
+            .unwrap_or(("", make_and_model)),
+        split => split,
+    };
+
+    let (name, native) = if escl {
+        let url = format!("{address}/{}", property("rs").trim_matches('/'));
+        native_name(url)
+    } else {
+        (address, false)
+    };
+
+    DeviceInfo {
+        name,
+        vendor: vendor.to_string(),
+        model: model.to_string(),
+        type_: String::from("network scanner"),
+        network: Some(NetworkInfo::new(
+            if escl { "eSCL" } else { "Bonjour" },
+            native,
+        )),
+    }
+}
+
+#[cfg(feature = "escl")]
+fn native_name(url: String) -> (String, bool) {
+    (format!("{}{url}", crate::backend::escl::PREFIX), true)
+}
+
+#[cfg(not(feature = "escl"))]
+fn native_name(url: String) -> (String, bool) {
+    (url, false)
+}
+
+/// Prefer IPv4 addresses, as link-local IPv6 addresses can not be used without the interface they belong to
+fn address(info: &ServiceInfo) -> Option<IpAddr> {
+    let addresses = info.get_addresses();
+    addresses
+        .iter()
+        .find(|address| address.is_ipv4())
+        .or_else(|| addresses.iter().next())
+        .copied()
+}
@@ -0,0 +1,183 @@
+//! Scanning from async code
+//!
+//! Backends like SANE are not thread-safe, so a single thread owns the backend and runs the work that [`Driver`]s send it, while async code
+//! awaits the results.
+
+use std::future::Future;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+
+use tokio::sync::mpsc;
+use tokio::sync::oneshot;
+
+use crate::backend::ScanBackend;
+use crate::job::ScanJob;
+use crate::output::Page;
+use crate::progress::ScanEvent;
+use crate::scan::scanlines;
+use crate::scan::CancellationToken;
+use crate::scan::Scanline;
+use crate::Error;
+
+type Work = Box<dyn FnOnce(&dyn ScanBackend) + Send>;
+
+/// Sends work to the thread owning the backend, cheap to clone
+#[derive(Clone)]
+pub struct Driver {
+    sender: Sender<Work>,
+}
+
+/// Runs the work sent by the [`Driver`]s on the thread owning the backend
+pub struct DriverLoop {
+    recv: Receiver<Work>,
+}
+
+/// Create a driver together with the loop running its work
+pub fn driver() -> (Driver, DriverLoop) {
+    let (sender, recv) = channel();
+    (Driver { sender }, DriverLoop { recv })
+}
+
+impl DriverLoop {
+    /// Run the work in the order it was sent until all drivers are dropped
+    pub fn run(self, backend: &dyn ScanBackend) {
+        for work in self.recv.iter() {
+            work(backend);
+        }
+    }
+}
+
+impl Driver {
+    /// Run `work` on the thread owning the backend and wait for its result
+    pub async fn run<T, F>(&self, work: F) -> miette::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn ScanBackend) -> T + Send + 'static,
+    {
+        let (responder, recv) = oneshot::channel();
+        self.sender
+            .send(Box::new(move |backend| {
+                let _ = responder.send(work(backend));
+            }))
+            .map_err(|_| Error::DriverStopped)?;
+
+        recv.await
+            .map_err(|_| Error::DriverStopped)
+            .map_err(miette::Report::from)
+    }
+
+    /// Scan a single page with the job, see [`ScanJob::scan`]
+    ///
+    /// The scan starts once the future is polled, the token cancels it at any time.
+    pub fn scan(
+        &self,
+        job: ScanJob,
+    ) -> (
+        impl Future<Output = miette::Result<Option<Page>>> + Send,
+        CancellationToken,
+    ) {
+        self.scan_with_progress(job, |_| {})
+    }
+
+    /// Like [`Driver::scan`], reporting the progress of the scan to `progress`, see [`ScanEvent`]
+    pub fn scan_with_progress(
+        &self,
+        job: ScanJob,
+        mut progress: impl FnMut(ScanEvent) + Send + 'static,
+    ) -> (
+        impl Future<Output = miette::Result<Option<Page>>> + Send,
+        CancellationToken,
+    ) {
+        let token = CancellationToken::default();
+        let cancel = token.clone();
+        let driver = self.clone();
+
+        let scan = async move {
+            driver
+                .run(move |backend| {
+                    // Cancelled while waiting for other work
+                    if cancel.is_cancelled() {
+                        return Err(Error::ScanCancelled.into());
+                    }
+
+                    job.scan_with_progress(backend, &mut |event| {
+                        progress(event);
+                        cancel.flow()
+                    })
+                })
+                .await?
+        };
+
+        (scan, token)
+    }
+
+    /// Scan every page in the document feeder with the job, see [`ScanJob::scan_feeder_with_progress`]
+    pub fn scan_feeder(
+        &self,
+        job: ScanJob,
+    ) -> (
+        impl Future<Output = miette::Result<Vec<Page>>> + Send,
+        CancellationToken,
+    ) {
+        let token = CancellationToken::default();
+        let cancel = token.clone();
+        let driver = self.clone();
+
+        let scan = async move {
+            driver
+                .run(move |backend| {
+                    if cancel.is_cancelled() {
+                        return Err(Error::ScanCancelled.into());
+                    }
+
+                    let mut pages = Vec::new();
+                    job.scan_feeder_with_progress(backend, &mut |_| cancel.flow(), &mut |page| {
+                        pages.push(page)
+                    })?;
+                    Ok(pages)
+                })
+                .await?
+        };
+
+        (scan, token)
+    }
+
+    /// Scan with the job and receive the lines of the page as they arrive, see [`scanlines`]
+    ///
+    /// No post-processing is done. The scan stops after the first error, once the token is cancelled or once the
+    /// receiver is dropped.
+    pub fn scanlines(
+        &self,
+        job: ScanJob,
+    ) -> (mpsc::Receiver<miette::Result<Scanline>>, CancellationToken) {
+        let token = CancellationToken::default();
+        let cancel = token.clone();
+        let (sender, recv) = mpsc::channel(64);
+        let stopped = sender.clone();
+
+        let work: Work = Box::new(move |backend| {
+            if cancel.is_cancelled() {
+                return;
+            }
+
+            let mut device = match job.open(backend) {
+                Ok(device) => device,
+                Err(error) => {
+                    let _ = sender.blocking_send(Err(error));
+                    return;
+                }
+            };
+            for line in scanlines(device.as_mut()) {
+                if cancel.is_cancelled() || sender.blocking_send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        if self.sender.send(work).is_err() {
+            let _ = stopped.try_send(Err(Error::DriverStopped.into()));
+        }
+
+        (recv, token)
+    }
+}
@@ -0,0 +1,238 @@
+use miette::Diagnostic;
+use thiserror::Error;
+
+use crate::output::Format;
+
+/// The errors that can happen while talking to a scanner and processing what it sends
+///
+/// Errors reported by the scanner get their own variant where there is something the user can do about them, with a
+/// suggestion as their help text. New variants can be added in any release.
+#[derive(Debug, Error, Diagnostic)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("Could not find scanner with name: '{}'", .name)]
+    #[diagnostic(help(
+        "Check that the scanner is switched on and connected, names are listed by `scannrs list`"
+    ))]
+    CouldNotFindScanner { name: String },
+
+    #[cfg(feature = "sane")]
+    #[error("An error occured while communicating with the scanner: {}", .error)]
+    Sane { error: sane_scan::Error },
+
+    #[cfg(any(feature = "escl", feature = "wsd"))]
+    #[error("An error occured while communicating with the scanner: {}", .error)]
+    Http { error: Box<ureq::Error> },
+
+    #[cfg(feature = "wsd")]
+    #[error("The scanner reported an error: {}", .fault)]
+    WsdFault { fault: String },
+
+    #[cfg(feature = "mdns")]
+    #[error("Could not look for scanners on the network: {}", .error)]
+    #[diagnostic(help(
+        "Check that multicast traffic on UDP port 5353 is not blocked by a firewall"
+    ))]
+    Discovery { error: mdns_sd::Error },
+
+    #[cfg(all(windows, feature = "wia"))]
+    #[error("An error occured while communicating with the scanner: {}", .error)]
+    Wia { error: windows::core::Error },
+
+    #[error("The scanner is busy")]
+    #[diagnostic(help(
+        "Another program may be using it, wait for its scan to finish or close it"
+    ))]
+    DeviceBusy,
+
+    #[error("Access to the scanner was denied")]
+    #[diagnostic(help(
+        "Check that your user may use the scanner, on Linux this usually means being in the `scanner` or `lp` \
+         group. Scanners that need a password take it from the configuration of their SANE backend"
+    ))]
+    AccessDenied,
+
+    #[error("The document feeder is jammed")]
+    #[diagnostic(help("Remove the jammed pages from the feeder and scan them again"))]
+    FeederJammed,
+
+    #[error("The cover of the scanner is open")]
+    #[diagnostic(help("Close the cover and scan again"))]
+    CoverOpen,
+
+    #[error("The connection to the scanner failed")]
+    #[diagnostic(help(
+        "Check the cable or network connection of the scanner, and that it did not go to sleep"
+    ))]
+    DeviceIo,
+
+    #[error("The scanner ran out of memory")]
+    #[diagnostic(help("Scan with a lower resolution or a smaller area"))]
+    OutOfMemory,
+
+    #[error("The scanner does not support this operation")]
+    #[diagnostic(help(
+        "The options of the scanner and the values they take are listed by `scannrs options`"
+    ))]
+    Unsupported,
+
+    #[error("The given option '{}' does not exist for scanner '{}'", .option, .name)]
+    #[diagnostic(help(
+        "The options of the scanner are listed by `scannrs options {}`",
+        .name
+    ))]
+    OptionNotFound { name: String, option: String },
+
+    #[error("The value '{}' is not allowed for '{}'", .value, .option)]
+    InvalidValue { option: String, value: String },
+
+    #[error("The value '{}' given for '{}' is not a boolean, use one of true/false, yes/no, on/off or 1/0", .value, .option)]
+    InvalidBool { option: String, value: String },
+
+    #[error("The value '{}' given for '{}' is not a number", .value, .option)]
+    InvalidNumber { option: String, value: String },
+
+    #[error("The value '{}' given for '{}' is not a whole number", .value, .option)]
+    NotAnInteger { option: String, value: String },
+
+    #[error("The value '{}' given for '{}' has the wrong unit, expected {}", .value, .option, .expected)]
+    WrongUnit {
+        option: String,
+        value: String,
+        expected: &'static str,
+    },
+
+    #[error("The value '{}' given for '{}' is out of range, it has to be between {} and {}", .value, .option, .min, .max)]
+    OutOfRange {
+        option: String,
+        value: String,
+        min: f64,
+        max: f64,
+    },
+
+    #[error("The value '{}' is not allowed for '{}', use one of: {}", .value, .option, .allowed)]
+    NotAllowed {
+        option: String,
+        value: String,
+        allowed: String,
+    },
+
+    #[error("Could not find the `tesseract` executable, which is needed for text recognition")]
+    #[diagnostic(help(
+        "Install tesseract and the language data you need, e.g. `tesseract-ocr-eng`"
+    ))]
+    TesseractNotFound,
+
+    #[error("Tesseract could not recognize the text: {}", .message)]
+    OcrFailed { message: String },
+
+    #[error("There is no tesseract language data for '{}'", .lang)]
+    #[diagnostic(help(
+        "Install it, e.g. `tesseract-ocr-{}`, the installed languages are: {}",
+        .lang,
+        .installed
+    ))]
+    OcrLanguageMissing { lang: String, installed: String },
+
+    #[error("The page segmentation mode {} does not exist, tesseract knows 0 to {}", .psm, crate::ocr::MAX_PSM)]
+    InvalidPsm { psm: u8 },
+
+    #[error("This build cannot run tesseract as a library")]
+    #[diagnostic(help("Build it with the `tesseract` feature, or use the `executable` engine"))]
+    OcrLibraryNotBuilt,
+
+    #[error("This build cannot send images to a remote OCR service")]
+    #[diagnostic(help("Build it with the `remote-ocr` feature, or use another engine"))]
+    OcrRemoteNotBuilt,
+
+    #[error("The remote OCR engine needs the service to send the images to")]
+    #[diagnostic(help("Set the `url` of `[ocr.remote]` in the configuration"))]
+    OcrRemoteNotConfigured,
+
+    #[error("Only the `tesseract` executable can create searchable PDFs")]
+    #[diagnostic(help("Use the `executable` engine for PDFs"))]
+    OcrPdfNeedsExecutable,
+
+    #[error("An I/O error occured: {}", .error)]
+    Io {
+        #[from]
+        error: std::io::Error,
+    },
+
+    #[error("The scan job has no output to save the pages to")]
+    NoOutput,
+
+    #[error("Scans with a bit depth of {} are not supported", .depth)]
+    #[diagnostic(help("Scan with a depth of 1, 8 or 16 bits"))]
+    UnsupportedBitDepth { depth: usize },
+
+    #[error("The scanner finished a three-pass scan without sending all three color planes")]
+    MissingColorPlane,
+
+    #[error("The scan was cancelled")]
+    ScanCancelled,
+
+    #[error("The document feeder is empty")]
+    #[diagnostic(help("Load the pages into the feeder, or scan from the flatbed"))]
+    FeederEmpty,
+
+    #[error("The mock scanner failed {} as it was told to", .stage)]
+    MockFailure { stage: &'static str },
+
+    #[error("The thread driving the scanners has stopped unexpectedly")]
+    DriverStopped,
+
+    #[error("The {:?} format can only hold a single page, use PDF or TIFF for multiple pages", .format)]
+    MultiPageUnsupported { format: Format },
+
+    #[error("The {:?} format cannot be written line by line, use JPEG or TIFF", .format)]
+    LineByLineUnsupported { format: Format },
+
+    #[error("The scanner does not know the number of lines in advance, which TIFF needs to be written line by line")]
+    UnknownPageHeight,
+
+    #[error("Could not encode the page as {:?}", .format)]
+    Encode {
+        format: Format,
+        #[source]
+        error: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("The scanner gave nonsensical values, or there is a bug. It was reported: {width}x{height}pixels with a\
+        bitdepth of {pixel_size} to fit into {buffer_size}. If the values make sense, please report it as a bug")]
+    InvalidImageSize {
+        width: u32,
+        height: u32,
+        buffer_size: usize,
+        pixel_size: u32,
+    },
+}
+
+impl Error {
+    /// A short name for the kind of error, after the SANE status it stands for, like `jammed` or `device_busy`
+    ///
+    /// Meant for metrics and logs, where the message varies too much. Errors without a SANE status are `other`.
+    pub fn status(&self) -> &'static str {
+        match self {
+            Error::DeviceBusy => "device_busy",
+            Error::AccessDenied => "access_denied",
+            Error::FeederJammed => "jammed",
+            Error::FeederEmpty => "no_docs",
+            Error::CoverOpen => "cover_open",
+            Error::DeviceIo => "io_error",
+            Error::OutOfMemory => "no_mem",
+            Error::Unsupported => "unsupported",
+            Error::ScanCancelled => "cancelled",
+            Error::OptionNotFound { .. }
+            | Error::InvalidValue { .. }
+            | Error::InvalidBool { .. }
+            | Error::InvalidNumber { .. }
+            | Error::NotAnInteger { .. }
+            | Error::WrongUnit { .. }
+            | Error::OutOfRange { .. }
+            | Error::NotAllowed { .. }
+            | Error::InvalidPsm { .. } => "inval",
+            _ => "other",
+        }
+    }
+}
@@ -0,0 +1,582 @@
+//! Describing a scan once and running it, so that every frontend resolves options and processes pages the same way
+
+use std::cell::Cell;
+use std::fs::File;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use image::DynamicImage;
+use image::ImageBuffer;
+use image::Luma;
+use image::Pixel;
+use image::Rgb;
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::backend::FrameFormat;
+use crate::backend::ScanBackend;
+use crate::backend::ScanDevice;
+use crate::backend::Unit;
+use crate::calibration::Calibration;
+use crate::decode::merge_planes;
+use crate::output::write_document;
+use crate::output::write_lines;
+use crate::output::Format;
+use crate::output::Page;
+use crate::output::PageSize;
+use crate::postprocess;
+use crate::postprocess::PostProcessing;
+use crate::postprocess::PostProcessor;
+use crate::progress;
+use crate::progress::ScanEvent;
+use crate::progress::Stage;
+use crate::scan::read_image_with_buffers;
+use crate::scan::resolution;
+use crate::scan::scanlines;
+use crate::scan::FrameBuffers;
+use crate::scan::Scanline;
+use crate::value::Value;
+use crate::Error;
+
+/// The color mode of a scan, as most backends name it
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Color,
+    Gray,
+    /// Black and white, one bit per pixel
+    Lineart,
+}
+
+impl Mode {
+    /// The value of the `mode` option selecting this mode
+    pub fn sane_name(self) -> &'static str {
+        match self {
+            Mode::Color => "Color",
+            Mode::Gray => "Gray",
+            Mode::Lineart => "Lineart",
+        }
+    }
+}
+
+/// A rectangle on the scan bed, in millimeters from its top left corner
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Area {
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A scan with everything needed to run it: the device, its options, the processing of the pages and where they go
+///
+/// ```no_run
+/// # fn main() -> miette::Result<()> {
+/// use scannrs_core::backend::sane::SaneBackend;
+/// use scannrs_core::job::Mode;
+/// use scannrs_core::job::ScanJob;
+/// use scannrs_core::output::Format;
+///
+/// let sane = SaneBackend::init()?;
+/// let job = ScanJob::new("pixma:04A91912")
+///     .resolution(300)
+///     .mode(Mode::Color)
+///     .output(Format::Png, "scan.png");
+/// if let Some(page) = job.scan(&sane)? {
+///     job.save(&[page])?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ScanJob {
+    device: String,
+    /// Later values of an option replace earlier ones
+    options: Vec<(String, Value)>,
+    calibration: Option<Calibration>,
+    processing: PostProcessing,
+    /// Run after the steps of `processing`
+    post_processors: Vec<Arc<dyn PostProcessor>>,
+    output: Option<(Format, PathBuf)>,
+}
+
+impl ScanJob {
+    pub fn new(device: impl Into<String>) -> ScanJob {
+        ScanJob {
+            device: device.into(),
+            options: Vec::new(),
+            calibration: None,
+            processing: PostProcessing::default(),
+            post_processors: Vec::new(),
+            output: None,
+        }
+    }
+
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+
+    /// The options set so far, each with its last value
+    pub fn options(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.options
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// Set an option by its name, text values are parsed like on the command line, e.g. `300dpi`
+    pub fn option(mut self, name: impl Into<String>, value: impl Into<Value>) -> ScanJob {
+        let name = name.into();
+        let value = value.into();
+        match self.options.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, v)) => *v = value,
+            None => self.options.push((name, value)),
+        }
+        self
+    }
+
+    /// Set several options, see [`ScanJob::option`]
+    pub fn with_options<N, V>(self, options: impl IntoIterator<Item = (N, V)>) -> ScanJob
+    where
+        N: Into<String>,
+        V: Into<Value>,
+    {
+        options
+            .into_iter()
+            .fold(self, |job, (name, value)| job.option(name, value))
+    }
+
+    /// The resolution in dots per inch
+    pub fn resolution(self, dpi: u32) -> ScanJob {
+        self.option("resolution", Value::with_unit(f64::from(dpi), Unit::Dpi))
+    }
+
+    pub fn mode(self, mode: Mode) -> ScanJob {
+        self.option("mode", mode.sane_name())
+    }
+
+    /// Only scan the given part of the scan bed
+    pub fn area_mm(self, area: Area) -> ScanJob {
+        let mm = |value| Value::with_unit(value, Unit::Mm);
+        self.option("tl-x", mm(area.left))
+            .option("tl-y", mm(area.top))
+            .option("br-x", mm(area.left + area.width))
+            .option("br-y", mm(area.top + area.height))
+    }
+
+    /// Correct every page with the calibration of the device
+    pub fn calibration(mut self, calibration: Calibration) -> ScanJob {
+        self.calibration = Some(calibration);
+        self
+    }
+
+    pub fn processing(mut self, processing: PostProcessing) -> ScanJob {
+        self.processing = processing;
+        self
+    }
+
+    /// Add a step to the post-processing, steps run in the order they are added after the ones of
+    /// [`ScanJob::processing`]
+    pub fn post_processor(mut self, processor: Arc<dyn PostProcessor>) -> ScanJob {
+        self.post_processors.push(processor);
+        self
+    }
+
+    /// Where [`ScanJob::save`] writes the pages to
+    pub fn output(mut self, format: Format, path: impl Into<PathBuf>) -> ScanJob {
+        self.output = Some((format, path.into()));
+        self
+    }
+
+    /// Check that the device has every option of the job and that the values fit their types
+    pub fn validate(&self, device: &dyn ScanDevice) -> miette::Result<()> {
+        let device_options = device.options()?;
+        for (name, value) in &self.options {
+            let option = device_options
+                .iter()
+                .find(|o| o.name == *name)
+                .ok_or_else(|| Error::OptionNotFound {
+                    name: self.device.clone(),
+                    option: name.clone(),
+                })?;
+            value.check_type(option)?;
+        }
+
+        Ok(())
+    }
+
+    /// Open the device and set the options of the job
+    ///
+    /// Options depend on each other (e.g. the available depths depend on the mode), so they are set in the order the
+    /// device lists them, which puts the ones others depend on first.
+    pub fn open(&self, backend: &dyn ScanBackend) -> miette::Result<Box<dyn ScanDevice>> {
+        let mut device = backend.open(&self.device)?;
+        self.validate(device.as_ref())?;
+
+        for option in device.options()? {
+            let Some((_, value)) = self.options.iter().find(|(name, _)| *name == option.name)
+            else {
+                continue;
+            };
+            let Some(value) = value.to_option_value(&option).into_diagnostic()? else {
+                continue;
+            };
+
+            device.set_option(&option, value).with_context(|| {
+                format!(
+                    "While setting '{}' of scanner '{}'",
+                    option.name, self.device
+                )
+            })?;
+        }
+
+        Ok(device)
+    }
+
+    /// Scan a single page, `None` if it was dropped as blank by the post-processing
+    pub fn scan(&self, backend: &dyn ScanBackend) -> miette::Result<Option<Page>> {
+        self.scan_with_progress(backend, &mut progress::ignore)
+    }
+
+    /// Like [`ScanJob::scan`], reporting the progress of the scan to `progress`, see [`ScanEvent`]
+    ///
+    /// Returning [`ControlFlow::Break`] from `progress` cancels the scan.
+    pub fn scan_with_progress(
+        &self,
+        backend: &dyn ScanBackend,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+    ) -> miette::Result<Option<Page>> {
+        let mut device = self.open(backend)?;
+        self.scan_page(device.as_mut(), 1, &mut FrameBuffers::default(), progress)
+    }
+
+    /// Scan a single page into the output of the job, encoding it while the scanner still sends the rest of it
+    ///
+    /// Only JPEG and TIFF pages without post-processing, which needs the whole page, are written line by line, see
+    /// [`write_lines`]. Other pages, three-pass scans and TIFF pages from scanners that do not know their height are
+    /// read as a whole and saved like with [`ScanJob::save`].
+    /// Returns the size of the page, `None` if it was dropped as blank.
+    pub fn scan_to_output(
+        &self,
+        backend: &dyn ScanBackend,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+    ) -> miette::Result<Option<PageSize>> {
+        let mut device = self.open(backend)?;
+        self.write_page(device.as_mut(), progress)
+    }
+
+    /// Like [`ScanJob::scan_to_output`], with a device opened with [`ScanJob::open`]
+    pub fn write_page(
+        &self,
+        device: &mut dyn ScanDevice,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+    ) -> miette::Result<Option<PageSize>> {
+        let (format, path) = self.output.as_ref().ok_or(Error::NoOutput)?;
+        let processed = !self.processing.steps().is_empty() || !self.post_processors.is_empty();
+        if processed || !matches!(format, Format::Jpeg | Format::Tiff) {
+            let page = self.scan_page(device, 1, &mut FrameBuffers::default(), progress)?;
+            if let Some(page) = &page {
+                self.save(std::slice::from_ref(page))?;
+            }
+            return Ok(page.as_ref().map(PageSize::of));
+        }
+
+        if progress(ScanEvent::PageStarted { page: 1 }).is_break() {
+            return Err(Error::ScanCancelled.into());
+        }
+        let dpi = resolution(device)?;
+        // Lines are written as they arrive, a failed scan must not leave half a page behind
+        write_atomically(path, |file| {
+            let mut scanlines = scanlines(device);
+            let frame = Cell::new(None);
+            let mut lines = std::iter::from_fn(|| {
+                let line = scanlines.next()?;
+                frame.set(scanlines.parameters());
+                Some(line.and_then(|line| {
+                    let Some(params) = frame.get() else {
+                        return Ok(line);
+                    };
+                    let started = ScanEvent::FrameStarted {
+                        format: params.format,
+                        lines: params.lines,
+                    };
+                    let read = ScanEvent::Read {
+                        bytes: (line.number + 1) * params.bytes_per_line,
+                        total: params.lines.map(|lines| lines * params.bytes_per_line),
+                        lines: line.number + 1,
+                    };
+                    if (line.number == 0 && progress(started).is_break())
+                        || progress(read).is_break()
+                    {
+                        return Err(Error::ScanCancelled.into());
+                    }
+                    Ok(line)
+                }))
+            });
+
+            let first = lines.next().transpose()?;
+            let whole = frame.get().is_some_and(|params| {
+                !matches!(params.format, FrameFormat::Gray | FrameFormat::Rgb)
+                    || (*format == Format::Tiff && params.lines.is_none())
+            });
+            if whole {
+                let mut image = assemble(first.into_iter().map(Ok).chain(lines))?;
+                if let Some(calibration) = &self.calibration {
+                    calibration.apply(&mut image);
+                }
+                let page = Page { image, dpi };
+                write_document(file, *format, std::slice::from_ref(&page))?;
+                let _ = progress(ScanEvent::PageFinished {
+                    page: 1,
+                    kept: true,
+                });
+                return Ok(Some(PageSize::of(&page)));
+            }
+
+            let height = frame
+                .get()
+                .and_then(|params| params.lines)
+                .map(|lines| lines as u32);
+            let calibrated = |line: miette::Result<Scanline>| -> miette::Result<DynamicImage> {
+                let mut image = line?.image;
+                if let Some(calibration) = &self.calibration {
+                    calibration.apply(&mut image);
+                }
+                Ok(image)
+            };
+            let size = write_lines(
+                file,
+                *format,
+                dpi,
+                height,
+                &mut first.into_iter().map(Ok).chain(lines).map(calibrated),
+            )?;
+            let _ = progress(ScanEvent::PageFinished {
+                page: 1,
+                kept: true,
+            });
+
+            Ok(Some(size))
+        })
+    }
+
+    /// Scan pages from the document feeder until it is empty, handing every page to `page_done` as soon as it is read
+    ///
+    /// Scanners signal an empty feeder by refusing to start another page, so only an error on the first page or a
+    /// cancellation is returned.
+    /// Returns the number of pages handed to `page_done`.
+    pub fn scan_feeder_with_progress(
+        &self,
+        backend: &dyn ScanBackend,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+        page_done: &mut dyn FnMut(Page),
+    ) -> miette::Result<usize> {
+        let mut device = self.open(backend)?;
+
+        let cancelled = Cell::new(false);
+        let mut progress = |event| {
+            let flow = progress(event);
+            cancelled.set(flow.is_break());
+            flow
+        };
+
+        // Shared by all pages, which are usually the same size
+        let mut buffers = FrameBuffers::default();
+        let mut read = 0;
+        let mut kept = 0;
+        loop {
+            let page = match self.scan_page(device.as_mut(), read + 1, &mut buffers, &mut progress)
+            {
+                Ok(page) => page,
+                Err(error) if read == 0 || cancelled.get() => return Err(error),
+                Err(_) => break,
+            };
+            read += 1;
+
+            if let Some(page) = page {
+                page_done(page);
+                kept += 1;
+            }
+        }
+
+        Ok(kept)
+    }
+
+    /// Read and process the page with the given number, reporting its start and end to `progress`
+    fn scan_page(
+        &self,
+        device: &mut dyn ScanDevice,
+        number: usize,
+        buffers: &mut FrameBuffers,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+    ) -> miette::Result<Option<Page>> {
+        if progress(ScanEvent::PageStarted { page: number }).is_break() {
+            return Err(Error::ScanCancelled.into());
+        }
+
+        let page = self.read_page_with_buffers(device, buffers, progress)?;
+        let page = self.process_with_progress(page, &mut |stage| {
+            let _ = progress(ScanEvent::Processing { stage });
+        });
+
+        let _ = progress(ScanEvent::PageFinished {
+            page: number,
+            kept: page.is_some(),
+        });
+        Ok(page)
+    }
+
+    /// Read a single page from a device opened with [`ScanJob::open`] and calibrate it, without post-processing it
+    pub fn read_page(
+        &self,
+        device: &mut dyn ScanDevice,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+    ) -> miette::Result<Page> {
+        self.read_page_with_buffers(device, &mut FrameBuffers::default(), progress)
+    }
+
+    /// Like [`ScanJob::read_page`], reading the frames into `buffers`, see [`FrameBuffers`]
+    pub fn read_page_with_buffers(
+        &self,
+        device: &mut dyn ScanDevice,
+        buffers: &mut FrameBuffers,
+        progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+    ) -> miette::Result<Page> {
+        let mut image = read_image_with_buffers(device, buffers, progress)?;
+        if let Some(calibration) = &self.calibration {
+            let _ = progress(ScanEvent::Processing {
+                stage: Stage::Calibration,
+            });
+            calibration.apply(&mut image);
+        }
+
+        Ok(Page {
+            image,
+            dpi: resolution(device)?,
+        })
+    }
+
+    /// Post-process a page, `None` if it is dropped as blank
+    pub fn process(&self, page: Page) -> Option<Page> {
+        self.process_with_progress(page, &mut |_| {})
+    }
+
+    /// Like [`ScanJob::process`], telling `progress` about every step before it runs
+    pub fn process_with_progress(
+        &self,
+        page: Page,
+        progress: &mut dyn FnMut(Stage),
+    ) -> Option<Page> {
+        let mut steps = self.processing.steps();
+        steps.extend(self.post_processors.iter().cloned());
+
+        match postprocess::run(&steps, page.image, progress) {
+            Some(image) => Some(Page {
+                image,
+                dpi: page.dpi,
+            }),
+            None => {
+                tracing::info!("The post-processing dropped the page");
+                None
+            }
+        }
+    }
+
+    /// Write the pages into a single document at the output of the job
+    pub fn save(&self, pages: &[Page]) -> miette::Result<&Path> {
+        let (format, path) = self.output.as_ref().ok_or(Error::NoOutput)?;
+
+        write_atomically(path, |file| write_document(file, *format, pages))?;
+
+        Ok(path)
+    }
+}
+
+/// Write to a file next to `path` that replaces it once `write` succeeded, or is removed if it failed
+fn write_atomically<T>(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> miette::Result<T>,
+) -> miette::Result<T> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    let partial = path.with_file_name(name);
+
+    let written = File::create(&partial)
+        .into_diagnostic()
+        .with_context(|| format!("Tried to write to file at {}", partial.display()))
+        .and_then(|mut file| write(&mut file));
+    let renamed = written.and_then(|value| {
+        std::fs::rename(&partial, path)
+            .into_diagnostic()
+            .with_context(|| format!("Tried to write to file at {}", path.display()))?;
+        Ok(value)
+    });
+    if renamed.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    renamed
+}
+
+/// Put the lines of a scan back together into a page, merging the frames of three-pass scans like
+/// [`crate::scan::read_image`] does
+fn assemble(lines: impl Iterator<Item = miette::Result<Scanline>>) -> miette::Result<DynamicImage> {
+    let mut frames: Vec<(FrameFormat, Vec<DynamicImage>)> = Vec::new();
+    for line in lines {
+        let line = line?;
+        match frames.last_mut() {
+            Some((_, frame)) if line.number > 0 => frame.push(line.image),
+            _ => frames.push((line.format, vec![line.image])),
+        }
+    }
+
+    let mut planes: [Option<DynamicImage>; 3] = [None, None, None];
+    for (format, lines) in frames {
+        let image: DynamicImage = match lines.first() {
+            Some(DynamicImage::ImageLuma16(_)) => {
+                stack::<Luma<u16>>(&lines, |line| line.to_luma16().into_raw())?.into()
+            }
+            Some(DynamicImage::ImageRgb8(_)) => {
+                stack::<Rgb<u8>>(&lines, |line| line.to_rgb8().into_raw())?.into()
+            }
+            Some(DynamicImage::ImageRgb16(_)) => {
+                stack::<Rgb<u16>>(&lines, |line| line.to_rgb16().into_raw())?.into()
+            }
+            _ => stack::<Luma<u8>>(&lines, |line| line.to_luma8().into_raw())?.into(),
+        };
+        let plane = match format {
+            FrameFormat::Gray | FrameFormat::Rgb => return Ok(image),
+            FrameFormat::Red => 0,
+            FrameFormat::Green => 1,
+            FrameFormat::Blue => 2,
+        };
+        planes[plane] = Some(image);
+    }
+
+    match planes {
+        [Some(red), Some(green), Some(blue)] => Ok(merge_planes(&red, &green, &blue)),
+        _ => Err(Error::MissingColorPlane.into()),
+    }
+}
+
+/// The lines of a frame as a single image
+fn stack<P: Pixel>(
+    lines: &[DynamicImage],
+    samples: fn(&DynamicImage) -> Vec<P::Subpixel>,
+) -> miette::Result<ImageBuffer<P, Vec<P::Subpixel>>> {
+    let width = lines.first().map_or(0, DynamicImage::width);
+    let height = lines.len() as u32;
+    let data = lines.iter().flat_map(samples).collect::<Vec<_>>();
+    let buffer_size = data.len();
+
+    ImageBuffer::from_raw(width, height, data)
+        .ok_or(Error::InvalidImageSize {
+            width,
+            height,
+            buffer_size,
+            pixel_size: u32::from(P::CHANNEL_COUNT),
+        })
+        .map_err(miette::Report::from)
+}
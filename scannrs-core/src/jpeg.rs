@@ -0,0 +1,441 @@
+//! A baseline JPEG encoder that takes a page line by line, see [`crate::output::write_lines`]
+//!
+//! Every eight lines form a row of blocks that is encoded as soon as they arrived, so only those have to be kept. Each
+//! row ends with a restart marker, which lets decoders pick up again after damaged data. As the height is only known
+//! once the last line arrived, the header is written with a height of 0 that is filled in at the end.
+
+use std::io;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+
+/// The quality the `image` crate encodes with, so that pages look the same however they were written
+const QUALITY: u32 = 75;
+
+/// The position in a block of each coefficient, in the order they are written
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// The example tables of Annex K of the JPEG standard, which most encoders use
+const LUMA_QUANTIZATION: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMA_QUANTIZATION: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+const LUMA_DC_LENGTHS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const CHROMA_DC_LENGTHS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const LUMA_AC_LENGTHS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const LUMA_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+const CHROMA_AC_LENGTHS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMA_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa,
+];
+
+/// The largest width and height JPEG can describe
+const MAX_SIZE: u32 = u16::MAX as u32;
+
+/// The code and its length in bits for every value of a Huffman table
+struct Huffman([(u16, u8); 256]);
+
+impl Huffman {
+    /// The codes for the values, given the number of codes of every length from 1 to 16 bits like in the file
+    fn new(lengths: &[u8; 16], values: &[u8]) -> Huffman {
+        let mut codes = [(0, 0); 256];
+        let mut code = 0u16;
+        let mut values = values.iter();
+        for (length, count) in (1..=16).zip(lengths) {
+            for value in values.by_ref().take(usize::from(*count)) {
+                codes[usize::from(*value)] = (code, length);
+                code += 1;
+            }
+            code <<= 1;
+        }
+
+        Huffman(codes)
+    }
+}
+
+/// The tables of a component, the luma and the chroma components each share theirs
+struct Tables {
+    /// The quantization of every coefficient, including the scaling of the DCT
+    quantization: [f32; 64],
+    dc: Huffman,
+    ac: Huffman,
+}
+
+impl Tables {
+    fn new(quantization: &[u8; 64], dc: (&[u8; 16], &[u8]), ac: (&[u8; 16], &[u8])) -> Tables {
+        Tables {
+            quantization: quantization.map(f32::from),
+            dc: Huffman::new(dc.0, dc.1),
+            ac: Huffman::new(ac.0, ac.1),
+        }
+    }
+}
+
+/// The quantization table scaled to [`QUALITY`] the way libjpeg does it
+fn scaled(table: &[u8; 64]) -> [u8; 64] {
+    let scale = if QUALITY < 50 {
+        5000 / QUALITY
+    } else {
+        200 - QUALITY * 2
+    };
+    table.map(|step| ((u32::from(step) * scale + 50) / 100).clamp(1, 255) as u8)
+}
+
+/// Writes the entropy coded data, escaping every `0xFF` byte
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl Bits {
+    fn put(&mut self, value: u16, length: u8) {
+        let length = u32::from(length);
+        self.buffer = (self.buffer << length) | (u32::from(value) & ((1 << length) - 1));
+        self.count += length;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.buffer >> self.count) as u8;
+            self.bytes.push(byte);
+            if byte == 0xFF {
+                self.bytes.push(0);
+            }
+        }
+        self.buffer &= (1 << self.count) - 1;
+    }
+
+    /// Fill the last byte with ones, as the standard asks for before markers
+    fn pad(&mut self) {
+        let padding = (8 - self.count % 8) % 8;
+        self.put((1 << padding) - 1, padding as u8);
+    }
+}
+
+/// The size category of a coefficient and the bits that encode it within the category
+fn category(value: i32) -> (u8, u16) {
+    let size = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 { value - 1 } else { value };
+    (size, bits as u16)
+}
+
+pub(crate) struct JpegWriter<W: Write + Seek> {
+    writer: W,
+    width: usize,
+    /// 1 for gray pages, 3 for color pages
+    components: usize,
+    /// Where the height is written in the header
+    height_at: u64,
+    tables: [Tables; 2],
+    /// `cosines[u][x]` is the factor of sample `x` in coefficient `u` of the DCT
+    cosines: [[f32; 8]; 8],
+    /// The samples of the lines of the current row of blocks, a plane of eight lines per component
+    planes: Vec<f32>,
+    /// Lines in `planes`
+    pending: usize,
+    lines: u32,
+    /// Rows of blocks written so far
+    rows: u32,
+    /// The DC coefficient of the last block of every component, which the next one is encoded relative to
+    predictions: [i32; 3],
+    bits: Bits,
+}
+
+impl<W: Write + Seek> JpegWriter<W> {
+    /// Write the header of a page `width` pixels wide with the given number of `components`, 1 for gray and 3 for RGB
+    pub(crate) fn new(
+        mut writer: W,
+        width: u32,
+        components: usize,
+        dpi: f32,
+    ) -> io::Result<JpegWriter<W>> {
+        if width == 0 || width > MAX_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("JPEG cannot be {width} pixels wide"),
+            ));
+        }
+        let luma = scaled(&LUMA_QUANTIZATION);
+        let chroma = scaled(&CHROMA_QUANTIZATION);
+        // One row of blocks per restart interval
+        let interval = width.div_ceil(8) as u16;
+        let density = (dpi.round() as u32).clamp(1, MAX_SIZE) as u16;
+
+        let mut header = vec![0xFF, 0xD8];
+        segment(&mut header, 0xE0, &{
+            let mut jfif = b"JFIF\0\x01\x01\x01".to_vec();
+            jfif.extend(density.to_be_bytes());
+            jfif.extend(density.to_be_bytes());
+            jfif.extend([0, 0]);
+            jfif
+        });
+        for (id, table) in [luma, chroma].iter().enumerate().take(components.min(2)) {
+            let mut dqt = vec![id as u8];
+            dqt.extend(ZIGZAG.map(|position| table[position]));
+            segment(&mut header, 0xDB, &dqt);
+        }
+        let mut frame = vec![8, 0, 0];
+        frame.extend((width as u16).to_be_bytes());
+        frame.push(components as u8);
+        for component in 0..components {
+            frame.extend([component as u8 + 1, 0x11, u8::from(component > 0)]);
+        }
+        // After the marker, the length and the precision
+        let height_at = writer.stream_position()? + header.len() as u64 + 5;
+        segment(&mut header, 0xC0, &frame);
+        let huffman = [
+            (0x00, &LUMA_DC_LENGTHS, &DC_VALUES[..]),
+            (0x10, &LUMA_AC_LENGTHS, &LUMA_AC_VALUES[..]),
+            (0x01, &CHROMA_DC_LENGTHS, &DC_VALUES[..]),
+            (0x11, &CHROMA_AC_LENGTHS, &CHROMA_AC_VALUES[..]),
+        ];
+        for (class, lengths, values) in huffman.into_iter().take(components.min(2) * 2) {
+            let mut dht = vec![class];
+            dht.extend(lengths);
+            dht.extend(values);
+            segment(&mut header, 0xC4, &dht);
+        }
+        segment(&mut header, 0xDD, &interval.to_be_bytes());
+        let mut scan = vec![components as u8];
+        for component in 0..components {
+            scan.extend([component as u8 + 1, if component > 0 { 0x11 } else { 0x00 }]);
+        }
+        scan.extend([0, 63, 0]);
+        segment(&mut header, 0xDA, &scan);
+        writer.write_all(&header)?;
+
+        let mut cosines = [[0.0; 8]; 8];
+        for (u, row) in cosines.iter_mut().enumerate() {
+            let scale = if u == 0 { 0.5 / 2f32.sqrt() } else { 0.5 };
+            for (x, cosine) in row.iter_mut().enumerate() {
+                *cosine =
+                    scale * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos();
+            }
+        }
+
+        Ok(JpegWriter {
+            writer,
+            width: width as usize,
+            components,
+            height_at,
+            tables: [
+                Tables::new(
+                    &luma,
+                    (&LUMA_DC_LENGTHS, &DC_VALUES),
+                    (&LUMA_AC_LENGTHS, &LUMA_AC_VALUES),
+                ),
+                Tables::new(
+                    &chroma,
+                    (&CHROMA_DC_LENGTHS, &DC_VALUES),
+                    (&CHROMA_AC_LENGTHS, &CHROMA_AC_VALUES),
+                ),
+            ],
+            cosines,
+            planes: vec![0.0; width as usize * 8 * components],
+            pending: 0,
+            lines: 0,
+            rows: 0,
+            predictions: [0; 3],
+            bits: Bits::default(),
+        })
+    }
+
+    /// Add the next line, with the samples of every pixel interleaved like in an `image` buffer
+    pub(crate) fn write_line(&mut self, samples: &[u8]) -> io::Result<()> {
+        if samples.len() != self.width * self.components {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The line does not have the width of the page",
+            ));
+        }
+        if self.lines >= MAX_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("JPEG cannot be more than {MAX_SIZE} pixels high"),
+            ));
+        }
+
+        let size = self.width * 8;
+        let line = self.pending * self.width;
+        for (x, pixel) in samples.chunks_exact(self.components).enumerate() {
+            let converted = match *pixel {
+                [r, g, b] => {
+                    let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+                    [
+                        0.299 * r + 0.587 * g + 0.114 * b,
+                        -0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0,
+                        0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0,
+                    ]
+                }
+                _ => [f32::from(pixel[0]), 0.0, 0.0],
+            };
+            for (component, sample) in converted.iter().take(self.components).enumerate() {
+                self.planes[component * size + line + x] = sample - 128.0;
+            }
+        }
+        self.pending += 1;
+        self.lines += 1;
+
+        if self.pending == 8 {
+            self.write_row()?;
+        }
+        Ok(())
+    }
+
+    /// Encode the lines in `planes` as a row of blocks, repeating the last line if there are less than eight
+    fn write_row(&mut self) -> io::Result<()> {
+        let size = self.width * 8;
+        for component in 0..self.components {
+            let plane = &mut self.planes[component * size..][..size];
+            let (filled, missing) = plane.split_at_mut(self.pending * self.width);
+            let last = &filled[filled.len() - self.width..];
+            for line in missing.chunks_exact_mut(self.width) {
+                line.copy_from_slice(last);
+            }
+        }
+
+        if self.rows > 0 {
+            self.bits.pad();
+            self.bits
+                .bytes
+                .extend([0xFF, 0xD0 + ((self.rows - 1) % 8) as u8]);
+            self.predictions = [0; 3];
+        }
+        for column in 0..self.width.div_ceil(8) {
+            for component in 0..self.components {
+                let plane = &self.planes[component * size..][..size];
+                let mut block = [0.0; 64];
+                for (y, row) in block.chunks_exact_mut(8).enumerate() {
+                    for (x, sample) in row.iter_mut().enumerate() {
+                        // The last column is repeated in blocks that reach past the right edge
+                        let x = (column * 8 + x).min(self.width - 1);
+                        *sample = plane[y * self.width + x];
+                    }
+                }
+                self.write_block(&block, component);
+            }
+        }
+
+        self.writer.write_all(&self.bits.bytes)?;
+        self.bits.bytes.clear();
+        self.pending = 0;
+        self.rows += 1;
+        Ok(())
+    }
+
+    fn write_block(&mut self, block: &[f32; 64], component: usize) {
+        let tables = &self.tables[usize::from(component > 0)];
+
+        // The DCT along the rows and then along the columns
+        let mut rows = [0.0; 64];
+        for y in 0..8 {
+            for u in 0..8 {
+                rows[y * 8 + u] = (0..8).map(|x| self.cosines[u][x] * block[y * 8 + x]).sum();
+            }
+        }
+        let mut coefficients = [0; 64];
+        for v in 0..8 {
+            for u in 0..8 {
+                let coefficient = (0..8)
+                    .map(|y| self.cosines[v][y] * rows[y * 8 + u])
+                    .sum::<f32>();
+                coefficients[v * 8 + u] =
+                    (coefficient / tables.quantization[v * 8 + u]).round() as i32;
+            }
+        }
+
+        let dc = coefficients[0];
+        let (size, bits) = category(dc - self.predictions[component]);
+        self.predictions[component] = dc;
+        let (code, length) = tables.dc.0[usize::from(size)];
+        self.bits.put(code, length);
+        self.bits.put(bits, size);
+
+        let mut zeros = 0;
+        for &position in &ZIGZAG[1..] {
+            let coefficient = coefficients[position];
+            if coefficient == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros > 15 {
+                let (code, length) = tables.ac.0[0xF0];
+                self.bits.put(code, length);
+                zeros -= 16;
+            }
+            let (size, bits) = category(coefficient);
+            let (code, length) = tables.ac.0[usize::from(zeros << 4 | size)];
+            self.bits.put(code, length);
+            self.bits.put(bits, size);
+            zeros = 0;
+        }
+        if zeros > 0 {
+            let (code, length) = tables.ac.0[0x00];
+            self.bits.put(code, length);
+        }
+    }
+
+    /// Encode the remaining lines, end the image and fill in its height, which is returned
+    pub(crate) fn finish(mut self) -> io::Result<u32> {
+        if self.lines == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The page has no lines",
+            ));
+        }
+        if self.pending > 0 {
+            self.write_row()?;
+        }
+        self.bits.pad();
+        self.bits.bytes.extend([0xFF, 0xD9]);
+        self.writer.write_all(&self.bits.bytes)?;
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.height_at))?;
+        self.writer.write_all(&(self.lines as u16).to_be_bytes())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok(self.lines)
+    }
+}
+
+/// Append a marker segment with its length
+fn segment(header: &mut Vec<u8>, marker: u8, content: &[u8]) {
+    header.extend([0xFF, marker]);
+    header.extend((content.len() as u16 + 2).to_be_bytes());
+    header.extend(content);
+}
@@ -0,0 +1,37 @@
+//! The scanning functionality of scannrs, for applications that want to embed it instead of calling the CLI
+//!
+//! - [`job`] describes a scan with its options and processing and runs it, the simplest way to scan
+//! - [`backend`] finds and opens scanners, [`backend::sane`] through SANE, `backend::escl` and `backend::wsd` over the
+//!   network, `backend::wia` through WIA on Windows and [`backend::mock`] makes some up for tests
+//! - [`device`] describes scanners and their options for output, `discovery` finds scanners on the network with the
+//!   `mdns` feature
+//! - [`value`] parses option values like `300dpi` and checks them against the options of a scanner
+//! - [`scan`] sets options, runs scans and decodes the frames the scanner sends into images, or line by line as they arrive
+//! - [`calibration`] and [`postprocess`] clean up scanned pages
+//! - [`progress`] describes the events a running scan reports
+//! - [`output`] encodes pages into JPEG, PNG, TIFF or PDF documents, JPEG and TIFF also line by line as they are scanned
+//! - [`ocr`] recognizes the text on pages with tesseract
+//!
+//! Scanners are accessed through a [`backend::ScanBackend`], like [`backend::sane::SaneBackend::init`]. Backends are
+//! not thread-safe, so all calls for a device have to be made from the same thread. With the `async` feature, `driver`
+//! runs scans on such a thread for async code.
+
+pub mod backend;
+pub mod calibration;
+pub mod decode;
+pub mod device;
+#[cfg(feature = "mdns")]
+pub mod discovery;
+#[cfg(feature = "async")]
+pub mod driver;
+mod error;
+pub mod job;
+mod jpeg;
+pub mod ocr;
+pub mod output;
+pub mod postprocess;
+pub mod progress;
+pub mod scan;
+pub mod value;
+
+pub use error::Error;
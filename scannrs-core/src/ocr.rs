@@ -0,0 +1,315 @@
+#[cfg(feature = "remote-ocr")]
+use std::io::Read;
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::Error;
+
+/// The output formats of the text recognition
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrFormat {
+    /// Plain text
+    Txt,
+    /// HTML with the position of every recognized word
+    Hocr,
+    /// The image as a PDF with an invisible, searchable text layer
+    Pdf,
+}
+
+impl OcrFormat {
+    fn tesseract_config(self) -> &'static str {
+        match self {
+            OcrFormat::Txt => "txt",
+            OcrFormat::Hocr => "hocr",
+            OcrFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// How tesseract is run
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OcrEngine {
+    /// The `tesseract` executable found in `$PATH`
+    #[default]
+    Executable,
+    /// libtesseract linked into the program, needs the `tesseract` feature and cannot create PDFs
+    Library,
+    /// The service of [`OcrOptions::remote`], needs the `remote-ocr` feature
+    Remote,
+}
+
+/// The highest page segmentation mode of tesseract, `13` treats the image as a single line of raw text
+pub const MAX_PSM: u8 = 13;
+
+/// How the text is recognized
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OcrOptions {
+    /// The tesseract languages of the text, like `eng` or `deu+eng`
+    pub lang: String,
+    /// The page segmentation mode of tesseract, from `0` to [`MAX_PSM`], tesseract picks one if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub psm: Option<u8>,
+    pub engine: OcrEngine,
+    /// Where the `remote` engine sends the images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<RemoteOcr>,
+}
+
+/// An OCR service reached over HTTP, self-hosted or in the cloud
+///
+/// The image is POSTed to the URL with its languages and format as the `lang` and `format` query parameters, like
+/// `https://ocr.example.com/recognize?lang=deu+eng&format=txt`, and the body of the response is the result. A
+/// `token` is sent as `Authorization: Bearer <token>`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteOcr {
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Default for OcrOptions {
+    fn default() -> Self {
+        OcrOptions {
+            lang: String::from("eng"),
+            psm: None,
+            engine: OcrEngine::default(),
+            remote: None,
+        }
+    }
+}
+
+/// The version of the installed tesseract executable, if there is one
+pub fn tesseract_version() -> Option<String> {
+    let output = Command::new("tesseract").arg("--version").output().ok()?;
+
+    // Older versions print their version on stderr
+    let text = if output.stdout.is_empty() {
+        output.stderr
+    } else {
+        output.stdout
+    };
+
+    String::from_utf8_lossy(&text)
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+}
+
+/// The languages tesseract has data for, if the executable can tell
+pub fn tesseract_languages() -> Option<Vec<String>> {
+    let output = Command::new("tesseract")
+        .arg("--list-langs")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    // The first line tells where the data is, like `List of available languages in "/usr/share/tessdata/" (2):`
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .skip(1)
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+    )
+}
+
+/// Recognize the text in an encoded image (PNG, JPEG, TIFF, ...) using tesseract
+pub fn recognize(image: &[u8], options: &OcrOptions, format: OcrFormat) -> miette::Result<Vec<u8>> {
+    if let Some(psm) = options.psm.filter(|psm| *psm > MAX_PSM) {
+        return Err(Error::InvalidPsm { psm }.into());
+    }
+
+    match options.engine {
+        OcrEngine::Executable => recognize_with_executable(image, options, format),
+        OcrEngine::Library => recognize_with_library(image, options, format),
+        OcrEngine::Remote => match &options.remote {
+            Some(remote) => recognize_remotely(image, &options.lang, remote, format),
+            None => Err(Error::OcrRemoteNotConfigured.into()),
+        },
+    }
+}
+
+fn recognize_with_executable(
+    image: &[u8],
+    options: &OcrOptions,
+    format: OcrFormat,
+) -> miette::Result<Vec<u8>> {
+    let mut command = Command::new("tesseract");
+    command.args(["-", "stdout", "-l", &options.lang]);
+    if let Some(psm) = options.psm {
+        command.args(["--psm", &psm.to_string()]);
+    }
+    let mut child = command
+        .arg(format.tesseract_config())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => Error::TesseractNotFound,
+            _ => Error::Io { error },
+        })?;
+
+    let mut stdin = child.stdin.take().ok_or(Error::TesseractNotFound)?;
+    let image = image.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&image));
+
+    let output = child
+        .wait_with_output()
+        .into_diagnostic()
+        .context("While waiting for tesseract to finish")?;
+    // Tesseract might stop reading early when it fails, the actual reason is in its output
+    let _ = writer.join();
+
+    if !output.status.success() {
+        // Tesseract only says it failed to load the data, the languages it has tell which one is missing
+        if let Some(installed) = tesseract_languages() {
+            if let Some(lang) = missing_language(&options.lang, &installed) {
+                return Err(Error::OcrLanguageMissing {
+                    lang,
+                    installed: installed.join(", "),
+                }
+                .into());
+            }
+        }
+
+        return Err(Error::OcrFailed {
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        }
+        .into());
+    }
+
+    Ok(output.stdout)
+}
+
+/// The first of the `+` separated languages that is not installed
+fn missing_language(lang: &str, installed: &[String]) -> Option<String> {
+    lang.split('+')
+        .find(|lang| !installed.iter().any(|installed| installed == lang))
+        .map(str::to_string)
+}
+
+#[cfg(feature = "tesseract")]
+fn recognize_with_library(
+    image: &[u8],
+    options: &OcrOptions,
+    format: OcrFormat,
+) -> miette::Result<Vec<u8>> {
+    let failed = |error: &dyn std::fmt::Display| Error::OcrFailed {
+        message: error.to_string(),
+    };
+
+    if format == OcrFormat::Pdf {
+        return Err(Error::OcrPdfNeedsExecutable.into());
+    }
+
+    // Loading fails only for missing or broken language data once the library is linked
+    let mut tesseract = tesseract::Tesseract::new(None, Some(&options.lang)).map_err(|_| {
+        Error::OcrLanguageMissing {
+            lang: options.lang.clone(),
+            installed: tesseract_languages().unwrap_or_default().join(", "),
+        }
+    })?;
+    if let Some(psm) = options.psm {
+        tesseract = tesseract
+            .set_variable("tessedit_pageseg_mode", &psm.to_string())
+            .map_err(|error| failed(&error))
+            .into_diagnostic()?;
+    }
+    let mut tesseract = tesseract
+        .set_image_from_mem(image)
+        .map_err(|error| failed(&error))
+        .into_diagnostic()?;
+
+    let text = match format {
+        OcrFormat::Hocr => tesseract.get_hocr_text(0),
+        _ => tesseract.get_text(),
+    };
+    text.map(String::into_bytes)
+        .map_err(|error| failed(&error))
+        .into_diagnostic()
+}
+
+#[cfg(not(feature = "tesseract"))]
+fn recognize_with_library(
+    _image: &[u8],
+    _options: &OcrOptions,
+    _format: OcrFormat,
+) -> miette::Result<Vec<u8>> {
+    Err(Error::OcrLibraryNotBuilt.into())
+}
+
+/// How long the remote service may take for a page, which is long for batches of large scans on a busy service
+#[cfg(feature = "remote-ocr")]
+const REMOTE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(120);
+
+#[cfg(feature = "remote-ocr")]
+fn recognize_remotely(
+    image: &[u8],
+    lang: &str,
+    remote: &RemoteOcr,
+    format: OcrFormat,
+) -> miette::Result<Vec<u8>> {
+    let mut request = ureq::AgentBuilder::new()
+        .user_agent(concat!("scannrs/", env!("CARGO_PKG_VERSION")))
+        .timeout(REMOTE_TIMEOUT)
+        .build()
+        .post(&remote.url)
+        .query("lang", lang)
+        .query("format", format.tesseract_config())
+        .set("Content-Type", "application/octet-stream");
+    if let Some(token) = &remote.token {
+        request = request.set("Authorization", &format!("Bearer {token}"));
+    }
+
+    let response = match request.send_bytes(image) {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => {
+            let message = response.into_string().unwrap_or_default();
+            return Err(Error::OcrFailed {
+                message: format!("{} answered {status}: {}", remote.url, message.trim()),
+            }
+            .into());
+        }
+        Err(error) => {
+            return Err(Error::OcrFailed {
+                message: error.to_string(),
+            }
+            .into())
+        }
+    };
+
+    let mut result = Vec::new();
+    response
+        .into_reader()
+        .read_to_end(&mut result)
+        .into_diagnostic()
+        .with_context(|| format!("While reading the result from {}", remote.url))?;
+
+    Ok(result)
+}
+
+#[cfg(not(feature = "remote-ocr"))]
+fn recognize_remotely(
+    _image: &[u8],
+    _lang: &str,
+    _remote: &RemoteOcr,
+    _format: OcrFormat,
+) -> miette::Result<Vec<u8>> {
+    Err(Error::OcrRemoteNotBuilt.into())
+}
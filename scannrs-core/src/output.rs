@@ -0,0 +1,570 @@
+use std::io::Seek;
+use std::io::Write;
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use image::ImageFormat;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+use serde::Serialize;
+use tiff::encoder::colortype;
+use tiff::encoder::Rational;
+use tiff::encoder::TiffEncoder;
+use tiff::encoder::TiffKind;
+use tiff::tags::ResolutionUnit;
+
+use crate::jpeg::JpegWriter;
+use crate::Error;
+
+/// Lines in every strip of TIFF files written with [`write_lines`]
+pub const STRIP_LINES: u32 = 64;
+
+/// The most bytes a classic TIFF can hold, as it addresses its data with 32 bits
+const TIFF_LIMIT: u64 = u32::MAX as u64;
+
+/// Room for the tags and strip offsets of every page in a TIFF, which take far less
+const TIFF_PAGE_OVERHEAD: u64 = 1024 * 1024;
+
+/// The file formats scans can be saved as
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Jpeg,
+    Png,
+    Tiff,
+    Pdf,
+}
+
+impl Format {
+    /// Guess the format from the extension of the path
+    pub fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "jpg" | "jpeg" => Some(Format::Jpeg),
+            "png" => Some(Format::Png),
+            "tif" | "tiff" => Some(Format::Tiff),
+            "pdf" => Some(Format::Pdf),
+            _ => None,
+        }
+    }
+
+    /// Use the explicitly given format, or guess it from the path, falling back to JPEG
+    pub fn for_path(path: &Path, format: Option<Format>) -> Format {
+        format
+            .or_else(|| Format::from_path(path))
+            .unwrap_or(Format::Jpeg)
+    }
+
+    /// The media type of documents in this format, like `image/png`
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::Tiff => "image/tiff",
+            Format::Pdf => "application/pdf",
+        }
+    }
+
+    pub fn supports_multiple_pages(self) -> bool {
+        matches!(self, Format::Tiff | Format::Pdf)
+    }
+}
+
+/// A single scanned page together with the resolution it was scanned at
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub image: DynamicImage,
+    /// Dots per inch, used to give the page its physical size in documents
+    pub dpi: f32,
+}
+
+/// The size of a page that was written line by line, without ever being held as a whole
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PageSize {
+    pub width: u32,
+    pub height: u32,
+    /// Dots per inch
+    pub dpi: f32,
+}
+
+impl PageSize {
+    pub fn of(page: &Page) -> PageSize {
+        PageSize {
+            width: page.image.width(),
+            height: page.image.height(),
+            dpi: page.dpi,
+        }
+    }
+}
+
+/// Write the pages into a single document of the given format
+pub fn write_document<W: Write + Seek>(
+    writer: &mut W,
+    format: Format,
+    pages: &[Page],
+) -> miette::Result<()> {
+    if pages.len() > 1 && !format.supports_multiple_pages() {
+        return Err(Error::MultiPageUnsupported { format }.into());
+    }
+
+    match format {
+        Format::Jpeg => {
+            for page in pages {
+                JpegEncoder::new(&mut *writer)
+                    .encode_image(&to_8bit(&page.image))
+                    .map_err(encode_error(format))?;
+            }
+        }
+        Format::Png => {
+            for page in pages {
+                page.image
+                    .write_to(&mut *writer, ImageFormat::Png)
+                    .map_err(encode_error(format))?;
+            }
+        }
+        Format::Tiff => write_tiff(writer, pages)?,
+        Format::Pdf => write_pdf(writer, pages)?,
+    }
+
+    Ok(())
+}
+
+/// Encode a page from its lines as they are scanned, see [`crate::scan::scanlines`]
+///
+/// Encoding overlaps with the scanner sending the rest of the page, and only a few lines are kept at a time: JPEG
+/// writes every eight lines, TIFF every [`STRIP_LINES`]. The lines need the same width and colors, and are reduced
+/// to 8 bits for JPEG like in [`write_document`]. TIFF needs the number of lines of the page in advance, lines that
+/// do not arrive are filled with white and extra ones are dropped. Pages too large for a classic TIFF are written as
+/// BigTIFF. PNG and PDF cannot be written like this.
+pub fn write_lines<W: Write + Seek>(
+    writer: &mut W,
+    format: Format,
+    dpi: f32,
+    height: Option<u32>,
+    lines: &mut dyn Iterator<Item = miette::Result<DynamicImage>>,
+) -> miette::Result<PageSize> {
+    let (width, height) = match format {
+        Format::Jpeg => write_jpeg_lines(writer, dpi, lines)?,
+        Format::Tiff => {
+            let height = height.ok_or(Error::UnknownPageHeight)?;
+            write_tiff_lines(writer, dpi, height, lines)?
+        }
+        Format::Png | Format::Pdf => return Err(Error::LineByLineUnsupported { format }.into()),
+    };
+
+    Ok(PageSize { width, height, dpi })
+}
+
+/// The first of the lines, which decides the width and colors of the page
+fn first_line(
+    format: Format,
+    lines: &mut dyn Iterator<Item = miette::Result<DynamicImage>>,
+) -> miette::Result<DynamicImage> {
+    lines.next().unwrap_or_else(|| {
+        Err(encode_error(format)(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "The page has no lines",
+        )))
+        .into_diagnostic()
+    })
+}
+
+fn write_jpeg_lines<W: Write + Seek>(
+    writer: &mut W,
+    dpi: f32,
+    lines: &mut dyn Iterator<Item = miette::Result<DynamicImage>>,
+) -> miette::Result<(u32, u32)> {
+    let first = first_line(Format::Jpeg, lines)?;
+    let gray = !first.color().has_color();
+    let samples = |line: &DynamicImage| {
+        if gray {
+            line.to_luma8().into_raw()
+        } else {
+            line.to_rgb8().into_raw()
+        }
+    };
+
+    let width = first.width();
+    let mut jpeg = JpegWriter::new(&mut *writer, width, if gray { 1 } else { 3 }, dpi)
+        .map_err(encode_error(Format::Jpeg))?;
+    jpeg.write_line(&samples(&first))
+        .map_err(encode_error(Format::Jpeg))?;
+    for line in lines {
+        jpeg.write_line(&samples(&line?))
+            .map_err(encode_error(Format::Jpeg))?;
+    }
+    let height = jpeg.finish().map_err(encode_error(Format::Jpeg))?;
+
+    Ok((width, height))
+}
+
+fn write_tiff_lines<W: Write + Seek>(
+    writer: &mut W,
+    dpi: f32,
+    height: u32,
+    lines: &mut dyn Iterator<Item = miette::Result<DynamicImage>>,
+) -> miette::Result<(u32, u32)> {
+    let first = first_line(Format::Tiff, lines)?;
+    let width = first.width();
+    let bytes = tiff_bytes(&first, width, height);
+    if needs_bigtiff(bytes, 1) {
+        tracing::info!("Writing a BigTIFF, as the page takes {bytes} bytes");
+        let encoder = TiffEncoder::new_big(writer).map_err(encode_error(Format::Tiff))?;
+        write_strips(encoder, first, dpi, height, lines)?;
+    } else {
+        let encoder = TiffEncoder::new(writer).map_err(encode_error(Format::Tiff))?;
+        write_strips(encoder, first, dpi, height, lines)?;
+    }
+
+    Ok((width, height))
+}
+
+/// Write the lines of the page as strips into a new image of the TIFF, see [`write_tiff_lines`]
+fn write_strips<W: Write + Seek, K: TiffKind>(
+    mut encoder: TiffEncoder<W, K>,
+    first: DynamicImage,
+    dpi: f32,
+    height: u32,
+    lines: &mut dyn Iterator<Item = miette::Result<DynamicImage>>,
+) -> miette::Result<()> {
+    let width = first.width();
+
+    // Like `write_tiff`, everything but 8 bit gray and 16 bit gray or color is written as 8 bit color
+    macro_rules! write_strips {
+        ($color:ty, $samples:ident, $white:expr) => {{
+            let mut image = encoder
+                .new_image::<$color>(width, height)
+                .map_err(encode_error(Format::Tiff))?;
+            image.resolution(ResolutionUnit::Inch, tiff_resolution(dpi));
+            image
+                .rows_per_strip(STRIP_LINES)
+                .map_err(encode_error(Format::Tiff))?;
+
+            let mut strip = Vec::new();
+            let mut write = |samples: &[_]| -> miette::Result<()> {
+                strip.extend_from_slice(samples);
+                if strip.len() as u64 == image.next_strip_sample_count() {
+                    image
+                        .write_strip(&strip)
+                        .map_err(encode_error(Format::Tiff))?;
+                    strip.clear();
+                }
+                Ok(())
+            };
+
+            let mut written = 0;
+            for line in std::iter::once(Ok(first)).chain(lines) {
+                let line = line?;
+                if written < height {
+                    write(line.$samples().as_raw().as_slice())?;
+                    written += 1;
+                }
+            }
+            let channels = <$color as colortype::ColorType>::BITS_PER_SAMPLE.len();
+            let white = vec![$white; width as usize * channels];
+            for _ in written..height {
+                write(white.as_slice())?;
+            }
+
+            image.finish().map_err(encode_error(Format::Tiff))?;
+        }};
+    }
+
+    match first {
+        DynamicImage::ImageLuma8(_) => write_strips!(colortype::Gray8, to_luma8, u8::MAX),
+        DynamicImage::ImageLuma16(_) => write_strips!(colortype::Gray16, to_luma16, u16::MAX),
+        DynamicImage::ImageRgb16(_) => write_strips!(colortype::RGB16, to_rgb16, u16::MAX),
+        _ => write_strips!(colortype::RGB8, to_rgb8, u8::MAX),
+    }
+
+    Ok(())
+}
+
+/// Wrap an error of the encoder of the format
+fn encode_error<E>(format: Format) -> impl FnOnce(E) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    move |error| Error::Encode {
+        format,
+        error: Box::new(error),
+    }
+}
+
+/// Reduce images to 8 bits per sample, as not all formats support more
+fn to_8bit(img: &DynamicImage) -> DynamicImage {
+    match img {
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageRgb8(_) => img.clone(),
+        DynamicImage::ImageLuma16(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageLumaA8(_) => img.to_luma8().into(),
+        _ => img.to_rgb8().into(),
+    }
+}
+
+/// The bytes a page of the given size takes in a TIFF, with the samples the TIFF writers use for `image`
+fn tiff_bytes(image: &DynamicImage, width: u32, height: u32) -> u64 {
+    let pixel = match image {
+        DynamicImage::ImageLuma8(_) => 1,
+        DynamicImage::ImageLuma16(_) => 2,
+        DynamicImage::ImageRgb16(_) => 6,
+        _ => 3,
+    };
+
+    u64::from(width) * u64::from(height) * pixel
+}
+
+/// Whether a TIFF of `pages` pages taking `bytes` has to be a BigTIFF
+fn needs_bigtiff(bytes: u64, pages: usize) -> bool {
+    bytes + pages as u64 * TIFF_PAGE_OVERHEAD > TIFF_LIMIT
+}
+
+/// Write the pages into a TIFF, or a BigTIFF if they are too large for a classic one
+fn write_tiff<W: Write + Seek>(writer: &mut W, pages: &[Page]) -> miette::Result<()> {
+    let bytes = pages
+        .iter()
+        .map(|page| tiff_bytes(&page.image, page.image.width(), page.image.height()))
+        .sum();
+    if needs_bigtiff(bytes, pages.len()) {
+        tracing::info!("Writing a BigTIFF, as the pages take {bytes} bytes");
+        let encoder = TiffEncoder::new_big(writer).map_err(encode_error(Format::Tiff))?;
+        write_tiff_pages(encoder, pages)
+    } else {
+        let encoder = TiffEncoder::new(writer).map_err(encode_error(Format::Tiff))?;
+        write_tiff_pages(encoder, pages)
+    }
+}
+
+fn write_tiff_pages<W: Write + Seek, K: TiffKind>(
+    mut encoder: TiffEncoder<W, K>,
+    pages: &[Page],
+) -> miette::Result<()> {
+    for page in pages {
+        let (width, height) = (page.image.width(), page.image.height());
+        let resolution = tiff_resolution(page.dpi);
+
+        macro_rules! write_page {
+            ($color:ty, $data:expr) => {{
+                let mut image = encoder
+                    .new_image::<$color>(width, height)
+                    .map_err(encode_error(Format::Tiff))?;
+                image.resolution(ResolutionUnit::Inch, resolution);
+                image
+                    .write_data($data)
+                    .map_err(encode_error(Format::Tiff))?;
+            }};
+        }
+
+        match &page.image {
+            DynamicImage::ImageLuma8(img) => write_page!(colortype::Gray8, img),
+            DynamicImage::ImageLuma16(img) => write_page!(colortype::Gray16, img),
+            DynamicImage::ImageRgb16(img) => write_page!(colortype::RGB16, img),
+            img => write_page!(colortype::RGB8, &img.to_rgb8()),
+        }
+    }
+
+    Ok(())
+}
+
+fn tiff_resolution(dpi: f32) -> Rational {
+    Rational {
+        n: (dpi * 100.0).round() as u32,
+        d: 100,
+    }
+}
+
+/// Write a minimal PDF with every page being a single JPEG compressed image
+fn write_pdf<W: Write>(writer: &mut W, pages: &[Page]) -> miette::Result<()> {
+    let mut pdf = PdfWriter::default();
+
+    // Object 1 is the catalog, object 2 the page tree, every page uses the three objects after those
+    let page_ids = (0..pages.len()).map(|i| 3 + i * 3).collect::<Vec<_>>();
+
+    pdf.object(1, b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids = page_ids
+        .iter()
+        .map(|id| format!("{id} 0 R"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    pdf.object(
+        2,
+        format!("<< /Type /Pages /Kids [{kids}] /Count {} >>", pages.len()).as_bytes(),
+    );
+
+    for (page, id) in pages.iter().zip(page_ids) {
+        let image = to_8bit(&page.image);
+        let (width, height) = (image.width(), image.height());
+        let color_space = match image {
+            DynamicImage::ImageLuma8(_) => "DeviceGray",
+            _ => "DeviceRGB",
+        };
+        let mut jpeg = vec![];
+        JpegEncoder::new(&mut jpeg)
+            .encode_image(&image)
+            .map_err(encode_error(Format::Pdf))?;
+
+        // PDF units are 1/72 of an inch
+        let points_width = width as f32 * 72.0 / page.dpi;
+        let points_height = height as f32 * 72.0 / page.dpi;
+
+        pdf.object(
+            id,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {points_width:.2} {points_height:.2}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>",
+                id + 1,
+                id + 2
+            )
+            .as_bytes(),
+        );
+        pdf.stream(
+            id + 1,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {width} /Height {height} /ColorSpace {color_space} \
+                 /BitsPerComponent 8 /Filter /DCTDecode"
+            ),
+            &jpeg,
+        );
+        pdf.stream(
+            id + 2,
+            "",
+            format!("q {points_width:.2} 0 0 {points_height:.2} 0 0 cm /Im0 Do Q").as_bytes(),
+        );
+    }
+
+    writer.write_all(&pdf.finish()).into_diagnostic()
+}
+
+#[derive(Default)]
+struct PdfWriter {
+    buffer: Vec<u8>,
+    offsets: Vec<(usize, usize)>,
+}
+
+impl PdfWriter {
+    fn header(&mut self) {
+        if self.buffer.is_empty() {
+            self.buffer
+                .extend_from_slice(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n");
+        }
+    }
+
+    fn object(&mut self, id: usize, content: &[u8]) {
+        self.header();
+        self.offsets.push((id, self.buffer.len()));
+        self.buffer
+            .extend_from_slice(format!("{id} 0 obj\n").as_bytes());
+        self.buffer.extend_from_slice(content);
+        self.buffer.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, id: usize, dictionary: &str, data: &[u8]) {
+        let mut content =
+            format!("<< {dictionary} /Length {} >>\nstream\n", data.len()).into_bytes();
+        content.extend_from_slice(data);
+        content.extend_from_slice(b"\nendstream");
+        self.object(id, &content);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.header();
+        self.offsets.sort_unstable();
+
+        let xref_offset = self.buffer.len();
+        let size = self.offsets.len() + 1;
+        self.buffer
+            .extend_from_slice(format!("xref\n0 {size}\n0000000000 65535 f \n").as_bytes());
+        for (_, offset) in &self.offsets {
+            self.buffer
+                .extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        self.buffer.extend_from_slice(
+            format!("trailer\n<< /Size {size} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n")
+                .as_bytes(),
+        );
+
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::DynamicImage;
+    use tiff::encoder::TiffEncoder;
+
+    use super::needs_bigtiff;
+    use super::tiff_bytes;
+    use super::write_strips;
+    use super::write_tiff;
+    use super::write_tiff_pages;
+    use super::Page;
+    use super::TIFF_LIMIT;
+    use super::TIFF_PAGE_OVERHEAD;
+
+    #[test]
+    fn bigtiff_is_used_past_the_limit() {
+        let largest = TIFF_LIMIT - TIFF_PAGE_OVERHEAD;
+
+        assert!(!needs_bigtiff(largest, 1));
+        assert!(needs_bigtiff(largest + 1, 1));
+        // Every page takes room for its tags
+        assert!(!needs_bigtiff(largest - TIFF_PAGE_OVERHEAD, 2));
+        assert!(needs_bigtiff(largest - TIFF_PAGE_OVERHEAD + 1, 2));
+    }
+
+    #[test]
+    fn tiff_bytes_count_the_written_samples() {
+        let size = |image: DynamicImage| tiff_bytes(&image, 1000, 100);
+
+        assert_eq!(size(DynamicImage::new_luma8(1, 1)), 100_000);
+        assert_eq!(size(DynamicImage::new_luma16(1, 1)), 200_000);
+        assert_eq!(size(DynamicImage::new_rgb8(1, 1)), 300_000);
+        assert_eq!(size(DynamicImage::new_rgba8(1, 1)), 300_000);
+        assert_eq!(size(DynamicImage::new_rgb16(1, 1)), 600_000);
+        // An A4 page in color at 3600 dpi still fits into a classic TIFF, at 4800 dpi it does not
+        let a4 = |dpi: u32| {
+            tiff_bytes(
+                &DynamicImage::new_rgb8(1, 1),
+                827 * dpi / 100,
+                1169 * dpi / 100,
+            )
+        };
+        assert!(!needs_bigtiff(a4(3600), 1));
+        assert!(needs_bigtiff(a4(4800), 1));
+    }
+
+    #[test]
+    fn the_header_tells_classic_and_bigtiff_apart() {
+        let page = Page {
+            image: DynamicImage::new_luma8(8, 8),
+            dpi: 100.0,
+        };
+
+        let mut classic = Cursor::new(Vec::new());
+        write_tiff(&mut classic, &[page.clone()]).expect("the page can be written");
+        assert_eq!(&classic.get_ref()[..4], b"II*\0");
+
+        let mut big = Cursor::new(Vec::new());
+        let encoder = TiffEncoder::new_big(&mut big).expect("the header can be written");
+        write_tiff_pages(encoder, &[page.clone()]).expect("the page can be written");
+        assert_eq!(&big.get_ref()[..4], b"II+\0");
+
+        let mut big = Cursor::new(Vec::new());
+        let encoder = TiffEncoder::new_big(&mut big).expect("the header can be written");
+        let mut lines = (1..8).map(|y| Ok::<_, miette::Report>(page.image.crop_imm(0, y, 8, 1)));
+        write_strips(
+            encoder,
+            page.image.crop_imm(0, 0, 8, 1),
+            100.0,
+            8,
+            &mut lines,
+        )
+        .expect("the lines can be written");
+        assert_eq!(&big.get_ref()[..4], b"II+\0");
+    }
+}
@@ -0,0 +1,505 @@
+//! Cleaning up scanned pages: straightening, cropping, rotating and dropping empty pages
+//!
+//! Every step is a [`PostProcessor`]. Steps that do not live in this crate are added to a [`Registry`] to be found by
+//! their name, or with the `plugins` feature registered from any crate with [`inventory::submit!`]:
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct FilmCorrection;
+//!
+//! impl PostProcessor for FilmCorrection {
+//!     fn name(&self) -> &'static str {
+//!         "film-correction"
+//!     }
+//!
+//!     fn process(&self, image: DynamicImage) -> Option<DynamicImage> {
+//!         Some(correct(image))
+//!     }
+//! }
+//!
+//! scannrs_core::postprocess::inventory::submit! {
+//!     scannrs_core::postprocess::Plugin(|| Box::new(FilmCorrection))
+//! }
+//! ```
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+use image::GrayImage;
+use image::ImageBuffer;
+use image::Pixel;
+use rayon::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::progress::Stage;
+
+/// How far a luma value has to be from the background to count as content
+const CONTENT_THRESHOLD: i16 = 48;
+/// Pages with less content than this fraction of their pixels are blank
+const BLANK_RATIO: f64 = 0.002;
+/// The largest skew in degrees that is corrected
+const MAX_SKEW: f32 = 5.0;
+const SKEW_STEP: f32 = 0.25;
+
+/// A quarter turn applied to every page
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise,
+    UpsideDown,
+    CounterClockwise,
+}
+
+impl Rotation {
+    /// The next rotation, turning clockwise by another quarter
+    pub fn next(self) -> Rotation {
+        match self {
+            Rotation::None => Rotation::Clockwise,
+            Rotation::Clockwise => Rotation::UpsideDown,
+            Rotation::UpsideDown => Rotation::CounterClockwise,
+            Rotation::CounterClockwise => Rotation::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rotation::None => "none",
+            Rotation::Clockwise => "90° clockwise",
+            Rotation::UpsideDown => "180°",
+            Rotation::CounterClockwise => "90° counter-clockwise",
+        }
+    }
+}
+
+/// The steps applied to every scanned page, in the order they are listed
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(default)]
+pub struct PostProcessing {
+    /// Drop pages without content, like the empty backsides of a duplex scan
+    pub skip_blank: bool,
+    /// Straighten pages that were fed in slightly tilted
+    pub deskew: bool,
+    /// Cut away the background around the page
+    pub autocrop: bool,
+    pub rotation: Rotation,
+}
+
+impl PostProcessing {
+    pub fn is_default(&self) -> bool {
+        *self == PostProcessing::default()
+    }
+
+    /// The enabled steps, in the order they run
+    pub fn steps(&self) -> Vec<Arc<dyn PostProcessor>> {
+        let mut steps: Vec<Arc<dyn PostProcessor>> = Vec::new();
+        if self.skip_blank {
+            steps.push(Arc::new(SkipBlank));
+        }
+        if self.deskew {
+            steps.push(Arc::new(Deskew));
+        }
+        if self.autocrop {
+            steps.push(Arc::new(Autocrop));
+        }
+        if self.rotation != Rotation::None {
+            steps.push(Arc::new(Rotate(self.rotation)));
+        }
+        steps
+    }
+
+    /// Process a page, returns `None` if it is blank and should be skipped
+    pub fn apply(&self, image: DynamicImage) -> Option<DynamicImage> {
+        self.apply_with_progress(image, &mut |_| {})
+    }
+
+    /// Like [`PostProcessing::apply`], telling `progress` about every step before it runs
+    pub fn apply_with_progress(
+        &self,
+        image: DynamicImage,
+        progress: &mut dyn FnMut(Stage),
+    ) -> Option<DynamicImage> {
+        run(&self.steps(), image, progress)
+    }
+}
+
+/// A step of the post-processing, applied to every scanned page
+pub trait PostProcessor: Debug + Send + Sync {
+    /// The name the step is selected by, like `deskew`
+    fn name(&self) -> &'static str;
+
+    /// The stage reported while the step runs
+    fn stage(&self) -> Stage {
+        Stage::Other(self.name())
+    }
+
+    /// Process a page, returns `None` to drop it
+    fn process(&self, image: DynamicImage) -> Option<DynamicImage>;
+}
+
+/// Run the steps in order, telling `progress` about every step before it runs
+///
+/// Returns `None` as soon as a step drops the page.
+pub fn run(
+    steps: &[Arc<dyn PostProcessor>],
+    image: DynamicImage,
+    progress: &mut dyn FnMut(Stage),
+) -> Option<DynamicImage> {
+    steps.iter().try_fold(image, |image, step| {
+        progress(step.stage());
+        step.process(image)
+    })
+}
+
+/// Drops pages without content
+#[derive(Clone, Copy, Debug)]
+pub struct SkipBlank;
+
+impl PostProcessor for SkipBlank {
+    fn name(&self) -> &'static str {
+        "skip-blank"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::BlankDetection
+    }
+
+    fn process(&self, image: DynamicImage) -> Option<DynamicImage> {
+        (!is_blank(&image.to_luma8())).then_some(image)
+    }
+}
+
+/// Straightens pages that were fed in slightly tilted
+#[derive(Clone, Copy, Debug)]
+pub struct Deskew;
+
+impl PostProcessor for Deskew {
+    fn name(&self) -> &'static str {
+        "deskew"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Deskew
+    }
+
+    fn process(&self, image: DynamicImage) -> Option<DynamicImage> {
+        let angle = skew_angle(&image);
+        if angle.abs() >= SKEW_STEP {
+            Some(rotate_by(&image, angle))
+        } else {
+            Some(image)
+        }
+    }
+}
+
+/// Cuts away the background around the page
+#[derive(Clone, Copy, Debug)]
+pub struct Autocrop;
+
+impl PostProcessor for Autocrop {
+    fn name(&self) -> &'static str {
+        "autocrop"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Autocrop
+    }
+
+    fn process(&self, image: DynamicImage) -> Option<DynamicImage> {
+        Some(match content_bounds(&image.to_luma8()) {
+            Some((x, y, width, height)) => image.crop_imm(x, y, width, height),
+            None => image,
+        })
+    }
+}
+
+/// Turns every page by a quarter turn or more
+#[derive(Clone, Copy, Debug)]
+pub struct Rotate(pub Rotation);
+
+impl PostProcessor for Rotate {
+    fn name(&self) -> &'static str {
+        "rotate"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Rotation
+    }
+
+    fn process(&self, image: DynamicImage) -> Option<DynamicImage> {
+        Some(match self.0 {
+            Rotation::None => image,
+            Rotation::Clockwise => image.rotate90(),
+            Rotation::UpsideDown => image.rotate180(),
+            Rotation::CounterClockwise => image.rotate270(),
+        })
+    }
+}
+
+/// Registers a post-processor from another crate, see the [module documentation](self)
+#[cfg(feature = "plugins")]
+pub struct Plugin(pub fn() -> Box<dyn PostProcessor>);
+
+#[cfg(feature = "plugins")]
+inventory::collect!(Plugin);
+
+#[cfg(feature = "plugins")]
+pub use inventory;
+
+/// The post-processors that can be selected by their name
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    processors: Vec<Arc<dyn PostProcessor>>,
+}
+
+impl Registry {
+    /// A registry with the steps of this crate that take no settings, and with the `plugins` feature every
+    /// registered plugin
+    pub fn with_builtins() -> Registry {
+        let mut registry = Registry::default();
+        registry.register(SkipBlank);
+        registry.register(Deskew);
+        registry.register(Autocrop);
+
+        #[cfg(feature = "plugins")]
+        for plugin in inventory::iter::<Plugin> {
+            registry.insert(Arc::from((plugin.0)()));
+        }
+
+        registry
+    }
+
+    /// Add a post-processor, replacing one with the same name
+    pub fn register(&mut self, processor: impl PostProcessor + 'static) {
+        self.insert(Arc::new(processor));
+    }
+
+    fn insert(&mut self, processor: Arc<dyn PostProcessor>) {
+        self.processors
+            .retain(|registered| registered.name() != processor.name());
+        self.processors.push(processor);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn PostProcessor>> {
+        self.processors
+            .iter()
+            .find(|processor| processor.name() == name)
+            .cloned()
+    }
+
+    /// The names of all registered post-processors, in the order they were registered
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.processors.iter().map(|processor| processor.name())
+    }
+}
+
+/// The average luma along the edges of the image, which is taken to be the background
+fn border_luma(luma: &GrayImage) -> u8 {
+    let (width, height) = luma.dimensions();
+    let mut sum = 0u64;
+    let mut count = 0u64;
+    for (x, y, pixel) in luma.enumerate_pixels() {
+        if x == 0 || y == 0 || x + 1 == width || y + 1 == height {
+            sum += u64::from(pixel[0]);
+            count += 1;
+        }
+    }
+
+    (sum / count.max(1)) as u8
+}
+
+fn is_content(value: u8, background: u8) -> bool {
+    (i16::from(value) - i16::from(background)).abs() > CONTENT_THRESHOLD
+}
+
+fn is_blank(luma: &GrayImage) -> bool {
+    let pixels = luma.as_raw().len().max(1) as f64;
+    let mean = luma
+        .as_raw()
+        .par_iter()
+        .map(|&value| u64::from(value))
+        .sum::<u64>() as f64
+        / pixels;
+    let content = luma
+        .as_raw()
+        .par_iter()
+        .filter(|&&value| is_content(value, mean as u8))
+        .count();
+
+    (content as f64 / pixels) < BLANK_RATIO
+}
+
+/// The area `(x, y, width, height)` holding the content, with a small margin
+///
+/// Single specks of dust are ignored by requiring a few content pixels per row and column.
+fn content_bounds(luma: &GrayImage) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = luma.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let background = border_luma(luma);
+
+    // Every row is counted on its own, the counts of the columns are summed up from groups of rows
+    let lines = || luma.as_raw().par_chunks(width as usize);
+    let rows = lines()
+        .map(|line| {
+            line.iter()
+                .filter(|&&value| is_content(value, background))
+                .count() as u32
+        })
+        .collect::<Vec<_>>();
+    let no_columns = || vec![0u32; width as usize];
+    let columns = lines()
+        .fold(no_columns, |mut columns, line| {
+            for (count, &value) in columns.iter_mut().zip(line) {
+                *count += u32::from(is_content(value, background));
+            }
+            columns
+        })
+        .reduce(no_columns, |mut columns, other| {
+            for (count, other) in columns.iter_mut().zip(other) {
+                *count += other;
+            }
+            columns
+        });
+
+    let range = |counts: &[u32], length: u32| {
+        let min = (length / 200).max(1);
+        let first = counts.iter().position(|count| *count >= min)? as u32;
+        let last = counts.iter().rposition(|count| *count >= min)? as u32;
+        Some((first, last))
+    };
+    let (top, bottom) = range(&rows, width)?;
+    let (left, right) = range(&columns, height)?;
+
+    let margin = width.min(height) / 100;
+    let x = left.saturating_sub(margin);
+    let y = top.saturating_sub(margin);
+    let right = (right + margin).min(width - 1);
+    let bottom = (bottom + margin).min(height - 1);
+
+    Some((x, y, right - x + 1, bottom - y + 1))
+}
+
+/// The angle in degrees by which the lines of the page run down to the right
+///
+/// The dark pixels are projected onto the vertical axis for every candidate angle, the angle at which the lines of
+/// text line up produces the sharpest peaks.
+fn skew_angle(image: &DynamicImage) -> f32 {
+    let small = image.resize(1000, 1000, FilterType::Triangle).to_luma8();
+    if small.width() == 0 {
+        return 0.0;
+    }
+    let background = border_luma(&small);
+    let dark = small
+        .as_raw()
+        .par_chunks(small.width() as usize)
+        .enumerate()
+        .flat_map_iter(|(y, line)| {
+            line.iter()
+                .enumerate()
+                .filter(move |(_, &value)| is_content(value, background))
+                .map(move |(x, _)| (x as f32, y as f32))
+        })
+        .collect::<Vec<_>>();
+    if dark.is_empty() {
+        return 0.0;
+    }
+
+    let offset = small.width() as f32;
+    let bins = (small.width() + small.height()) as usize * 2;
+    let steps = (MAX_SKEW / SKEW_STEP) as i32;
+    // Going outwards from zero, so that no correction wins a tie
+    let angles = (0..=steps)
+        .flat_map(|step| [step, -step])
+        .map(|step| step as f32 * SKEW_STEP)
+        .collect::<Vec<_>>();
+    // Every angle is tried on its own, the scores keep the order of the angles
+    let scores = angles
+        .par_iter()
+        .map(|angle| {
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let mut bins = vec![0u32; bins];
+            for (x, y) in &dark {
+                let bin = (y * cos - x * sin + offset) as usize;
+                if let Some(bin) = bins.get_mut(bin) {
+                    *bin += 1;
+                }
+            }
+
+            bins.iter()
+                .map(|count| f64::from(*count).powi(2))
+                .sum::<f64>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut best = (0.0, 0.0);
+    for (&angle, score) in angles.iter().zip(scores) {
+        if score > best.1 {
+            best = (angle, score);
+        }
+    }
+
+    best.0
+}
+
+/// Rotate the image counter-clockwise by the given angle in degrees, keeping its size
+fn rotate_by(image: &DynamicImage, angle: f32) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(image) => rotate_buffer(image, angle).into(),
+        DynamicImage::ImageLuma16(image) => rotate_buffer(image, angle).into(),
+        DynamicImage::ImageRgb8(image) => rotate_buffer(image, angle).into(),
+        DynamicImage::ImageRgb16(image) => rotate_buffer(image, angle).into(),
+        image => rotate_buffer(&image.to_rgba8(), angle).into(),
+    }
+}
+
+/// Uncovered corners are filled with the color of the top left pixel, which is usually the background
+///
+/// The rows of the rotated image are filled in parallel.
+fn rotate_buffer<P>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    angle: f32,
+) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Send + Sync,
+{
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return image.clone();
+    }
+    let fill = *image.get_pixel(0, 0);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let channels = usize::from(P::CHANNEL_COUNT);
+
+    let mut rotated = ImageBuffer::<P, Vec<P::Subpixel>>::new(width, height);
+    rotated
+        .par_chunks_mut(width as usize * channels)
+        .enumerate()
+        .for_each(|(y, line)| {
+            let dy = y as f32 - center_y;
+            for (x, target) in line.chunks_exact_mut(channels).enumerate() {
+                let dx = x as f32 - center_x;
+                let source_x = center_x + cos * dx - sin * dy;
+                let source_y = center_y + sin * dx + cos * dy;
+
+                let pixel = if source_x < 0.0 || source_y < 0.0 {
+                    fill
+                } else {
+                    image
+                        .get_pixel_checked(source_x as u32, source_y as u32)
+                        .copied()
+                        .unwrap_or(fill)
+                };
+                target.copy_from_slice(pixel.channels());
+            }
+        });
+
+    rotated
+}
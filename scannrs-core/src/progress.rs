@@ -0,0 +1,78 @@
+//! What a running scan reports, so that progress bars, status screens and job APIs all show the same thing
+
+use std::ops::ControlFlow;
+
+use crate::backend::FrameFormat;
+
+/// An event of a running scan, handed to the progress callback of the scan functions
+///
+/// Returning [`ControlFlow::Break`] from the callback cancels the scan while pages are read. Once a page is read, the
+/// post-processing runs to the end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScanEvent {
+    /// A page is about to be scanned, counted from 1
+    PageStarted { page: usize },
+    /// The scanner started sending a frame, three-pass scans send one per color
+    FrameStarted {
+        format: FrameFormat,
+        /// `None` for hand-scanners, which do not know the amount of lines in advance
+        lines: Option<usize>,
+    },
+    /// Data of the current frame arrived
+    Read {
+        /// Bytes of the frame read so far
+        bytes: usize,
+        /// The expected size of the frame in bytes, if the scanner knows the amount of lines
+        total: Option<usize>,
+        /// Lines of the frame that are complete
+        lines: usize,
+    },
+    /// The page was read and is being processed
+    Processing { stage: Stage },
+    /// The page is done, `kept` is false if it was dropped as blank
+    PageFinished { page: usize, kept: bool },
+}
+
+impl ScanEvent {
+    /// How much of the current frame was read, from 0 to 1, if its size is known
+    pub fn fraction(&self) -> Option<f64> {
+        match self {
+            ScanEvent::Read {
+                bytes,
+                total: Some(total),
+                ..
+            } if *total > 0 => Some((*bytes as f64 / *total as f64).clamp(0.0, 1.0)),
+            _ => None,
+        }
+    }
+}
+
+/// A step of the processing of a read page
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Calibration,
+    BlankDetection,
+    Deskew,
+    Autocrop,
+    Rotation,
+    /// A post-processor from outside this crate, by its name
+    Other(&'static str),
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Calibration => "calibration",
+            Stage::BlankDetection => "blank page detection",
+            Stage::Deskew => "deskewing",
+            Stage::Autocrop => "cropping",
+            Stage::Rotation => "rotation",
+            Stage::Other(name) => name,
+        }
+    }
+}
+
+/// A progress callback that ignores all events and never cancels
+pub fn ignore(_: ScanEvent) -> ControlFlow<()> {
+    ControlFlow::Continue(())
+}
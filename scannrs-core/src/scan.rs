@@ -0,0 +1,325 @@
+//! Reading pages from a scanner: setting its options, starting the scan and decoding what it sends
+//!
+//! [`read_image`] waits for the whole page, [`scanlines`] hands out every line as soon as it was read.
+
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use image::DynamicImage;
+use miette::IntoDiagnostic;
+
+use crate::backend::FrameFormat;
+use crate::backend::FrameParameters;
+use crate::backend::OptionValue;
+use crate::backend::ScanDevice;
+use crate::decode::decode_frame;
+use crate::decode::merge_planes;
+use crate::progress;
+use crate::progress::ScanEvent;
+use crate::value::Value;
+use crate::Error;
+
+/// Cancels a running scan from another thread, cheap to clone
+#[derive(Clone, Default, Debug)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// What a progress callback returns to cancel the scan once the token is cancelled
+    pub fn flow(&self) -> ControlFlow<()> {
+        if self.is_cancelled() {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+/// The resolution assumed for scanners that do not report one
+pub const DEFAULT_DPI: f32 = 300.0;
+
+/// Read the resolution the device is set to, in dots per inch
+pub fn resolution(device: &dyn ScanDevice) -> miette::Result<f32> {
+    let Some(option) = device
+        .options()?
+        .into_iter()
+        .find(|o| o.name == "resolution")
+    else {
+        return Ok(DEFAULT_DPI);
+    };
+
+    let dpi = match device.get_option(&option)? {
+        OptionValue::Int(dpi) => dpi as f32,
+        OptionValue::Fixed(dpi) => dpi as f32,
+        _ => DEFAULT_DPI,
+    };
+
+    Ok(if dpi > 0.0 { dpi } else { DEFAULT_DPI })
+}
+
+/// Set all given options on the device, options the device does not know about are ignored
+pub fn apply_options(
+    device: &mut dyn ScanDevice,
+    options: &HashMap<String, Value>,
+) -> miette::Result<()> {
+    for opt in device.options()? {
+        if let Some(val) = options.get(&opt.name) {
+            let Some(val) = val.to_option_value(&opt)? else {
+                continue;
+            };
+
+            device.set_option(&opt, val)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse the ways a boolean is commonly written, like `true`, `yes`, `on` or `1`
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Start a scan and read the resulting frame into an image, three-pass scans are merged into a single color image
+pub fn read_image(device: &mut dyn ScanDevice) -> miette::Result<DynamicImage> {
+    read_image_with_progress(device, &mut progress::ignore)
+}
+
+/// Like [`read_image`], reporting the frames and the data read to `progress`
+///
+/// Returning [`ControlFlow::Break`] from `progress` cancels the scan.
+pub fn read_image_with_progress(
+    device: &mut dyn ScanDevice,
+    progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+) -> miette::Result<DynamicImage> {
+    read_image_with_buffers(device, &mut FrameBuffers::default(), progress)
+}
+
+/// The memory frames are read into, kept between the pages of a batch instead of being allocated for every page
+///
+/// The raw data of a frame can take hundreds of megabytes at high resolutions.
+#[derive(Default)]
+pub struct FrameBuffers {
+    /// The data of the current frame
+    data: Vec<u8>,
+    /// What a single read from the device goes into
+    chunk: Vec<u8>,
+}
+
+/// Like [`read_image_with_progress`], reading the frames into `buffers`
+pub fn read_image_with_buffers(
+    device: &mut dyn ScanDevice,
+    buffers: &mut FrameBuffers,
+    progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+) -> miette::Result<DynamicImage> {
+    let mut planes: [Option<DynamicImage>; 3] = [None, None, None];
+
+    loop {
+        let params = device.start()?;
+        let started = ScanEvent::FrameStarted {
+            format: params.format,
+            lines: params.lines,
+        };
+        if progress(started).is_break() {
+            return cancel_scan(device);
+        }
+        read_frame(device, &params, buffers, progress)?;
+        let img = decode_frame(&params, &buffers.data)?;
+
+        let plane = match params.format {
+            FrameFormat::Gray | FrameFormat::Rgb => return Ok(img),
+            FrameFormat::Red => 0,
+            FrameFormat::Green => 1,
+            FrameFormat::Blue => 2,
+        };
+        planes[plane] = Some(img);
+
+        if params.last_frame {
+            break;
+        }
+    }
+
+    match planes {
+        [Some(red), Some(green), Some(blue)] => Ok(merge_planes(&red, &green, &blue)),
+        _ => Err(Error::MissingColorPlane.into()),
+    }
+}
+
+/// Read the data of the current frame into `buffers.data` until the scanner signals its end
+fn read_frame(
+    device: &mut dyn ScanDevice,
+    params: &FrameParameters,
+    buffers: &mut FrameBuffers,
+    progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+) -> miette::Result<()> {
+    let total = params.lines.map(|lines| lines * params.bytes_per_line);
+    let FrameBuffers { data, chunk } = buffers;
+    data.clear();
+    data.reserve(total.unwrap_or_default());
+    chunk.resize(64 * 1024, 0);
+
+    let mut report = |bytes: usize| {
+        progress(ScanEvent::Read {
+            bytes,
+            total,
+            lines: bytes / params.bytes_per_line.max(1),
+        })
+    };
+
+    if report(0).is_break() {
+        return cancel_scan(device);
+    }
+    while let Some(read) = device.read(chunk)? {
+        data.extend_from_slice(&chunk[..read]);
+        if report(data.len()).is_break() {
+            return cancel_scan(device);
+        }
+    }
+
+    Ok(())
+}
+
+/// Stop the running scan, as asked for by the progress callback
+fn cancel_scan<T>(device: &mut dyn ScanDevice) -> miette::Result<T> {
+    device.cancel();
+    Err(Error::ScanCancelled.into())
+}
+
+/// A single line of a frame, decoded into an image one pixel high
+#[derive(Clone, Debug)]
+pub struct Scanline {
+    /// The format of the frame the line belongs to, three-pass scans send the lines of every color in turn
+    pub format: FrameFormat,
+    /// The line within its frame, counted from 0
+    pub number: usize,
+    pub image: DynamicImage,
+}
+
+/// Start a scan and decode the lines of its frames as they arrive, see [`Scanlines`]
+pub fn scanlines(device: &mut dyn ScanDevice) -> Scanlines<'_> {
+    Scanlines {
+        device,
+        frame: None,
+        complete: false,
+        pending: Vec::new(),
+        buffer: vec![0; 64 * 1024],
+        line: 0,
+        done: false,
+    }
+}
+
+/// The lines of a running scan, decoded as soon as the scanner sent them
+///
+/// Unlike [`read_image`], three-pass scans are not merged. The iterator ends after the last frame or the first error,
+/// dropping it before that cancels the scan.
+pub struct Scanlines<'a> {
+    device: &'a mut dyn ScanDevice,
+    /// The frame being read, `None` until it is started
+    frame: Option<FrameParameters>,
+    /// Whether the scanner sent the whole frame
+    complete: bool,
+    /// Data of the frame that does not form a complete line yet
+    pending: Vec<u8>,
+    buffer: Vec<u8>,
+    line: usize,
+    done: bool,
+}
+
+impl Scanlines<'_> {
+    /// The parameters of the frame being read, `None` before its first line
+    pub fn parameters(&self) -> Option<FrameParameters> {
+        self.frame
+    }
+
+    fn next_line(&mut self) -> miette::Result<Option<Scanline>> {
+        loop {
+            let Some(params) = self.frame else {
+                let params = self.device.start()?;
+                // Decoding no data at all still checks the layout of the frame
+                decode_frame(&params, &[])?;
+                self.frame = Some(params);
+                self.complete = false;
+                self.line = 0;
+                continue;
+            };
+
+            if self.pending.len() >= params.bytes_per_line {
+                let data = self
+                    .pending
+                    .drain(..params.bytes_per_line)
+                    .collect::<Vec<_>>();
+                let line = FrameParameters {
+                    lines: Some(1),
+                    ..params
+                };
+                let scanline = Scanline {
+                    format: params.format,
+                    number: self.line,
+                    image: decode_frame(&line, &data)?,
+                };
+                self.line += 1;
+                return Ok(Some(scanline));
+            }
+
+            if self.complete {
+                // Like `decode_frame`, an incomplete last line is dropped
+                self.pending.clear();
+                self.frame = None;
+                if params.last_frame
+                    || matches!(params.format, FrameFormat::Gray | FrameFormat::Rgb)
+                {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            match self.device.read(&mut self.buffer)? {
+                Some(read) => self.pending.extend_from_slice(&self.buffer[..read]),
+                None => self.complete = true,
+            }
+        }
+    }
+}
+
+impl Iterator for Scanlines<'_> {
+    type Item = miette::Result<Scanline>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let line = self.next_line().transpose();
+        match line {
+            Some(Ok(_)) => {}
+            Some(Err(_)) => {
+                self.device.cancel();
+                self.done = true;
+            }
+            None => self.done = true,
+        }
+        line
+    }
+}
+
+impl Drop for Scanlines<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.device.cancel();
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Option values as users write them, like `300dpi`, `210mm` or `true`, and their conversion to what an option takes
+//!
+//! The command line, the configuration, profiles and the TUI all hand values around as [`Value`]s, which are only
+//! converted into an [`OptionValue`] once the [`OptionDescriptor`] of the option is known.
+
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::backend::Constraint;
+use crate::backend::OptionDescriptor;
+use crate::backend::OptionValue;
+use crate::backend::Unit;
+use crate::backend::ValueType;
+use crate::scan::parse_bool;
+use crate::Error;
+
+/// The units a number can be written with, with the factor converting it into the unit options use
+const UNITS: [(&str, Unit, f64); 12] = [
+    ("mm", Unit::Mm, 1.0),
+    ("cm", Unit::Mm, 10.0),
+    ("in", Unit::Mm, 25.4),
+    ("dpi", Unit::Dpi, 1.0),
+    ("%", Unit::Percent, 1.0),
+    ("bits", Unit::Bit, 1.0),
+    ("bit", Unit::Bit, 1.0),
+    ("px", Unit::Pixel, 1.0),
+    ("pixels", Unit::Pixel, 1.0),
+    ("µs", Unit::Microsecond, 1.0),
+    ("us", Unit::Microsecond, 1.0),
+    ("ms", Unit::Microsecond, 1000.0),
+];
+
+/// A value given for an option, before it is known what the option takes
+///
+/// Parsing never fails, anything that is neither a boolean nor a number is kept as text. Whether the value fits an
+/// option is checked by [`Value::to_option_value`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", from = "RawValue")]
+pub enum Value {
+    Bool(bool),
+    /// A number, with the unit it was given in if there was one
+    Number {
+        value: f64,
+        unit: Option<Unit>,
+    },
+    Text(String),
+}
+
+impl Value {
+    /// A number in the given unit
+    pub fn with_unit(value: f64, unit: Unit) -> Value {
+        Value::Number {
+            value,
+            unit: Some(unit),
+        }
+    }
+
+    /// Convert the value to what the option takes, checking its type, unit and the values the option allows
+    ///
+    /// Options that hold no value, like buttons, yield `None`.
+    pub fn to_option_value(&self, option: &OptionDescriptor) -> Result<Option<OptionValue>, Error> {
+        let Some(value) = self.convert(option)? else {
+            return Ok(None);
+        };
+
+        Ok(Some(self.constrain(option, value)?))
+    }
+
+    /// Check that the value has the type and unit of the option, without checking the values the option allows
+    ///
+    /// Which values are allowed can depend on other options, like the available depths on the mode.
+    pub fn check_type(&self, option: &OptionDescriptor) -> Result<(), Error> {
+        self.convert(option).map(drop)
+    }
+
+    fn convert(&self, option: &OptionDescriptor) -> Result<Option<OptionValue>, Error> {
+        Ok(Some(match option.type_ {
+            ValueType::Bool => OptionValue::Bool(self.to_bool(option)?),
+            ValueType::Int => {
+                let number = self.to_number(option)?;
+                let range = f64::from(i32::MIN)..=f64::from(i32::MAX);
+                if number.fract() != 0.0 || !range.contains(&number) {
+                    return Err(Error::NotAnInteger {
+                        option: option.name.clone(),
+                        value: self.to_string(),
+                    });
+                }
+                OptionValue::Int(number as i32)
+            }
+            ValueType::Fixed => OptionValue::Fixed(self.to_number(option)?),
+            ValueType::String => OptionValue::String(self.to_string()),
+            ValueType::Button | ValueType::Group => return Ok(None),
+        }))
+    }
+
+    fn to_bool(&self, option: &OptionDescriptor) -> Result<bool, Error> {
+        let value = match self {
+            Value::Bool(value) => Some(*value),
+            Value::Number { unit: None, .. } | Value::Text(_) => parse_bool(&self.to_string()),
+            Value::Number { .. } => None,
+        };
+
+        value.ok_or_else(|| Error::InvalidBool {
+            option: option.name.clone(),
+            value: self.to_string(),
+        })
+    }
+
+    fn to_number(&self, option: &OptionDescriptor) -> Result<f64, Error> {
+        let Value::Number { value, unit } = self else {
+            return Err(Error::InvalidNumber {
+                option: option.name.clone(),
+                value: self.to_string(),
+            });
+        };
+
+        match unit {
+            Some(unit) if *unit != option.unit => Err(Error::WrongUnit {
+                option: option.name.clone(),
+                value: self.to_string(),
+                expected: option.unit.name().unwrap_or("no unit"),
+            }),
+            _ => Ok(*value),
+        }
+    }
+
+    /// Check the converted value against the constraint of the option
+    ///
+    /// Strings are matched ignoring case and replaced by the spelling of the option.
+    fn constrain(
+        &self,
+        option: &OptionDescriptor,
+        value: OptionValue,
+    ) -> Result<OptionValue, Error> {
+        let not_allowed = |allowed: String| Error::NotAllowed {
+            option: option.name.clone(),
+            value: self.to_string(),
+            allowed,
+        };
+        let number = match value {
+            OptionValue::Int(value) => Some(f64::from(value)),
+            OptionValue::Fixed(value) => Some(value),
+            _ => None,
+        };
+
+        match (&option.constraint, number, &value) {
+            (Constraint::Range { min, max, .. }, Some(number), _)
+                if !(*min..=*max).contains(&number) =>
+            {
+                Err(Error::OutOfRange {
+                    option: option.name.clone(),
+                    value: self.to_string(),
+                    min: *min,
+                    max: *max,
+                })
+            }
+            (Constraint::Numbers(numbers), Some(number), _) if !numbers.contains(&number) => {
+                Err(not_allowed(
+                    numbers
+                        .iter()
+                        .map(|number| number.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ))
+            }
+            (Constraint::Strings(strings), _, OptionValue::String(string)) => strings
+                .iter()
+                .find(|allowed| allowed.eq_ignore_ascii_case(string))
+                .map(|allowed| OptionValue::String(allowed.clone()))
+                .ok_or_else(|| not_allowed(strings.join(", "))),
+            _ => Ok(value),
+        }
+    }
+}
+
+/// The suffix a number in the unit is written with
+fn suffix(unit: Unit) -> &'static str {
+    match unit {
+        Unit::None => "",
+        Unit::Pixel => "px",
+        Unit::Bit => "bit",
+        Unit::Mm => "mm",
+        Unit::Dpi => "dpi",
+        Unit::Percent => "%",
+        Unit::Microsecond => "us",
+    }
+}
+
+/// Parse a number, `None` for infinite values and NaN, which no option takes
+fn parse_number(text: &str) -> Option<f64> {
+    text.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|value| value.is_finite())
+}
+
+impl FromStr for Value {
+    type Err = Infallible;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let trimmed = text.trim();
+        if trimmed.eq_ignore_ascii_case("true") || trimmed.eq_ignore_ascii_case("false") {
+            return Ok(Value::Bool(trimmed.eq_ignore_ascii_case("true")));
+        }
+
+        if let Some(value) = parse_number(trimmed) {
+            return Ok(Value::Number { value, unit: None });
+        }
+
+        for (name, unit, factor) in UNITS {
+            let Some(split) = trimmed.len().checked_sub(name.len()) else {
+                continue;
+            };
+            if !trimmed.is_char_boundary(split) || !trimmed[split..].eq_ignore_ascii_case(name) {
+                continue;
+            }
+            if let Some(value) = parse_number(&trimmed[..split]) {
+                return Ok(Value::with_unit(value * factor, unit));
+            }
+        }
+
+        Ok(Value::Text(text.to_string()))
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(value) => value.fmt(f),
+            Value::Number { value, unit } => {
+                write!(f, "{value}{}", unit.map_or("", suffix))
+            }
+            Value::Text(text) => text.fmt(f),
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        let Ok(value) = text.parse();
+        value
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::from(text.as_str())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Number {
+            value: f64::from(value),
+            unit: None,
+        }
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Number {
+            value: f64::from(value),
+            unit: None,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Value::Number { value, unit: None }
+    }
+}
+
+impl From<Value> for String {
+    fn from(value: Value) -> Self {
+        value.to_string()
+    }
+}
+
+/// How values are stored, as text or as the native booleans and numbers of formats like TOML and JSON
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+impl From<RawValue> for Value {
+    fn from(raw: RawValue) -> Self {
+        match raw {
+            RawValue::Bool(value) => Value::Bool(value),
+            RawValue::Int(value) => Value::from(value as f64),
+            RawValue::Float(value) => Value::from(value),
+            RawValue::Text(text) => Value::from(text),
+        }
+    }
+}
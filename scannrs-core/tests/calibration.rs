@@ -0,0 +1,42 @@
+//! Evening out scans with a calibration from a white target
+
+use image::DynamicImage;
+use image::ImageBuffer;
+use image::Luma;
+use image::Rgb;
+use scannrs_core::calibration::Calibration;
+
+/// A white target that the left half of the scan head sees darker
+fn white_scan() -> DynamicImage {
+    DynamicImage::ImageRgb8(ImageBuffer::from_fn(8, 4, |x, _| {
+        Rgb(if x < 4 { [200; 3] } else { [250; 3] })
+    }))
+}
+
+#[test]
+fn sixteen_bit_scans_are_calibrated() {
+    let calibration = Calibration::from_white_scan(&white_scan());
+    let mut scan = DynamicImage::ImageRgb16(ImageBuffer::from_fn(8, 2, |x, _| {
+        Rgb(if x < 4 { [40000; 3] } else { [50000; 3] })
+    }));
+
+    calibration.apply(&mut scan);
+
+    let scan = scan.as_rgb16().expect("the scan stays 16-bit");
+    assert_eq!(scan[(0, 0)], Rgb([51000; 3]));
+    assert_eq!(scan[(7, 1)], Rgb([51000; 3]));
+}
+
+#[test]
+fn color_calibrations_apply_to_gray_scans() {
+    let calibration = Calibration::from_white_scan(&white_scan());
+    let mut scan = DynamicImage::ImageLuma8(ImageBuffer::from_fn(8, 2, |x, _| {
+        Luma(if x < 4 { [80] } else { [100] })
+    }));
+
+    calibration.apply(&mut scan);
+
+    let scan = scan.as_luma8().expect("the scan stays gray");
+    assert_eq!(scan[(0, 0)], Luma([102]));
+    assert_eq!(scan[(7, 1)], Luma([102]));
+}
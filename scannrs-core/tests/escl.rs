@@ -0,0 +1,228 @@
+//! Scanning over eSCL from a scanner faked on a local port
+#![cfg(feature = "escl")]
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use image::DynamicImage;
+use image::ImageFormat;
+use image::RgbImage;
+use scannrs_core::backend::escl::EsclBackend;
+use scannrs_core::backend::mock::MockBackend;
+use scannrs_core::backend::Backends;
+use scannrs_core::backend::Constraint;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::job::Mode;
+use scannrs_core::job::ScanJob;
+
+const CAPABILITIES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<scan:ScannerCapabilities xmlns:scan="http://schemas.hp.com/imaging/escl/2011/05/03" xmlns:pwg="http://www.pwg.org/schemas/2010/12/sm">
+  <pwg:Version>2.63</pwg:Version>
+  <pwg:MakeAndModel>Acme Scanjet 3000</pwg:MakeAndModel>
+  <scan:Platen>
+    <scan:PlatenInputCaps>
+      <scan:MaxWidth>2550</scan:MaxWidth>
+      <scan:MaxHeight>3508</scan:MaxHeight>
+      <scan:SettingProfiles>
+        <scan:SettingProfile>
+          <scan:ColorModes>
+            <scan:ColorMode>RGB24</scan:ColorMode>
+            <scan:ColorMode>Grayscale8</scan:ColorMode>
+          </scan:ColorModes>
+          <scan:DocumentFormats>
+            <pwg:DocumentFormat>image/jpeg</pwg:DocumentFormat>
+            <pwg:DocumentFormat>image/png</pwg:DocumentFormat>
+          </scan:DocumentFormats>
+          <scan:SupportedResolutions>
+            <scan:DiscreteResolutions>
+              <scan:DiscreteResolution><scan:XResolution>100</scan:XResolution><scan:YResolution>100</scan:YResolution></scan:DiscreteResolution>
+              <scan:DiscreteResolution><scan:XResolution>300</scan:XResolution><scan:YResolution>300</scan:YResolution></scan:DiscreteResolution>
+            </scan:DiscreteResolutions>
+          </scan:SupportedResolutions>
+        </scan:SettingProfile>
+      </scan:SettingProfiles>
+    </scan:PlatenInputCaps>
+  </scan:Platen>
+</scan:ScannerCapabilities>
+"#;
+
+/// Serve a scanner that sends a red page of 40 by 20 pixels, returning its eSCL root and the scan settings it got
+fn serve() -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a local port is free");
+    let root = format!(
+        "http://{}/eSCL",
+        listener.local_addr().expect("the port is bound")
+    );
+    let settings = Arc::new(Mutex::new(Vec::new()));
+
+    let mut page = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(40, 20, image::Rgb([255, 0, 0])))
+        .write_to(&mut Cursor::new(&mut page), ImageFormat::Png)
+        .expect("the page can be encoded");
+
+    let received = settings.clone();
+    std::thread::spawn(move || {
+        let mut pages_left = 0;
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().expect("the stream can be cloned"));
+
+            let mut request = String::new();
+            let mut length = 0;
+            reader.read_line(&mut request).expect("the request is read");
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).expect("the headers are read");
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().expect("the length is a number");
+                    }
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).expect("the body is read");
+
+            let (status, headers, body): (&str, &str, &[u8]) =
+                match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+                    ["GET", "/eSCL/ScannerCapabilities"] => (
+                        "200 OK",
+                        "Content-Type: text/xml\r\n",
+                        CAPABILITIES.as_bytes(),
+                    ),
+                    ["POST", "/eSCL/ScanJobs"] => {
+                        received
+                            .lock()
+                            .expect("the lock is not poisoned")
+                            .push(String::from_utf8_lossy(&body).to_string());
+                        pages_left = 1;
+                        ("201 Created", "Location: /eSCL/ScanJobs/1\r\n", b"")
+                    }
+                    ["GET", "/eSCL/ScanJobs/1/NextDocument"] if pages_left > 0 => {
+                        pages_left -= 1;
+                        ("200 OK", "Content-Type: image/png\r\n", &page)
+                    }
+                    _ => ("404 Not Found", "", b""),
+                };
+
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(body);
+        }
+    });
+
+    (root, settings)
+}
+
+#[test]
+fn scans_over_escl() -> miette::Result<()> {
+    let (root, settings) = serve();
+    let name = format!("escl:{root}");
+
+    let page = ScanJob::new(&name)
+        .mode(Mode::Gray)
+        .resolution(100)
+        .scan(&EsclBackend::default())?
+        .expect("pages are only dropped by the post-processing");
+
+    assert_eq!((page.image.width(), page.image.height()), (40, 20));
+    assert!(page.image.as_luma8().is_some(), "gray scans are gray");
+
+    let settings = settings.lock().expect("the lock is not poisoned");
+    assert_eq!(settings.len(), 1);
+    assert!(
+        settings[0].contains("<scan:ColorMode>Grayscale8</scan:ColorMode>"),
+        "{}",
+        settings[0]
+    );
+    assert!(
+        settings[0].contains("<scan:XResolution>100</scan:XResolution>"),
+        "{}",
+        settings[0]
+    );
+    assert!(
+        settings[0].contains("<pwg:DocumentFormat>image/png</pwg:DocumentFormat>"),
+        "{}",
+        settings[0]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn maps_the_capabilities_onto_options() -> miette::Result<()> {
+    let (root, _) = serve();
+    let device = EsclBackend::default().open(&format!("escl:{root}"))?;
+    let options = device.options()?;
+
+    let option = |name: &str| {
+        options
+            .iter()
+            .find(|option| option.name == name)
+            .unwrap_or_else(|| panic!("the option {name} exists"))
+    };
+    assert_eq!(
+        format!("{:?}", option("mode").constraint),
+        r#"Strings(["Gray", "Color"])"#
+    );
+    assert_eq!(
+        format!("{:?}", option("resolution").constraint),
+        "Numbers([100.0, 300.0])"
+    );
+    let Constraint::Range { max, .. } = option("br-y").constraint else {
+        panic!("the geometry is a range");
+    };
+    assert!(
+        (max - 297.0).abs() < 0.1,
+        "3508 300ths of an inch are A4: {max}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn lists_scanners_as_they_answer() -> miette::Result<()> {
+    let (root, _) = serve();
+    let escl = EsclBackend::default()
+        .scanner(root.parse().expect("the root is a URL"))
+        // Nothing listens on the discard port, the scanner is left out
+        .scanner(
+            "http://127.0.0.1:9/eSCL"
+                .parse()
+                .expect("the root is a URL"),
+        );
+    let backends = Backends::new([
+        Box::new(MockBackend::default()) as Box<dyn ScanBackend>,
+        Box::new(escl),
+    ]);
+
+    let mut groups = Vec::new();
+    backends.devices_streamed(&mut |devices| {
+        groups.push(
+            devices
+                .into_iter()
+                .map(|device| device.name)
+                .collect::<Vec<_>>(),
+        )
+    })?;
+
+    let mock = MockBackend::default()
+        .devices()?
+        .into_iter()
+        .map(|device| device.name)
+        .collect::<Vec<_>>();
+    assert_eq!(groups, [mock, vec![format!("escl:{root}")]]);
+
+    Ok(())
+}
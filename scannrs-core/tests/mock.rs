@@ -0,0 +1,399 @@
+//! Scanning with the mock backend, through the same pipeline as real scanners
+
+use std::io::Cursor;
+use std::ops::ControlFlow;
+
+use image::ColorType;
+use image::DynamicImage;
+use scannrs_core::backend::mock::Failure;
+use scannrs_core::backend::mock::MockBackend;
+use scannrs_core::backend::mock::MockScanner;
+use scannrs_core::job::Area;
+use scannrs_core::job::Mode;
+use scannrs_core::job::ScanJob;
+use scannrs_core::output::write_document;
+use scannrs_core::output::write_lines;
+use scannrs_core::output::Format;
+use scannrs_core::postprocess::PostProcessing;
+use scannrs_core::postprocess::PostProcessor;
+use scannrs_core::postprocess::Registry;
+use scannrs_core::postprocess::Rotation;
+use scannrs_core::progress;
+use scannrs_core::progress::ScanEvent;
+use scannrs_core::progress::Stage;
+use scannrs_core::scan::scanlines;
+use scannrs_core::scan::FrameBuffers;
+
+/// One by two inches, which is 100 by 200 pixels at 100 dpi
+const AREA: Area = Area {
+    left: 0.0,
+    top: 0.0,
+    width: 25.4,
+    height: 50.8,
+};
+
+fn job() -> ScanJob {
+    ScanJob::new("mock:0").resolution(100).area_mm(AREA)
+}
+
+fn scan(scanner: MockScanner, job: ScanJob) -> miette::Result<DynamicImage> {
+    let page = job
+        .scan(&MockBackend::new([scanner]))?
+        .expect("pages are only dropped by the post-processing");
+    Ok(page.image)
+}
+
+#[test]
+fn scans_the_selected_area() -> miette::Result<()> {
+    let image = scan(MockScanner::new("mock:0"), job())?;
+
+    assert_eq!((image.width(), image.height()), (100, 200));
+    let image = image.as_rgb8().expect("8-bit color scans are RGB8");
+    assert_eq!(image[(0, 0)].0, [0, 0, 0]);
+    assert_eq!(image[(99, 0)].0[0], 255);
+    assert_eq!(image[(0, 199)].0[1], 255);
+
+    Ok(())
+}
+
+#[test]
+fn decodes_every_depth() -> miette::Result<()> {
+    let cases = [
+        (Mode::Gray, "1", ColorType::L8),
+        (Mode::Gray, "8", ColorType::L8),
+        (Mode::Gray, "16", ColorType::L16),
+        (Mode::Color, "1", ColorType::Rgb8),
+        (Mode::Color, "8", ColorType::Rgb8),
+        (Mode::Color, "16", ColorType::Rgb16),
+    ];
+    for (mode, depth, color) in cases {
+        let image = scan(
+            MockScanner::new("mock:0"),
+            job().mode(mode).option("depth", depth),
+        )?;
+        assert_eq!(image.color(), color, "{mode:?} at depth {depth}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn lineart_is_black_and_white() -> miette::Result<()> {
+    let image = scan(
+        MockScanner::new("mock:0"),
+        job().mode(Mode::Gray).option("depth", "1"),
+    )?;
+    let image = image
+        .as_luma8()
+        .expect("1-bit scans are decoded as 8-bit gray");
+
+    assert!(image.pixels().all(|pixel| matches!(pixel.0[0], 0 | 255)));
+    assert_eq!(image[(0, 0)].0[0], 0);
+    assert_eq!(image[(99, 199)].0[0], 255);
+
+    Ok(())
+}
+
+#[test]
+fn frame_layout_does_not_change_the_image() -> miette::Result<()> {
+    let expected = scan(MockScanner::new("mock:0"), job())?;
+
+    let scanners = [
+        MockScanner::new("mock:0").three_pass(),
+        MockScanner::new("mock:0").hand_scanner(),
+        MockScanner::new("mock:0").padding(3).chunk_size(7),
+    ];
+    for scanner in scanners {
+        let description = format!("{scanner:?}");
+        assert_eq!(scan(scanner, job())?, expected, "{description}");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn reused_buffers_do_not_change_the_page() -> miette::Result<()> {
+    let expected = scan(MockScanner::new("mock:0"), job())?;
+    let backend = MockBackend::default();
+    let mut buffers = FrameBuffers::default();
+
+    // A larger page first, so that the buffers hold more than the next page
+    let larger = job().resolution(300);
+    let mut device = larger.open(&backend)?;
+    larger.read_page_with_buffers(device.as_mut(), &mut buffers, &mut progress::ignore)?;
+
+    let mut device = job().open(&backend)?;
+    let page =
+        job().read_page_with_buffers(device.as_mut(), &mut buffers, &mut progress::ignore)?;
+    assert_eq!(page.image, expected);
+
+    Ok(())
+}
+
+#[test]
+fn feeder_scans_until_empty() -> miette::Result<()> {
+    let backend = MockBackend::new([MockScanner::new("mock:0").pages(3)]);
+
+    let mut pages = Vec::new();
+    let kept = job().scan_feeder_with_progress(&backend, &mut progress::ignore, &mut |page| {
+        pages.push(page)
+    })?;
+
+    assert_eq!(kept, 3);
+    assert_eq!(pages.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn empty_feeder_fails() {
+    let backend = MockBackend::new([MockScanner::new("mock:0").pages(0)]);
+
+    let error = job().scan(&backend).expect_err("there is no page to scan");
+    assert_eq!(error.to_string(), "The document feeder is empty");
+    assert!(error.help().is_some(), "the error suggests what to do");
+}
+
+#[test]
+fn failures_are_reported() {
+    let failures = [Failure::Open, Failure::Start, Failure::Read { after: 1000 }];
+    for failure in failures {
+        let result = scan(MockScanner::new("mock:0").fail_at(failure), job());
+        assert!(result.is_err(), "{failure:?}");
+    }
+
+    let result = scan(MockScanner::new("mock:1"), job());
+    assert!(result.is_err(), "the scanner does not exist");
+
+    let result = scan(MockScanner::new("mock:0"), job().option("depth", "12"));
+    assert!(result.is_err(), "the depth is not supported");
+}
+
+#[test]
+fn progress_can_cancel() {
+    let backend = MockBackend::default();
+
+    let mut calls = 0;
+    let error = job()
+        .scan_with_progress(&backend, &mut |_| {
+            calls += 1;
+            if calls > 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })
+        .expect_err("the scan was cancelled");
+
+    assert_eq!(error.to_string(), "The scan was cancelled");
+}
+
+#[test]
+fn pages_survive_encoding() -> miette::Result<()> {
+    let page = job()
+        .scan(&MockBackend::default())?
+        .expect("pages are only dropped by the post-processing");
+
+    let mut png = Cursor::new(Vec::new());
+    write_document(&mut png, Format::Png, std::slice::from_ref(&page))?;
+    let decoded = image::load_from_memory(png.get_ref()).expect("the PNG is valid");
+
+    assert_eq!(decoded, page.image);
+
+    Ok(())
+}
+
+#[test]
+fn reports_progress_events() -> miette::Result<()> {
+    let backend = MockBackend::new([MockScanner::new("mock:0").three_pass()]);
+
+    let mut events = Vec::new();
+    job().scan_with_progress(&backend, &mut |event| {
+        events.push(event);
+        ControlFlow::Continue(())
+    })?;
+
+    assert_eq!(events.first(), Some(&ScanEvent::PageStarted { page: 1 }));
+    assert_eq!(
+        events.last(),
+        Some(&ScanEvent::PageFinished {
+            page: 1,
+            kept: true
+        })
+    );
+    let frames = events
+        .iter()
+        .filter(|event| matches!(event, ScanEvent::FrameStarted { .. }))
+        .count();
+    assert_eq!(frames, 3);
+    let read = events.iter().rev().find_map(|event| match event {
+        ScanEvent::Read { lines, .. } => Some(*lines),
+        _ => None,
+    });
+    assert_eq!(read, Some(200));
+
+    Ok(())
+}
+
+#[test]
+fn scanlines_arrive_one_by_one() -> miette::Result<()> {
+    let expected = scan(MockScanner::new("mock:0"), job())?;
+    let expected = expected.as_rgb8().expect("8-bit color scans are RGB8");
+
+    let backend = MockBackend::new([MockScanner::new("mock:0").padding(3).chunk_size(7)]);
+    let mut device = job().open(&backend)?;
+    let lines = scanlines(device.as_mut()).collect::<miette::Result<Vec<_>>>()?;
+
+    assert_eq!(lines.len(), 200);
+    for line in lines {
+        let image = line.image.as_rgb8().expect("8-bit color scans are RGB8");
+        assert_eq!((image.width(), image.height()), (100, 1));
+        let row = line.number as u32;
+        assert!((0..100).all(|x| image[(x, 0)] == expected[(x, row)]));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pages_are_written_line_by_line() -> miette::Result<()> {
+    let page = job()
+        .scan(&MockBackend::default())?
+        .expect("pages are only dropped by the post-processing");
+    let expected = page.image.to_rgb8();
+
+    for format in [Format::Jpeg, Format::Tiff] {
+        let backend = MockBackend::default();
+        let mut device = job().open(&backend)?;
+        let mut lines = scanlines(device.as_mut()).map(|line| line.map(|line| line.image));
+        let mut document = Cursor::new(Vec::new());
+        let size = write_lines(&mut document, format, page.dpi, Some(200), &mut lines)?;
+
+        assert_eq!((size.width, size.height), (100, 200));
+        let decoded = image::load_from_memory(document.get_ref())
+            .expect("the document is valid")
+            .to_rgb8();
+        assert_eq!(decoded.dimensions(), (100, 200));
+        // JPEG is lossy, but not by much
+        let difference = decoded
+            .as_raw()
+            .iter()
+            .zip(expected.as_raw())
+            .map(|(decoded, expected)| u64::from(decoded.abs_diff(*expected)))
+            .sum::<u64>()
+            / expected.as_raw().len() as u64;
+        assert!(
+            difference < 8,
+            "{format:?} differs by {difference} on average"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn three_pass_scans_are_written_as_a_whole() -> miette::Result<()> {
+    let expected = scan(MockScanner::new("mock:0"), job())?;
+    let path = std::env::temp_dir().join(format!("scannrs-three-pass-{}.tif", std::process::id()));
+
+    let backend = MockBackend::new([MockScanner::new("mock:0").three_pass()]);
+    let size = job()
+        .output(Format::Tiff, &path)
+        .scan_to_output(&backend, &mut progress::ignore)?
+        .expect("pages are only dropped by the post-processing");
+    let written = image::open(&path).expect("the TIFF is valid");
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!((size.width, size.height), (100, 200));
+    assert_eq!(written, expected);
+
+    Ok(())
+}
+
+#[test]
+fn cancelled_scans_leave_the_output_alone() {
+    let path = std::env::temp_dir().join(format!("scannrs-cancelled-{}.jpg", std::process::id()));
+    std::fs::write(&path, "an earlier scan").expect("the output can be written");
+
+    let error = job()
+        .output(Format::Jpeg, &path)
+        .scan_to_output(&MockBackend::default(), &mut |event| match event {
+            ScanEvent::Read { lines: 100, .. } => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        })
+        .expect_err("the scan was cancelled");
+    let written = std::fs::read_to_string(&path);
+    let partial = path.with_extension("jpg.part").exists();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(error.to_string(), "The scan was cancelled");
+    assert_eq!(written.ok().as_deref(), Some("an earlier scan"));
+    assert!(!partial, "the partial page is removed");
+}
+
+#[derive(Debug)]
+struct Invert;
+
+impl PostProcessor for Invert {
+    fn name(&self) -> &'static str {
+        "invert"
+    }
+
+    fn process(&self, mut image: DynamicImage) -> Option<DynamicImage> {
+        image.invert();
+        Some(image)
+    }
+}
+
+#[test]
+fn registered_post_processors_run_after_the_builtin_ones() -> miette::Result<()> {
+    let mut registry = Registry::with_builtins();
+    registry.register(Invert);
+    let invert = registry.get("invert").expect("the step was registered");
+
+    let backend = MockBackend::default();
+    let mut stages = Vec::new();
+    let page = job()
+        .processing(PostProcessing {
+            rotation: Rotation::UpsideDown,
+            ..PostProcessing::default()
+        })
+        .post_processor(invert)
+        .scan_with_progress(&backend, &mut |event| {
+            if let ScanEvent::Processing { stage } = event {
+                stages.push(stage);
+            }
+            ControlFlow::Continue(())
+        })?
+        .expect("no step drops the page");
+
+    assert_eq!(stages, [Stage::Rotation, Stage::Other("invert")]);
+    let image = page.image.as_rgb8().expect("8-bit color scans are RGB8");
+    assert_eq!(image[(99, 199)].0, [255, 255, 255]);
+
+    Ok(())
+}
+
+#[test]
+fn straight_pages_are_cropped_to_their_content() {
+    let mut page = image::RgbImage::from_pixel(400, 300, image::Rgb([255, 255, 255]));
+    for y in 50..150 {
+        for x in 100..200 {
+            page[(x, y)] = image::Rgb([0, 0, 0]);
+        }
+    }
+
+    let processed = PostProcessing {
+        deskew: true,
+        autocrop: true,
+        ..PostProcessing::default()
+    }
+    .apply(DynamicImage::ImageRgb8(page))
+    .expect("the page has content");
+
+    // The content with a margin of a hundredth of the shorter side
+    assert_eq!((processed.width(), processed.height()), (106, 106));
+    let processed = processed.to_rgb8();
+    assert_eq!(processed[(0, 0)].0, [255, 255, 255]);
+    assert_eq!(processed[(53, 53)].0, [0, 0, 0]);
+}
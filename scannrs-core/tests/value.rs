@@ -0,0 +1,86 @@
+//! Parsing option values and converting them for the options of the mock scanner
+
+use scannrs_core::backend::mock::MockBackend;
+use scannrs_core::backend::OptionDescriptor;
+use scannrs_core::backend::OptionValue;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::backend::Unit;
+use scannrs_core::value::Value;
+use scannrs_core::Error;
+
+fn option(name: &str) -> OptionDescriptor {
+    let device = MockBackend::default()
+        .open("mock:0")
+        .expect("the mock scanner exists");
+    device
+        .options()
+        .expect("the mock scanner lists its options")
+        .into_iter()
+        .find(|option| option.name == name)
+        .expect("the mock scanner has the option")
+}
+
+fn convert(name: &str, value: &str) -> Result<Option<OptionValue>, Error> {
+    Value::from(value).to_option_value(&option(name))
+}
+
+#[test]
+fn parses_units() {
+    assert_eq!(Value::from("300dpi"), Value::with_unit(300.0, Unit::Dpi));
+    assert_eq!(Value::from("210 mm"), Value::with_unit(210.0, Unit::Mm));
+    assert_eq!(Value::from("21cm"), Value::with_unit(210.0, Unit::Mm));
+    assert_eq!(Value::from("2in"), Value::with_unit(50.8, Unit::Mm));
+    assert_eq!(Value::from("8"), Value::from(8.0));
+    assert_eq!(Value::from("TRUE"), Value::Bool(true));
+    assert_eq!(Value::from("Color"), Value::Text(String::from("Color")));
+    assert_eq!(Value::from("inf"), Value::Text(String::from("inf")));
+}
+
+#[test]
+fn round_trips_through_text() {
+    for text in ["300dpi", "12.5mm", "8", "false", "Color pattern"] {
+        assert_eq!(Value::from(text).to_string(), text);
+    }
+}
+
+#[test]
+fn converts_per_option() -> Result<(), Error> {
+    assert_eq!(
+        convert("resolution", "300dpi")?,
+        Some(OptionValue::Int(300))
+    );
+    assert_eq!(convert("resolution", "300")?, Some(OptionValue::Int(300)));
+    assert_eq!(convert("br-x", "10cm")?, Some(OptionValue::Fixed(100.0)));
+    assert_eq!(convert("three-pass", "yes")?, Some(OptionValue::Bool(true)));
+    assert_eq!(
+        convert("mode", "gray")?,
+        Some(OptionValue::String(String::from("Gray")))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn rejects_values_the_option_does_not_take() {
+    let cases = [
+        ("resolution", "300mm"),
+        ("resolution", "150.5"),
+        ("resolution", "high"),
+        ("resolution", "2400"),
+        ("depth", "12"),
+        ("mode", "Sepia"),
+        ("three-pass", "maybe"),
+        ("br-x", "30cm"),
+    ];
+    for (name, value) in cases {
+        assert!(convert(name, value).is_err(), "{name}={value}");
+    }
+}
+
+#[test]
+fn type_checks_ignore_the_constraint() {
+    let depth = option("depth");
+
+    assert!(Value::from("12").check_type(&depth).is_ok());
+    assert!(Value::from("12mm").check_type(&depth).is_err());
+}
@@ -0,0 +1,50 @@
+//! Storing the software calibration of the scanners, one file per device in the state directory
+
+use std::path::PathBuf;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::calibration::Calibration;
+
+fn path(device: &str) -> miette::Result<PathBuf> {
+    let file_name = device
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+
+    Ok(crate::paths::state_dir()?
+        .join("calibration")
+        .join(format!("{file_name}.json")))
+}
+
+/// Load the stored calibration for the given device, if there is one
+pub(crate) fn load(device: &str) -> miette::Result<Option<Calibration>> {
+    let path = path(device)?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .into_diagnostic()
+            .with_context(|| format!("While reading the calibration at {}", path.display())),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error).into_diagnostic(),
+    }
+}
+
+pub(crate) fn save(calibration: &Calibration, device: &str) -> miette::Result<PathBuf> {
+    let path = path(device)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).into_diagnostic()?;
+    }
+    std::fs::write(&path, serde_json::to_vec(calibration).into_diagnostic()?)
+        .into_diagnostic()
+        .with_context(|| format!("While writing the calibration to {}", path.display()))?;
+
+    Ok(path)
+}
+
+pub(crate) fn remove(device: &str) -> miette::Result<()> {
+    let path = path(device)?;
+    match std::fs::remove_file(&path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error).into_diagnostic(),
+        _ => Ok(()),
+    }
+}
@@ -1,21 +1,94 @@
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
+#[cfg(unix)]
+use chrono::NaiveTime;
+use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use clap::ValueEnum;
 use miette::IntoDiagnostic;
+use scannrs_core::ocr::OcrEngine;
+use scannrs_core::ocr::OcrFormat;
+use scannrs_core::ocr::MAX_PSM;
+use scannrs_core::output::Format;
+use scannrs_core::value::Value;
+use serde::Serialize;
 
+use super::config::DestinationConfig;
+use super::destination::parse_upload_target;
 use super::error::ScannrsError;
+use super::history::HistoryRef;
 
 #[derive(Parser)]
 pub struct Cli {
+    /// How to print results, `json` is stable and meant for scripts
+    #[arg(
+        long = "output",
+        id = "output_format",
+        global = true,
+        value_enum,
+        default_value_t
+    )]
+    pub(crate) output: OutputFormat,
+
+    /// Write the progress of scans as newline-delimited JSON events to stderr, for programs wrapping scannrs
+    #[arg(long, global = true)]
+    pub(crate) json_events: bool,
+
+    /// The configuration file to use instead of `$XDG_CONFIG_HOME/scannrs/config.toml`
+    #[arg(long, global = true)]
+    pub(crate) config: Option<PathBuf>,
+
+    /// The profile from the configuration to apply to every scan, replacing the default profile
+    #[arg(long, global = true)]
+    pub(crate) profile: Option<String>,
+
+    /// Have `list` enumerate the scanners again instead of using the list an earlier one found, see `device_cache` in the
+    /// configuration
+    #[arg(long, global = true)]
+    pub(crate) refresh: bool,
+
     #[command(subcommand)]
     pub(crate) command: Command,
 }
 
+#[derive(clap::ValueEnum, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Print a value as pretty JSON on stdout
+pub(crate) fn print_json<T: Serialize>(value: &T) -> miette::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value).into_diagnostic()?);
+    Ok(())
+}
+
 #[derive(Subcommand)]
 pub(crate) enum Command {
+    /// Show the version of scannrs and the SANE library, and what this build supports
+    About,
     /// List available scanners
-    List,
+    List {
+        /// Only show scanners whose vendor contains this text
+        #[arg(long)]
+        vendor: Option<String>,
+
+        /// Only show scanners whose model contains this text
+        #[arg(long)]
+        model: Option<String>,
+
+        /// Only show scanners whose type contains this text, like `flatbed scanner`
+        #[arg(long = "type")]
+        type_: Option<String>,
+
+        /// Print one tab-separated line per scanner without a header, in a format that stays stable
+        #[arg(long)]
+        porcelain: bool,
+    },
     /// Get all options this scanner exposes
     Options {
         /// Which scanner to operate on
@@ -25,28 +98,439 @@ pub(crate) enum Command {
         command: Option<OptionsCommand>,
     },
     Scan {
-        /// Which scanner to operate on
-        name: String,
+        /// Which scanner to operate on, `$SCANNRS_DEVICE` or the default one of the configuration if not given
+        name: Option<String>,
+
+        /// The resolution in dots per inch, `$SCANNRS_RESOLUTION` if not given, replaced by `-o resolution=...`
+        #[arg(short, long, value_parser = parse_dpi)]
+        resolution: Option<f32>,
 
-        /// A list of options in `key=value` format to set before scanning, can be used multiple times, later options
-        /// replace earlier ones.
+        /// Where relative paths are saved, `$SCANNRS_OUTPUT_DIR` or the default of the configuration if not given
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+
+        /// A list of options in `key=value` format to set before scanning, like `resolution=300dpi` or `br-x=21cm`, can
+        /// be used multiple times, later options replace earlier ones.
         #[arg(short, long, value_parser = split_options)]
-        options: Vec<(Vec<u8>, String)>,
+        options: Vec<(String, Value)>,
 
-        /// The path to save the scan at
+        /// The path to save the scan at, `{doc_date}` is replaced by the date found in the text of the scan
         #[arg(short, long)]
         path: PathBuf,
+
+        /// The format to save the scan in, guessed from the extension of the path if not given
+        #[arg(short, long)]
+        format: Option<Format>,
+
+        /// A file created by `options export` to apply before the other options
+        #[arg(long)]
+        settings: Option<PathBuf>,
+
+        /// Serve the scan on the local network for a while, `10m` if no duration is given, and print a QR code
+        /// linking to it to grab it on a phone
+        #[arg(long, value_name = "DURATION", num_args = 0..=1, default_missing_value = "10m", value_parser = parse_duration)]
+        share: Option<Duration>,
+
+        #[command(flatten)]
+        uploads: UploadArgs,
+    },
+    /// Scan several pages in a row, either into numbered files or assembled into a single document
+    Batch {
+        /// Which scanner to operate on, `$SCANNRS_DEVICE` or the default one of the configuration if not given
+        name: Option<String>,
+
+        /// The resolution in dots per inch, `$SCANNRS_RESOLUTION` if not given, replaced by `-o resolution=...`
+        #[arg(short, long, value_parser = parse_dpi)]
+        resolution: Option<f32>,
+
+        /// Where relative paths are saved, `$SCANNRS_OUTPUT_DIR` or the default of the configuration if not given
+        #[arg(long)]
+        output_dir: Option<PathBuf>,
+
+        /// A list of options in `key=value` format to set before scanning, like `resolution=300dpi` or `br-x=21cm`, can
+        /// be used multiple times, later options replace earlier ones.
+        #[arg(short, long, value_parser = split_options)]
+        options: Vec<(String, Value)>,
+
+        /// Where to save the scans, `{n}` is replaced by the page number, without it all pages are assembled into a
+        /// single PDF or TIFF document
+        #[arg(short, long, required_unless_present = "resume")]
+        path: Option<PathBuf>,
+
+        /// The format to save the scans in, guessed from the extension of the path if not given
+        #[arg(short, long)]
+        format: Option<Format>,
+
+        /// A file created by `options export` to apply before the other options
+        #[arg(long)]
+        settings: Option<PathBuf>,
+
+        /// Stop after this many pages, instead of asking before every page
+        #[arg(long)]
+        pages: Option<usize>,
+
+        /// The number of the first page
+        #[arg(long, default_value_t = 1)]
+        start: usize,
+
+        /// Show a desktop notification once the batch is done or has failed, also when it is resumed later
+        #[arg(long)]
+        notify: bool,
+
+        /// Continue an interrupted batch where it left off
+        #[arg(long, conflicts_with_all = ["name", "resolution", "output_dir", "options", "path", "format", "settings", "pages", "start"])]
+        resume: bool,
+    },
+    /// Scan a page and print it right away, like a photocopier
+    Copy {
+        /// Which scanner to operate on, `$SCANNRS_DEVICE` or the default one of the configuration if not given
+        name: Option<String>,
+
+        /// The resolution in dots per inch, `$SCANNRS_RESOLUTION` if not given, replaced by `-o resolution=...`
+        #[arg(short, long, value_parser = parse_dpi)]
+        resolution: Option<f32>,
+
+        /// A list of options in `key=value` format to set before scanning, like `resolution=300dpi` or `br-x=21cm`, can
+        /// be used multiple times, later options replace earlier ones.
+        #[arg(short, long, value_parser = split_options)]
+        options: Vec<(String, Value)>,
+
+        /// A file created by `options export` to apply before the other options
+        #[arg(long)]
+        settings: Option<PathBuf>,
+
+        /// The CUPS queue to print to with `lp`, or the `ipp://` or `ipps://` URI of a printer
+        #[arg(long)]
+        printer: String,
+
+        /// How many copies to print
+        #[arg(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=999))]
+        copies: u32,
+    },
+    /// Scan again with the device, options and format of a previous scan
+    Rerun {
+        /// The id of the scan in the history, or `last`
+        entry: HistoryRef,
+
+        /// The path to save the scan at, by default the previous path with an increasing number appended
+        #[arg(short, long)]
+        path: Option<PathBuf>,
     },
     Tui,
+    /// Calibrate a scanner, either with its own calibration routine or by scanning a white sheet
+    Calibrate {
+        /// Which scanner to operate on
+        name: String,
+
+        /// Always perform a software calibration, even if the scanner can calibrate itself
+        #[arg(long)]
+        software: bool,
+
+        /// Remove the stored software calibration instead
+        #[arg(long, conflicts_with = "software")]
+        reset: bool,
+
+        /// A list of options in `key=value` format to set before the calibration scan, can be used multiple times
+        #[arg(short, long, value_parser = split_options)]
+        options: Vec<(String, Value)>,
+    },
+    /// Combine existing images into a single PDF or TIFF document
+    Merge {
+        /// The document to create, its format is guessed from the extension
+        output: PathBuf,
+
+        /// The images to use as pages, in order
+        #[arg(required = true)]
+        pages: Vec<PathBuf>,
+
+        /// The resolution the images were scanned at, to give the pages their physical size
+        #[arg(long, default_value_t = 300.0, value_parser = parse_dpi)]
+        dpi: f32,
+
+        /// The format of the document, guessed from the extension of the output if not given
+        #[arg(short, long)]
+        format: Option<Format>,
+    },
+    /// Recognize the text in an existing image
+    Ocr {
+        /// The image to recognize the text in
+        input: PathBuf,
+
+        /// The language(s) of the text, like `eng` or `deu+eng`, the one of the `[ocr]` configuration or `eng` if
+        /// not given
+        #[arg(short, long)]
+        lang: Option<String>,
+
+        /// The page segmentation mode of tesseract, see `tesseract --help-psm`
+        #[arg(long, value_parser = clap::value_parser!(u8).range(0..=MAX_PSM as i64))]
+        psm: Option<u8>,
+
+        /// Whether to run the `tesseract` executable, the linked library or the remote service of the configuration
+        #[arg(long)]
+        engine: Option<OcrEngine>,
+
+        /// The format of the recognized text
+        #[arg(short, long, default_value = "txt")]
+        format: OcrFormat,
+
+        /// Where to save the result, printed to stdout if not given
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+    },
+    /// Exercise the whole scan pipeline against the virtual scanner of SANE's `test` backend
+    Selftest {
+        /// The name of the test scanner, the `test` backend needs to be enabled in SANE's `dll.conf`
+        #[arg(long, default_value = "test:0")]
+        device: String,
+    },
+    /// Store the password for a service in the keyring of the system
+    Login {
+        /// The service to log in to, with the account of the configuration
+        service: LoginService,
+    },
+    /// Expose the scanners over an HTTP API, with metrics of the scans for Prometheus at `/metrics`
+    Serve {
+        /// The address to listen on
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Also act as an eSCL (AirScan) network scanner for the default scanner, under `/eSCL`
+        #[arg(long)]
+        escl: bool,
+        /// Also serve the gRPC API of `proto/scannrs.proto` on this address, needs the `grpc` feature
+        #[arg(long, value_name = "ADDRESS")]
+        grpc: Option<SocketAddr>,
+    },
+    /// Print a systemd unit running `daemon` or `serve`, to install as `~/.config/systemd/user/scannrs-<service>.service`
+    #[cfg(unix)]
+    SystemdUnit {
+        service: UnitService,
+
+        /// Print the socket unit starting the service on the first connection instead, to install next to it as
+        /// `scannrs-<service>.socket`
+        #[arg(long)]
+        socket: bool,
+    },
+    /// Offer the scanners as the `org.scannrs` D-Bus service, for desktop environments and other local programs
+    #[cfg(feature = "dbus")]
+    Dbus {
+        /// Use the system bus instead of the session bus of the user
+        #[arg(long)]
+        system: bool,
+    },
+    /// Review previous scans
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+    /// Add, inspect and cancel jobs of a running daemon
+    #[cfg(unix)]
+    Queue {
+        /// The unix socket of the daemon, defaults to `$XDG_RUNTIME_DIR/scannrs.sock`
+        #[arg(short, long, global = true)]
+        socket: Option<PathBuf>,
+
+        #[command(subcommand)]
+        command: QueueCommand,
+    },
+    /// Run a long-lived daemon executing queued scan jobs one after another
+    ///
+    /// With an [mqtt] section in the configuration, its events are also published to the broker there. With
+    /// [[buttons]], pressing the buttons of the scanners queues scans with the profiles and destinations given there.
+    #[cfg(unix)]
+    Daemon {
+        /// The unix socket to accept jobs on, defaults to `$XDG_RUNTIME_DIR/scannrs.sock`
+        #[arg(short, long)]
+        socket: Option<PathBuf>,
+
+        /// Offer metrics of the scans for Prometheus at `/metrics` on this address, like `127.0.0.1:9464`
+        #[arg(long)]
+        metrics: Option<SocketAddr>,
+    },
 }
 
-pub(crate) fn split_options(opt: &str) -> miette::Result<(Vec<u8>, String)> {
+pub(crate) fn split_options(opt: &str) -> miette::Result<(String, Value)> {
     opt.split_once('=')
-        .map(|(k, v)| (k.trim().to_string().into_bytes(), v.trim().to_string()))
+        .map(|(k, v)| (k.trim().to_string(), Value::from(v.trim())))
         .ok_or(ScannrsError::InvalidOption)
         .into_diagnostic()
 }
 
+/// Check that the URL of `--upload` is one scannrs can upload to, see [`parse_upload_target`]
+pub(crate) fn parse_upload_url(url: &str) -> miette::Result<String> {
+    parse_upload_target(url).map(|_| url.to_string())
+}
+
+pub(crate) fn parse_dpi(dpi: &str) -> miette::Result<f32> {
+    dpi.parse::<f32>()
+        .ok()
+        .filter(|dpi| *dpi > 0.0)
+        .ok_or(ScannrsError::InvalidDpi)
+        .into_diagnostic()
+}
+
+/// Parse a duration like `90`, `30s`, `5m`, `2h` or `1d`
+pub(crate) fn parse_duration(duration: &str) -> miette::Result<Duration> {
+    let duration = duration.trim();
+    let (number, unit) = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or((duration, ""), |idx| duration.split_at(idx));
+
+    let factor = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(ScannrsError::InvalidDuration).into_diagnostic(),
+    };
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| ScannrsError::InvalidDuration)
+        .into_diagnostic()?;
+
+    let seconds = number
+        .checked_mul(factor)
+        .ok_or(ScannrsError::InvalidDuration)?;
+
+    Ok(Duration::from_secs(seconds))
+}
+
+#[cfg(unix)]
+pub(crate) fn parse_time(time: &str) -> miette::Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| ScannrsError::InvalidTime)
+        .into_diagnostic()
+}
+
+/// The services whose passwords can be stored with `login`
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum LoginService {
+    Webdav,
+    Email,
+    Mqtt,
+}
+
+#[cfg(unix)]
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum UnitService {
+    /// The queue of `scannrs daemon`, started by its socket
+    Daemon,
+    /// The HTTP API of `scannrs serve`
+    Serve,
+}
+
+/// The services to send the saved scan on to
+#[derive(Args, Debug)]
+pub(crate) struct UploadArgs {
+    /// Upload the scan to paperless-ngx once it is saved, as configured in the [paperless] section of the
+    /// configuration
+    #[arg(long)]
+    pub(crate) paperless: bool,
+
+    /// The title of the document in paperless-ngx, the name of the file if not given
+    #[arg(long, requires = "paperless")]
+    pub(crate) title: Option<String>,
+
+    /// A tag to give the document in paperless-ngx by its name or id, in addition to those of the configuration, can
+    /// be used multiple times
+    #[arg(long = "tag", requires = "paperless")]
+    pub(crate) tags: Vec<String>,
+
+    /// The correspondent of the document in paperless-ngx, by their name or id
+    #[arg(long, requires = "paperless")]
+    pub(crate) correspondent: Option<String>,
+
+    /// Upload the scan to the WebDAV server of the [webdav] section of the configuration once it is saved
+    #[arg(long)]
+    pub(crate) webdav: bool,
+
+    /// Email the scan to this address once it is saved, through the account of the [email] section of the
+    /// configuration, can be used multiple times
+    #[arg(long = "email", value_name = "ADDRESS")]
+    pub(crate) emails: Vec<String>,
+
+    /// Upload the scan to the given place once it is saved, like `s3://bucket/prefix/` for S3 or
+    /// `sftp://user@host/path/` and `scp://user@host/path/` over SSH, can be used multiple times
+    #[arg(long = "upload", value_name = "URL", value_parser = parse_upload_url)]
+    pub(crate) urls: Vec<String>,
+}
+
+impl UploadArgs {
+    /// The destinations given on the command line, sent to after the ones of the profile
+    pub(crate) fn destinations(self) -> Vec<DestinationConfig> {
+        let paperless = self.paperless.then(|| DestinationConfig::Paperless {
+            title: self.title,
+            tags: self.tags,
+            correspondent: self.correspondent,
+        });
+        let webdav = self
+            .webdav
+            .then_some(DestinationConfig::Webdav { path: None });
+        let email = (!self.emails.is_empty()).then(|| DestinationConfig::Email { to: self.emails });
+
+        paperless
+            .into_iter()
+            .chain(webdav)
+            .chain(email)
+            .chain(
+                self.urls
+                    .into_iter()
+                    .map(|url| DestinationConfig::Upload { url }),
+            )
+            .collect()
+    }
+}
+
+#[derive(Default, Subcommand)]
+pub(crate) enum HistoryCommand {
+    /// List all previous scans
+    #[default]
+    List,
+    /// Show the details of a scan
+    Show {
+        /// The id of the scan, or `last`
+        entry: HistoryRef,
+    },
+    /// Open the files of a scan with the default application
+    Open {
+        /// The id of the scan, or `last`
+        entry: HistoryRef,
+    },
+}
+
+#[cfg(unix)]
+#[derive(Subcommand)]
+pub(crate) enum QueueCommand {
+    /// Queue a scan
+    Add {
+        /// Which scanner to operate on
+        name: String,
+
+        /// The path to save the scan at
+        #[arg(short, long)]
+        path: PathBuf,
+
+        /// A list of options in `key=value` format to set before scanning, like `resolution=300dpi` or `br-x=21cm`, can
+        /// be used multiple times, later options replace earlier ones.
+        #[arg(short, long, value_parser = split_options)]
+        options: Vec<(String, Value)>,
+
+        /// Wait this long before starting the scan, like `30s`, `5m` or `2h`
+        #[arg(long, value_parser = parse_duration, conflicts_with = "at")]
+        delay: Option<Duration>,
+
+        /// Start the scan at this time of day, in `HH:MM` format
+        #[arg(long, value_parser = parse_time)]
+        at: Option<NaiveTime>,
+    },
+    /// List all jobs
+    List,
+    /// Show the status of a job
+    Status { id: u64 },
+    /// Cancel a job that has not started yet
+    Cancel { id: u64 },
+}
+
 #[derive(Default, Subcommand)]
 pub(crate) enum OptionsCommand {
     #[default]
@@ -54,4 +538,25 @@ pub(crate) enum OptionsCommand {
     Show {
         option: String,
     },
+    /// Describe options in detail, including their unit, current value and allowed values
+    Describe {
+        /// Only describe this option instead of all of them
+        option: Option<String>,
+    },
+    /// Print the current value of every settable option as TOML
+    Export,
+    /// Persistently set an option, applying it to all future scans with this scanner
+    Set {
+        option: String,
+        /// Numbers can be given with a unit, like `300dpi` or `21cm`
+        value: Value,
+    },
+    /// Remove a persistently set option
+    Unset {
+        option: String,
+    },
+    /// Set the options from a file created by `export`
+    Import {
+        file: PathBuf,
+    },
 }
@@ -0,0 +1,108 @@
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::ocr::tesseract_version;
+use scannrs_core::output::Format;
+use scannrs_core::postprocess::Registry;
+use serde::Serialize;
+
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+
+/// The optional cargo features this binary was built with
+const FEATURES: &[&str] = &[
+    #[cfg(feature = "escl")]
+    "escl",
+    #[cfg(feature = "mdns")]
+    "mdns",
+    #[cfg(feature = "sane")]
+    "sane",
+    #[cfg(feature = "wia")]
+    "wia",
+    #[cfg(feature = "wsd")]
+    "wsd",
+    #[cfg(feature = "s3")]
+    "s3",
+    #[cfg(feature = "sftp")]
+    "sftp",
+    #[cfg(feature = "mqtt")]
+    "mqtt",
+    #[cfg(feature = "dbus")]
+    "dbus",
+    #[cfg(feature = "notifications")]
+    "notifications",
+    #[cfg(feature = "tesseract")]
+    "tesseract",
+    #[cfg(feature = "remote-ocr")]
+    "remote-ocr",
+    #[cfg(feature = "plugins")]
+    "plugins",
+    #[cfg(feature = "grpc")]
+    "grpc",
+];
+
+#[derive(Serialize, Debug)]
+struct About {
+    version: &'static str,
+    /// The backend talking to the scanners and its version
+    backend: String,
+    os: &'static str,
+    arch: &'static str,
+    debug_build: bool,
+    features: &'static [&'static str],
+    formats: Vec<Format>,
+    /// The names of the post-processors that can be enabled in the configuration
+    post_processors: Vec<&'static str>,
+    /// The version of the tesseract executable used for text recognition, if it is installed
+    tesseract: Option<String>,
+}
+
+pub fn about(backend: &dyn ScanBackend, output: OutputFormat) -> Result<(), miette::Error> {
+    let about = About {
+        version: env!("CARGO_PKG_VERSION"),
+        backend: backend.version(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        debug_build: cfg!(debug_assertions),
+        features: FEATURES,
+        formats: vec![Format::Jpeg, Format::Png, Format::Tiff, Format::Pdf],
+        post_processors: Registry::with_builtins().names().collect(),
+        tesseract: tesseract_version(),
+    };
+
+    match output {
+        OutputFormat::Json => print_json(&about)?,
+        OutputFormat::Text => {
+            println!("scannrs {}", about.version);
+            println!("Backend: {}", about.backend);
+            println!(
+                "Build: {}-{}{}",
+                about.arch,
+                about.os,
+                if about.debug_build { " (debug)" } else { "" }
+            );
+            println!(
+                "Features: {}",
+                if about.features.is_empty() {
+                    "none".to_string()
+                } else {
+                    about.features.join(", ")
+                }
+            );
+            println!(
+                "Formats: {}",
+                about
+                    .formats
+                    .iter()
+                    .map(|f| format!("{f:?}").to_lowercase())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            println!("Post-processors: {}", about.post_processors.join(", "));
+            println!(
+                "OCR: {}",
+                about.tesseract.as_deref().unwrap_or("tesseract not found")
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,414 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::DateTime;
+use chrono::Utc;
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::output::write_document;
+use scannrs_core::output::Format;
+use scannrs_core::output::Page;
+use scannrs_core::scan::FrameBuffers;
+use scannrs_core::value::Value;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::scan::scan_page;
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::events::Event;
+use crate::history::History;
+use crate::history::HistoryEntry;
+use crate::i18n::tr;
+use crate::notification;
+use crate::progress::ProgressBar;
+use crate::webhook;
+use crate::webhook::JobReport;
+
+/// The placeholder in the path template that is replaced by the page number
+const PAGE_NUMBER: &str = "{n}";
+
+/// How a new batch should be run, not needed when resuming
+pub struct NewBatch {
+    /// The scanner from the environment or the configuration if not given
+    pub name: Option<String>,
+    pub resolution: Option<f32>,
+    pub output_dir: Option<PathBuf>,
+    pub path: PathBuf,
+    pub format: Option<Format>,
+    pub settings: Option<PathBuf>,
+    pub options: Vec<(String, Value)>,
+    pub pages: Option<usize>,
+    pub start: usize,
+}
+
+/// Everything needed to continue a batch after it was interrupted
+#[derive(Serialize, Deserialize, Debug)]
+struct BatchState {
+    device: String,
+    options: BTreeMap<String, Value>,
+    settings: Option<PathBuf>,
+    /// Either contains `{n}` to save every page on its own, or is the document all pages are assembled into
+    template: PathBuf,
+    format: Format,
+    /// The number of the first page
+    start: usize,
+    /// The number of the next page
+    counter: usize,
+    /// Stop after this many pages instead of asking before every page
+    limit: Option<usize>,
+    started_at: DateTime<Utc>,
+    /// Pages waiting to be assembled into the document
+    pages: Vec<BatchPage>,
+    /// Files that have been written so far
+    outputs: Vec<PathBuf>,
+    /// Show a desktop notification once the batch is done or has failed
+    #[serde(default)]
+    notify: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BatchPage {
+    file: PathBuf,
+    dpi: f32,
+}
+
+impl BatchState {
+    fn path() -> miette::Result<PathBuf> {
+        Ok(crate::paths::state_dir()?.join("batch.json"))
+    }
+
+    /// Where pages are kept until they are assembled into the document
+    fn pages_dir() -> miette::Result<PathBuf> {
+        Ok(crate::paths::state_dir()?.join("batch"))
+    }
+
+    fn load() -> miette::Result<Option<BatchState>> {
+        let path = BatchState::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .into_diagnostic()
+                .with_context(|| format!("While reading the batch state at {}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error).into_diagnostic(),
+        }
+    }
+
+    fn save(&self) -> miette::Result<()> {
+        let path = BatchState::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        let tmp = path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self).into_diagnostic()?)
+            .into_diagnostic()?;
+        std::fs::rename(&tmp, &path)
+            .into_diagnostic()
+            .with_context(|| format!("While writing the batch state to {}", path.display()))
+    }
+
+    fn remove() -> miette::Result<()> {
+        match std::fs::remove_dir_all(BatchState::pages_dir()?) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                return Err(error).into_diagnostic()
+            }
+            _ => {}
+        }
+        match std::fs::remove_file(BatchState::path()?) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).into_diagnostic()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn assembles(&self) -> bool {
+        !self.template.to_string_lossy().contains(PAGE_NUMBER)
+    }
+
+    fn scanned(&self) -> usize {
+        self.counter - self.start
+    }
+
+    fn page_path(&self, number: usize) -> PathBuf {
+        PathBuf::from(
+            self.template
+                .to_string_lossy()
+                .replace(PAGE_NUMBER, &number.to_string()),
+        )
+    }
+}
+
+/// Scan several pages, either numbering them with the `{n}` placeholder of the path or assembling them into one
+/// document
+///
+/// The progress is saved after every page, so that an interrupted batch can be continued with `resume`. With `notify`
+/// a desktop notification is shown once it is done or has failed, which resuming it turns on as well.
+pub fn batch(
+    backend: &dyn ScanBackend,
+    new: Option<NewBatch>,
+    notify: bool,
+    output: OutputFormat,
+) -> Result<(), miette::Error> {
+    if notify {
+        notification::supported()?;
+    }
+
+    let existing = BatchState::load()?;
+
+    let mut state = match (new, existing) {
+        (Some(_), Some(_)) => {
+            return Err(ScannrsError::BatchInProgress {
+                state: BatchState::path()?,
+            }
+            .into())
+        }
+        (None, None) => return Err(ScannrsError::NoBatchToResume.into()),
+        (None, Some(state)) => {
+            events::status(tr!(
+                "batch-resuming",
+                device = state.device.as_str(),
+                page = state.counter
+            ));
+            state
+        }
+        (Some(new), None) => {
+            let config = Config::load()?;
+            let path = config.output_path(new.output_dir.as_deref(), &new.path);
+            let format = Format::for_path(&path, config.format(new.format)?);
+            let resolution = config
+                .resolution(new.resolution)?
+                .map(|dpi| (String::from("resolution"), dpi));
+            let state = BatchState {
+                device: config.device(new.name)?,
+                options: resolution.into_iter().chain(new.options).collect(),
+                settings: new.settings.map(|s| std::path::absolute(&s).unwrap_or(s)),
+                template: std::path::absolute(&path).unwrap_or(path),
+                format,
+                start: new.start,
+                counter: new.start,
+                limit: new.pages,
+                started_at: Utc::now(),
+                pages: vec![],
+                outputs: vec![],
+                notify,
+            };
+
+            if state.assembles() && !format.supports_multiple_pages() {
+                return Err(scannrs_core::Error::MultiPageUnsupported { format }).into_diagnostic();
+            }
+
+            state.save()?;
+            state
+        }
+    };
+    state.notify |= notify;
+
+    let notify = state.notify;
+    let device = state.device.clone();
+    let res = scan_batch(backend, state, output);
+    if notify {
+        match &res {
+            Ok(entry) => notification::show(
+                &tr!("batch-notify-done", device = device.as_str()),
+                &tr!("batch-done", pages = entry.pages, outputs = outputs(entry)),
+            ),
+            Err(error) => notification::show(
+                &tr!("batch-notify-failed", device = device.as_str()),
+                &error_chain(error),
+            ),
+        }
+    }
+
+    res.map(|_| ())
+}
+
+/// Scan the remaining pages of the batch and record it in the history
+fn scan_batch(
+    backend: &dyn ScanBackend,
+    mut state: BatchState,
+    output: OutputFormat,
+) -> miette::Result<HistoryEntry> {
+    let options = state.options.clone().into_iter().collect::<HashMap<_, _>>();
+    // Shared by all pages, which are usually the same size
+    let mut buffers = FrameBuffers::default();
+
+    loop {
+        match state.limit {
+            Some(limit) if state.scanned() >= limit => break,
+            Some(_) => {}
+            None => {
+                if !ask_for_page(state.counter)? {
+                    break;
+                }
+            }
+        }
+
+        let mut progress = ProgressBar::new();
+        let page = scan_page(
+            backend,
+            &state.device,
+            state.counter,
+            state.settings.as_deref(),
+            &options,
+            &mut buffers,
+            &mut |event| progress.update(event),
+        );
+        drop(progress);
+        let page = page.with_context(|| {
+            format!(
+                "While scanning page {}, continue with `scannrs batch --resume`",
+                state.counter
+            )
+        })?;
+
+        if state.assembles() {
+            let dir = BatchState::pages_dir()?;
+            std::fs::create_dir_all(&dir).into_diagnostic()?;
+            let file = dir.join(format!("{:04}.png", state.counter));
+            page.image.save(&file).into_diagnostic().with_context(|| {
+                format!("While saving page {} to {}", state.counter, file.display())
+            })?;
+            events::emit(&Event::PageSaved {
+                page: state.counter,
+                path: &file,
+            });
+            state.pages.push(BatchPage {
+                file,
+                dpi: page.dpi,
+            });
+        } else {
+            let path = state.page_path(state.counter);
+            write_file(&path, state.format, &[page])?;
+            events::emit(&Event::PageSaved {
+                page: state.counter,
+                path: &path,
+            });
+            state.outputs.push(path);
+        }
+
+        state.counter += 1;
+        state.save()?;
+        events::status(tr!("batch-page-scanned", page = state.counter - 1));
+    }
+
+    if state.assembles() && !state.pages.is_empty() {
+        let pages = state
+            .pages
+            .iter()
+            .map(|page| {
+                let image = image::open(&page.file).into_diagnostic().with_context(|| {
+                    format!("While reading the page at {}", page.file.display())
+                })?;
+
+                Ok(Page {
+                    image,
+                    dpi: page.dpi,
+                })
+            })
+            .collect::<miette::Result<Vec<_>>>()?;
+
+        write_file(&state.template, state.format, &pages)?;
+        state.outputs.push(state.template.clone());
+    }
+
+    let mut entry = HistoryEntry {
+        id: 0,
+        finished_at: Utc::now(),
+        device: state.device.clone(),
+        options: state.options.clone(),
+        settings: state.settings.clone(),
+        outputs: state.outputs.clone(),
+        format: state.format,
+        pages: state.scanned(),
+        duration_ms: (Utc::now() - state.started_at).num_milliseconds().max(0) as u64,
+    };
+    BatchState::remove()?;
+
+    let mut job = None;
+    if entry.pages > 0 {
+        // The pages are saved already, a history that cannot be written must not fail the batch
+        match History::open().and_then(|history| history.record(entry.clone())) {
+            Ok(recorded) => {
+                job = Some(recorded.id);
+                entry = recorded;
+            }
+            Err(error) => events::warning(format!(
+                "Could not record the scan in the history: {}",
+                error_chain(&error)
+            )),
+        }
+    }
+    webhook::notify(
+        &Config::load()?,
+        &JobReport {
+            command: "batch",
+            job,
+            device: &entry.device,
+            files: entry.outputs.clone(),
+            pages: entry.pages,
+            duration_ms: entry.duration_ms,
+            error: None,
+        },
+    );
+
+    events::emit(&Event::Done {
+        pages: entry.pages,
+        outputs: entry.outputs.clone(),
+    });
+
+    match output {
+        OutputFormat::Json => print_json(&entry)?,
+        OutputFormat::Text => println!(
+            "{}",
+            tr!("batch-done", pages = entry.pages, outputs = outputs(&entry))
+        ),
+    }
+
+    Ok(entry)
+}
+
+fn outputs(entry: &HistoryEntry) -> String {
+    entry
+        .outputs
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Ask on the terminal whether to scan another page, returns `false` once the user is done
+fn ask_for_page(number: usize) -> miette::Result<bool> {
+    if events::enabled() {
+        events::emit(&Event::WaitingForPage { page: number });
+    } else {
+        eprint!("{}", tr!("batch-ask-for-page", page = number));
+        std::io::stderr().flush().into_diagnostic()?;
+    }
+
+    let mut line = String::new();
+    let read = std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .into_diagnostic()?;
+
+    Ok(read > 0 && line.trim() != "done")
+}
+
+fn write_file(path: &Path, format: Format, pages: &[Page]) -> miette::Result<()> {
+    let mut file = std::fs::File::create(path)
+        .into_diagnostic()
+        .with_context(|| format!("Tried to write to file at {}", path.display()))?;
+
+    write_document(&mut file, format, pages)
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::backend::OptionValue;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::backend::ValueType;
+use scannrs_core::calibration::Calibration;
+use scannrs_core::scan::apply_options;
+use scannrs_core::scan::read_image;
+use scannrs_core::value::Value;
+
+use crate::calibration;
+use crate::i18n::tr;
+
+/// Names backends use for their calibration button
+const CALIBRATION_OPTIONS: &[&str] = &["calibrate", "calibration", "cal"];
+
+pub fn calibrate(
+    backend: &dyn ScanBackend,
+    name: String,
+    software: bool,
+    reset: bool,
+    options: Vec<(String, Value)>,
+) -> Result<(), miette::Error> {
+    if reset {
+        calibration::remove(&name)?;
+        println!("{}", tr!("calibration-removed", name = name.as_str()));
+        return Ok(());
+    }
+
+    let mut device = backend.open(&name)?;
+
+    if !software {
+        let button = device.options()?.into_iter().find(|o| {
+            o.type_ == ValueType::Button && CALIBRATION_OPTIONS.contains(&o.name.as_str())
+        });
+
+        if let Some(button) = button {
+            device
+                .set_option(&button, OptionValue::Button)
+                .with_context(|| format!("While triggering the calibration of '{name}'"))?;
+            println!("{}", tr!("calibration-done", name = name.as_str()));
+            return Ok(());
+        }
+
+        println!("{}", tr!("calibration-software", name = name.as_str()));
+    }
+
+    println!("{}", tr!("calibration-place-sheet"));
+    std::io::stdin()
+        .read_line(&mut String::new())
+        .into_diagnostic()?;
+
+    let options = options.into_iter().collect::<HashMap<_, _>>();
+    apply_options(device.as_mut(), &options)?;
+    let img = read_image(device.as_mut())?;
+
+    let path = calibration::save(&Calibration::from_white_scan(&img), &name)?;
+    println!(
+        "{}",
+        tr!("calibration-saved", path = path.display().to_string())
+    );
+
+    Ok(())
+}
@@ -0,0 +1,212 @@
+//! `scannrs copy`, turning a scanner and a printer into a photocopier
+//!
+//! The page is printed as a PDF, so that it keeps the size it was scanned at. A printer given as an `ipp://` or
+//! `ipps://` URI is sent the job over IPP directly, any other name is a CUPS queue printed to with `lp`.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::time::Duration;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::output::write_document;
+use scannrs_core::output::Format;
+use scannrs_core::scan::FrameBuffers;
+use scannrs_core::value::Value;
+
+use super::scan::scan_page;
+use crate::config::Config;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::i18n::tr;
+use crate::progress::ProgressBar;
+
+/// The port of IPP when the URI of the printer has none
+const IPP_PORT: u16 = 631;
+
+/// The name of the jobs on the printer
+const JOB_NAME: &str = "scannrs copy";
+
+pub fn copy(
+    backend: &dyn ScanBackend,
+    name: Option<String>,
+    resolution: Option<f32>,
+    options: Vec<(String, Value)>,
+    settings: Option<PathBuf>,
+    printer: String,
+    copies: u32,
+) -> Result<(), miette::Error> {
+    let config = Config::load()?;
+    let name = config.device(name)?;
+    let options = config
+        .resolution(resolution)?
+        .map(|dpi| (String::from("resolution"), dpi))
+        .into_iter()
+        .chain(options)
+        .collect::<HashMap<_, _>>();
+
+    let mut progress = ProgressBar::new();
+    let page = scan_page(
+        backend,
+        &name,
+        1,
+        settings.as_deref(),
+        &options,
+        &mut FrameBuffers::default(),
+        &mut |event| progress.update(event),
+    );
+    drop(progress);
+
+    let mut document = Cursor::new(vec![]);
+    write_document(&mut document, Format::Pdf, &[page?])?;
+    match ipp_url(&printer) {
+        Some(url) => print_ipp(&url, &printer, copies, document.get_ref())?,
+        None => print_lp(&printer, copies, document.get_ref())?,
+    }
+
+    events::status(tr!(
+        "copy-printed",
+        copies = copies,
+        printer = printer.as_str()
+    ));
+
+    Ok(())
+}
+
+/// The HTTP URL to send IPP requests for the printer to, if it is given as an IPP URI
+fn ipp_url(printer: &str) -> Option<String> {
+    let (scheme, rest) = printer.split_once("://")?;
+    let scheme = match scheme.to_ascii_lowercase().as_str() {
+        "ipp" => "http",
+        "ipps" => "https",
+        _ => return None,
+    };
+    let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+
+    Some(match has_port {
+        true => format!("{scheme}://{authority}/{path}"),
+        false => format!("{scheme}://{authority}:{IPP_PORT}/{path}"),
+    })
+}
+
+fn print_lp(printer: &str, copies: u32, document: &[u8]) -> miette::Result<()> {
+    let mut lp = Command::new("lp")
+        .args([
+            "-s",
+            "-d",
+            printer,
+            "-n",
+            &copies.to_string(),
+            "-t",
+            JOB_NAME,
+        ])
+        .stdin(Stdio::piped())
+        .spawn()
+        .into_diagnostic()
+        .context("Could not run lp, is CUPS installed?")?;
+    if let Some(mut stdin) = lp.stdin.take() {
+        stdin
+            .write_all(document)
+            .into_diagnostic()
+            .context("While sending the scan to lp")?;
+    }
+
+    let status = lp.wait().into_diagnostic()?;
+    if !status.success() {
+        return Err(ScannrsError::CommandFailed {
+            program: String::from("lp"),
+            status: status.to_string(),
+        })
+        .into_diagnostic();
+    }
+
+    Ok(())
+}
+
+/// The version of IPP the requests are sent with, 2.0
+const IPP_VERSION: [u8; 2] = [2, 0];
+
+/// The `Print-Job` operation
+const PRINT_JOB: u16 = 0x0002;
+
+// The tags of IPP attribute groups and values
+const OPERATION_ATTRIBUTES: u8 = 0x01;
+const JOB_ATTRIBUTES: u8 = 0x02;
+const END_OF_ATTRIBUTES: u8 = 0x03;
+const INTEGER: u8 = 0x21;
+const NAME: u8 = 0x42;
+const URI: u8 = 0x45;
+const CHARSET: u8 = 0x47;
+const NATURAL_LANGUAGE: u8 = 0x48;
+const MIME_MEDIA_TYPE: u8 = 0x49;
+
+fn print_ipp(url: &str, printer: &str, copies: u32, document: &[u8]) -> miette::Result<()> {
+    let mut request = Vec::with_capacity(document.len() + 256);
+    request.extend(IPP_VERSION);
+    request.extend(PRINT_JOB.to_be_bytes());
+    request.extend(1u32.to_be_bytes());
+    request.push(OPERATION_ATTRIBUTES);
+    attribute(&mut request, CHARSET, "attributes-charset", b"utf-8");
+    attribute(
+        &mut request,
+        NATURAL_LANGUAGE,
+        "attributes-natural-language",
+        b"en",
+    );
+    attribute(&mut request, URI, "printer-uri", printer.as_bytes());
+    attribute(&mut request, NAME, "job-name", JOB_NAME.as_bytes());
+    attribute(
+        &mut request,
+        MIME_MEDIA_TYPE,
+        "document-format",
+        Format::Pdf.mime_type().as_bytes(),
+    );
+    request.push(JOB_ATTRIBUTES);
+    attribute(&mut request, INTEGER, "copies", &copies.to_be_bytes());
+    request.push(END_OF_ATTRIBUTES);
+    request.extend(document);
+
+    let mut response = vec![];
+    ureq::post(url)
+        .set("Content-Type", "application/ipp")
+        .timeout(Duration::from_secs(120))
+        .send_bytes(&request)
+        .into_diagnostic()
+        .with_context(|| format!("While sending the scan to {printer}"))?
+        .into_reader()
+        .read_to_end(&mut response)
+        .into_diagnostic()
+        .with_context(|| format!("While reading the answer of {printer}"))?;
+
+    // The status follows the version, everything from 0x0100 on did not print
+    let status = match response.get(2..4) {
+        Some(&[high, low]) => u16::from_be_bytes([high, low]),
+        _ => u16::MAX,
+    };
+    if status >= 0x0100 {
+        return Err(ScannrsError::PrintFailed {
+            printer: printer.to_string(),
+            status: format!("{status:#06x}"),
+        })
+        .into_diagnostic();
+    }
+
+    Ok(())
+}
+
+fn attribute(request: &mut Vec<u8>, tag: u8, name: &str, value: &[u8]) {
+    request.push(tag);
+    request.extend((name.len() as u16).to_be_bytes());
+    request.extend(name.as_bytes());
+    request.extend((value.len() as u16).to_be_bytes());
+    request.extend(value);
+}
@@ -0,0 +1,192 @@
+//! Starting scans with the buttons on the front panel of the scanner, like scanbd does
+//!
+//! Scanners report their buttons as sensor options, like `scan`, `copy`, `email` and `file`, and many have a function
+//! selector showing a number next to them. Every `[[buttons]]` entry of the configuration maps a button, and the number
+//! of the selector if it is given, to the profile and destinations the scan is made with:
+//!
+//! ```toml
+//! [[buttons]]
+//! button = "email"
+//! destinations = [{ type = "email", to = ["office@example.com"] }]
+//!
+//! [[buttons]]
+//! button = "scan"
+//! function = 2
+//! profile = "invoices"
+//! ```
+//!
+//! The buttons are read while the daemon has nothing to scan, a press queues a scan like `scannrs queue` does. The
+//! scanners stay open between readings and are only closed for the jobs scanning with them. The entries are read
+//! when the daemon starts.
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use scannrs_core::backend::OptionValue;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::backend::ScanDevice;
+
+use super::submit_profile_scan;
+use super::Queue;
+use crate::config::Config;
+use crate::error::error_chain;
+use crate::events;
+use crate::i18n::tr;
+
+/// How often the buttons are read
+pub(super) const POLL: Duration = Duration::from_millis(500);
+
+/// Names backends use for the function selector of the front panel
+const FUNCTION_OPTIONS: &[&str] = &["function", "func", "function-number"];
+
+pub(super) struct Buttons {
+    config: Config,
+    /// The scanners whose buttons are read, kept open between readings
+    devices: BTreeMap<String, Box<dyn ScanDevice>>,
+    /// The buttons that were held at the last reading, to start a scan only once per press
+    held: BTreeSet<(String, String)>,
+}
+
+impl Buttons {
+    /// Read the buttons of the entries of the configuration, `None` if there are none
+    pub(super) fn new(config: Config) -> Option<Buttons> {
+        (!config.buttons.is_empty()).then(|| Buttons {
+            config,
+            devices: BTreeMap::new(),
+            held: BTreeSet::new(),
+        })
+    }
+
+    /// Close the scanner for a job to scan with it, it is opened again at the next reading after the job
+    pub(super) fn release(&mut self, device: &str) {
+        self.devices.remove(device);
+    }
+
+    /// Read the buttons of the configured scanners, queueing a scan for every new press
+    pub(super) fn poll(&mut self, backend: &dyn ScanBackend, queue: &Queue) {
+        let mut names = BTreeSet::new();
+        for button in &self.config.buttons {
+            if let Some(device) = button
+                .device
+                .clone()
+                .or_else(|| self.config.device(None).ok())
+            {
+                names.insert(device);
+            }
+        }
+
+        let busy = queue.busy();
+        let mut held = BTreeSet::new();
+        for name in names {
+            if busy.contains(&name) {
+                self.release(&name);
+                continue;
+            }
+
+            let device = match self.devices.entry(name.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => match backend.open(&name) {
+                    Ok(device) => entry.insert(device),
+                    Err(error) => {
+                        tracing::debug!("Opening {name} failed: {}", error_chain(&error));
+                        continue;
+                    }
+                },
+            };
+            let pressed = match pressed(device.as_ref()) {
+                Ok(pressed) => pressed,
+                Err(error) => {
+                    tracing::debug!(
+                        "Reading the buttons of {name} failed: {}",
+                        error_chain(&error)
+                    );
+                    // The scanner may have been unplugged, it is opened again at the next reading
+                    self.release(&name);
+                    continue;
+                }
+            };
+
+            for (button, function) in pressed.buttons {
+                let key = (name.clone(), button);
+                if !self.held.contains(&key) {
+                    press(
+                        &self.config,
+                        queue,
+                        &name,
+                        &key.1,
+                        function.or(pressed.function),
+                    );
+                }
+                held.insert(key);
+            }
+        }
+        self.held = held;
+    }
+}
+
+/// The buttons held on a scanner and the number its function selector shows
+struct Pressed {
+    buttons: Vec<(String, Option<i32>)>,
+    function: Option<i32>,
+}
+
+fn pressed(device: &dyn ScanDevice) -> miette::Result<Pressed> {
+    let mut pressed = Pressed {
+        buttons: Vec::new(),
+        function: None,
+    };
+    for option in device.options()? {
+        if !option.hardware || option.settable || !option.active || !option.has_value() {
+            continue;
+        }
+
+        let value = device.get_option(&option)?;
+        if FUNCTION_OPTIONS.contains(&option.name.as_str()) {
+            if let OptionValue::Int(function) = value {
+                pressed.function = Some(function);
+            }
+            continue;
+        }
+        match value {
+            OptionValue::Bool(true) => pressed.buttons.push((option.name, None)),
+            // Some backends report the number of the button that is held instead
+            OptionValue::Int(number) if number != 0 => {
+                pressed.buttons.push((option.name, Some(number)))
+            }
+            _ => {}
+        }
+    }
+
+    Ok(pressed)
+}
+
+/// Queue the scan of the first entry for the button, if there is one
+fn press(config: &Config, queue: &Queue, device: &str, button: &str, function: Option<i32>) {
+    let default_device = config.device(None).ok();
+    let Some(entry) = config.buttons.iter().find(|entry| {
+        entry.button == button
+            && entry
+                .function
+                .map_or(true, |wanted| Some(wanted) == function)
+            && entry.device.as_deref().or(default_device.as_deref()) == Some(device)
+    }) else {
+        return;
+    };
+
+    let res = submit_profile_scan(
+        queue,
+        device.to_string(),
+        entry.profile.as_deref(),
+        entry.destinations.clone(),
+    );
+    match res {
+        Ok(job) => events::status(tr!("button-pressed", button = button, job = job.id)),
+        Err(error) => events::warning(tr!(
+            "button-scan-failed",
+            button = button,
+            message = error_chain(&error)
+        )),
+    }
+}
@@ -0,0 +1,547 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use buttons::Buttons;
+use miette::Context;
+use miette::IntoDiagnostic;
+use protocol::DaemonRequest;
+use protocol::DaemonResponse;
+use protocol::Job;
+use protocol::JobRequest;
+use protocol::JobStatus;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::output::Format;
+use scannrs_core::scan::CancellationToken;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::commands::scan::scan_to_file;
+use crate::commands::scan::ScanSummary;
+use crate::config::Config;
+use crate::config::DestinationConfig;
+use crate::config::MqttConfig;
+use crate::destination::Destinations;
+use crate::destination::SavedScan;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::events::Event;
+use crate::metrics;
+use crate::paths::expand_path;
+use crate::systemd;
+use crate::webhook;
+use crate::webhook::JobReport;
+
+mod buttons;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+pub(crate) mod protocol;
+
+pub fn daemon(
+    backend: &dyn ScanBackend,
+    socket: Option<PathBuf>,
+    metrics: Option<SocketAddr>,
+) -> miette::Result<()> {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => crate::paths::daemon_socket()?,
+    };
+    let queue = Arc::new(Queue::load(crate::paths::state_dir()?.join("queue.json"))?);
+    let listener = match systemd::activated_socket::<UnixListener>() {
+        Some(listener) => {
+            eprintln!("Listening on the socket passed by systemd");
+            listener
+        }
+        None => {
+            let listener = bind(&socket)?;
+            eprintln!("Listening on {}", socket.display());
+            listener
+        }
+    };
+
+    if let Some(mqtt) = &Config::load()?.mqtt {
+        connect_mqtt(mqtt, queue.clone())?;
+    }
+    if let Some(listen) = metrics {
+        metrics::serve(listen)?;
+    }
+    systemd::ready();
+
+    {
+        let queue = queue.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let queue = queue.clone();
+                std::thread::spawn(move || handle_client(stream, &queue));
+            }
+        });
+    }
+
+    // Without buttons to read there is nothing to do between jobs
+    let mut buttons = Buttons::new(Config::load()?);
+    std::thread::scope(|scope| loop {
+        // The loop coming around is what tells the watchdog that the daemon still works
+        systemd::alive();
+        let within = match (buttons.as_ref().map(|_| buttons::POLL), systemd::watchdog()) {
+            (Some(poll), Some(watchdog)) => Some(poll.min(watchdog)),
+            (poll, watchdog) => poll.or(watchdog),
+        };
+        let Some((job, cancel)) = queue.next_job(within) else {
+            if let Some(buttons) = &mut buttons {
+                buttons.poll(backend, &queue);
+            }
+            continue;
+        };
+        if let Some(buttons) = &mut buttons {
+            buttons.release(&job.request.device);
+        }
+
+        match backend.as_sync_for(&job.request.device) {
+            // Scanners that can be used from another thread work through their jobs next to the others
+            Some(backend) => {
+                let queue = &queue;
+                scope.spawn(move || execute(backend, queue, job, &cancel));
+            }
+            None => execute(backend, &queue, job, &cancel),
+        }
+    })
+}
+
+/// Run a job taken from the queue, reporting how it went
+fn execute(backend: &dyn ScanBackend, queue: &Queue, job: Job, cancel: &CancellationToken) {
+    events::emit(&Event::JobStarted {
+        job: job.id,
+        device: &job.request.device,
+        profile: job.request.profile.as_deref(),
+    });
+
+    let started = Instant::now();
+    let res = run(backend, &job.request, cancel);
+    let duration = started.elapsed();
+    let report = match &res {
+        Ok(summary) => {
+            metrics::scan_finished(
+                &summary.device,
+                summary.pages,
+                std::fs::metadata(&summary.path).map_or(0, |metadata| metadata.len()),
+                duration,
+            );
+            JobReport::saved("daemon", Some(job.id), summary, duration)
+        }
+        Err(error) => {
+            metrics::scan_failed(error);
+            JobReport::failed("daemon", Some(job.id), &job.request.device, duration, error)
+        }
+    };
+    // A configuration that cannot be read already failed the job
+    if let Ok(config) = Config::load() {
+        webhook::notify(&config, &report);
+    }
+    events::emit(&Event::JobFinished {
+        job: job.id,
+        error: res.as_ref().err().map(error_chain),
+    });
+
+    queue.finish(job.id, res.map(|_| ()));
+}
+
+/// Where scans queued with a profile are saved when the profile has no output
+const OUTPUT: &str = "scan-{date}-{time}.pdf";
+
+/// Queue a scan with the named profile, or the default one if there is none, saved where the profile says
+fn submit_profile_scan(
+    queue: &Queue,
+    device: String,
+    profile: Option<&str>,
+    destinations: Vec<DestinationConfig>,
+) -> miette::Result<Job> {
+    let config = Config::load()?;
+    let profile = match profile {
+        Some(name) => Some(config.profile_named(name)?),
+        None => config.profile()?,
+    };
+    let template = profile
+        .and_then(|(_, profile)| profile.output.clone())
+        .unwrap_or_else(|| String::from(OUTPUT));
+
+    queue.submit(JobRequest {
+        options: config
+            .options_with(&device, profile.map(|(_, profile)| profile))
+            .into_iter()
+            .collect(),
+        output: config.output_path(None, &expand_path(&template, &device)),
+        profile: profile.map(|(name, _)| name.to_string()),
+        destinations,
+        device,
+        not_before: None,
+    })
+}
+
+/// Scan a job, sending the scan to the destinations of its profile and the ones of the job
+fn run(
+    backend: &dyn ScanBackend,
+    request: &JobRequest,
+    cancel: &CancellationToken,
+) -> miette::Result<ScanSummary> {
+    let options = request
+        .options
+        .clone()
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+    let summary = scan_to_file(
+        backend,
+        &request.device,
+        &request.output,
+        Format::for_path(&request.output, None),
+        None,
+        &options,
+        &mut |_| {
+            systemd::alive();
+            cancel.flow()
+        },
+    )?;
+
+    if request.profile.is_some() || !request.destinations.is_empty() {
+        let config = Config::load()?;
+        let profile = match &request.profile {
+            Some(name) => Some(config.profile_named(name)?.1),
+            None => None,
+        };
+        Destinations::new(&config, profile, &request.destinations)?.send(&SavedScan {
+            path: &summary.path,
+            device: &request.device,
+            tags: &summary.tags,
+            correspondent: summary.correspondent.as_deref(),
+        })?;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(feature = "mqtt")]
+fn connect_mqtt(config: &MqttConfig, queue: Arc<Queue>) -> miette::Result<()> {
+    mqtt::connect(config, queue)
+}
+
+#[cfg(not(feature = "mqtt"))]
+fn connect_mqtt(_config: &MqttConfig, _queue: Arc<Queue>) -> miette::Result<()> {
+    Err(ScannrsError::MqttNotBuilt.into())
+}
+
+/// Bind the daemon socket, replacing a stale socket left behind by a previous daemon
+fn bind(socket: &Path) -> miette::Result<UnixListener> {
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(ScannrsError::DaemonAlreadyRunning {
+                socket: socket.to_path_buf(),
+            }
+            .into());
+        }
+
+        std::fs::remove_file(socket)
+            .into_diagnostic()
+            .with_context(|| format!("While removing the stale socket at {}", socket.display()))?;
+    }
+
+    if let Some(parent) = socket.parent() {
+        std::fs::create_dir_all(parent).into_diagnostic()?;
+    }
+
+    UnixListener::bind(socket)
+        .into_diagnostic()
+        .with_context(|| format!("While trying to listen on {}", socket.display()))
+}
+
+fn handle_client(stream: UnixStream, queue: &Queue) {
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+
+        let response = match serde_json::from_str::<DaemonRequest>(&line) {
+            Ok(request) => queue.handle(request),
+            Err(error) => DaemonResponse::Error {
+                message: format!("Invalid request: {error}"),
+            },
+        };
+
+        let Ok(mut response) = serde_json::to_string(&response) else {
+            return;
+        };
+        response.push('\n');
+
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// The current time as seconds since the unix epoch
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// How many finished jobs are kept in the queue, the oldest are forgotten first
+const FINISHED_JOBS: usize = 100;
+
+#[derive(Serialize, Deserialize, Default)]
+struct QueueState {
+    next_id: u64,
+    jobs: Vec<Job>,
+    /// Cancels the jobs that are scanning
+    #[serde(skip)]
+    running: HashMap<u64, CancellationToken>,
+}
+
+impl QueueState {
+    /// The scanners a job is scanning with
+    fn busy(&self) -> BTreeSet<String> {
+        self.jobs
+            .iter()
+            .filter(|job| job.status == JobStatus::Scanning)
+            .map(|job| job.request.device.clone())
+            .collect()
+    }
+
+    /// Forget the oldest finished jobs past [`FINISHED_JOBS`]
+    fn prune(&mut self) {
+        let finished = self
+            .jobs
+            .iter()
+            .filter(|job| job.status.is_finished())
+            .count();
+        let mut excess = finished.saturating_sub(FINISHED_JOBS);
+        self.jobs.retain(|job| {
+            let forget = excess > 0 && job.status.is_finished();
+            excess -= usize::from(forget);
+            !forget
+        });
+    }
+}
+
+/// The job queue, every change is written to disk so that it survives restarts
+struct Queue {
+    path: PathBuf,
+    state: Mutex<QueueState>,
+    wakeup: Condvar,
+}
+
+impl Queue {
+    fn load(path: PathBuf) -> miette::Result<Queue> {
+        let mut state: QueueState = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .into_diagnostic()
+                .with_context(|| format!("While reading the job queue at {}", path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => QueueState::default(),
+            Err(error) => return Err(error).into_diagnostic(),
+        };
+
+        // A job that was scanning when the daemon went away never finished
+        for job in &mut state.jobs {
+            if job.status == JobStatus::Scanning {
+                job.status = JobStatus::Queued;
+            }
+        }
+
+        Ok(Queue {
+            path,
+            state: Mutex::new(state),
+            wakeup: Condvar::new(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn persist(&self, state: &QueueState) -> miette::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_vec_pretty(state).into_diagnostic()?)
+            .into_diagnostic()?;
+        std::fs::rename(&tmp, &self.path)
+            .into_diagnostic()
+            .with_context(|| format!("While writing the job queue to {}", self.path.display()))
+    }
+
+    /// The scanners a job is scanning with
+    fn busy(&self) -> BTreeSet<String> {
+        self.lock().busy()
+    }
+
+    /// Like [`Queue::persist`], for the daemon itself which keeps serving the jobs it knows about
+    fn persist_or_warn(&self, state: &QueueState) {
+        if let Err(error) = self.persist(state) {
+            tracing::warn!("The job queue could not be saved: {}", error_chain(&error));
+        }
+    }
+
+    fn handle(&self, request: DaemonRequest) -> DaemonResponse {
+        let res = match request {
+            DaemonRequest::Submit { job } => {
+                self.submit(job).map(|job| DaemonResponse::Job { job })
+            }
+            DaemonRequest::Status { id } => self.status(id).map(|job| DaemonResponse::Job { job }),
+            DaemonRequest::List => Ok(DaemonResponse::Jobs {
+                jobs: self.lock().jobs.clone(),
+            }),
+            DaemonRequest::Cancel { id } => self.cancel(id).map(|job| DaemonResponse::Job { job }),
+        };
+
+        res.unwrap_or_else(|error| DaemonResponse::Error {
+            message: error_chain(&error),
+        })
+    }
+
+    fn submit(&self, request: JobRequest) -> miette::Result<Job> {
+        let mut state = self.lock();
+        state.next_id += 1;
+        let job = Job {
+            id: state.next_id,
+            request,
+            status: JobStatus::Queued,
+        };
+        state.jobs.push(job.clone());
+        self.persist(&state)?;
+        self.wakeup.notify_all();
+
+        Ok(job)
+    }
+
+    fn status(&self, id: u64) -> miette::Result<Job> {
+        self.lock()
+            .jobs
+            .iter()
+            .find(|job| job.id == id)
+            .cloned()
+            .ok_or(ScannrsError::JobNotFound { id })
+            .into_diagnostic()
+    }
+
+    fn cancel(&self, id: u64) -> miette::Result<Job> {
+        let mut state = self.lock();
+        let job = state
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == id)
+            .ok_or(ScannrsError::JobNotFound { id })
+            .into_diagnostic()?;
+
+        // Scanning jobs are marked as cancelled once their scan stopped
+        if job.status == JobStatus::Queued {
+            job.status = JobStatus::Cancelled;
+        }
+        let job = job.clone();
+        if let Some(cancel) = state.running.get(&id) {
+            cancel.cancel();
+        }
+        state.prune();
+        self.persist(&state)?;
+
+        Ok(job)
+    }
+
+    /// Wait for the next queued job that is due and mark it as scanning, at most for `within` if given
+    ///
+    /// Jobs for a scanner wait until its previous job finished. Returns the job with the token cancelling it.
+    fn next_job(&self, within: Option<Duration>) -> Option<(Job, CancellationToken)> {
+        let deadline = within.map(|within| Instant::now() + within);
+        let mut state = self.lock();
+        loop {
+            let now = unix_now();
+
+            let busy = state.busy();
+            if let Some(job) = state.jobs.iter_mut().find(|job| {
+                job.status == JobStatus::Queued
+                    && !busy.contains(&job.request.device)
+                    && job
+                        .request
+                        .not_before
+                        .map_or(true, |not_before| not_before <= now)
+            }) {
+                job.status = JobStatus::Scanning;
+                let job = job.clone();
+                let cancel = CancellationToken::default();
+                state.running.insert(job.id, cancel.clone());
+                self.persist_or_warn(&state);
+                return Some((job, cancel));
+            }
+
+            let next_scheduled = state
+                .jobs
+                .iter()
+                .filter(|job| job.status == JobStatus::Queued)
+                .filter_map(|job| job.request.not_before)
+                .min();
+
+            let left = match deadline {
+                Some(deadline) if deadline <= Instant::now() => return None,
+                Some(deadline) => Some(deadline - Instant::now()),
+                None => None,
+            };
+            let scheduled = next_scheduled
+                .map(|not_before| Duration::from_secs(not_before.saturating_sub(now)));
+            let timeout = match (scheduled, left) {
+                (Some(scheduled), Some(left)) => Some(scheduled.min(left)),
+                (scheduled, left) => scheduled.or(left),
+            };
+
+            state = match timeout {
+                Some(timeout) => self
+                    .wakeup
+                    .wait_timeout(state, timeout)
+                    .map(|(state, _)| state)
+                    .unwrap_or_else(|e| e.into_inner().0),
+                None => self.wakeup.wait(state).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    fn finish(&self, id: u64, res: miette::Result<()>) {
+        let mut state = self.lock();
+        let cancelled = state
+            .running
+            .remove(&id)
+            .is_some_and(|cancel| cancel.is_cancelled());
+        if let Some(job) = state.jobs.iter_mut().find(|job| job.id == id) {
+            job.status = match res {
+                Ok(()) => JobStatus::Done,
+                Err(_) if cancelled => JobStatus::Cancelled,
+                Err(error) => JobStatus::Failed {
+                    error: error_chain(&error),
+                },
+            };
+        }
+        state.prune();
+
+        self.persist_or_warn(&state);
+        // Jobs for the scanner can start now
+        self.wakeup.notify_all();
+    }
+}
@@ -0,0 +1,153 @@
+//! Publishing the events of the daemon to an MQTT broker, for home automation like Home Assistant
+//!
+//! Events are published as their JSON to `<topic>/event/<name>`, like `scannrs/event/page-saved`, and
+//! `<topic>/status` is `online` while the daemon is connected and `offline` once it is gone. With `commands`, a message
+//! to `<topic>/scan` queues a scan with the profile it names, or with the default profile if it is empty.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use miette::IntoDiagnostic;
+use rumqttc::Client;
+use rumqttc::LastWill;
+use rumqttc::MqttOptions;
+use rumqttc::Packet;
+use rumqttc::QoS;
+use rumqttc::Transport;
+
+use super::protocol::Job;
+use super::submit_profile_scan;
+use super::Queue;
+use crate::config::Config;
+use crate::config::MqttConfig;
+use crate::destination::stored_password;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::i18n::tr;
+
+/// How long to wait before connecting again after the connection to the broker was lost
+const RECONNECT: Duration = Duration::from_secs(5);
+
+/// Connect to the broker in the background and publish every event from now on
+pub(super) fn connect(config: &MqttConfig, queue: Arc<Queue>) -> miette::Result<()> {
+    let (host, port, tls) = broker(&config.broker)?;
+    let topic = config.topic.trim_end_matches('/').to_string();
+    let status = format!("{topic}/status");
+    let scan = format!("{topic}/scan");
+
+    let mut options = MqttOptions::new(&config.client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(&status, "offline", QoS::AtLeastOnce, true));
+    if tls {
+        options.set_transport(Transport::tls_with_default_config());
+    }
+    if let Some(user) = &config.user {
+        options.set_credentials(user, password(config, user)?);
+    }
+    let (client, mut connection) = Client::new(options, 64);
+
+    {
+        let client = client.clone();
+        events::forward(move |event| {
+            let Ok(payload) = serde_json::to_value(event) else {
+                return;
+            };
+            let Some(name) = payload["event"].as_str() else {
+                return;
+            };
+            // A scan is never held up by the broker, events are dropped while it cannot be reached
+            let _ = client.try_publish(
+                format!("{topic}/event/{name}"),
+                QoS::AtLeastOnce,
+                false,
+                payload.to_string(),
+            );
+        });
+    }
+
+    events::status(tr!("mqtt-connected", broker = config.broker.clone()));
+    let broker = config.broker.clone();
+    let commands = config.commands;
+    thread::spawn(move || {
+        for notification in connection.iter() {
+            match notification {
+                // Every connection starts a new session, so the subscription is renewed after reconnecting
+                Ok(rumqttc::Event::Incoming(Packet::ConnAck(_))) => {
+                    let _ = client.try_publish(&status, QoS::AtLeastOnce, true, "online");
+                    if commands {
+                        let _ = client.try_subscribe(&scan, QoS::AtLeastOnce);
+                    }
+                }
+                Ok(rumqttc::Event::Incoming(Packet::Publish(publish))) if publish.topic == scan => {
+                    let profile = String::from_utf8_lossy(&publish.payload).trim().to_string();
+                    if let Err(error) = submit(&queue, (!profile.is_empty()).then_some(profile)) {
+                        events::warning(tr!("mqtt-scan-failed", message = error_chain(&error)));
+                    }
+                }
+                Ok(_) => {}
+                Err(error) => {
+                    events::warning(tr!(
+                        "mqtt-disconnected",
+                        broker = broker.clone(),
+                        message = error.to_string()
+                    ));
+                    thread::sleep(RECONNECT);
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// The password from the configuration, or the one stored in the keyring
+fn password(config: &MqttConfig, user: &str) -> miette::Result<String> {
+    if let Some(password) = &config.password {
+        return Ok(password.clone());
+    }
+
+    let stored = match config.account() {
+        Some(account) => stored_password(&account)?,
+        None => None,
+    };
+    stored
+        .ok_or_else(|| ScannrsError::MqttNoPassword {
+            user: user.to_string(),
+        })
+        .into_diagnostic()
+}
+
+/// The host, the port and whether to use TLS of a broker given like `mqtt://host:1883`
+fn broker(url: &str) -> miette::Result<(String, u16, bool)> {
+    let invalid = || ScannrsError::InvalidBroker {
+        url: url.to_string(),
+    };
+
+    let (tls, address) = match url.split_once("://") {
+        Some(("mqtt" | "tcp", address)) => (false, address),
+        Some(("mqtts" | "ssl", address)) => (true, address),
+        Some(_) => return Err(invalid()).into_diagnostic(),
+        None => (false, url),
+    };
+    let address = address.trim_end_matches('/');
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => {
+            (host, port.parse().map_err(|_| invalid()).into_diagnostic()?)
+        }
+        _ => (address, if tls { 8883 } else { 1883 }),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() {
+        return Err(invalid()).into_diagnostic();
+    }
+
+    Ok((host.to_string(), port, tls))
+}
+
+/// Queue a scan of the default scanner with the named profile, or the default one if there is none
+fn submit(queue: &Queue, profile: Option<String>) -> miette::Result<Job> {
+    let device = Config::load()?.device(None)?;
+    submit_profile_scan(queue, device, profile.as_deref(), Vec::new())
+}
@@ -0,0 +1,76 @@
+//! The line based JSON protocol spoken over the daemon socket
+//!
+//! Every request is a single line of JSON, answered by a single line of JSON.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use scannrs_core::value::Value;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::DestinationConfig;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "request", rename_all = "snake_case")]
+pub(crate) enum DaemonRequest {
+    Submit { job: JobRequest },
+    Status { id: u64 },
+    List,
+    Cancel { id: u64 },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub(crate) enum DaemonResponse {
+    Job { job: Job },
+    Jobs { jobs: Vec<Job> },
+    Error { message: String },
+}
+
+/// Everything needed to execute a scan
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct JobRequest {
+    pub(crate) device: String,
+    #[serde(default)]
+    pub(crate) options: BTreeMap<String, Value>,
+    /// Where to save the scan, relative paths are resolved against the working directory of the daemon
+    pub(crate) output: PathBuf,
+    /// Do not start the job before this time, in seconds since the unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) not_before: Option<u64>,
+    /// The profile the scan was asked for with, whose destinations the scan is sent to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) profile: Option<String>,
+    /// Where the scan is sent, in addition to the destinations of the profile
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) destinations: Vec<DestinationConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub(crate) enum JobStatus {
+    Queued,
+    Scanning,
+    Done,
+    Failed { error: String },
+    Cancelled,
+}
+
+impl JobStatus {
+    pub(crate) fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Done | JobStatus::Failed { .. } | JobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct Job {
+    pub(crate) id: u64,
+    #[serde(flatten)]
+    pub(crate) request: JobRequest,
+    #[serde(flatten)]
+    pub(crate) status: JobStatus,
+}
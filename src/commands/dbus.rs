@@ -0,0 +1,245 @@
+//! The `org.scannrs` D-Bus service, for desktop environments and other local programs
+//!
+//! The object `/org/scannrs` implements `org.scannrs.Scanner1`, with methods to list the scanners and to start and
+//! cancel scans, which are saved to a file like `scannrs scan` saves them. Running scans send `Progress` signals and
+//! every scan ends with a `Finished` signal, carrying the error if it failed.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Instant;
+
+use miette::IntoDiagnostic;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::driver::driver;
+use scannrs_core::driver::Driver;
+use scannrs_core::output::Format;
+use scannrs_core::progress::ScanEvent;
+use scannrs_core::scan::CancellationToken;
+use scannrs_core::value::Value;
+use zbus::fdo;
+use zbus::interface;
+use zbus::object_server::SignalContext;
+
+use crate::commands::scan::save_page;
+use crate::commands::scan::scan_job;
+use crate::commands::scan::ScanSource;
+use crate::config::Config;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+#[cfg(unix)]
+use crate::systemd;
+
+/// The well-known name the service is reachable at
+const NAME: &str = "org.scannrs";
+
+/// The path of the object implementing [`Scanner`]
+const PATH: &str = "/org/scannrs";
+
+pub fn dbus(backend: &dyn ScanBackend, system: bool) -> miette::Result<()> {
+    let (driver, driver_loop) = driver();
+    let scanner = Scanner {
+        driver,
+        jobs: Arc::default(),
+        next_job: Arc::new(AtomicU64::new(1)),
+    };
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .into_diagnostic()?;
+
+    let service_thread = std::thread::spawn(move || runtime.block_on(run_service(scanner, system)));
+
+    driver_loop.run(backend);
+
+    match service_thread.join() {
+        Ok(res) => res?,
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+
+    Ok(())
+}
+
+async fn run_service(scanner: Scanner, system: bool) -> miette::Result<()> {
+    #[cfg(unix)]
+    let driver = scanner.driver.clone();
+    let builder = if system {
+        zbus::connection::Builder::system()
+    } else {
+        zbus::connection::Builder::session()
+    };
+    let _connection = builder
+        .and_then(|builder| builder.name(NAME))
+        .and_then(|builder| builder.serve_at(PATH, scanner))
+        .map_err(|error| ScannrsError::Dbus { error })
+        .into_diagnostic()?
+        .build()
+        .await
+        .map_err(|error| ScannrsError::Dbus { error })
+        .into_diagnostic()?;
+
+    eprintln!(
+        "Serving {NAME} on the {} bus",
+        if system { "system" } else { "session" }
+    );
+
+    #[cfg(unix)]
+    {
+        systemd::ready();
+        tokio::spawn(systemd::watch(driver));
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+
+    Ok(())
+}
+
+/// The scans that are still running, to cancel them
+type Jobs = Arc<Mutex<HashMap<u64, CancellationToken>>>;
+
+struct Scanner {
+    driver: Driver,
+    jobs: Jobs,
+    next_job: Arc<AtomicU64>,
+}
+
+impl Scanner {
+    fn jobs(&self) -> MutexGuard<'_, HashMap<u64, CancellationToken>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[interface(name = "org.scannrs.Scanner1")]
+impl Scanner {
+    /// The scanners that are available, as their name, vendor, model and type
+    async fn list_devices(&self) -> fdo::Result<Vec<(String, String, String, String)>> {
+        let devices = self
+            .driver
+            .run(|backend| backend.devices())
+            .await
+            .and_then(|devices| devices)
+            .map_err(failed)?;
+
+        Ok(devices
+            .into_iter()
+            .map(|device| (device.name, device.vendor, device.model, device.type_))
+            .collect())
+    }
+
+    /// Start a scan with the device, saved at `path` in the format of its extension, returning the id of the job
+    ///
+    /// The options are given like `scannrs scan -o name=value`, relative paths go into the output directory of the
+    /// configuration.
+    async fn scan(
+        &self,
+        #[zbus(signal_context)] context: SignalContext<'_>,
+        device: String,
+        path: String,
+        options: HashMap<String, String>,
+    ) -> fdo::Result<u64> {
+        let options = options
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+            .collect::<HashMap<_, _>>();
+        let path = Config::load()
+            .map_err(failed)?
+            .output_path(None, &PathBuf::from(path));
+        let job = scan_job(&device, None, &options).map_err(failed)?;
+
+        let id = self.next_job.fetch_add(1, Ordering::Relaxed);
+        let (sender, mut progress) = tokio::sync::mpsc::unbounded_channel();
+        let mut percent = None;
+        let (scan, cancel) = self.driver.scan_with_progress(job, move |event| {
+            // Only whole percents are worth a signal, the scanner reports far more often
+            let update = match event {
+                ScanEvent::Processing { stage } => Some((-1, stage.name())),
+                event => match event.fraction().map(|fraction| (fraction * 100.0) as i32) {
+                    Some(new) if Some(new) != percent => {
+                        percent = Some(new);
+                        Some((new, ""))
+                    }
+                    _ => None,
+                },
+            };
+            if let Some(update) = update {
+                let _ = sender.send(update);
+            }
+            #[cfg(unix)]
+            systemd::alive();
+        });
+        self.jobs().insert(id, cancel);
+
+        let context = context.to_owned();
+        let jobs = self.jobs.clone();
+        tokio::spawn(async move {
+            let started = Instant::now();
+            let signals = async {
+                while let Some((percent, stage)) = progress.recv().await {
+                    let _ = Scanner::progress(&context, id, percent, stage).await;
+                }
+            };
+            let (res, ()) = tokio::join!(scan, signals);
+            jobs.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+
+            let res = res.and_then(|page| {
+                let page = page.ok_or(ScannrsError::PageDropped).into_diagnostic()?;
+                save_page(
+                    &path,
+                    Format::for_path(&path, None),
+                    &page,
+                    ScanSource {
+                        device: &device,
+                        settings: None,
+                        options: &options,
+                        duration: started.elapsed(),
+                    },
+                )
+            });
+            let error = res
+                .err()
+                .map(|error| error_chain(&error))
+                .unwrap_or_default();
+            let _ = Scanner::finished(&context, id, &path.to_string_lossy(), &error).await;
+        });
+
+        Ok(id)
+    }
+
+    /// Stop a running scan, which then finishes with an error
+    async fn cancel(&self, job: u64) -> fdo::Result<()> {
+        let jobs = self.jobs();
+        let cancel = jobs
+            .get(&job)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("There is no running job {job}")))?;
+        cancel.cancel();
+
+        Ok(())
+    }
+
+    /// How far the job is, the percent read of the current page or -1 while it is processed in `stage`
+    #[zbus(signal)]
+    async fn progress(
+        context: &SignalContext<'_>,
+        job: u64,
+        percent: i32,
+        stage: &str,
+    ) -> zbus::Result<()>;
+
+    /// The job is done, the scan was saved at `path` unless `error` is not empty
+    #[zbus(signal)]
+    async fn finished(
+        context: &SignalContext<'_>,
+        job: u64,
+        path: &str,
+        error: &str,
+    ) -> zbus::Result<()>;
+}
+
+fn failed(error: miette::Report) -> fdo::Error {
+    fdo::Error::Failed(error_chain(&error))
+}
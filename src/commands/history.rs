@@ -0,0 +1,113 @@
+use std::path::Path;
+use std::process::Command;
+
+use chrono::Local;
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use crate::cli::print_json;
+use crate::cli::HistoryCommand;
+use crate::cli::OutputFormat;
+use crate::error::ScannrsError;
+use crate::history::History;
+use crate::history::HistoryEntry;
+use crate::i18n::tr;
+
+pub fn history(command: Option<HistoryCommand>, output: OutputFormat) -> Result<(), miette::Error> {
+    let history = History::open()?;
+
+    match command.unwrap_or_default() {
+        HistoryCommand::List => {
+            let entries = history.entries()?;
+
+            match output {
+                OutputFormat::Json => print_json(&entries)?,
+                OutputFormat::Text if entries.is_empty() => println!("{}", tr!("history-empty")),
+                OutputFormat::Text => {
+                    for entry in entries {
+                        println!(
+                            "{:>4}  {}  {}  {} page(s)  {}",
+                            entry.id,
+                            entry
+                                .finished_at
+                                .with_timezone(&Local)
+                                .format("%Y-%m-%d %H:%M"),
+                            entry.device,
+                            entry.pages,
+                            entry
+                                .outputs
+                                .iter()
+                                .map(|p| p.display().to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                    }
+                }
+            }
+        }
+        HistoryCommand::Show { entry } => {
+            let entry = history.get(entry)?;
+
+            match output {
+                OutputFormat::Json => print_json(&entry)?,
+                OutputFormat::Text => print_entry(&entry),
+            }
+        }
+        HistoryCommand::Open { entry } => {
+            let entry = history.get(entry)?;
+            for path in &entry.outputs {
+                open_path(path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_entry(entry: &HistoryEntry) {
+    println!("Scan {}", entry.id);
+    println!(
+        "  Finished: {}",
+        entry
+            .finished_at
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+    );
+    println!("  Device: {}", entry.device);
+    println!("  Duration: {:.1}s", entry.duration_ms as f64 / 1000.0);
+    println!("  Pages: {}", entry.pages);
+    println!("  Format: {:?}", entry.format);
+    if let Some(settings) = &entry.settings {
+        println!("  Settings: {}", settings.display());
+    }
+    for (key, value) in &entry.options {
+        println!("  Option: {key}={value}");
+    }
+    for path in &entry.outputs {
+        println!("  Output: {}", path.display());
+    }
+}
+
+/// Open a file with the default application of the desktop
+pub(crate) fn open_path(path: &Path) -> miette::Result<()> {
+    if !path.exists() {
+        return Err(ScannrsError::FileMissing {
+            path: path.to_path_buf(),
+        })
+        .into_diagnostic();
+    }
+
+    let opener = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    Command::new(opener)
+        .arg(path)
+        .spawn()
+        .into_diagnostic()
+        .with_context(|| format!("While trying to open {} with {opener}", path.display()))?;
+
+    Ok(())
+}
@@ -1,10 +1,192 @@
-use miette::IntoDiagnostic;
-use sane_scan::Sane;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::device::DeviceInfo;
 
-pub fn list(sane: Sane) -> Result<(), miette::Error> {
-    for device in sane.get_devices().into_diagnostic()? {
-        println!("{device:?}");
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+#[cfg(feature = "mdns")]
+use crate::events;
+use crate::i18n::tr;
+
+/// Case-insensitive substring filters on the device description
+#[derive(Default, Debug)]
+pub struct ListFilter {
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+    pub type_: Option<String>,
+}
+
+impl ListFilter {
+    fn matches(&self, device: &DeviceInfo) -> bool {
+        let contains = |filter: &Option<String>, value: &str| {
+            filter.as_ref().map_or(true, |filter| {
+                value.to_lowercase().contains(&filter.to_lowercase())
+            })
+        };
+
+        contains(&self.vendor, &device.vendor)
+            && contains(&self.model, &device.model)
+            && contains(&self.type_, &device.type_)
+    }
+}
+
+/// Lists the scanners of the backend, and with `discover` also those found on the network that are not listed yet
+///
+/// The network is searched while the backend enumerates, and text is printed as soon as scanners are found, so that
+/// local scanners show up before the network scanners answered. JSON is printed once all are known.
+pub fn list(
+    backend: &dyn ScanBackend,
+    filter: ListFilter,
+    porcelain: bool,
+    discover: bool,
+    output: OutputFormat,
+) -> Result<(), miette::Error> {
+    let discovery = discover.then(|| std::thread::spawn(discovered));
+    let mut listing = Listing {
+        filter,
+        output,
+        porcelain,
+        network: discover,
+        known: Vec::new(),
+        listed: Vec::new(),
+        table: Table::default(),
+    };
+
+    backend.devices_streamed(&mut |devices| listing.show(devices))?;
+    if let Some(discovery) = discovery {
+        let found = discovery
+            .join()
+            .unwrap_or_else(|payload| std::panic::resume_unwind(payload));
+        let found = found
+            .into_iter()
+            .filter(|device| !listing.known.contains(&device.name))
+            .collect();
+        listing.show(found);
+    }
+
+    match output {
+        OutputFormat::Json => print_json(&listing.listed)?,
+        OutputFormat::Text if !porcelain && listing.listed.is_empty() => {
+            println!("{}", tr!("list-empty"))
+        }
+        OutputFormat::Text => {}
     }
 
     Ok(())
 }
+
+/// The scanners printed so far
+struct Listing {
+    filter: ListFilter,
+    output: OutputFormat,
+    porcelain: bool,
+    /// Whether network scanners may still be found, so their columns are shown from the start
+    network: bool,
+    /// The names of all scanners found, also those filtered out
+    known: Vec<String>,
+    listed: Vec<DeviceInfo>,
+    table: Table,
+}
+
+impl Listing {
+    fn show(&mut self, devices: Vec<DeviceInfo>) {
+        self.known
+            .extend(devices.iter().map(|device| device.name.clone()));
+        let devices = devices
+            .into_iter()
+            .filter(|device| self.filter.matches(device))
+            .collect::<Vec<_>>();
+
+        match self.output {
+            OutputFormat::Json => {}
+            OutputFormat::Text if self.porcelain => {
+                for device in &devices {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        device.name, device.vendor, device.model, device.type_
+                    );
+                }
+            }
+            OutputFormat::Text => self.table.print(&devices, self.network),
+        }
+        self.listed.extend(devices);
+    }
+}
+
+/// Network scanners found through mDNS, failing to look for them only prints a warning
+#[cfg(feature = "mdns")]
+fn discovered() -> Vec<DeviceInfo> {
+    scannrs_core::discovery::discover(scannrs_core::discovery::TIMEOUT).unwrap_or_else(|error| {
+        events::warning(error);
+        Vec::new()
+    })
+}
+
+#[cfg(not(feature = "mdns"))]
+fn discovered() -> Vec<DeviceInfo> {
+    Vec::new()
+}
+
+/// A table printed a few rows at a time, its columns only ever get wider
+#[derive(Default)]
+struct Table {
+    /// The widths of the columns, empty until the header is printed
+    widths: Vec<usize>,
+}
+
+impl Table {
+    fn print(&mut self, devices: &[DeviceInfo], network: bool) {
+        if devices.is_empty() {
+            return;
+        }
+
+        // How network scanners are reached is only shown when there may be any
+        let header = ["NAME", "VENDOR", "MODEL", "TYPE", "PROTOCOL", "NATIVE"];
+        let columns = match self.widths.len() {
+            0 if network || devices.iter().any(|d| d.network.is_some()) => 6,
+            0 => 4,
+            columns => columns,
+        };
+        let header = &header[..columns];
+        let rows = devices
+            .iter()
+            .map(|d| {
+                let (protocol, native) = d.network.as_ref().map_or(("", ""), |network| {
+                    (
+                        network.protocol.as_str(),
+                        if network.native { "yes" } else { "no" },
+                    )
+                });
+                [
+                    d.name.as_str(),
+                    d.vendor.as_str(),
+                    d.model.as_str(),
+                    d.type_.as_str(),
+                    protocol,
+                    native,
+                ]
+            })
+            .collect::<Vec<_>>();
+        let rows = rows.iter().map(|row| &row[..header.len()]);
+
+        let first = self.widths.is_empty();
+        if first {
+            self.widths = header.iter().map(|h| h.chars().count()).collect();
+        }
+        for row in rows.clone() {
+            for (width, cell) in self.widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let header = first.then_some(header);
+        for row in header.into_iter().chain(rows) {
+            let line = row
+                .iter()
+                .zip(&self.widths)
+                .map(|(cell, &width)| format!("{cell:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            println!("{}", line.trim_end());
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use crate::cli::LoginService;
+use crate::config::Config;
+use crate::destination;
+use crate::destination::email;
+use crate::destination::webdav;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::i18n::tr;
+
+/// Ask for the password of the configured account and store it in the keyring of the system
+pub fn login(service: LoginService) -> Result<(), miette::Error> {
+    let config = Config::load()?;
+    let (entry, prompt) = match service {
+        LoginService::Webdav => {
+            let webdav = config.webdav()?;
+            (
+                destination::keyring_entry(&webdav::account(webdav))?,
+                tr!(
+                    "login-password",
+                    user = webdav.user.clone(),
+                    url = webdav.url.clone()
+                ),
+            )
+        }
+        LoginService::Email => {
+            let email = config.email()?;
+            let (Some(user), Some(account)) = (&email.user, email::account(email)) else {
+                return Err(ScannrsError::EmailNoUser.into());
+            };
+            (
+                destination::keyring_entry(&account)?,
+                tr!(
+                    "login-password",
+                    user = user.clone(),
+                    url = email.server.clone()
+                ),
+            )
+        }
+        LoginService::Mqtt => {
+            let mqtt = config.mqtt()?;
+            let (Some(user), Some(account)) = (&mqtt.user, mqtt.account()) else {
+                return Err(ScannrsError::MqttNoUser.into());
+            };
+            (
+                destination::keyring_entry(&account)?,
+                tr!(
+                    "login-password",
+                    user = user.clone(),
+                    url = mqtt.broker.clone()
+                ),
+            )
+        }
+    };
+
+    let password = rpassword::prompt_password(prompt).into_diagnostic()?;
+    entry
+        .set_password(&password)
+        .into_diagnostic()
+        .context("Could not store the password in the keyring of the system")?;
+    events::status(tr!("login-stored"));
+
+    Ok(())
+}
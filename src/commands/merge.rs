@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::output::write_document;
+use scannrs_core::output::Format;
+use scannrs_core::output::Page;
+
+pub fn merge(
+    output: PathBuf,
+    pages: Vec<PathBuf>,
+    dpi: f32,
+    format: Option<Format>,
+) -> Result<(), miette::Error> {
+    let format = Format::for_path(&output, format);
+
+    let pages = pages
+        .iter()
+        .map(|path| {
+            let image = image::open(path)
+                .into_diagnostic()
+                .with_context(|| format!("While reading the page at {}", path.display()))?;
+
+            Ok(Page { image, dpi })
+        })
+        .collect::<miette::Result<Vec<_>>>()?;
+
+    let mut file = std::fs::File::create(&output)
+        .into_diagnostic()
+        .with_context(|| format!("Tried to write to file at {}", output.display()))?;
+
+    write_document(&mut file, format, &pages)
+}
@@ -1,9 +1,52 @@
+mod about;
+mod batch;
+mod calibrate;
+mod copy;
+#[cfg(unix)]
+mod daemon;
+#[cfg(feature = "dbus")]
+mod dbus;
+mod history;
 mod list;
+mod login;
+mod merge;
+mod ocr;
 mod options;
+#[cfg(unix)]
+mod queue;
+mod rerun;
 mod scan;
+mod selftest;
+mod serve;
 mod tui;
+#[cfg(unix)]
+mod unit;
 
+pub use about::about;
+pub use batch::batch;
+pub use batch::NewBatch;
+pub use calibrate::calibrate;
+pub use copy::copy;
+#[cfg(unix)]
+pub use daemon::daemon;
+#[cfg(feature = "dbus")]
+pub use dbus::dbus;
+pub use history::history;
 pub use list::list;
+pub use list::ListFilter;
+pub use login::login;
+pub use merge::merge;
+pub use ocr::ocr;
+pub(crate) use ocr::recognize_page;
 pub use options::options;
+#[cfg(unix)]
+pub use queue::queue;
+pub use rerun::rerun;
 pub use scan::scan;
+pub(crate) use scan::ScanSummary;
+pub use scan::ScanTarget;
+pub use selftest::selftest;
+pub use serve::serve;
 pub use tui::tui;
+#[cfg(unix)]
+pub use unit::systemd_unit;
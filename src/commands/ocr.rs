@@ -0,0 +1,61 @@
+use std::io::Cursor;
+use std::io::Write;
+use std::path::PathBuf;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::ocr::recognize;
+use scannrs_core::ocr::OcrEngine;
+use scannrs_core::ocr::OcrFormat;
+use scannrs_core::ocr::OcrOptions;
+use scannrs_core::output::write_document;
+use scannrs_core::output::Format;
+use scannrs_core::output::Page;
+
+use crate::config::Config;
+
+/// Recognize the text in an image as set up by the profile or the `[ocr]` section, unless given otherwise
+pub fn ocr(
+    input: PathBuf,
+    lang: Option<String>,
+    psm: Option<u8>,
+    engine: Option<OcrEngine>,
+    format: OcrFormat,
+    path: Option<PathBuf>,
+) -> Result<(), miette::Error> {
+    let mut options = Config::load()?.ocr()?;
+    if let Some(lang) = lang {
+        options.lang = lang;
+    }
+    if psm.is_some() {
+        options.psm = psm;
+    }
+    if let Some(engine) = engine {
+        options.engine = engine;
+    }
+
+    let image = std::fs::read(&input)
+        .into_diagnostic()
+        .with_context(|| format!("While reading the image at {}", input.display()))?;
+
+    let result = recognize(&image, &options, format)
+        .with_context(|| format!("While recognizing the text in {}", input.display()))?;
+
+    match path {
+        Some(path) => std::fs::write(&path, result)
+            .into_diagnostic()
+            .with_context(|| format!("Tried to write to file at {}", path.display()))?,
+        None => std::io::stdout().write_all(&result).into_diagnostic()?,
+    }
+
+    Ok(())
+}
+
+/// Recognize the text on a scanned page, which tesseract needs as an encoded image
+pub(crate) fn recognize_page(page: &Page, options: &OcrOptions) -> miette::Result<String> {
+    let mut png = Cursor::new(Vec::new());
+    write_document(&mut png, Format::Png, std::slice::from_ref(page))?;
+    let text = recognize(png.get_ref(), options, OcrFormat::Txt)?;
+
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
@@ -1,67 +1,351 @@
+use std::path::Path;
+
 use miette::Context;
 use miette::IntoDiagnostic;
-use sane_scan::Sane;
+use scannrs_core::backend::Constraint;
+use scannrs_core::backend::OptionDescriptor;
+use scannrs_core::backend::OptionValue;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::backend::ScanDevice;
+use scannrs_core::backend::ValueType;
+use scannrs_core::device::OptionInfo;
+use scannrs_core::value::Value;
 
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+use crate::config::parse_toml;
+use crate::config::Config;
 use crate::error::ScannrsError;
 
 pub fn options(
-    sane: Sane,
+    backend: &dyn ScanBackend,
     name: String,
     command: Option<crate::cli::OptionsCommand>,
+    output: OutputFormat,
 ) -> Result<(), miette::Error> {
-    let device = match sane
-        .get_devices()
-        .into_diagnostic()?
-        .into_iter()
-        .find_map(|d| (d.name.as_bytes() == name.as_bytes()).then(|| d.open()))
-    {
-        Some(device) => device
-            .map_err(ScannrsError::from)
-            .into_diagnostic()
-            .with_context(|| format!("While trying to open a connection with scanner {}", name))?,
-        None => return Err(ScannrsError::CouldNotFindScanner { name }.into()),
-    };
+    let mut device = backend.open(&name)?;
     match command.unwrap_or_default() {
         crate::cli::OptionsCommand::List => {
-            let options = device.get_options().into_diagnostic()?;
+            let options = device.options()?;
+
+            if output == OutputFormat::Json {
+                return print_json(&options.iter().map(OptionInfo::from).collect::<Vec<_>>());
+            }
 
             for option in options {
                 match option.type_ {
-                    sane_scan::ValueType::Group => {
-                        println!("[{}]", option.title.to_string_lossy());
+                    ValueType::Group => {
+                        println!("[{}]", option.title);
                     }
                     t => {
-                        println!(
-                            "# {}\n{} = {t:?}",
-                            option.title.to_string_lossy(),
-                            option.name.to_string_lossy(),
-                        );
+                        println!("# {}\n{} = {t:?}", option.title, option.name);
                     }
                 }
             }
         }
         crate::cli::OptionsCommand::Show { option } => {
-            let options = device.get_options().into_diagnostic()?;
+            let options = device.options()?;
+
+            let device_option =
+                options
+                    .into_iter()
+                    .find(|o| o.name == option)
+                    .ok_or_else(|| scannrs_core::Error::OptionNotFound {
+                        name: name.clone(),
+                        option: option.clone(),
+                    })?;
+
+            let value = device.get_option(&device_option).with_context(|| {
+                format!("While trying to read the option '{option}' from scanner '{name}'")
+            })?;
+
+            match output {
+                OutputFormat::Text => println!("{value:?}"),
+                OutputFormat::Json => {
+                    print_json(&OptionInfo::from(&device_option).with_value(&value))?
+                }
+            }
+        }
+        crate::cli::OptionsCommand::Describe { option } => {
+            let options = device.options()?;
+
+            if let Some(option) = &option {
+                if !options.iter().any(|o| o.name == *option) {
+                    return Err(scannrs_core::Error::OptionNotFound {
+                        name,
+                        option: option.clone(),
+                    }
+                    .into());
+                }
+            }
+
+            let selected = options
+                .iter()
+                .filter(|o| option.as_ref().map_or(true, |option| o.name == *option));
+
+            if output == OutputFormat::Json {
+                let infos = selected
+                    .map(|o| {
+                        let info = OptionInfo::from(o);
+                        if !info.active || !o.has_value() {
+                            return Ok(info);
+                        }
+                        let value = device.get_option(o)?;
+                        Ok(info.with_value(&value))
+                    })
+                    .collect::<miette::Result<Vec<_>>>()?;
+
+                return print_json(&infos);
+            }
+
+            for device_option in selected {
+                if device_option.type_ == ValueType::Group {
+                    if option.is_none() {
+                        println!("[{}]\n", device_option.title);
+                    }
+                    continue;
+                }
+
+                describe(device.as_ref(), device_option)?;
+            }
+        }
+        crate::cli::OptionsCommand::Export => {
+            let mut table = toml::Table::new();
+
+            for option in device.options()? {
+                if !is_settable(&option) {
+                    continue;
+                }
+
+                let value = device.get_option(&option).with_context(|| {
+                    format!(
+                        "While trying to read the option '{}' from scanner '{name}'",
+                        option.name
+                    )
+                })?;
 
-            let device_option = options
+                if let Some(value) = to_toml(value) {
+                    table.insert(option.name, value);
+                }
+            }
+
+            println!("# Options of scanner '{name}'");
+            print!("{}", toml::to_string(&table).into_diagnostic()?);
+        }
+        crate::cli::OptionsCommand::Set { option, value } => {
+            let device_option = device
+                .options()?
                 .into_iter()
-                .find(|o| o.name.as_bytes() == option.as_bytes())
-                .ok_or_else(|| ScannrsError::OptionNotFound {
+                .find(|o| o.name == option)
+                .ok_or_else(|| scannrs_core::Error::OptionNotFound {
                     name: name.clone(),
                     option: option.clone(),
-                })
-                .into_diagnostic()?;
-
-            let value = device
-                .get_option(&device_option)
-                .into_diagnostic()
-                .with_context(|| {
-                    format!("While trying to read the option '{option}' from scanner '{name}'")
                 })?;
 
-            println!("{value:?}");
+            if !is_settable(&device_option) {
+                return Err(ScannrsError::OptionNotSettable { name, option }.into());
+            }
+
+            // Make sure the scanner accepts the value before storing it
+            if let Some(parsed) = value.to_option_value(&device_option)? {
+                device.set_option(&device_option, parsed).with_context(|| {
+                    format!("While setting the option '{option}' on scanner '{name}'")
+                })?;
+            }
+
+            let mut config = Config::load()?;
+            config
+                .devices
+                .entry(name)
+                .or_default()
+                .options
+                .insert(option, value);
+            config.save()?;
+        }
+        crate::cli::OptionsCommand::Unset { option } => {
+            let mut config = Config::load()?;
+            if let Some(device) = config.devices.get_mut(&name) {
+                device.options.remove(&option);
+                if device.options.is_empty() {
+                    config.devices.remove(&name);
+                }
+            }
+            config.save()?;
+        }
+        crate::cli::OptionsCommand::Import { file } => {
+            import_options(device.as_mut(), &name, &file)?;
         }
     }
 
     Ok(())
 }
+
+/// Describe all options of the device, together with the current value of those that are active
+pub(crate) fn option_infos(device: &dyn ScanDevice) -> miette::Result<Vec<OptionInfo>> {
+    device
+        .options()?
+        .iter()
+        .map(|option| {
+            let info = OptionInfo::from(option);
+            if !info.active || !option.has_value() {
+                return Ok(info);
+            }
+            let value = device.get_option(option)?;
+            Ok(info.with_value(&value))
+        })
+        .collect()
+}
+
+/// Set the options from a TOML file as created by `options export`, failing on unknown or inactive options
+pub(crate) fn import_options(
+    device: &mut dyn ScanDevice,
+    name: &str,
+    file: &Path,
+) -> miette::Result<()> {
+    // Options depend on each other (e.g. the available depths depend on the mode), so apply them in order and
+    // look them up again every time
+    for (key, value) in read_settings(file)? {
+        let option = device
+            .options()?
+            .into_iter()
+            .find(|o| o.name == key)
+            .ok_or_else(|| scannrs_core::Error::OptionNotFound {
+                name: name.to_string(),
+                option: key.clone(),
+            })?;
+
+        if !is_settable(&option) {
+            return Err(ScannrsError::OptionNotSettable {
+                name: name.to_string(),
+                option: key,
+            }
+            .into());
+        }
+
+        let Some(value) = value.to_option_value(&option)? else {
+            continue;
+        };
+
+        device
+            .set_option(&option, value)
+            .with_context(|| format!("While setting the option '{key}' on scanner '{name}'"))?;
+    }
+
+    Ok(())
+}
+
+/// Read a TOML file as created by `options export` into the options it sets, in the order of the file
+pub(crate) fn read_settings(file: &Path) -> miette::Result<Vec<(String, Value)>> {
+    let contents = std::fs::read_to_string(file)
+        .into_diagnostic()
+        .with_context(|| format!("While reading the options at {}", file.display()))?;
+    let table: toml::Table = parse_toml(file, contents)
+        .with_context(|| format!("While parsing the options at {}", file.display()))?;
+
+    Ok(table
+        .into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::String(value) => Value::from(value),
+                toml::Value::Boolean(value) => Value::Bool(value),
+                toml::Value::Integer(value) => Value::from(value as f64),
+                toml::Value::Float(value) => Value::from(value),
+                value => Value::from(value.to_string()),
+            };
+            (key, value)
+        })
+        .collect())
+}
+
+fn describe(device: &dyn ScanDevice, option: &OptionDescriptor) -> miette::Result<()> {
+    println!("{} ({})", option.name, option.title);
+    if !option.description.is_empty() {
+        println!("  {}", option.description.replace('\n', "\n  "));
+    }
+    println!("  Type: {:?}", option.type_);
+    if let Some(unit) = option.unit.name() {
+        println!("  Unit: {unit}");
+    }
+
+    // Inactive options cannot be read
+    if option.active && option.has_value() {
+        let value = device
+            .get_option(option)
+            .with_context(|| format!("While trying to read the option '{}'", option.name))?;
+        println!("  Value: {}", format_value(&value));
+    }
+
+    if let Some(allowed) = format_constraint(option) {
+        println!("  Allowed: {allowed}");
+    }
+
+    let mut flags = vec![];
+    if !option.active {
+        flags.push("inactive");
+    }
+    if !option.settable {
+        flags.push("read-only");
+    }
+    if option.automatic {
+        flags.push("can be set automatically");
+    }
+    if option.advanced {
+        flags.push("advanced");
+    }
+    if option.emulated {
+        flags.push("emulated");
+    }
+    if !flags.is_empty() {
+        println!("  Flags: {}", flags.join(", "));
+    }
+    println!();
+
+    Ok(())
+}
+
+pub(crate) fn format_value(value: &OptionValue) -> String {
+    match value {
+        OptionValue::Bool(value) => value.to_string(),
+        OptionValue::Int(value) => value.to_string(),
+        OptionValue::Fixed(value) => value.to_string(),
+        OptionValue::String(value) => value.clone(),
+        value => format!("{value:?}"),
+    }
+}
+
+/// Describe the values the option accepts
+pub(crate) fn format_constraint(option: &OptionDescriptor) -> Option<String> {
+    match &option.constraint {
+        Constraint::None => None,
+        Constraint::Range { min, max, step } => {
+            let mut allowed = format!("{min} to {max}");
+            if let Some(step) = step {
+                allowed.push_str(&format!(" in steps of {step}"));
+            }
+            Some(allowed)
+        }
+        Constraint::Numbers(numbers) => Some(
+            numbers
+                .iter()
+                .map(|number| number.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+        Constraint::Strings(strings) => Some(strings.join(", ")),
+    }
+}
+
+/// Whether the option is active and can be set by software
+fn is_settable(option: &OptionDescriptor) -> bool {
+    option.has_value() && option.settable && option.active
+}
+
+fn to_toml(value: OptionValue) -> Option<toml::Value> {
+    Some(match value {
+        OptionValue::Bool(value) => toml::Value::Boolean(value),
+        OptionValue::Int(value) => toml::Value::Integer(value.into()),
+        OptionValue::Fixed(value) => toml::Value::Float(value),
+        OptionValue::String(value) => toml::Value::String(value),
+        OptionValue::Button => return None,
+    })
+}
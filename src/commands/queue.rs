@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::Local;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use super::daemon::protocol::DaemonRequest;
+use super::daemon::protocol::DaemonResponse;
+use super::daemon::protocol::Job;
+use super::daemon::protocol::JobRequest;
+use super::daemon::protocol::JobStatus;
+use super::daemon::unix_now;
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+use crate::cli::QueueCommand;
+use crate::error::ScannrsError;
+use crate::i18n::tr;
+
+pub fn queue(
+    socket: Option<PathBuf>,
+    command: QueueCommand,
+    output: OutputFormat,
+) -> Result<(), miette::Error> {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => crate::paths::daemon_socket()?,
+    };
+
+    let request = match command {
+        QueueCommand::Add {
+            name,
+            path,
+            options,
+            delay,
+            at,
+        } => {
+            let not_before = match (delay, at) {
+                (Some(delay), _) => Some(
+                    unix_now()
+                        .checked_add(delay.as_secs())
+                        .ok_or(ScannrsError::InvalidDuration)?,
+                ),
+                (None, Some(at)) => Some(next_occurrence(at)?),
+                (None, None) => None,
+            };
+
+            DaemonRequest::Submit {
+                job: JobRequest {
+                    device: name,
+                    options: options.into_iter().collect::<BTreeMap<_, _>>(),
+                    // The daemon does not share our working directory
+                    output: std::env::current_dir().into_diagnostic()?.join(path),
+                    not_before,
+                    profile: None,
+                    destinations: Vec::new(),
+                },
+            }
+        }
+        QueueCommand::List => DaemonRequest::List,
+        QueueCommand::Status { id } => DaemonRequest::Status { id },
+        QueueCommand::Cancel { id } => DaemonRequest::Cancel { id },
+    };
+
+    match send(&socket, &request)? {
+        DaemonResponse::Job { job } => match output {
+            OutputFormat::Json => print_json(&job)?,
+            OutputFormat::Text => print_jobs(std::slice::from_ref(&job)),
+        },
+        DaemonResponse::Jobs { jobs } => match output {
+            OutputFormat::Json => print_json(&jobs)?,
+            OutputFormat::Text => print_jobs(&jobs),
+        },
+        DaemonResponse::Error { message } => return Err(ScannrsError::Daemon { message }.into()),
+    }
+
+    Ok(())
+}
+
+/// Send a single request to the daemon and wait for its response
+pub(crate) fn send(socket: &Path, request: &DaemonRequest) -> miette::Result<DaemonResponse> {
+    let mut stream = UnixStream::connect(socket).map_err(|_| ScannrsError::DaemonNotRunning {
+        socket: socket.to_path_buf(),
+    })?;
+
+    let mut line = serde_json::to_string(request).into_diagnostic()?;
+    line.push('\n');
+    stream.write_all(line.as_bytes()).into_diagnostic()?;
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_line(&mut response)
+        .into_diagnostic()
+        .context("While reading the response of the daemon")?;
+
+    serde_json::from_str(&response)
+        .into_diagnostic()
+        .context("The daemon sent an invalid response")
+}
+
+fn print_jobs(jobs: &[Job]) {
+    if jobs.is_empty() {
+        println!("{}", tr!("queue-empty"));
+        return;
+    }
+
+    for job in jobs {
+        let status = match &job.status {
+            JobStatus::Queued => match job.request.not_before {
+                Some(not_before) if not_before > unix_now() => {
+                    format!("scheduled in {}s", not_before - unix_now())
+                }
+                _ => "queued".to_string(),
+            },
+            JobStatus::Scanning => "scanning".to_string(),
+            JobStatus::Done => "done".to_string(),
+            JobStatus::Failed { error } => format!("failed: {error}"),
+            JobStatus::Cancelled => "cancelled".to_string(),
+        };
+
+        println!(
+            "{:>4}  {}  {}  {}",
+            job.id,
+            job.request.device,
+            job.request.output.display(),
+            status
+        );
+    }
+}
+
+/// The next time the wall clock shows the given time, in seconds since the unix epoch
+fn next_occurrence(at: NaiveTime) -> miette::Result<u64> {
+    let now = Local::now();
+    let mut date = now.date_naive();
+    if at <= now.time() {
+        date = date.succ_opt().unwrap_or(date);
+    }
+
+    let at = Local
+        .from_local_datetime(&date.and_time(at))
+        .earliest()
+        .ok_or(ScannrsError::InvalidTime)?;
+
+    Ok(at.timestamp().max(0) as u64)
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::path::PathBuf;
+
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::progress::ScanEvent;
+
+use super::scan::scan_to_file;
+use super::scan::ScanSummary;
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+use crate::events;
+use crate::events::Event;
+use crate::history::History;
+use crate::history::HistoryEntry;
+use crate::history::HistoryRef;
+use crate::i18n::tr;
+use crate::progress::ProgressBar;
+
+pub fn rerun(
+    backend: &dyn ScanBackend,
+    entry: HistoryRef,
+    path: Option<PathBuf>,
+    output: OutputFormat,
+) -> Result<(), miette::Error> {
+    let entry = History::open()?.get(entry)?;
+    let mut progress = ProgressBar::new();
+    let summary = rerun_entry(backend, &entry, path, &mut |event| progress.update(event));
+    drop(progress);
+    let summary = summary?;
+    events::emit(&Event::Done {
+        pages: summary.pages,
+        outputs: vec![summary.path.clone()],
+    });
+
+    match output {
+        OutputFormat::Json => print_json(&summary)?,
+        OutputFormat::Text => println!(
+            "{}",
+            tr!("scan-saved", path = summary.path.display().to_string())
+        ),
+    }
+
+    Ok(())
+}
+
+/// Scan again with the device, settings and options of the entry
+///
+/// Without a path, the scan is saved next to the previous one instead of overwriting it.
+pub(crate) fn rerun_entry(
+    backend: &dyn ScanBackend,
+    entry: &HistoryEntry,
+    path: Option<PathBuf>,
+    progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+) -> miette::Result<ScanSummary> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let previous = entry
+                .outputs
+                .first()
+                .cloned()
+                .unwrap_or_else(|| PathBuf::from("scan.jpg"));
+            next_free_path(&previous)
+        }
+    };
+
+    let options = entry.options.clone().into_iter().collect::<HashMap<_, _>>();
+    scan_to_file(
+        backend,
+        &entry.device,
+        &path,
+        entry.format,
+        entry.settings.as_deref(),
+        &options,
+        progress,
+    )
+}
+
+/// Find the first path of the form `<stem>-<n>.<extension>` that does not exist yet
+///
+/// A numeric suffix already present is continued, so `scan-2.jpg` is followed by `scan-3.jpg`.
+fn next_free_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (base, start) = match stem.rsplit_once('-') {
+        Some((base, n)) if !base.is_empty() => match n.parse::<u32>() {
+            Ok(n) => (base.to_string(), n + 1),
+            Err(_) => (stem.clone(), 1),
+        },
+        _ => (stem.clone(), 1),
+    };
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    (start..)
+        .map(|n| path.with_file_name(format!("{base}-{n}{extension}")))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_else(|| path.to_path_buf())
+}
@@ -1,89 +1,391 @@
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::Instant;
 
-use image::codecs::jpeg::JpegEncoder;
-use image::DynamicImage;
+use chrono::Utc;
 use miette::Context;
 use miette::IntoDiagnostic;
-use sane_scan::DeviceOptionValue;
-use sane_scan::Sane;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::backend::ScanDevice;
+use scannrs_core::job::ScanJob;
+use scannrs_core::output::write_document;
+use scannrs_core::output::Format;
+use scannrs_core::output::Page;
+use scannrs_core::output::PageSize;
+use scannrs_core::postprocess::Registry;
+use scannrs_core::progress::ScanEvent;
+use scannrs_core::scan::FrameBuffers;
+use scannrs_core::value::Value;
+use serde::Serialize;
 
+use super::options::read_settings;
+use crate::calibration;
+use crate::cli::print_json;
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::config::DestinationConfig;
+use crate::destination::Destinations;
+use crate::destination::SavedScan;
+use crate::error::error_chain;
 use crate::error::ScannrsError;
+use crate::events;
+use crate::events::Event;
+use crate::history::History;
+use crate::history::HistoryEntry;
+use crate::i18n::tr;
+use crate::progress::ProgressBar;
+use crate::rules;
+use crate::share;
+use crate::webhook;
+use crate::webhook::JobReport;
+
+/// What to scan with and where to save it, as given on the command line
+///
+/// Everything that is not given is taken from the environment or the configuration.
+pub struct ScanTarget {
+    pub name: Option<String>,
+    pub resolution: Option<f32>,
+    pub output_dir: Option<PathBuf>,
+    pub path: PathBuf,
+    pub format: Option<Format>,
+}
 
 pub fn scan(
-    sane: Sane,
-    name: String,
-    path: std::path::PathBuf,
-    options: Vec<(Vec<u8>, String)>,
+    backend: &dyn ScanBackend,
+    target: ScanTarget,
+    settings: Option<std::path::PathBuf>,
+    options: Vec<(String, Value)>,
+    destinations: Vec<DestinationConfig>,
+    share: Option<Duration>,
+    output: OutputFormat,
 ) -> Result<(), miette::Error> {
-    let mut device = match sane
-        .get_devices()
-        .into_diagnostic()?
+    let config = Config::load()?;
+    let profile = config.profile()?.map(|(_, profile)| profile);
+    let destinations = Destinations::new(&config, profile, &destinations)?;
+    let name = config.device(target.name)?;
+    let path = config.output_path(target.output_dir.as_deref(), &target.path);
+    let format = Format::for_path(&path, config.format(target.format)?);
+    let options = config
+        .resolution(target.resolution)?
+        .map(|dpi| (String::from("resolution"), dpi))
         .into_iter()
-        .find_map(|d| (d.name.as_bytes() == name.as_bytes()).then(|| d.open()))
-    {
-        Some(device) => device
-            .map_err(ScannrsError::from)
-            .into_diagnostic()
-            .with_context(|| format!("While trying to open a connection with scanner {}", name))?,
-        None => return Err(ScannrsError::CouldNotFindScanner { name }.into()),
+        .chain(options)
+        .collect::<HashMap<_, _>>();
+    let mut progress = ProgressBar::new();
+    let started = Instant::now();
+    let summary = scan_to_file(
+        backend,
+        &name,
+        &path,
+        format,
+        settings.as_deref(),
+        &options,
+        &mut |event| progress.update(event),
+    );
+    drop(progress);
+    let report = match &summary {
+        Ok(summary) => JobReport::saved("scan", None, summary, started.elapsed()),
+        Err(error) => JobReport::failed("scan", None, &name, started.elapsed(), error),
     };
-    let file = std::fs::OpenOptions::new()
+    webhook::notify(&config, &report);
+    let summary = summary?;
+    destinations.send(&SavedScan {
+        path: &summary.path,
+        device: &name,
+        tags: &summary.tags,
+        correspondent: summary.correspondent.as_deref(),
+    })?;
+
+    events::emit(&Event::Done {
+        pages: summary.pages,
+        outputs: vec![summary.path.clone()],
+    });
+
+    if output == OutputFormat::Json {
+        print_json(&summary)?;
+    }
+
+    if let Some(duration) = share {
+        share::share(&summary.path, summary.format, duration)?;
+    }
+
+    Ok(())
+}
+
+/// What was scanned and where it was saved
+#[derive(Serialize, Debug, Clone)]
+pub(crate) struct ScanSummary {
+    pub(crate) device: String,
+    pub(crate) path: PathBuf,
+    pub(crate) format: Format,
+    pub(crate) pages: usize,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) dpi: f32,
+    /// Given by the rules matching the text of the scan
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) correspondent: Option<String>,
+}
+
+/// Scan a single page with the given options and save it in the given format at `path`
+///
+/// Successful scans are recorded in the history, failing to do so only prints a warning. A scan matching the
+/// `[[rules]]` of the configuration is saved where they say instead, in the format of the extension there. Without
+/// rules to read the page, JPEG and TIFF are written while the page is scanned, see [`ScanJob::write_page`].
+pub(crate) fn scan_to_file(
+    backend: &dyn ScanBackend,
+    name: &str,
+    path: &Path,
+    format: Format,
+    settings: Option<&Path>,
+    options: &HashMap<String, Value>,
+    progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+) -> miette::Result<ScanSummary> {
+    // Fail before scanning if the file cannot be written
+    create_file(path)?;
+    let started = Instant::now();
+    if !rules::reads_page(&Config::load()?, path) {
+        let job = scan_job(name, settings, options)?.output(format, path);
+        let mut device = job.open(backend)?;
+        events::emit(&Event::DeviceOpened { device: name });
+        events::emit(&Event::ScanStarted {
+            device: name,
+            page: 1,
+        });
+        let size = job
+            .write_page(device.as_mut(), progress)?
+            .ok_or(ScannrsError::PageDropped)
+            .into_diagnostic()?;
+        let summary = record(
+            path,
+            format,
+            1,
+            Some(size),
+            ScanSource {
+                device: name,
+                settings,
+                options,
+                duration: started.elapsed(),
+            },
+        );
+        events::emit(&Event::PageSaved { page: 1, path });
+        return Ok(summary);
+    }
+
+    let page = scan_page(
+        backend,
+        name,
+        1,
+        settings,
+        options,
+        &mut FrameBuffers::default(),
+        progress,
+    )?;
+
+    let routing = rules::route(&Config::load()?, &page, name, path);
+    let (path, format) = match &routing.output {
+        Some(routed) => {
+            // Nothing was written to the file created above, so it only has to go
+            let _ = std::fs::remove_file(path);
+            if let Some(parent) = routed.parent() {
+                std::fs::create_dir_all(parent)
+                    .into_diagnostic()
+                    .with_context(|| {
+                        format!("Tried to create the directory {}", parent.display())
+                    })?;
+            }
+            events::status(tr!("rules-routed", path = routed.display().to_string()));
+            (
+                routed.as_path(),
+                Format::from_path(routed).unwrap_or(format),
+            )
+        }
+        None => (path, format),
+    };
+
+    let mut summary = save_page(
+        path,
+        format,
+        &page,
+        ScanSource {
+            device: name,
+            settings,
+            options,
+            duration: started.elapsed(),
+        },
+    )?;
+    summary.tags = routing.tags;
+    summary.correspondent = routing.correspondent;
+    events::emit(&Event::PageSaved { page: 1, path });
+
+    Ok(summary)
+}
+
+/// Where a page came from, as recorded in the history
+pub(crate) struct ScanSource<'a> {
+    pub(crate) device: &'a str,
+    pub(crate) settings: Option<&'a Path>,
+    pub(crate) options: &'a HashMap<String, Value>,
+    pub(crate) duration: Duration,
+}
+
+/// Save an already scanned page in the given format at `path`, recording it in the history
+pub(crate) fn save_page(
+    path: &Path,
+    format: Format,
+    page: &Page,
+    source: ScanSource<'_>,
+) -> miette::Result<ScanSummary> {
+    save_pages(path, format, std::slice::from_ref(page), source)
+}
+
+/// Like [`save_page`], but puts several pages into a single document
+///
+/// The size and resolution in the summary are the ones of the first page.
+pub(crate) fn save_pages(
+    path: &Path,
+    format: Format,
+    pages: &[Page],
+    source: ScanSource<'_>,
+) -> miette::Result<ScanSummary> {
+    let mut file = create_file(path)?;
+    write_document(&mut file, format, pages)?;
+
+    Ok(record(
+        path,
+        format,
+        pages.len(),
+        pages.first().map(PageSize::of),
+        source,
+    ))
+}
+
+/// Record a saved document of `pages` pages in the history, `first` is the size of its first page
+fn record(
+    path: &Path,
+    format: Format,
+    pages: usize,
+    first: Option<PageSize>,
+    source: ScanSource<'_>,
+) -> ScanSummary {
+    let entry = HistoryEntry {
+        id: 0,
+        finished_at: Utc::now(),
+        device: source.device.to_string(),
+        options: source
+            .options
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        settings: source
+            .settings
+            .map(|s| std::path::absolute(s).unwrap_or_else(|_| s.to_path_buf())),
+        outputs: vec![std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())],
+        format,
+        pages,
+        duration_ms: source.duration.as_millis() as u64,
+    };
+    if let Err(error) = History::open().and_then(|history| history.record(entry)) {
+        events::warning(format!(
+            "Could not record the scan in the history: {}",
+            error_chain(&error)
+        ));
+    }
+
+    ScanSummary {
+        device: source.device.to_string(),
+        path: path.to_path_buf(),
+        format,
+        pages,
+        width: first.map_or(0, |size| size.width),
+        height: first.map_or(0, |size| size.height),
+        dpi: first.map_or(0.0, |size| size.dpi),
+        tags: Vec::new(),
+        correspondent: None,
+    }
+}
+
+fn create_file(path: &Path) -> miette::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
         .write(true)
         .truncate(true)
         .create(true)
-        .open(&path)
+        .open(path)
         .into_diagnostic()
-        .with_context(|| format!("Tried to write to file at {}", path.display()))?;
-    let options = options.into_iter().collect::<HashMap<_, _>>();
-    for opt in device.get_options().into_diagnostic()? {
-        if let Some(val) = options.get(opt.name.as_bytes()) {
-            let val = match opt.type_ {
-                sane_scan::ValueType::Int => {
-                    DeviceOptionValue::Int(val.parse().into_diagnostic()?)
-                }
-                sane_scan::ValueType::String => DeviceOptionValue::String(
-                    CString::new(val.to_string()).into_diagnostic().with_context(|| {
-                        format!("The value given for '{}' contains a NUL (\\0) byte, which is invalid", opt.name.to_string_lossy())
-                    })?,
-                ),
-                _ => {
-                    continue;
-                }
-            };
-
-            device.set_option(&opt, val).into_diagnostic()?;
-        }
+        .with_context(|| format!("Tried to write to file at {}", path.display()))
+}
+
+/// The job for a scan with the given device, applying its stored calibration if there is one
+///
+/// The options from the configuration come first, then the settings file (as created by `options export`) and then
+/// the given options, each replacing the values of the ones before. The post-processors of the configuration
+/// are added by their name.
+pub(crate) fn scan_job(
+    name: &str,
+    settings: Option<&Path>,
+    options: &HashMap<String, Value>,
+) -> miette::Result<ScanJob> {
+    let config = Config::load()?;
+    let mut job = ScanJob::new(name).with_options(config.options_for(name)?);
+    if let Some(settings) = settings {
+        job = job.with_options(read_settings(settings)?);
     }
-    let params = device.start_scan().into_diagnostic()?;
-    let data = device.read_to_vec().into_diagnostic()?;
-    let buffer_size = data.len();
-    let img = match params.format {
-        sane_scan::Frame::Gray => DynamicImage::from(
-            image::GrayImage::from_raw(params.pixels_per_line as u32, params.lines as u32, data)
-                .ok_or(ScannrsError::InvalidImageSize {
-                    width: params.pixels_per_line as u32,
-                    height: params.lines as u32,
-                    buffer_size,
-                    pixel_size: params.depth as u32,
-                })
-                .into_diagnostic()?,
-        ),
-        sane_scan::Frame::Rgb => DynamicImage::from(
-            image::RgbImage::from_raw(params.pixels_per_line as u32, params.lines as u32, data)
-                .ok_or(ScannrsError::InvalidImageSize {
-                    width: params.pixels_per_line as u32,
-                    height: params.lines as u32,
-                    buffer_size,
-                    pixel_size: params.depth as u32,
-                })
-                .into_diagnostic()?,
-        ),
-        sane_scan::Frame::Red => todo!(),
-        sane_scan::Frame::Green => todo!(),
-        sane_scan::Frame::Blue => todo!(),
-    };
-    let mut jpeg_encoder = JpegEncoder::new(file);
-    jpeg_encoder.encode_image(&img).into_diagnostic()?;
-    Ok(())
+    job = job.with_options(options.clone());
+    if let Some(calibration) = calibration::load(name)? {
+        job = job.calibration(calibration);
+    }
+
+    let registry = Registry::with_builtins();
+    for name in &config.post_processors {
+        let processor = registry
+            .get(name)
+            .ok_or_else(|| ScannrsError::UnknownPostProcessor {
+                name: name.clone(),
+                available: registry.names().collect::<Vec<_>>().join(", "),
+            })
+            .into_diagnostic()?;
+        job = job.post_processor(processor);
+    }
+
+    Ok(job)
+}
+
+/// Scan and post-process a single page with the given options, see [`scan_job`]
+///
+/// The number of the page is only used for the events. Scans of several pages pass the same `buffers` for every page.
+pub(crate) fn scan_page(
+    backend: &dyn ScanBackend,
+    name: &str,
+    page: usize,
+    settings: Option<&Path>,
+    options: &HashMap<String, Value>,
+    buffers: &mut FrameBuffers,
+    progress: &mut dyn FnMut(ScanEvent) -> ControlFlow<()>,
+) -> miette::Result<Page> {
+    let job = scan_job(name, settings, options)?;
+    let mut device = job.open(backend)?;
+    events::emit(&Event::DeviceOpened { device: name });
+    events::emit(&Event::ScanStarted { device: name, page });
+    let page = job.read_page_with_buffers(device.as_mut(), buffers, progress)?;
+
+    job.process_with_progress(page, &mut |stage| {
+        let _ = progress(ScanEvent::Processing { stage });
+    })
+    .ok_or(ScannrsError::PageDropped)
+    .into_diagnostic()
+}
+
+/// Open the device and set the options, see [`scan_job`]
+pub(crate) fn prepare_device(
+    backend: &dyn ScanBackend,
+    name: &str,
+    settings: Option<&Path>,
+    options: &HashMap<String, Value>,
+) -> miette::Result<Box<dyn ScanDevice>> {
+    scan_job(name, settings, options)?.open(backend)
 }
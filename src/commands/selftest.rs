@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use miette::IntoDiagnostic;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::scan::apply_options;
+use scannrs_core::scan::read_image;
+use scannrs_core::value::Value;
+
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+
+/// The scan modes of the SANE `test` backend that are exercised, as `(description, options)`
+const CASES: &[(&str, &[(&str, &str)])] = &[
+    ("lineart", &[("mode", "Gray"), ("depth", "1")]),
+    ("gray 8-bit", &[("mode", "Gray"), ("depth", "8")]),
+    ("gray 16-bit", &[("mode", "Gray"), ("depth", "16")]),
+    ("color 8-bit", &[("mode", "Color"), ("depth", "8")]),
+    ("color 16-bit", &[("mode", "Color"), ("depth", "16")]),
+    (
+        "three-pass color",
+        &[("mode", "Color"), ("depth", "8"), ("three-pass", "true")],
+    ),
+    (
+        "hand-scanner",
+        &[("mode", "Gray"), ("depth", "8"), ("hand-scanner", "true")],
+    ),
+];
+
+/// Options shared by all cases, keeping the scans small and fast
+const COMMON_OPTIONS: &[(&str, &str)] = &[("resolution", "50"), ("test-picture", "Color pattern")];
+
+pub fn selftest(backend: &dyn ScanBackend, device: String) -> Result<(), miette::Error> {
+    let mut failed = 0;
+
+    for (description, options) in CASES {
+        let options = COMMON_OPTIONS
+            .iter()
+            .chain(options.iter())
+            .map(|(k, v)| (k.to_string(), Value::from(*v)))
+            .collect::<HashMap<_, _>>();
+
+        match run_case(backend, &device, &options) {
+            Ok(img) => println!("ok     {description} ({}x{})", img.width(), img.height()),
+            Err(error) => {
+                failed += 1;
+                println!("FAILED {description}: {}", error_chain(&error));
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(ScannrsError::SelfTestFailed {
+            failed,
+            total: CASES.len(),
+        }
+        .into());
+    }
+
+    println!("All {} self-tests passed", CASES.len());
+
+    Ok(())
+}
+
+fn run_case(
+    backend: &dyn ScanBackend,
+    device: &str,
+    options: &HashMap<String, Value>,
+) -> miette::Result<DynamicImage> {
+    let mut device = backend.open(device)?;
+    apply_options(device.as_mut(), options)?;
+    let img = read_image(device.as_mut())?;
+
+    if img.width() == 0 || img.height() == 0 {
+        return Err(ScannrsError::EmptyImage).into_diagnostic();
+    }
+
+    JpegEncoder::new(std::io::sink())
+        .encode_image(&img)
+        .into_diagnostic()?;
+
+    Ok(img)
+}
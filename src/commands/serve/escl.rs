@@ -0,0 +1,587 @@
+//! An eSCL (AirScan) scanner in front of a scanner of scannrs, for the scan clients built into macOS, Windows, iOS
+//! and Android, and sane-airscan on Linux
+//!
+//! `serve --escl` offers the default scanner of the configuration under `/eSCL`: `ScannerCapabilities` describes it
+//! from its options, `ScannerStatus` tells whether it is scanning, a POST to `ScanJobs` starts a scan with the region,
+//! resolution, color mode, source and format of its `ScanSettings`, and `ScanJobs/<id>/NextDocument` returns the page
+//! once it is scanned. Every job scans a single page. With the `mdns` feature the scanner is announced as
+//! `_uscan._tcp`, so that clients find it by themselves.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Instant;
+
+use axum::body::Body;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::delete;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Router;
+use miette::IntoDiagnostic;
+use scannrs_core::backend::Constraint;
+use scannrs_core::backend::OptionDescriptor;
+use scannrs_core::device::DeviceInfo;
+use scannrs_core::driver::Driver;
+use scannrs_core::output::write_document;
+use scannrs_core::output::Format;
+use scannrs_core::scan::CancellationToken;
+use scannrs_core::value::Value;
+use tokio::sync::oneshot;
+
+use super::ApiError;
+use crate::commands::scan::scan_job;
+use crate::error::ScannrsError;
+use crate::metrics;
+
+/// The namespaces of eSCL documents
+const XMLNS: &str = r#"xmlns:scan="http://schemas.hp.com/imaging/escl/2011/05/03" xmlns:pwg="http://www.pwg.org/schemas/2010/12/sm""#;
+
+/// The version of eSCL that is offered
+const VERSION: &str = "2.63";
+
+/// eSCL lengths are in 300ths of an inch
+const UNITS_PER_INCH: f64 = 300.0;
+
+/// Resolutions offered for scanners taking any resolution of a range
+const RESOLUTIONS: &[f64] = &[75.0, 100.0, 150.0, 200.0, 300.0, 600.0, 1200.0];
+
+/// The formats of the documents, by their media type
+const FORMATS: &[Format] = &[Format::Jpeg, Format::Png, Format::Pdf];
+
+/// The color modes of eSCL and the names of the `mode` option that stand for them
+const COLOR_MODES: &[(&str, &[&str])] = &[
+    ("RGB24", &["color", "colour", "24bit color"]),
+    ("Grayscale8", &["gray", "grey", "grayscale", "true gray"]),
+    (
+        "BlackAndWhite1",
+        &["lineart", "binary", "halftone", "black & white"],
+    ),
+];
+
+#[derive(Clone)]
+pub(super) struct Escl {
+    driver: Driver,
+    device: Arc<str>,
+    jobs: Arc<Mutex<HashMap<u64, EsclJob>>>,
+    next_job: Arc<AtomicU64>,
+    /// How many jobs are scanning right now
+    scanning: Arc<AtomicUsize>,
+}
+
+/// A job whose page has not been fetched yet
+struct EsclJob {
+    document: Option<oneshot::Receiver<miette::Result<(Vec<u8>, Format)>>>,
+    cancel: CancellationToken,
+}
+
+impl Escl {
+    pub(super) fn new(driver: Driver, device: String) -> Self {
+        Escl {
+            driver,
+            device: device.into(),
+            jobs: Arc::default(),
+            next_job: Arc::new(AtomicU64::new(1)),
+            scanning: Arc::default(),
+        }
+    }
+
+    pub(super) fn device(&self) -> &str {
+        &self.device
+    }
+
+    fn jobs(&self) -> MutexGuard<'_, HashMap<u64, EsclJob>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn options(&self) -> miette::Result<Vec<OptionDescriptor>> {
+        let name = self.device.to_string();
+        self.driver
+            .run(move |backend| backend.open(&name)?.options())
+            .await?
+    }
+}
+
+pub(super) fn router(escl: Escl) -> Router {
+    Router::new()
+        .route("/eSCL/ScannerCapabilities", get(capabilities))
+        .route("/eSCL/ScannerStatus", get(status))
+        .route("/eSCL/ScanJobs", post(create_job))
+        .route("/eSCL/ScanJobs/:id", delete(cancel_job))
+        .route("/eSCL/ScanJobs/:id/NextDocument", get(next_document))
+        .with_state(escl)
+}
+
+fn xml(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/xml")], body).into_response()
+}
+
+async fn capabilities(State(escl): State<Escl>) -> Result<Response, ApiError> {
+    let name = escl.device.to_string();
+    let info = escl
+        .driver
+        .run(move |backend| {
+            Ok::<_, miette::Report>(
+                backend
+                    .devices()?
+                    .into_iter()
+                    .find(|device| device.name == name),
+            )
+        })
+        .await??;
+    let options = escl.options().await?;
+
+    Ok(xml(capabilities_xml(&escl.device, info.as_ref(), &options)))
+}
+
+fn capabilities_xml(name: &str, info: Option<&DeviceInfo>, options: &[OptionDescriptor]) -> String {
+    let model = info.map_or_else(
+        || name.to_string(),
+        |info| format!("{} {}", info.vendor, info.model),
+    );
+
+    let modes = color_modes(options)
+        .into_iter()
+        .map(|(mode, _)| format!("<scan:ColorMode>{mode}</scan:ColorMode>"))
+        .collect::<String>();
+    let formats = FORMATS
+        .iter()
+        .map(|format| {
+            format!(
+                "<pwg:DocumentFormat>{0}</pwg:DocumentFormat><scan:DocumentFormatExt>{0}</scan:DocumentFormatExt>",
+                format.mime_type()
+            )
+        })
+        .collect::<String>();
+    let resolutions = resolutions(options)
+        .into_iter()
+        .map(|dpi| {
+            format!(
+                "<scan:DiscreteResolution><scan:XResolution>{dpi}</scan:XResolution>\
+                 <scan:YResolution>{dpi}</scan:YResolution></scan:DiscreteResolution>"
+            )
+        })
+        .collect::<String>();
+    let units = |option| max_mm(options, option).map_or(2550.0, |mm| mm / 25.4 * UNITS_PER_INCH);
+    let caps = format!(
+        "<scan:MinWidth>1</scan:MinWidth><scan:MaxWidth>{:.0}</scan:MaxWidth>\
+         <scan:MinHeight>1</scan:MinHeight><scan:MaxHeight>{:.0}</scan:MaxHeight>\
+         <scan:SettingProfiles><scan:SettingProfile><scan:ColorModes>{modes}</scan:ColorModes>\
+         <scan:DocumentFormats>{formats}</scan:DocumentFormats><scan:SupportedResolutions>\
+         <scan:DiscreteResolutions>{resolutions}</scan:DiscreteResolutions></scan:SupportedResolutions>\
+         </scan:SettingProfile></scan:SettingProfiles>",
+        units("br-x"),
+        units("br-y"),
+    );
+
+    let (platen, feeder) = sources(options);
+    let platen = platen
+        .map(|_| {
+            format!(
+                "<scan:Platen><scan:PlatenInputCaps>{caps}</scan:PlatenInputCaps></scan:Platen>"
+            )
+        })
+        .unwrap_or_default();
+    let feeder = feeder
+        .map(|_| {
+            format!(
+                "<scan:Adf><scan:AdfSimplexInputCaps>{caps}</scan:AdfSimplexInputCaps></scan:Adf>"
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<scan:ScannerCapabilities {XMLNS}>
+  <pwg:Version>{VERSION}</pwg:Version>
+  <pwg:MakeAndModel>{}</pwg:MakeAndModel>
+  <scan:UUID>{}</scan:UUID>
+  {platen}
+  {feeder}
+</scan:ScannerCapabilities>
+"#,
+        escape(&model),
+        uuid(name),
+    )
+}
+
+async fn status(State(escl): State<Escl>) -> Response {
+    let state = if escl.scanning.load(Ordering::Relaxed) > 0 {
+        "Processing"
+    } else {
+        "Idle"
+    };
+
+    xml(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<scan:ScannerStatus {XMLNS}>
+  <pwg:Version>{VERSION}</pwg:Version>
+  <pwg:State>{state}</pwg:State>
+</scan:ScannerStatus>
+"#
+    ))
+}
+
+async fn create_job(
+    State(escl): State<Escl>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Response, ApiError> {
+    let settings = ScanSettings::parse(&body)
+        .map_err(|message| ApiError::BadRequest(format!("Invalid ScanSettings: {message}")))?;
+    let options = settings.options(&escl.options().await?);
+    let job = scan_job(&escl.device, None, &options)?;
+
+    let id = escl.next_job.fetch_add(1, Ordering::Relaxed);
+    let (scan, cancel) = escl.driver.scan(job);
+    let (sender, document) = oneshot::channel();
+    escl.jobs().insert(
+        id,
+        EsclJob {
+            document: Some(document),
+            cancel,
+        },
+    );
+
+    escl.scanning.fetch_add(1, Ordering::Relaxed);
+    let scanning = escl.scanning.clone();
+    let device = escl.device.to_string();
+    let format = settings.format;
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let res = scan.await.and_then(|page| {
+            let page = page.ok_or(ScannrsError::PageDropped).into_diagnostic()?;
+            let mut data = Vec::new();
+            write_document(&mut data, format, std::slice::from_ref(&page))?;
+            Ok(data)
+        });
+        match &res {
+            Ok(data) => metrics::scan_finished(&device, 1, data.len() as u64, started.elapsed()),
+            Err(error) => metrics::scan_failed(error),
+        }
+        scanning.fetch_sub(1, Ordering::Relaxed);
+
+        let _ = sender.send(res.map(|data| (data, format)));
+    });
+
+    // Clients follow the location to fetch the page, some only take absolute URLs
+    let path = format!("/eSCL/ScanJobs/{id}");
+    let location = match headers
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+    {
+        Some(host) => format!("http://{host}{path}"),
+        None => path,
+    };
+
+    Ok((StatusCode::CREATED, [(header::LOCATION, location)]).into_response())
+}
+
+/// The page of the job, waiting for it to be scanned, and `404` once it was fetched
+async fn next_document(
+    State(escl): State<Escl>,
+    Path(id): Path<u64>,
+) -> Result<Response, ApiError> {
+    let document = {
+        let mut jobs = escl.jobs();
+        let job = jobs.get_mut(&id).ok_or(ApiError::JobNotFound(id))?;
+        match job.document.take() {
+            Some(document) => document,
+            None => {
+                jobs.remove(&id);
+                return Err(ApiError::JobNotFound(id));
+            }
+        }
+    };
+
+    // The scan always sends its result, unless it panicked
+    match document.await.map_err(|_| ApiError::JobNotFound(id))? {
+        Ok((data, format)) => Ok((
+            [(header::CONTENT_TYPE, format.mime_type())],
+            Body::from(data),
+        )
+            .into_response()),
+        // A cancelled job has no more pages, which clients expect to be told with a 404
+        Err(error)
+            if matches!(
+                error.downcast_ref::<scannrs_core::Error>(),
+                Some(scannrs_core::Error::ScanCancelled)
+            ) =>
+        {
+            Err(ApiError::JobNotFound(id))
+        }
+        Err(error) => Err(ApiError::Internal(error)),
+    }
+}
+
+async fn cancel_job(State(escl): State<Escl>, Path(id): Path<u64>) -> Result<StatusCode, ApiError> {
+    let job = escl.jobs().remove(&id).ok_or(ApiError::JobNotFound(id))?;
+    job.cancel.cancel();
+
+    Ok(StatusCode::OK)
+}
+
+/// What a client asked to scan
+#[derive(Debug)]
+struct ScanSettings {
+    resolution: Option<f64>,
+    color_mode: Option<String>,
+    source: Option<String>,
+    format: Format,
+    /// The x and y offsets, width and height in 300ths of an inch
+    region: Option<[f64; 4]>,
+}
+
+impl ScanSettings {
+    fn parse(xml: &str) -> Result<ScanSettings, String> {
+        let document = roxmltree::Document::parse(xml).map_err(|error| error.to_string())?;
+        let text = |name: &str| {
+            document
+                .descendants()
+                .find(|node| node.tag_name().name() == name)
+                .and_then(|node| node.text())
+                .map(str::trim)
+        };
+        let number = |name: &str| text(name).and_then(|text| text.parse::<f64>().ok());
+
+        // Newer clients put the format they want into `DocumentFormatExt`
+        let format = text("DocumentFormatExt")
+            .or_else(|| text("DocumentFormat"))
+            .and_then(|mime| FORMATS.iter().find(|format| format.mime_type() == mime))
+            .copied()
+            .unwrap_or(Format::Jpeg);
+        let region = match (
+            number("XOffset"),
+            number("YOffset"),
+            number("Width"),
+            number("Height"),
+        ) {
+            (x, y, Some(width), Some(height)) => {
+                Some([x.unwrap_or(0.0), y.unwrap_or(0.0), width, height])
+            }
+            _ => None,
+        };
+
+        Ok(ScanSettings {
+            resolution: number("XResolution"),
+            color_mode: text("ColorMode").map(str::to_string),
+            source: text("InputSource").map(str::to_string),
+            format,
+            region,
+        })
+    }
+
+    /// The options of the scanner for the settings, leaving out the ones it does not have
+    fn options(&self, descriptors: &[OptionDescriptor]) -> HashMap<String, Value> {
+        let has = |name: &str| descriptors.iter().any(|option| option.name == name);
+        let mut options = HashMap::new();
+
+        if let Some(dpi) = self.resolution.filter(|_| has("resolution")) {
+            options.insert(
+                String::from("resolution"),
+                Value::from(format!("{dpi}dpi").as_str()),
+            );
+        }
+        // Scanners without a `mode` or `source` option only have one, which has no name to set
+        if let Some(mode) = &self.color_mode {
+            if let Some((_, name)) = color_modes(descriptors)
+                .into_iter()
+                .find(|(escl, name)| escl == mode && !name.is_empty())
+            {
+                options.insert(String::from("mode"), Value::from(name.as_str()));
+            }
+        }
+        let (platen, feeder) = sources(descriptors);
+        let source = match self.source.as_deref() {
+            Some("Feeder") => feeder,
+            Some("Platen") => platen,
+            _ => None,
+        };
+        if let Some(source) = source.filter(|source| !source.is_empty()) {
+            options.insert(String::from("source"), Value::from(source.as_str()));
+        }
+        if let Some([x, y, width, height]) = self.region {
+            let mm = |units: f64| units / UNITS_PER_INCH * 25.4;
+            for (name, value) in [
+                ("tl-x", mm(x)),
+                ("tl-y", mm(y)),
+                ("br-x", mm(x + width)),
+                ("br-y", mm(y + height)),
+            ] {
+                if has(name) {
+                    options.insert(
+                        name.to_string(),
+                        Value::from(format!("{value:.2}mm").as_str()),
+                    );
+                }
+            }
+        }
+
+        options
+    }
+}
+
+fn strings<'a>(options: &'a [OptionDescriptor], name: &str) -> &'a [String] {
+    match options.iter().find(|option| option.name == name) {
+        Some(OptionDescriptor {
+            constraint: Constraint::Strings(strings),
+            ..
+        }) => strings,
+        _ => &[],
+    }
+}
+
+/// The eSCL color modes the scanner has, with the value of its `mode` option for them
+fn color_modes(options: &[OptionDescriptor]) -> Vec<(&'static str, String)> {
+    let modes = strings(options, "mode");
+    if modes.is_empty() {
+        return vec![("RGB24", String::new())];
+    }
+
+    COLOR_MODES
+        .iter()
+        .filter_map(|(escl, names)| {
+            let mode = modes
+                .iter()
+                .find(|mode| names.contains(&mode.to_lowercase().as_str()))?;
+            Some((*escl, mode.clone()))
+        })
+        .collect()
+}
+
+/// The values of the `source` option for the flatbed and the document feeder, if the scanner has them
+///
+/// Scanners without a `source` option are taken to be flatbeds.
+fn sources(options: &[OptionDescriptor]) -> (Option<String>, Option<String>) {
+    let sources = strings(options, "source");
+    if sources.is_empty() {
+        return (Some(String::new()), None);
+    }
+
+    let find = |words: &[&str]| {
+        sources
+            .iter()
+            .find(|source| {
+                let source = source.to_lowercase();
+                words.iter().any(|word| source.contains(word))
+            })
+            .cloned()
+    };
+    (find(&["flatbed", "platen"]), find(&["adf", "feeder"]))
+}
+
+fn resolutions(options: &[OptionDescriptor]) -> Vec<f64> {
+    match options.iter().find(|option| option.name == "resolution") {
+        Some(OptionDescriptor {
+            constraint: Constraint::Numbers(numbers),
+            ..
+        }) => numbers.clone(),
+        Some(OptionDescriptor {
+            constraint: Constraint::Range { min, max, .. },
+            ..
+        }) => RESOLUTIONS
+            .iter()
+            .copied()
+            .filter(|dpi| (*min..=*max).contains(dpi))
+            .collect(),
+        _ => vec![300.0],
+    }
+}
+
+fn max_mm(options: &[OptionDescriptor], name: &str) -> Option<f64> {
+    match options
+        .iter()
+        .find(|option| option.name == name)?
+        .constraint
+    {
+        Constraint::Range { max, .. } => Some(max),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A UUID that stays the same for the scanner, which clients recognize it by
+fn uuid(name: &str) -> String {
+    let hash = |seed: u8| {
+        let mut hasher = DefaultHasher::new();
+        (seed, name).hash(&mut hasher);
+        hasher.finish()
+    };
+    let (high, low) = (hash(0), hash(1));
+
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xffff,
+        low >> 48,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+/// Keeps the scanner announced until it is dropped
+pub(super) struct Announcement {
+    #[cfg(feature = "mdns")]
+    _daemon: mdns_sd::ServiceDaemon,
+}
+
+/// Announce the scanner as `_uscan._tcp` on the port
+#[cfg(feature = "mdns")]
+pub(super) fn announce(name: &str, port: u16) -> miette::Result<Announcement> {
+    let daemon = mdns_sd::ServiceDaemon::new().into_diagnostic()?;
+    let formats = FORMATS
+        .iter()
+        .map(|format| format.mime_type())
+        .collect::<Vec<_>>()
+        .join(",");
+    let uuid = uuid(name);
+    let properties = [
+        ("txtvers", "1"),
+        ("vers", VERSION),
+        ("rs", "eSCL"),
+        ("ty", name),
+        ("pdl", formats.as_str()),
+        ("cs", "color,grayscale,binary"),
+        ("is", "platen,adf"),
+        ("uuid", uuid.as_str()),
+    ];
+    let instance = format!("scannrs {name}");
+    let host = format!("scannrs-{port}.local.");
+    let service = mdns_sd::ServiceInfo::new(
+        "_uscan._tcp.local.",
+        &instance,
+        &host,
+        "",
+        port,
+        &properties[..],
+    )
+    .into_diagnostic()?
+    .enable_addr_auto();
+    daemon.register(service).into_diagnostic()?;
+
+    Ok(Announcement { _daemon: daemon })
+}
+
+#[cfg(not(feature = "mdns"))]
+pub(super) fn announce(_name: &str, _port: u16) -> miette::Result<Announcement> {
+    Ok(Announcement {})
+}
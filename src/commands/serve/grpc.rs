@@ -0,0 +1,187 @@
+//! The gRPC API of `serve --grpc`, defined in `proto/scannrs.proto`
+//!
+//! It shares the jobs with the HTTP API, so a scan started over one can be followed and fetched over the other.
+//! `WatchJob` streams the status of a job whenever it changes, until the job is finished.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use proto::job_status;
+use proto::scanner_server::Scanner;
+use proto::scanner_server::ScannerServer;
+use scannrs_core::value::Value;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+use super::start_job;
+use super::JobInfo;
+use super::JobStatus;
+use super::ServeState;
+use crate::error::error_chain;
+
+mod proto {
+    tonic::include_proto!("scannrs.v1");
+}
+
+pub(super) async fn run(listen: SocketAddr, state: ServeState) -> miette::Result<()> {
+    eprintln!("Serving gRPC on {listen}");
+
+    tonic::transport::Server::builder()
+        .add_service(ScannerServer::new(GrpcScanner { state }))
+        .serve_with_shutdown(listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .into_diagnostic()
+        .with_context(|| format!("While trying to serve gRPC on {listen}"))
+}
+
+struct GrpcScanner {
+    state: ServeState,
+}
+
+fn internal(error: miette::Report) -> Status {
+    Status::internal(error_chain(&error))
+}
+
+fn not_found(job: u64) -> Status {
+    Status::not_found(format!("No job with id {job}"))
+}
+
+impl From<JobInfo> for proto::JobStatus {
+    fn from(info: JobInfo) -> Self {
+        let status = match info.status {
+            JobStatus::Queued => job_status::Status::Queued(proto::Queued {}),
+            JobStatus::Scanning { progress } => {
+                job_status::Status::Scanning(proto::Scanning { progress })
+            }
+            JobStatus::Processing { stage } => job_status::Status::Processing(proto::Processing {
+                stage: stage.to_string(),
+            }),
+            JobStatus::Done => job_status::Status::Done(proto::Done {}),
+            JobStatus::Cancelled => job_status::Status::Cancelled(proto::Cancelled {}),
+            JobStatus::Failed { error } => job_status::Status::Failed(proto::Failed { error }),
+        };
+
+        proto::JobStatus {
+            job: info.job,
+            device: info.device,
+            status: Some(status),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Scanner for GrpcScanner {
+    async fn list_devices(
+        &self,
+        _request: Request<proto::ListDevicesRequest>,
+    ) -> Result<Response<proto::ListDevicesResponse>, Status> {
+        let devices = self
+            .state
+            .driver
+            .run(|backend| backend.devices())
+            .await
+            .and_then(|devices| devices)
+            .map_err(internal)?;
+
+        Ok(Response::new(proto::ListDevicesResponse {
+            devices: devices
+                .into_iter()
+                .map(|device| proto::Device {
+                    name: device.name,
+                    vendor: device.vendor,
+                    model: device.model,
+                    r#type: device.type_,
+                })
+                .collect(),
+        }))
+    }
+
+    async fn start_scan(
+        &self,
+        request: Request<proto::StartScanRequest>,
+    ) -> Result<Response<proto::StartScanResponse>, Status> {
+        let request = request.into_inner();
+        let options = request
+            .options
+            .iter()
+            .map(|(name, value)| (name.clone(), Value::from(value.as_str())))
+            .collect::<HashMap<_, _>>();
+        let job = start_job(&self.state, request.device, &options).map_err(internal)?;
+
+        Ok(Response::new(proto::StartScanResponse { job }))
+    }
+
+    type WatchJobStream = ReceiverStream<Result<proto::JobStatus, Status>>;
+
+    async fn watch_job(
+        &self,
+        request: Request<proto::JobRequest>,
+    ) -> Result<Response<Self::WatchJobStream>, Status> {
+        let id = request.into_inner().job;
+        let jobs = self.state.jobs.clone();
+        // Subscribed before the first look at the job, so that no change is missed in between
+        let mut changed = jobs.changed.subscribe();
+        let mut info = jobs.info(id).ok_or_else(|| not_found(id))?;
+
+        let (sender, receiver) = mpsc::channel(16);
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                let finished = info.status.is_finished();
+                let status = proto::JobStatus::from(info);
+                if last.as_ref() != Some(&status) {
+                    if sender.send(Ok(status.clone())).await.is_err() {
+                        break;
+                    }
+                    last = Some(status);
+                }
+                if finished || changed.changed().await.is_err() {
+                    break;
+                }
+                match jobs.info(id) {
+                    Some(next) => info = next,
+                    None => break,
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn get_result(
+        &self,
+        request: Request<proto::JobRequest>,
+    ) -> Result<Response<proto::ScanResult>, Status> {
+        let id = request.into_inner().job;
+        let data = self
+            .state
+            .jobs
+            .take_result(id)
+            .ok_or_else(|| not_found(id))?
+            .ok_or_else(|| Status::failed_precondition(format!("Job {id} has not finished")))?;
+
+        Ok(Response::new(proto::ScanResult {
+            media_type: String::from("image/jpeg"),
+            data,
+        }))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<proto::JobRequest>,
+    ) -> Result<Response<proto::CancelJobResponse>, Status> {
+        let id = request.into_inner().job;
+        let jobs = self.state.jobs.lock();
+        let job = jobs.get(&id).ok_or_else(|| not_found(id))?;
+        job.cancel.cancel();
+
+        Ok(Response::new(proto::CancelJobResponse {}))
+    }
+}
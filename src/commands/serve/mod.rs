@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::MutexGuard;
+use std::time::Duration;
+use std::time::Instant;
+
+use axum::body::Body;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::header;
+use axum::http::StatusCode;
+use axum::response::Html;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use escl::Escl;
+use image::codecs::jpeg::JpegEncoder;
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::device::DeviceInfo;
+use scannrs_core::device::OptionInfo;
+use scannrs_core::driver::driver;
+use scannrs_core::driver::Driver;
+use scannrs_core::output::Page;
+use scannrs_core::progress::ScanEvent;
+use scannrs_core::scan::CancellationToken;
+use scannrs_core::value::Value;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::watch;
+
+use crate::commands::scan::scan_job;
+use crate::config::Config;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::metrics;
+#[cfg(unix)]
+use crate::systemd;
+
+mod escl;
+#[cfg(feature = "grpc")]
+mod grpc;
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum JobStatus {
+    Queued,
+    Scanning {
+        /// How much of the current frame was read, from 0 to 1, if the scanner knows its size
+        #[serde(skip_serializing_if = "Option::is_none")]
+        progress: Option<f64>,
+    },
+    /// The page was read and is being processed
+    Processing {
+        stage: &'static str,
+    },
+    Done,
+    Cancelled,
+    Failed {
+        error: String,
+    },
+}
+
+impl JobStatus {
+    fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Done | JobStatus::Cancelled | JobStatus::Failed { .. }
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct JobInfo {
+    job: u64,
+    device: String,
+    #[serde(flatten)]
+    status: JobStatus,
+}
+
+/// How long the result of a finished job is kept for its client to fetch it
+const RESULT_TTL: Duration = Duration::from_secs(10 * 60);
+/// How many finished jobs are kept at most, the oldest are forgotten first
+const FINISHED_JOBS: usize = 32;
+
+struct Job {
+    device: String,
+    status: JobStatus,
+    result: Option<Vec<u8>>,
+    cancel: CancellationToken,
+    finished: Option<Instant>,
+}
+
+#[derive(Clone)]
+struct Jobs {
+    jobs: Arc<Mutex<HashMap<u64, Job>>>,
+    /// Sent to whenever the status of a job changes, for the gRPC streams following them
+    changed: Arc<watch::Sender<()>>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        Jobs {
+            jobs: Arc::default(),
+            changed: Arc::new(watch::Sender::new(())),
+        }
+    }
+}
+
+impl Jobs {
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, Job>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn info(&self, id: u64) -> Option<JobInfo> {
+        self.lock().get(&id).map(|job| JobInfo {
+            job: id,
+            device: job.device.clone(),
+            status: job.status.clone(),
+        })
+    }
+
+    fn set_status(&self, job: u64, status: JobStatus) {
+        if let Some(job) = self.lock().get_mut(&job) {
+            job.status = status;
+        }
+        self.changed.send_replace(());
+    }
+
+    /// Hand out the result of a finished job, which is forgotten afterwards
+    ///
+    /// Returns `None` for unknown jobs, and no result for jobs that have not finished yet.
+    fn take_result(&self, id: u64) -> Option<Option<Vec<u8>>> {
+        let mut jobs = self.lock();
+        let result = jobs.get_mut(&id)?.result.take();
+        if result.is_some() {
+            jobs.remove(&id);
+        }
+        Some(result)
+    }
+}
+
+/// Forget finished jobs whose result was not fetched in time, and the oldest past [`FINISHED_JOBS`]
+fn prune(jobs: &mut HashMap<u64, Job>) {
+    jobs.retain(|_, job| !job.finished.is_some_and(|at| at.elapsed() >= RESULT_TTL));
+
+    let mut finished = jobs
+        .iter()
+        .filter_map(|(id, job)| Some((job.finished?, *id)))
+        .collect::<Vec<_>>();
+    if let Some(excess) = finished.len().checked_sub(FINISHED_JOBS) {
+        finished.sort_unstable();
+        for (_, id) in &finished[..excess] {
+            jobs.remove(id);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ServeState {
+    driver: Driver,
+    jobs: Jobs,
+    next_job: Arc<AtomicU64>,
+}
+
+/// Serve the HTTP API, and with `escl` also act as an eSCL scanner for the default scanner of the configuration
+///
+/// With `grpc` the gRPC API is served on that address as well.
+pub fn serve(
+    backend: &dyn ScanBackend,
+    listen: SocketAddr,
+    escl: bool,
+    grpc: Option<SocketAddr>,
+) -> miette::Result<()> {
+    if grpc.is_some() && !cfg!(feature = "grpc") {
+        return Err(ScannrsError::GrpcNotBuilt.into());
+    }
+
+    let (driver, driver_loop) = driver();
+    let escl = match escl {
+        true => Some(Escl::new(driver.clone(), Config::load()?.device(None)?)),
+        false => None,
+    };
+    let state = ServeState {
+        driver,
+        jobs: Jobs::default(),
+        next_job: Arc::new(AtomicU64::new(1)),
+    };
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .into_diagnostic()?;
+
+    let server_thread =
+        std::thread::spawn(move || runtime.block_on(run_server(listen, state, escl, grpc)));
+
+    driver_loop.run(backend);
+
+    match server_thread.join() {
+        Ok(res) => res?,
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+
+    Ok(())
+}
+
+async fn run_server(
+    listen: SocketAddr,
+    state: ServeState,
+    escl: Option<Escl>,
+    grpc: Option<SocketAddr>,
+) -> miette::Result<()> {
+    #[cfg(unix)]
+    let activated = systemd::activated_socket::<std::net::TcpListener>();
+    #[cfg(not(unix))]
+    let activated = None;
+    let listener = match activated {
+        Some(listener) => {
+            listener.set_nonblocking(true).into_diagnostic()?;
+            eprintln!("Listening on the socket passed by systemd");
+            tokio::net::TcpListener::from_std(listener).into_diagnostic()?
+        }
+        None => {
+            let listener = tokio::net::TcpListener::bind(listen)
+                .await
+                .into_diagnostic()
+                .with_context(|| format!("While trying to listen on {listen}"))?;
+            eprintln!("Listening on http://{listen}");
+            listener
+        }
+    };
+    let _announcement = match &escl {
+        Some(escl) => Some(escl::announce(
+            escl.device(),
+            listener.local_addr().into_diagnostic()?.port(),
+        )?),
+        None => None,
+    };
+    #[cfg(unix)]
+    {
+        systemd::ready();
+        tokio::spawn(systemd::watch(state.driver.clone()));
+    }
+
+    #[cfg(feature = "grpc")]
+    let grpc = grpc.map(|listen| tokio::spawn(grpc::run(listen, state.clone())));
+    #[cfg(not(feature = "grpc"))]
+    let _ = grpc;
+
+    let mut router = router(state);
+    if let Some(escl) = escl {
+        router = router.merge(escl::router(escl));
+    }
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .into_diagnostic()?;
+
+    #[cfg(feature = "grpc")]
+    if let Some(grpc) = grpc {
+        grpc.await.into_diagnostic()??;
+    }
+
+    Ok(())
+}
+
+fn router(state: ServeState) -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/devices", get(list_devices))
+        .route("/devices/:name/options", get(list_options))
+        .route("/devices/:name/scan", post(start_scan))
+        .route("/jobs/:id", get(job_status).delete(cancel_job))
+        .route("/jobs/:id/result", get(job_result))
+        .route("/metrics", get(export_metrics))
+        .with_state(state)
+}
+
+fn encode_jpeg(page: Page) -> miette::Result<Vec<u8>> {
+    let mut data = vec![];
+    JpegEncoder::new(&mut data)
+        .encode_image(&page.image)
+        .into_diagnostic()?;
+
+    Ok(data)
+}
+
+enum ApiError {
+    BadRequest(String),
+    JobNotFound(u64),
+    JobNotFinished(u64),
+    Internal(miette::Report),
+}
+
+impl From<miette::Report> for ApiError {
+    fn from(error: miette::Report) -> Self {
+        ApiError::Internal(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::JobNotFound(id) => (StatusCode::NOT_FOUND, format!("No job with id {id}")),
+            ApiError::JobNotFinished(id) => {
+                (StatusCode::CONFLICT, format!("Job {id} has not finished"))
+            }
+            ApiError::Internal(error) => (StatusCode::INTERNAL_SERVER_ERROR, error_chain(&error)),
+        };
+
+        (status, Json(serde_json::json!({ "error": error }))).into_response()
+    }
+}
+
+/// The single-page web interface
+const INDEX_HTML: &str = include_str!("index.html");
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+async fn list_devices(State(state): State<ServeState>) -> Result<Json<Vec<DeviceInfo>>, ApiError> {
+    let devices = state.driver.run(|backend| backend.devices()).await??;
+
+    Ok(Json(devices))
+}
+
+async fn list_options(
+    State(state): State<ServeState>,
+    Path(name): Path<String>,
+) -> Result<Json<Vec<OptionInfo>>, ApiError> {
+    let options = state
+        .driver
+        .run(move |backend| {
+            let device = backend.open(&name)?;
+            Ok::<_, miette::Report>(device.options()?.iter().map(OptionInfo::from).collect())
+        })
+        .await??;
+
+    Ok(Json(options))
+}
+
+#[derive(Deserialize, Default)]
+struct ScanRequest {
+    /// Options to set before scanning, as `name: value`
+    #[serde(default)]
+    options: HashMap<String, Value>,
+}
+
+async fn start_scan(
+    State(state): State<ServeState>,
+    Path(name): Path<String>,
+    request: Option<Json<ScanRequest>>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(request) = request.unwrap_or_default();
+    let job = start_job(&state, name, &request.options)?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "job": job })),
+    ))
+}
+
+/// Start scanning with the device in the background, returning the id of the job
+fn start_job(
+    state: &ServeState,
+    name: String,
+    options: &HashMap<String, Value>,
+) -> miette::Result<u64> {
+    let scan_job = scan_job(&name, None, options)?;
+
+    let job = state.next_job.fetch_add(1, Ordering::Relaxed);
+    let jobs = state.jobs.clone();
+    let (scan, cancel) = state.driver.scan_with_progress(scan_job, move |event| {
+        let status = match event {
+            ScanEvent::Processing { stage } => JobStatus::Processing {
+                stage: stage.name(),
+            },
+            event => JobStatus::Scanning {
+                progress: event.fraction(),
+            },
+        };
+        jobs.set_status(job, status);
+        #[cfg(unix)]
+        systemd::alive();
+    });
+
+    prune(&mut state.jobs.lock());
+    state.jobs.lock().insert(
+        job,
+        Job {
+            device: name.clone(),
+            status: JobStatus::Queued,
+            result: None,
+            cancel,
+            finished: None,
+        },
+    );
+
+    let jobs = state.jobs.clone();
+    let started = Instant::now();
+    tokio::spawn(async move {
+        // Pages are not post-processed here, so none are dropped as blank
+        let res = scan
+            .await
+            .and_then(|page| page.map(encode_jpeg).transpose());
+        match &res {
+            Ok(data) => metrics::scan_finished(
+                &name,
+                usize::from(data.is_some()),
+                data.as_ref().map_or(0, |data| data.len() as u64),
+                started.elapsed(),
+            ),
+            Err(error) => metrics::scan_failed(error),
+        }
+
+        if let Some(job) = jobs.lock().get_mut(&job) {
+            job.finished = Some(Instant::now());
+            match res {
+                Ok(data) => {
+                    job.status = JobStatus::Done;
+                    job.result = data;
+                }
+                Err(_) if job.cancel.is_cancelled() => job.status = JobStatus::Cancelled,
+                Err(error) => {
+                    job.status = JobStatus::Failed {
+                        error: error_chain(&error),
+                    }
+                }
+            }
+        }
+        prune(&mut jobs.lock());
+        jobs.changed.send_replace(());
+    });
+
+    Ok(job)
+}
+
+async fn job_status(
+    State(state): State<ServeState>,
+    Path(id): Path<u64>,
+) -> Result<Json<JobInfo>, ApiError> {
+    let info = state.jobs.info(id).ok_or(ApiError::JobNotFound(id))?;
+
+    Ok(Json(info))
+}
+
+async fn job_result(
+    State(state): State<ServeState>,
+    Path(id): Path<u64>,
+) -> Result<impl IntoResponse, ApiError> {
+    let data = state
+        .jobs
+        .take_result(id)
+        .ok_or(ApiError::JobNotFound(id))?
+        .ok_or(ApiError::JobNotFinished(id))?;
+
+    Ok(([(header::CONTENT_TYPE, "image/jpeg")], Body::from(data)))
+}
+
+async fn export_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+        metrics::render(),
+    )
+}
+
+/// Stop a queued or running scan, finished jobs are left as they are
+async fn cancel_job(
+    State(state): State<ServeState>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let jobs = state.jobs.lock();
+    let job = jobs.get(&id).ok_or(ApiError::JobNotFound(id))?;
+    job.cancel.cancel();
+
+    Ok(StatusCode::ACCEPTED)
+}
@@ -0,0 +1,40 @@
+//! Copying text to the system clipboard through the terminal
+//!
+//! The OSC 52 escape sequence asks the terminal to put the text into the clipboard, which also works over SSH and
+//! without a display server. Terminals that do not support it ignore it.
+
+use std::io::stdout;
+use std::io::Write;
+
+use miette::IntoDiagnostic;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let triple = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        for idx in 0..4 {
+            if idx <= chunk.len() {
+                let sextet = (triple >> (18 - 6 * idx)) & 0x3f;
+                encoded.push(char::from(BASE64[sextet as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+pub(crate) fn copy(text: &str) -> miette::Result<()> {
+    let mut stdout = stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes())).into_diagnostic()?;
+    stdout.flush().into_diagnostic()
+}
@@ -0,0 +1,132 @@
+//! Keeping the values entered in the options editor within the constraints of the option
+
+use scannrs_core::device::ConstraintInfo;
+use scannrs_core::device::OptionInfo;
+use scannrs_core::device::ValueInfo;
+
+/// The amount of steps a range without quantization is divided into
+const UNQUANTIZED_STEPS: f64 = 100.0;
+
+fn as_number(value: &ValueInfo) -> Option<f64> {
+    match value {
+        ValueInfo::Int(value) => Some(f64::from(*value)),
+        ValueInfo::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// The bounds and step size of a range constrained option, and whether it only takes integers
+fn range(option: &OptionInfo) -> Option<(f64, f64, f64, bool)> {
+    let Some(ConstraintInfo::Range { min, max, step }) = &option.constraint else {
+        return None;
+    };
+
+    let integer = matches!(min, ValueInfo::Int(_));
+    let (min, max) = (as_number(min)?, as_number(max)?);
+    let step = match step.as_ref().and_then(as_number) {
+        Some(step) if step > 0.0 => step,
+        _ if integer => 1.0,
+        _ => (max - min) / UNQUANTIZED_STEPS,
+    };
+
+    Some((min, max, step, integer))
+}
+
+fn format_number(value: f64, integer: bool) -> String {
+    if integer {
+        (value.round() as i64).to_string()
+    } else {
+        // Avoid printing the noise of repeated floating point additions
+        ((value * 10_000.0).round() / 10_000.0).to_string()
+    }
+}
+
+/// The values a list constrained option can take
+pub(crate) fn choices(option: &OptionInfo) -> Option<&[ValueInfo]> {
+    match &option.constraint {
+        Some(ConstraintInfo::List { values }) => Some(values),
+        _ => None,
+    }
+}
+
+/// Whether the value of the option can be stepped through with the arrow keys
+pub(crate) fn is_steppable(option: &OptionInfo) -> bool {
+    range(option).is_some() || choices(option).is_some()
+}
+
+/// Clamp the number to the range of the option and round it to the nearest allowed step
+pub(crate) fn snap(option: &OptionInfo, value: f64) -> Option<String> {
+    let (min, max, step, integer) = range(option)?;
+    let steps = ((value.clamp(min, max) - min) / step).round();
+
+    Some(format_number((min + steps * step).clamp(min, max), integer))
+}
+
+/// The allowed value closest to the given number
+pub(crate) fn closest(option: &OptionInfo, value: f64) -> Option<String> {
+    if let Some(values) = choices(option) {
+        return values
+            .iter()
+            .filter_map(|choice| Some((choice, as_number(choice)?)))
+            .min_by(|(_, a), (_, b)| (a - value).abs().total_cmp(&(b - value).abs()))
+            .map(|(choice, _)| choice.to_string());
+    }
+
+    snap(option, value)
+}
+
+/// The bounds of a range constrained option
+pub(crate) fn bounds(option: &OptionInfo) -> Option<(f64, f64)> {
+    range(option).map(|(min, max, _, _)| (min, max))
+}
+
+/// The value `steps` steps away from the current one, staying within the constraint of the option
+pub(crate) fn step(option: &OptionInfo, steps: i32) -> Option<String> {
+    if let Some(values) = choices(option) {
+        let current = option.value.as_ref().map(ToString::to_string);
+        let position = values
+            .iter()
+            .position(|value| Some(value.to_string()) == current)
+            .unwrap_or_default();
+        let position = (position as i64 + i64::from(steps)).clamp(0, values.len() as i64 - 1);
+
+        return values.get(position as usize).map(ToString::to_string);
+    }
+
+    let (min, _, step, _) = range(option)?;
+    let current = option.value.as_ref().and_then(as_number).unwrap_or(min);
+
+    snap(option, current + f64::from(steps) * step)
+}
+
+/// Where the current value lies within the range of the option, from 0 to 1
+pub(crate) fn position(option: &OptionInfo) -> Option<f64> {
+    let (min, max, _, _) = range(option)?;
+    let current = option.value.as_ref().and_then(as_number)?;
+
+    if max > min {
+        Some(((current - min) / (max - min)).clamp(0.0, 1.0))
+    } else {
+        Some(1.0)
+    }
+}
+
+/// Describe the allowed values, for example `75 – 1200 (step 25)`
+pub(crate) fn describe(option: &OptionInfo) -> Option<String> {
+    if let Some((min, max, step, integer)) = range(option) {
+        return Some(format!(
+            "{} – {} (step {})",
+            format_number(min, integer),
+            format_number(max, integer),
+            format_number(step, integer)
+        ));
+    }
+
+    choices(option).map(|values| {
+        values
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
+}
@@ -1,52 +1,215 @@
 use std::sync::mpsc::channel;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
+use std::time::Instant;
 
 use miette::IntoDiagnostic;
 use ratatui::crossterm::event::KeyCode;
 use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::crossterm::event::MouseButton;
+use ratatui::crossterm::event::MouseEvent;
+use ratatui::crossterm::event::MouseEventKind;
 use ratatui::layout::Constraint;
 use ratatui::layout::Direction;
 use ratatui::layout::Layout;
-use ratatui::style::Style;
+use ratatui::layout::Rect;
 use ratatui::style::Stylize;
-use ratatui::widgets::List;
-use ratatui::widgets::ListState;
-use sane_scan::Device;
+use ratatui::text::Line;
+use ratatui::widgets::Row;
+use ratatui::widgets::Table;
+use ratatui::widgets::TableState;
+use scannrs_core::device::DeviceInfo;
 
+use super::key_hints::KeyHints;
+use super::keys::KeyBindings;
+use super::list_item_at;
+use super::theme::Theme;
 use super::Component;
 use super::SaneQuery;
+use super::SaneSender;
+use crate::error::ScannrsError;
+
+/// How often the device list is refreshed while the picker is shown
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+type DevicesResponse = miette::Result<Vec<DeviceInfo>>;
 
 pub struct DevicePicker {
-    sane_sender: Sender<SaneQuery>,
+    sane_sender: SaneSender,
 
-    available_devices: Option<Vec<Device>>,
-    list_state: ListState,
+    available_devices: Option<Vec<DeviceInfo>>,
+    list_state: TableState,
+    /// A refresh of the device list that has not been answered yet
+    pending: Option<Receiver<DevicesResponse>>,
+    last_refresh: Instant,
+    /// The device to highlight once the devices have been found
+    select_on_load: Option<String>,
+    /// Only devices whose name, vendor or model contain this are listed
+    filter: String,
+    /// Whether the user is currently typing the filter
+    filtering: bool,
+    /// Where the list was last drawn, to find the device that was clicked
+    list_area: Rect,
+    hints: KeyHints,
+    theme: Theme,
 }
 impl DevicePicker {
-    pub(crate) fn new(sane_sender: Sender<SaneQuery>) -> Self {
+    pub(crate) fn new(sane_sender: SaneSender, keys: KeyBindings, theme: Theme) -> Self {
         Self {
             sane_sender,
             available_devices: None,
-            list_state: ListState::default(),
+            list_state: TableState::default(),
+            pending: None,
+            last_refresh: Instant::now(),
+            select_on_load: None,
+            filter: String::new(),
+            filtering: false,
+            list_area: Rect::default(),
+            hints: KeyHints::new(keys),
+            theme,
+        }
+    }
+
+    /// Ask the SANE handler for the devices again, the answer is picked up in `tick`
+    fn refresh(&mut self) -> miette::Result<()> {
+        let (responder, recv) = channel();
+        self.sane_sender
+            .send(SaneQuery::ListDevices { responder })
+            .into_diagnostic()?;
+        self.pending = Some(recv);
+        self.last_refresh = Instant::now();
+
+        Ok(())
+    }
+
+    /// The devices matching the filter, in the order they are listed
+    fn visible(&self) -> Vec<&DeviceInfo> {
+        let filter = self.filter.to_lowercase();
+        self.available_devices
+            .iter()
+            .flatten()
+            .filter(|device| {
+                [&device.name, &device.vendor, &device.model]
+                    .iter()
+                    .any(|value| value.to_lowercase().contains(&filter))
+            })
+            .collect()
+    }
+
+    fn selected_name(&self) -> Option<String> {
+        let idx = self.list_state.selected()?;
+        let device = self.visible().get(idx).copied()?;
+        Some(device.name.clone())
+    }
+
+    /// Change the filter, highlighting the first device that matches it
+    fn change_filter(&mut self, change: impl FnOnce(&mut String)) {
+        change(&mut self.filter);
+        let first = (!self.visible().is_empty()).then_some(0);
+        self.list_state.select(first);
+    }
+
+    /// Replace the listed devices, keeping the highlighted device if it is still there
+    fn set_devices(&mut self, devices: Vec<DeviceInfo>) {
+        let selected = self.selected_name().or_else(|| self.select_on_load.take());
+
+        self.available_devices = Some(devices);
+        self.list_state.select(None);
+        if let Some(selected) = selected {
+            self.select_device(&selected);
+        }
+    }
+
+    /// Highlight the device with the given name, if it is in the list
+    pub(crate) fn select_device(&mut self, name: &str) {
+        if self.available_devices.is_none() {
+            self.select_on_load = Some(name.to_string());
+            return;
+        }
+
+        let position = self.visible().iter().position(|d| d.name == name);
+
+        if position.is_some() {
+            self.list_state.select(position);
         }
     }
 }
 
 impl Component for DevicePicker {
     fn init(&mut self) -> miette::Result<()> {
-        let (resp, recv) = channel();
-        self.sane_sender
-            .send(SaneQuery::ListDevices { responder: resp })
-            .into_diagnostic()?;
+        // Enumerating can take many seconds with network backends, the answer is picked up in `tick`
+        self.available_devices = None;
+        self.list_state = TableState::default();
+        self.refresh()
+    }
 
-        self.available_devices = Some(recv.recv().into_diagnostic()?);
+    fn tick(&mut self) -> miette::Result<super::Action> {
+        if let Some(recv) = &self.pending {
+            match recv.try_recv() {
+                Ok(devices) => {
+                    self.pending = None;
+                    self.set_devices(devices?);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    return Err(ScannrsError::SaneHandlerStopped).into_diagnostic();
+                }
+            }
+        } else if self.last_refresh.elapsed() >= REFRESH_INTERVAL {
+            self.refresh()?;
+        }
 
-        self.list_state = ListState::default();
+        Ok(super::Action::Noop)
+    }
 
-        Ok(())
+    fn captures_input(&self) -> bool {
+        self.filtering
     }
 
     fn handle_event(&mut self, event: Option<super::Event>) -> miette::Result<super::Action> {
+        if let (true, Some(super::Event::Paste(text))) = (self.filtering, &event) {
+            let text = super::pasted_line(text);
+            self.change_filter(|filter| filter.push_str(&text));
+            return Ok(super::Action::Noop);
+        }
+
+        if let (
+            true,
+            Some(super::Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            })),
+        ) = (self.filtering, &event)
+        {
+            match code {
+                KeyCode::Char(c) => self.change_filter(|filter| filter.push(*c)),
+                KeyCode::Backspace => self.change_filter(|filter| {
+                    filter.pop();
+                }),
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Esc => {
+                    let selected = self.selected_name();
+                    self.filtering = false;
+                    self.change_filter(String::clear);
+                    if let Some(selected) = selected {
+                        self.select_device(&selected);
+                    }
+                }
+                _ => {}
+            }
+
+            // The highlighted device can still be moved while typing
+            if !matches!(code, KeyCode::Up | KeyCode::Down) {
+                return Ok(super::Action::Noop);
+            }
+        }
+
         match event {
             Some(super::Event::Key(KeyEvent {
                 code: KeyCode::Up, ..
@@ -55,20 +218,49 @@ impl Component for DevicePicker {
                 code: KeyCode::Down,
                 ..
             })) => self.list_state.select_next(),
+            Some(super::Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollUp,
+                ..
+            })) => self.list_state.select_previous(),
+            Some(super::Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                ..
+            })) => self.list_state.select_next(),
+            Some(super::Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            })) => {
+                let len = self.visible().len();
+                let Some(idx) =
+                    list_item_at(self.list_area, self.list_state.offset(), len, column, row)
+                else {
+                    return Ok(super::Action::Noop);
+                };
 
+                // Clicking the highlighted device picks it, like Enter
+                if self.list_state.selected() != Some(idx) {
+                    self.list_state.select(Some(idx));
+                    return Ok(super::Action::Noop);
+                }
+                let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+                return self.handle_event(Some(super::Event::Key(enter)));
+            }
+            Some(super::Event::Key(KeyEvent {
+                code: KeyCode::Char('r'),
+                ..
+            })) if self.pending.is_none() => self.refresh()?,
+            Some(super::Event::Key(KeyEvent {
+                code: KeyCode::Char('/'),
+                ..
+            })) => self.filtering = true,
             Some(super::Event::Key(KeyEvent {
                 code: KeyCode::Enter,
                 ..
             })) => {
-                let device = self
-                    .available_devices
-                    .as_ref()
-                    .and_then(|d| d.get(self.list_state.selected()?));
-
-                if let Some(device) = device {
-                    return Ok(super::Action::SetActiveDevice(
-                        device.name.to_string_lossy().to_string(),
-                    ));
+                if let Some(device) = self.selected_name() {
+                    return Ok(super::Action::SetActiveDevice(device));
                 }
             }
             _ => (),
@@ -77,24 +269,93 @@ impl Component for DevicePicker {
         Ok(super::Action::Noop)
     }
 
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("↑/↓", "highlight a scanner"),
+            ("Enter", "use the highlighted scanner"),
+            ("/", "filter the scanners by name, vendor or model"),
+            ("r", "search for scanners again"),
+            ("Click", "highlight a scanner, click again to use it"),
+        ]
+    }
+
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.hints.key_at(column, row)
+    }
+
     fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
-        let Some(devices) = self.available_devices.as_ref() else {
-            return;
+        let [rect, help_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(rect);
+        let help = if self.filtering {
+            "Enter: keep filter  Esc: clear filter"
+        } else {
+            "Enter: select  /: filter  r: refresh  ?: help  Esc: quit"
         };
+        self.hints.draw(frame, help_area, help);
+
+        if self.available_devices.is_none() {
+            let frame_idx =
+                (self.last_refresh.elapsed().as_millis() / 100) as usize % SPINNER.len();
+            let searching = Line::from(format!("{} Searching for scanners...", SPINNER[frame_idx]));
+            frame.render_widget(searching.centered(), rect);
+            return;
+        }
 
         let [_left, list_area, _right] = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
                 Constraint::Fill(1),
-                Constraint::Length(40),
+                Constraint::Max(100),
                 Constraint::Fill(1),
             ])
             .areas(rect);
+        let [list_area, name_area] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(list_area);
+
+        let list_area = if self.filtering || !self.filter.is_empty() {
+            let [filter_area, list_area] =
+                Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(list_area);
+            let cursor = if self.filtering { "_" } else { "" };
+            frame.render_widget(Line::from(format!("/{}{cursor}", self.filter)), filter_area);
+            list_area
+        } else {
+            list_area
+        };
 
-        let list = List::new(devices.iter().map(|d| d.name.to_string_lossy()))
-            .highlight_style(Style::new().reversed())
-            .highlight_symbol(">>");
+        let devices = self.visible();
+        // The backend name is cryptic, so it is only shown for the highlighted device
+        let name = self
+            .list_state
+            .selected()
+            .and_then(|idx| devices.get(idx))
+            .map(|device| format!("Device: {}", device.name));
+        let rows = devices
+            .iter()
+            .map(|d| Row::new([d.vendor.clone(), d.model.clone(), d.type_.clone()]))
+            .collect::<Vec<_>>();
 
-        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(25),
+                Constraint::Percentage(50),
+                Constraint::Percentage(25),
+            ],
+        )
+        .header(Row::new(["Vendor", "Model", "Type"]).bold())
+        .row_highlight_style(self.theme.highlight)
+        .highlight_symbol(">>");
+
+        frame.render_stateful_widget(table, list_area, &mut self.list_state);
+        if let Some(name) = name {
+            frame.render_widget(Line::from(name).dim(), name_area);
+        }
+
+        // The rows start below the header
+        self.list_area = Rect {
+            y: list_area.y + 1,
+            height: list_area.height.saturating_sub(1),
+            ..list_area
+        };
     }
 }
@@ -0,0 +1,99 @@
+use miette::GraphicalReportHandler;
+use miette::GraphicalTheme;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Flex;
+use ratatui::layout::Layout;
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+use super::Retry;
+
+/// Shows an error on top of the interface until it is dismissed
+pub struct ErrorPopup {
+    message: String,
+    retry: Option<Retry>,
+    border: Style,
+}
+
+impl ErrorPopup {
+    pub(crate) fn new(error: &miette::Report, retry: Option<Retry>, theme: &Theme) -> Self {
+        // The colors of the fancy output would show up as escape codes
+        let mut message = String::new();
+        let rendered = GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+            .render_report(&mut message, error.as_ref());
+        if rendered.is_err() || message.trim().is_empty() {
+            message = crate::error::error_chain(error);
+        }
+
+        ErrorPopup {
+            message,
+            retry,
+            border: theme.error,
+        }
+    }
+}
+
+impl Component for ErrorPopup {
+    fn captures_input(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        Ok(match code {
+            KeyCode::Esc | KeyCode::Enter => Action::DismissError,
+            KeyCode::Char('r') => match &self.retry {
+                Some(retry) => Action::Retry(retry.clone()),
+                None => Action::Noop,
+            },
+            _ => Action::Noop,
+        })
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [area] = Layout::horizontal([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(rect);
+        let [area] = Layout::vertical([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let help = if self.retry.is_some() {
+            " r: retry  Enter/Esc: dismiss "
+        } else {
+            " Enter/Esc: dismiss "
+        };
+        let block = Block::bordered()
+            .title(" Error ")
+            .title_bottom(Line::from(help).dim())
+            .border_style(self.border);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(self.message.as_str())
+                .block(block)
+                .wrap(Wrap { trim: false }),
+            area,
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! The sources of the events driving the interface, which all feed into a single bus
+//!
+//! The interface sleeps until something arrives on the bus: a key press, the answer of the SANE handler or the
+//! periodic tick that animates spinners and triggers refreshes.
+
+use std::time::Duration;
+
+use ratatui::crossterm::event;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::time::MissedTickBehavior;
+
+use super::Event;
+
+pub(crate) enum AppEvent {
+    Terminal(Event),
+    /// Reading from the terminal failed, the interface cannot continue
+    TerminalFailed(std::io::Error),
+    Tick,
+    /// The SANE handler answered a request or reported progress
+    Sane,
+}
+
+pub(crate) type EventBus = UnboundedSender<AppEvent>;
+
+/// How often the screens are ticked when nothing else happens
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Send a tick on the bus in regular intervals, needs to be called within the runtime
+pub(crate) fn spawn_ticker(bus: EventBus) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK_INTERVAL);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            if bus.send(AppEvent::Tick).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Reading the terminal blocks, so it gets a thread of its own which stops once the interface is gone
+pub(crate) fn spawn_terminal_reader(bus: EventBus) {
+    std::thread::spawn(move || {
+        while !bus.is_closed() {
+            let event = match event::poll(TICK_INTERVAL)
+                .and_then(|ready| ready.then(event::read).transpose())
+            {
+                Ok(Some(event)) => event,
+                Ok(None) => continue,
+                Err(error) => {
+                    let _ = bus.send(AppEvent::TerminalFailed(error));
+                    break;
+                }
+            };
+
+            let event = match event {
+                event::Event::Key(key) => Event::Key(key),
+                event::Event::Mouse(mouse) => Event::Mouse(mouse),
+                event::Event::Resize(w, h) => Event::Resize(w, h),
+                event::Event::Paste(text) => Event::Paste(text),
+                _ => continue,
+            };
+
+            if bus.send(AppEvent::Terminal(event)).is_err() {
+                break;
+            }
+        }
+    });
+}
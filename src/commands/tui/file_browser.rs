@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Flex;
+use ratatui::layout::Layout;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
+use ratatui::widgets::List;
+use ratatui::widgets::ListState;
+
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+
+/// Lets the user walk through the directories to choose where scans are saved
+pub struct FileBrowser {
+    dir: PathBuf,
+    /// The names of the directories inside `dir`
+    entries: Vec<String>,
+    error: Option<String>,
+    list_state: ListState,
+    theme: Theme,
+}
+
+impl FileBrowser {
+    pub(crate) fn new(dir: PathBuf, theme: Theme) -> Self {
+        let mut browser = FileBrowser {
+            dir: std::path::absolute(&dir).unwrap_or(dir),
+            entries: Vec::new(),
+            error: None,
+            list_state: ListState::default(),
+            theme,
+        };
+        browser.read_dir();
+        browser
+    }
+
+    /// List the directories in `dir`, hidden ones are left out
+    fn read_dir(&mut self) {
+        self.entries.clear();
+        self.error = None;
+
+        match std::fs::read_dir(&self.dir) {
+            Ok(entries) => {
+                self.entries = entries
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.path().is_dir())
+                    .map(|entry| entry.file_name().to_string_lossy().to_string())
+                    .filter(|name| !name.starts_with('.'))
+                    .collect();
+                self.entries.sort();
+            }
+            Err(error) => self.error = Some(error.to_string()),
+        }
+
+        self.list_state.select(Some(0));
+    }
+
+    fn has_parent(&self) -> bool {
+        self.dir.parent().is_some()
+    }
+
+    fn go_up(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            let left = self
+                .dir
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string());
+            self.dir = parent.to_path_buf();
+            self.read_dir();
+
+            // Keep the directory that was left highlighted
+            if let Some(position) = self.entries.iter().position(|e| Some(e) == left.as_ref()) {
+                self.list_state
+                    .select(Some(position + usize::from(self.has_parent())));
+            }
+        }
+    }
+}
+
+impl Component for FileBrowser {
+    fn captures_input(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        match code {
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Backspace | KeyCode::Left => self.go_up(),
+            KeyCode::Enter | KeyCode::Right => {
+                let Some(selected) = self.list_state.selected() else {
+                    return Ok(Action::Noop);
+                };
+
+                // The first entry is the parent directory, if there is one
+                match selected.checked_sub(usize::from(self.has_parent())) {
+                    None => self.go_up(),
+                    Some(idx) => {
+                        if let Some(name) = self.entries.get(idx) {
+                            self.dir = self.dir.join(name);
+                            self.read_dir();
+                        }
+                    }
+                }
+            }
+            KeyCode::Char(' ') => return Ok(Action::ChooseDirectory(Some(self.dir.clone()))),
+            KeyCode::Esc => return Ok(Action::ChooseDirectory(None)),
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(rect);
+        let [area] = Layout::vertical([Constraint::Percentage(70)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let parent = self.has_parent().then(|| String::from("../"));
+        let items = parent
+            .into_iter()
+            .chain(self.entries.iter().map(|name| format!("{name}/")))
+            .collect::<Vec<_>>();
+
+        let mut block = Block::bordered()
+            .title(Line::styled(
+                format!(" {} ", self.dir.display()),
+                self.theme.title,
+            ))
+            .title_bottom(
+                Line::from(" Enter: open  Backspace: up  Space: save here  Esc: cancel ").dim(),
+            )
+            .border_style(self.theme.border);
+        if let Some(error) = &self.error {
+            block = block.title_bottom(Line::styled(format!(" {error} "), self.theme.error));
+        }
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(self.theme.highlight);
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+    }
+}
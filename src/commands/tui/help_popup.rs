@@ -0,0 +1,109 @@
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Flex;
+use ratatui::layout::Layout;
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+
+use super::keys::key_name;
+use super::keys::parse_key;
+use super::keys::KeyBindings;
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+
+/// Lists the keys of the current screen on top of it, until it is closed
+pub struct HelpPopup {
+    entries: Vec<(String, &'static str)>,
+    border: Style,
+    title: Style,
+}
+
+impl HelpPopup {
+    /// The entries are given with the default keys, remapped keys are shown as the key that replaces them
+    pub(crate) fn new(
+        entries: &[(&'static str, &'static str)],
+        keys: &KeyBindings,
+        theme: &Theme,
+    ) -> Self {
+        let entries = entries
+            .iter()
+            .map(|(key, description)| {
+                let key = match parse_key(key) {
+                    Some(code) => key_name(keys.active(code)),
+                    None => key.to_string(),
+                };
+                (key, *description)
+            })
+            .collect();
+
+        HelpPopup {
+            entries,
+            border: theme.border,
+            title: theme.title,
+        }
+    }
+}
+
+impl Component for HelpPopup {
+    fn captures_input(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        Ok(match code {
+            KeyCode::Esc | KeyCode::Enter | KeyCode::Char('?') => Action::CloseHelp,
+            _ => Action::Noop,
+        })
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let key_width = self
+            .entries
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or_default();
+        let lines = self
+            .entries
+            .iter()
+            .map(|(key, description)| {
+                Line::from(vec![
+                    format!("{key:>key_width$}").bold(),
+                    format!("  {description}").into(),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let [area] = Layout::horizontal([Constraint::Percentage(60)])
+            .flex(Flex::Center)
+            .areas(rect);
+        let [area] = Layout::vertical([Constraint::Length(lines.len() as u16 + 2)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let block = Block::bordered()
+            .title(Line::styled(" Keys ", self.title))
+            .border_style(self.border)
+            .title_bottom(Line::from(" Enter/Esc: close ").dim());
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
@@ -0,0 +1,275 @@
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+
+use chrono::Local;
+use miette::IntoDiagnostic;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::List;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+
+use super::clipboard;
+use super::key_hints::KeyHints;
+use super::keys::KeyBindings;
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+use super::SaneQuery;
+use super::SaneSender;
+use crate::commands::history::open_path;
+use crate::commands::scan::ScanSummary;
+use crate::history::History;
+use crate::history::HistoryEntry;
+
+/// The answer of the SANE handler to a request to scan again like an entry of the history
+pub(crate) type RerunResponse = Result<ScanSummary, String>;
+
+enum Status {
+    Idle,
+    Running(Receiver<RerunResponse>),
+    Done(String),
+    Failed(String),
+}
+
+/// Lists the previous scans, opens their files and scans again with the same settings
+pub struct HistoryScreen {
+    sane_sender: SaneSender,
+    /// Newest first
+    entries: Vec<HistoryEntry>,
+    list_state: ListState,
+    status: Status,
+    hints: KeyHints,
+    theme: Theme,
+}
+
+impl HistoryScreen {
+    pub(crate) fn new(sane_sender: SaneSender, keys: KeyBindings, theme: Theme) -> Self {
+        Self {
+            sane_sender,
+            entries: Vec::new(),
+            list_state: ListState::default(),
+            status: Status::Idle,
+            hints: KeyHints::new(keys),
+            theme,
+        }
+    }
+
+    fn reload(&mut self) -> miette::Result<()> {
+        self.entries = History::open()?.entries()?;
+        self.entries.reverse();
+        if self.list_state.selected().is_none() && !self.entries.is_empty() {
+            self.list_state.select(Some(0));
+        }
+
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&HistoryEntry> {
+        self.entries.get(self.list_state.selected()?)
+    }
+
+    fn rerun(&mut self) -> miette::Result<()> {
+        let Some(entry) = self.selected().cloned() else {
+            return Ok(());
+        };
+
+        let (responder, recv) = channel();
+        self.sane_sender
+            .send(SaneQuery::Rerun { entry, responder })
+            .into_diagnostic()?;
+        self.status = Status::Running(recv);
+
+        Ok(())
+    }
+
+    fn copy_path(&mut self) -> miette::Result<()> {
+        let Some(path) = self.selected().and_then(|entry| entry.outputs.first()) else {
+            return Ok(());
+        };
+
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        clipboard::copy(&path.to_string_lossy())?;
+        self.status = Status::Done(format!("Copied {} to the clipboard", path.display()));
+
+        Ok(())
+    }
+}
+
+impl Component for HistoryScreen {
+    fn init(&mut self) -> miette::Result<()> {
+        self.reload()
+    }
+
+    fn tick(&mut self) -> miette::Result<Action> {
+        let Status::Running(recv) = &self.status else {
+            return Ok(Action::Noop);
+        };
+
+        match recv.try_recv() {
+            Ok(Ok(summary)) => {
+                self.status = Status::Done(format!("Saved to {}", summary.path.display()));
+                // The new scan is the newest entry
+                self.list_state.select(Some(0));
+                self.reload()?;
+            }
+            Ok(Err(error)) => self.status = Status::Failed(error),
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => {
+                self.status = Status::Failed(String::from("The scan stopped without a result"))
+            }
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("↑/↓", "highlight a scan"),
+            ("Enter", "open the files of the scan"),
+            ("y", "copy the path of the scan to the clipboard"),
+            (
+                "s",
+                "scan again with the device and options of the scan, saving next to it",
+            ),
+            ("r", "reload the history"),
+        ]
+    }
+
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.hints.key_at(column, row)
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        let running = matches!(self.status, Status::Running(_));
+        match code {
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Enter => {
+                if let Some(entry) = self.selected() {
+                    for path in &entry.outputs {
+                        open_path(path)?;
+                    }
+                }
+            }
+            KeyCode::Char('y') => self.copy_path()?,
+            KeyCode::Char('s') if !running => self.rerun()?,
+            KeyCode::Char('r') => self.reload()?,
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [main_area, status_area, help_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(rect);
+        let [list_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main_area);
+
+        let items = self.entries.iter().map(|entry| {
+            format!(
+                "{:>4}  {}  {} page(s)",
+                entry.id,
+                entry
+                    .finished_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M"),
+                entry.pages
+            )
+        });
+        let list = List::new(items)
+            .block(Block::new().borders(Borders::RIGHT))
+            .highlight_style(self.theme.highlight);
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        if self.entries.is_empty() {
+            frame.render_widget(Line::from("No scans yet").dim(), list_area);
+        }
+
+        if let Some(entry) = self.selected() {
+            let mut details = vec![
+                Line::from(vec!["Device: ".bold(), entry.device.as_str().into()]),
+                Line::from(vec![
+                    "Duration: ".bold(),
+                    format!("{:.1}s", entry.duration_ms as f64 / 1000.0).into(),
+                ]),
+                Line::from(vec![
+                    "Format: ".bold(),
+                    format!("{:?}", entry.format).into(),
+                ]),
+            ];
+            if let Some(settings) = &entry.settings {
+                details.push(Line::from(vec![
+                    "Settings: ".bold(),
+                    settings.display().to_string().into(),
+                ]));
+            }
+            for path in &entry.outputs {
+                details.push(Line::from(vec![
+                    "Output: ".bold(),
+                    path.display().to_string().into(),
+                ]));
+            }
+            if !entry.options.is_empty() {
+                details.push(Line::default());
+                details.push(Line::from("Options".bold()));
+                details.extend(
+                    entry
+                        .options
+                        .iter()
+                        .map(|(name, value)| Line::from(format!("  {name} = {value}"))),
+                );
+            }
+
+            let details_area = details_area.inner(ratatui::layout::Margin::new(1, 0));
+            frame.render_widget(
+                Paragraph::new(details).wrap(Wrap { trim: false }),
+                details_area,
+            );
+        }
+
+        match &self.status {
+            Status::Idle => {}
+            Status::Running(_) => frame.render_widget(Line::from("Scanning..."), status_area),
+            Status::Done(message) => frame.render_widget(
+                Line::styled(message.as_str(), self.theme.success),
+                status_area,
+            ),
+            Status::Failed(error) => frame.render_widget(
+                Line::styled(format!("Failed: {error}"), self.theme.error),
+                status_area,
+            ),
+        }
+
+        self.hints.draw(
+            frame,
+            help_area,
+            "Enter: open  y: copy path  s: scan again  r: reload  Tab: next  d: device  ?: help  Esc: quit",
+        );
+    }
+}
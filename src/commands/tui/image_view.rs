@@ -0,0 +1,75 @@
+//! Drawing images inside the terminal
+//!
+//! Terminals supporting the sixel, kitty or iTerm2 graphics protocols show the image itself, all others get an
+//! approximation drawn with unicode half blocks.
+
+use image::DynamicImage;
+use ratatui::layout::Rect;
+use ratatui::Frame;
+use ratatui_image::picker::Picker;
+use ratatui_image::protocol::Protocol;
+use ratatui_image::Image;
+use ratatui_image::Resize;
+
+/// Find out which graphics protocol the terminal supports, needs to be called in raw mode before reading any events
+pub(crate) fn query_picker() -> Picker {
+    Picker::from_query_stdio().unwrap_or_else(|_| Picker::from_fontsize((8, 16)))
+}
+
+/// The largest area within `area` that shows an image of the given size without distorting it
+fn fit(width: u32, height: u32, (font_width, font_height): (u16, u16), area: Rect) -> Rect {
+    if width == 0 || height == 0 || font_width == 0 || font_height == 0 || area.is_empty() {
+        return Rect::new(area.x, area.y, 0, 0);
+    }
+
+    let (font_width, font_height) = (f64::from(font_width), f64::from(font_height));
+    let scale = (f64::from(area.width) * font_width / f64::from(width))
+        .min(f64::from(area.height) * font_height / f64::from(height));
+    let cols = ((f64::from(width) * scale / font_width) as u16).clamp(1, area.width);
+    let rows = ((f64::from(height) * scale / font_height) as u16).clamp(1, area.height);
+
+    Rect::new(
+        area.x + (area.width - cols) / 2,
+        area.y + (area.height - rows) / 2,
+        cols,
+        rows,
+    )
+}
+
+/// An image together with its encoding for the terminal, which is only redone when the available area changes
+pub(crate) struct ImageView {
+    image: DynamicImage,
+    encoded: Option<(Rect, Protocol)>,
+}
+
+impl ImageView {
+    pub(crate) fn new(image: DynamicImage) -> Self {
+        ImageView {
+            image,
+            encoded: None,
+        }
+    }
+
+    /// Draw the image as large as possible within `area` without distorting it, returning where it was drawn
+    pub(crate) fn draw(&mut self, picker: &mut Picker, frame: &mut Frame, area: Rect) -> Rect {
+        let area = fit(
+            self.image.width(),
+            self.image.height(),
+            picker.font_size(),
+            area,
+        );
+
+        if !matches!(&self.encoded, Some((encoded_area, _)) if *encoded_area == area) {
+            self.encoded = picker
+                .new_protocol(self.image.clone(), area, Resize::Fit(None))
+                .ok()
+                .map(|protocol| (area, protocol));
+        }
+
+        if let Some((_, protocol)) = &self.encoded {
+            frame.render_widget(Image::new(protocol), area);
+        }
+
+        area
+    }
+}
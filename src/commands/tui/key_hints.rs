@@ -0,0 +1,69 @@
+//! The line of key hints at the bottom of each screen, which doubles as a row of buttons
+
+use ratatui::crossterm::event::KeyCode;
+use ratatui::layout::Position;
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::Frame;
+
+use super::keys::key_name;
+use super::keys::parse_key;
+use super::keys::KeyBindings;
+
+/// Renders hints like `s: scan  w: save` and remembers where each one was drawn
+///
+/// Clicking a hint works like pressing its key.
+pub(crate) struct KeyHints {
+    keys: KeyBindings,
+    buttons: Vec<(Rect, KeyCode)>,
+}
+
+impl KeyHints {
+    pub(crate) fn new(keys: KeyBindings) -> Self {
+        Self {
+            keys,
+            buttons: Vec::new(),
+        }
+    }
+
+    /// Draw hints given as `key: description` with the default keys, separated by two spaces
+    ///
+    /// Keys that were remapped in the configuration are shown as the key that replaces them.
+    pub(crate) fn draw(&mut self, frame: &mut Frame, area: Rect, hints: &str) {
+        self.buttons.clear();
+        let mut line = Vec::new();
+        let mut x = area.x;
+        for hint in hints.split("  ") {
+            let (hint, code) = match hint.split_once(':') {
+                Some((key, description)) => match parse_key(key) {
+                    Some(code) => {
+                        let code = self.keys.active(code);
+                        (format!("{}:{description}", key_name(code)), Some(code))
+                    }
+                    // Hints for several keys at once, like `←/→`, are not buttons
+                    None => (hint.to_string(), None),
+                },
+                None => (hint.to_string(), None),
+            };
+
+            let width = hint.chars().count() as u16;
+            if let Some(code) = code {
+                let button = Rect::new(x, area.y, width, 1).intersection(area);
+                self.buttons.push((button, code));
+            }
+            x = x.saturating_add(width + 2);
+            line.push(hint);
+        }
+
+        frame.render_widget(Line::from(line.join("  ")).dim(), area);
+    }
+
+    /// The key of the hint at the given position
+    pub(crate) fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.buttons
+            .iter()
+            .find(|(button, _)| button.contains(Position::new(column, row)))
+            .map(|(_, code)| *code)
+    }
+}
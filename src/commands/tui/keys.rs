@@ -0,0 +1,166 @@
+//! The keys of the TUI that can be remapped in the `[keys]` section of its configuration
+
+use std::collections::BTreeMap;
+
+use ratatui::crossterm::event::KeyCode;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::ScannrsError;
+
+/// Something the user can do with a single key
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum KeyAction {
+    Quit,
+    NextScreen,
+    SwitchDevice,
+    /// Reload the devices or options
+    Refresh,
+    Filter,
+    Scan,
+    Save,
+    EditPath,
+    Preview,
+    SelectAll,
+    Help,
+    /// Show or hide the log panel
+    Log,
+}
+
+impl KeyAction {
+    pub(crate) const ALL: [KeyAction; 12] = [
+        KeyAction::Quit,
+        KeyAction::NextScreen,
+        KeyAction::SwitchDevice,
+        KeyAction::Refresh,
+        KeyAction::Filter,
+        KeyAction::Scan,
+        KeyAction::Save,
+        KeyAction::EditPath,
+        KeyAction::Preview,
+        KeyAction::SelectAll,
+        KeyAction::Help,
+        KeyAction::Log,
+    ];
+
+    /// The key the screens handle for this action
+    fn default_key(self) -> KeyCode {
+        match self {
+            KeyAction::Quit => KeyCode::Esc,
+            KeyAction::NextScreen => KeyCode::Tab,
+            KeyAction::SwitchDevice => KeyCode::Char('d'),
+            KeyAction::Refresh => KeyCode::Char('r'),
+            KeyAction::Filter => KeyCode::Char('/'),
+            KeyAction::Scan => KeyCode::Char('s'),
+            KeyAction::Save => KeyCode::Char('w'),
+            KeyAction::EditPath => KeyCode::Char('e'),
+            KeyAction::Preview => KeyCode::Char('p'),
+            KeyAction::SelectAll => KeyCode::Char('a'),
+            KeyAction::Help => KeyCode::Char('?'),
+            KeyAction::Log => KeyCode::Char('l'),
+        }
+    }
+}
+
+/// The active key of every action
+///
+/// The screens only know the default keys, pressed keys are translated to them before being handled.
+#[derive(Clone, Debug)]
+pub(crate) struct KeyBindings {
+    keys: BTreeMap<KeyAction, KeyCode>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: KeyAction::ALL
+                .into_iter()
+                .map(|action| (action, action.default_key()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Use the keys from the configuration, and the defaults for all other actions
+    pub(crate) fn new(config: &BTreeMap<KeyAction, String>) -> Result<KeyBindings, ScannrsError> {
+        let mut bindings = KeyBindings::default();
+        for (action, key) in config {
+            let code =
+                parse_key(key).ok_or_else(|| ScannrsError::InvalidKey { key: key.clone() })?;
+            bindings.keys.insert(*action, code);
+        }
+
+        Ok(bindings)
+    }
+
+    pub(crate) fn key(&self, action: KeyAction) -> KeyCode {
+        self.keys
+            .get(&action)
+            .copied()
+            .unwrap_or_else(|| action.default_key())
+    }
+
+    /// Turn a pressed key into the default key of its action
+    ///
+    /// Default keys that were replaced by another key do nothing anymore.
+    pub(crate) fn translate(&self, code: KeyCode) -> KeyCode {
+        if let Some((action, _)) = self.keys.iter().find(|(_, key)| **key == code) {
+            return action.default_key();
+        }
+
+        let replaced = KeyAction::ALL
+            .into_iter()
+            .any(|action| action.default_key() == code);
+        if replaced {
+            KeyCode::Null
+        } else {
+            code
+        }
+    }
+
+    /// The key that is active in place of the given default key
+    pub(crate) fn active(&self, default: KeyCode) -> KeyCode {
+        KeyAction::ALL
+            .into_iter()
+            .find(|action| action.default_key() == default)
+            .map_or(default, |action| self.key(action))
+    }
+}
+
+/// Parse a key as it is written in the configuration and in the key hints
+pub(crate) fn parse_key(key: &str) -> Option<KeyCode> {
+    match key {
+        "Enter" => Some(KeyCode::Enter),
+        "Esc" => Some(KeyCode::Esc),
+        "Tab" => Some(KeyCode::Tab),
+        "Backspace" => Some(KeyCode::Backspace),
+        "Space" => Some(KeyCode::Char(' ')),
+        _ => {
+            if let Some(number) = key.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                return (1..=12).contains(&number).then_some(KeyCode::F(number));
+            }
+
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(KeyCode::Char(c)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// The name of a key, as understood by [`parse_key`]
+pub(crate) fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Enter => String::from("Enter"),
+        KeyCode::Esc => String::from("Esc"),
+        KeyCode::Tab => String::from("Tab"),
+        KeyCode::Backspace => String::from("Backspace"),
+        KeyCode::Char(' ') => String::from("Space"),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(number) => format!("F{number}"),
+        code => format!("{code:?}"),
+    }
+}
@@ -0,0 +1,151 @@
+//! Collecting the tracing events of the TUI, so they can be shown in its log panel
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use chrono::DateTime;
+use chrono::Local;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::Paragraph;
+use ratatui::Frame;
+use tracing::field::Field;
+use tracing::field::Visit;
+use tracing::Level;
+use tracing::Metadata;
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use super::theme::Theme;
+
+/// How many entries are kept, older ones are dropped
+const CAPACITY: usize = 200;
+
+pub(crate) struct LogEntry {
+    pub(crate) time: DateTime<Local>,
+    pub(crate) level: Level,
+    pub(crate) message: String,
+}
+
+impl LogEntry {
+    fn style(&self, theme: &Theme) -> Style {
+        match self.level {
+            Level::ERROR | Level::WARN => theme.error,
+            Level::INFO => Style::new(),
+            _ => Style::new().dim(),
+        }
+    }
+}
+
+/// The most recent log entries, shared between the tracing layer and the interface
+#[derive(Clone, Default)]
+pub(crate) struct LogBuffer {
+    entries: Arc<Mutex<VecDeque<LogEntry>>>,
+}
+
+impl LogBuffer {
+    fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// The message and style of the latest entry at info level or above
+    pub(crate) fn last(&self, theme: &Theme) -> Option<Span<'static>> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        entries
+            .iter()
+            .rev()
+            .find(|entry| entry.level <= Level::INFO)
+            .map(|entry| Span::styled(entry.message.clone(), entry.style(theme)))
+    }
+
+    /// Draw the entries that fit into `area`, newest at the bottom
+    pub(crate) fn draw(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .border_style(theme.border)
+            .title(Line::styled(" Log ", theme.title));
+        let inner = block.inner(area);
+
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let lines = entries
+            .iter()
+            .skip(entries.len().saturating_sub(usize::from(inner.height)))
+            .map(|entry| {
+                Line::from(vec![
+                    entry.time.format("%H:%M:%S ").to_string().dim(),
+                    Span::styled(format!("{:<5} ", entry.level), entry.style(theme)),
+                    Span::styled(entry.message.clone(), entry.style(theme)),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+/// A tracing layer writing every event at debug level or above into a [`LogBuffer`]
+pub(crate) struct LogLayer {
+    buffer: LogBuffer,
+}
+
+impl LogLayer {
+    pub(crate) fn new(buffer: LogBuffer) -> Self {
+        Self { buffer }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for LogLayer {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        *metadata.level() <= Level::DEBUG
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        self.buffer.push(LogEntry {
+            time: Local::now(),
+            level: *event.metadata().level(),
+            message: visitor.message + &visitor.fields,
+        });
+    }
+}
+
+/// Formats the message of an event followed by its other fields as `name=value`
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
@@ -1,154 +1,930 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::io::stdout;
+use std::io::IsTerminal;
 use std::io::Stdout;
-use std::sync::mpsc::Receiver;
+use std::path::PathBuf;
 use std::sync::mpsc::Sender;
-use std::time::Duration;
+use std::time::Instant;
 
 use device_picker::DevicePicker;
+use error_popup::ErrorPopup;
+use events::AppEvent;
+use events::EventBus;
+use help_popup::HelpPopup;
+use history::HistoryScreen;
+use history::RerunResponse;
+use image_view::query_picker;
+use keys::KeyAction;
+use keys::KeyBindings;
+use log::LogBuffer;
+use log::LogLayer;
+use miette::Context;
 use miette::IntoDiagnostic;
+use options::OptionsEditor;
+use options::OptionsResponse;
+use preview::take_preview;
+use preview::PreviewResponse;
+use preview::PreviewScreen;
+use profiles::ProfilesScreen;
+use quit_popup::QuitChoice;
+use quit_popup::QuitPopup;
 use ratatui::crossterm;
-use ratatui::crossterm::event;
 use ratatui::crossterm::event::DisableBracketedPaste;
+use ratatui::crossterm::event::DisableMouseCapture;
 use ratatui::crossterm::event::EnableBracketedPaste;
+use ratatui::crossterm::event::EnableMouseCapture;
 use ratatui::crossterm::event::KeyCode;
 use ratatui::crossterm::event::KeyEvent;
 use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::crossterm::event::MouseButton;
+use ratatui::crossterm::event::MouseEvent;
+use ratatui::crossterm::event::MouseEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Position;
 use ratatui::layout::Rect;
 use ratatui::prelude::CrosstermBackend;
+use ratatui::text::Line;
+use ratatui::text::Span;
 use ratatui::widgets::Block;
 use ratatui::widgets::BorderType;
 use ratatui::widgets::Borders;
 use ratatui::widgets::Padding;
 use ratatui::Frame;
 use ratatui::Terminal;
-use sane_scan::Sane;
+use ratatui_image::picker::Picker;
+use scan::ScanScreen;
+use scan::ScanUpdate;
+use scan::SensorsResponse;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::device::DeviceInfo;
+use scannrs_core::ocr::OcrOptions;
+use scannrs_core::output::Page;
+use scannrs_core::postprocess::PostProcessing;
+use scannrs_core::progress;
+use scannrs_core::scan::CancellationToken;
+use scannrs_core::value::Value;
 use serde::Deserialize;
 use serde::Serialize;
+use theme::Theme;
+use theme::ThemeConfig;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing_subscriber::layer::SubscriberExt;
 
+use crate::commands::options::option_infos;
+use crate::commands::rerun::rerun_entry;
+use crate::commands::scan::prepare_device;
+use crate::commands::scan::scan_job;
+use crate::config::parse_toml;
+use crate::config::Config;
+use crate::config::Profile;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::history::HistoryEntry;
+
+mod clipboard;
+mod constraint;
 mod device_picker;
+mod error_popup;
+mod events;
+mod file_browser;
+mod help_popup;
+mod history;
+mod image_view;
+mod key_hints;
+mod keys;
+mod log;
+mod options;
+mod preview;
+mod processing;
+mod profiles;
+mod quit_popup;
+mod scan;
+mod theme;
+
+/// Requests to the SANE handler, which answers on the channel given with each request and wakes the interface
+type SaneSender = UnboundedSender<SaneQuery>;
 
 enum SaneQuery {
     ListDevices {
-        responder: Sender<Vec<sane_scan::Device>>,
+        responder: Sender<miette::Result<Vec<DeviceInfo>>>,
+    },
+    ListOptions {
+        device: String,
+        options: HashMap<String, Value>,
+        responder: Sender<OptionsResponse>,
+    },
+    Preview {
+        device: String,
+        options: HashMap<String, Value>,
+        responder: Sender<PreviewResponse>,
+    },
+    /// Read the options reflecting the state of the hardware
+    ReadSensors {
+        device: String,
+        responder: Sender<SensorsResponse>,
+    },
+    Scan {
+        device: String,
+        options: HashMap<String, Value>,
+        /// Scan every page in the document feeder instead of a single one
+        feeder: bool,
+        processing: PostProcessing,
+        /// Set by the interface to stop the scan
+        cancel: CancellationToken,
+        responder: Sender<ScanUpdate>,
+    },
+    /// Scan again with the device and options of an entry of the history
+    Rerun {
+        entry: HistoryEntry,
+        responder: Sender<RerunResponse>,
     },
 }
 
-pub fn tui(sane: Sane) -> miette::Result<()> {
-    let (sane_sender, sane_recv) = std::sync::mpsc::channel();
-    let mut tui = Tui::new(sane_sender)?;
-    crossterm::terminal::enable_raw_mode().into_diagnostic()?;
-    crossterm::execute!(stdout(), EnableBracketedPaste).into_diagnostic()?;
+pub fn tui(backend: &dyn ScanBackend) -> miette::Result<()> {
+    // Drawing into a pipe or a log file would only fill it with escape codes
+    if !std::io::stdout().is_terminal() {
+        return Err(ScannrsError::NotATerminal { stream: "stdout" }.into());
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(ScannrsError::NotATerminal { stream: "stdin" }.into());
+    }
+
+    let (sane_sender, sane_recv) = tokio::sync::mpsc::unbounded_channel();
+    let (bus, events) = tokio::sync::mpsc::unbounded_channel();
+
+    // The terminal belongs to the interface, so events are only collected for its log panel
+    let log = LogBuffer::default();
+    let _ = tracing::subscriber::set_global_default(
+        tracing_subscriber::registry().with(LogLayer::new(log.clone())),
+    );
+
+    let guard = TerminalGuard::new()?;
+
+    // Asking the terminal for its graphics capabilities needs raw mode
+    let picker = query_picker();
+    let mut tui = Tui::new(sane_sender, picker, log)?;
 
-    let tui_thread = std::thread::spawn(move || tui.run());
+    let sane_bus = bus.clone();
+    let tui_thread = std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .into_diagnostic()?;
+        runtime.block_on(tui.run(bus, events))
+    });
 
-    let sane_handler_res = sane_handler(sane_recv, sane);
+    // Backends are not thread-safe, so all calls to them are made from this thread
+    let sane_handler_res = sane_handler(sane_recv, backend, sane_bus);
 
     let res = tui_thread.join();
 
-    crossterm::execute!(stdout(), DisableBracketedPaste).into_diagnostic()?;
-    crossterm::terminal::disable_raw_mode().into_diagnostic()?;
+    drop(guard);
 
     match res {
-        Ok(res) => sane_handler_res.or(res)?,
+        Ok(res) => res.and(sane_handler_res),
         Err(payload) => std::panic::resume_unwind(payload),
     }
+}
 
-    Ok(())
+/// Keeps the terminal in raw mode and puts it back into its normal state when dropped, so that returning early with
+/// an error does not leave the terminal unusable
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> miette::Result<TerminalGuard> {
+        crossterm::terminal::enable_raw_mode().into_diagnostic()?;
+        let guard = TerminalGuard;
+        crossterm::execute!(stdout(), EnableBracketedPaste, EnableMouseCapture)
+            .into_diagnostic()?;
+
+        // Panics are reported by the hook of human_panic installed in `main`, which runs before unwinding drops the
+        // guard, so the terminal is restored before the report is printed
+        let report = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = restore_terminal();
+            report(info);
+        }));
+
+        Ok(guard)
+    }
 }
 
-fn sane_handler(sane_recv: Receiver<SaneQuery>, sane: Sane) -> miette::Result<()> {
-    for query in sane_recv.iter() {
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore_terminal();
+    }
+}
+
+fn restore_terminal() -> miette::Result<()> {
+    crossterm::execute!(
+        stdout(),
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        crossterm::cursor::Show
+    )
+    .into_diagnostic()?;
+    crossterm::terminal::disable_raw_mode().into_diagnostic()
+}
+
+fn sane_handler(
+    mut sane_recv: UnboundedReceiver<SaneQuery>,
+    backend: &dyn ScanBackend,
+    bus: EventBus,
+) -> miette::Result<()> {
+    // The interface may have gone away and closed screens no longer wait for their answers, which is fine: answers are
+    // sent regardless, and the handler only stops once no more queries can come
+    let wake = || {
+        let _ = bus.send(AppEvent::Sane);
+    };
+
+    while let Some(query) = sane_recv.blocking_recv() {
         match query {
             SaneQuery::ListDevices { responder: resp } => {
-                let devices = sane.get_devices().into_diagnostic()?;
+                tracing::debug!("Listing the devices");
+                let devices = backend.devices();
+                match &devices {
+                    Ok(devices) => tracing::debug!("Found {} device(s)", devices.len()),
+                    Err(error) => tracing::warn!("Listing the devices failed: {error}"),
+                }
 
-                if resp.send(devices).is_err() {
-                    break;
+                let _ = resp.send(devices);
+            }
+            SaneQuery::ListOptions {
+                device,
+                options,
+                responder,
+            } => {
+                tracing::debug!("Reading the options of {device}");
+                let options = prepare_device(backend, &device, None, &options)
+                    .and_then(|device| option_infos(device.as_ref()))
+                    .map_err(|error| error_chain(&error));
+                if let Err(error) = &options {
+                    tracing::warn!("Reading the options of {device} failed: {error}");
+                }
+
+                let _ = responder.send(options);
+            }
+            SaneQuery::Preview {
+                device,
+                options,
+                responder,
+            } => {
+                tracing::debug!("Taking a preview with {device}");
+                let preview =
+                    take_preview(backend, &device, &options).map_err(|error| error_chain(&error));
+                if let Err(error) = &preview {
+                    tracing::warn!("The preview failed: {error}");
+                }
+
+                let _ = responder.send(preview);
+            }
+            SaneQuery::ReadSensors { device, responder } => {
+                let sensors = backend
+                    .open(&device)
+                    .and_then(|device| option_infos(device.as_ref()))
+                    .map(|options| {
+                        options
+                            .into_iter()
+                            .filter(|option| option.sensor && option.active)
+                            .collect()
+                    })
+                    .map_err(|error| error_chain(&error));
+                if let Err(error) = &sensors {
+                    tracing::debug!("Reading the sensors of {device} failed: {error}");
+                }
+
+                let _ = responder.send(sensors);
+            }
+            SaneQuery::Scan {
+                device,
+                options,
+                feeder,
+                processing,
+                cancel,
+                responder,
+            } => {
+                if feeder {
+                    tracing::info!("Scanning from the feeder of {device}");
+                } else {
+                    tracing::info!("Scanning with {device}");
+                }
+
+                let mut started = Instant::now();
+                let mut progress = |event| {
+                    let _ = responder.send(ScanUpdate::Progress(event));
+                    wake();
+                    cancel.flow()
+                };
+                let mut page_done = |page: Page| {
+                    tracing::debug!("Read a page in {:.1?}", started.elapsed());
+                    let _ = responder.send(ScanUpdate::Page {
+                        page,
+                        duration: started.elapsed(),
+                    });
+                    wake();
+                    started = Instant::now();
+                };
+                let res = scan_job(&device, None, &options).and_then(|job| {
+                    let job = job.processing(processing);
+                    if feeder {
+                        job.scan_feeder_with_progress(backend, &mut progress, &mut page_done)
+                            .map(|_| ())
+                    } else {
+                        job.scan_with_progress(backend, &mut progress).map(|page| {
+                            if let Some(page) = page {
+                                page_done(page);
+                            }
+                        })
+                    }
+                });
+
+                let update = match res {
+                    _ if cancel.is_cancelled() => {
+                        tracing::info!("The scan was cancelled");
+                        ScanUpdate::Cancelled
+                    }
+                    Ok(()) => {
+                        tracing::info!("The scan is done");
+                        ScanUpdate::Done
+                    }
+                    Err(error) => {
+                        let error = error_chain(&error);
+                        tracing::warn!("The scan failed: {error}");
+                        ScanUpdate::Failed(error)
+                    }
+                };
+
+                let _ = responder.send(update);
+            }
+            SaneQuery::Rerun { entry, responder } => {
+                tracing::info!(
+                    "Scanning again like scan {} with {}",
+                    entry.id,
+                    entry.device
+                );
+                let res = rerun_entry(backend, &entry, None, &mut progress::ignore)
+                    .map_err(|error| error_chain(&error));
+                match &res {
+                    Ok(summary) => tracing::info!("Saved to {}", summary.path.display()),
+                    Err(error) => tracing::warn!("Scanning again failed: {error}"),
                 }
+
+                let _ = responder.send(res);
             }
         }
+
+        wake();
     }
 
     Ok(())
 }
 
+/// The configuration and state of the TUI that is kept between runs, stored at `$XDG_CONFIG_HOME/scannrs/tui.toml`
 #[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
 struct AppConfig {
     active_device: Option<String>,
+    /// Where the last scan was saved
+    output_path: Option<PathBuf>,
+    /// Keys replacing the default key of an action, like `scan = "Space"`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    keys: BTreeMap<KeyAction, String>,
+    #[serde(skip_serializing_if = "ThemeConfig::is_default")]
+    theme: ThemeConfig,
+    /// Applied to every scanned page
+    #[serde(skip_serializing_if = "PostProcessing::is_default")]
+    processing: PostProcessing,
+    /// The tesseract languages of the text recognition, like `deu+eng`, replacing the one of the configuration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ocr_language: Option<String>,
+    /// How the shared configuration recognizes text
+    #[serde(skip)]
+    ocr: OcrOptions,
+}
+
+impl AppConfig {
+    fn path() -> miette::Result<PathBuf> {
+        Ok(crate::paths::config_dir()?.join("tui.toml"))
+    }
+
+    /// Load the configuration, a missing file is the same as an empty one
+    fn load() -> miette::Result<AppConfig> {
+        let path = AppConfig::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => parse_toml(&path, contents).with_context(|| {
+                format!("While reading the TUI configuration at {}", path.display())
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(AppConfig::default()),
+            Err(error) => Err(error).into_diagnostic(),
+        }
+    }
+
+    fn save(&self) -> miette::Result<()> {
+        let path = AppConfig::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        std::fs::write(&path, toml::to_string_pretty(self).into_diagnostic()?)
+            .into_diagnostic()
+            .with_context(|| format!("While writing the TUI configuration to {}", path.display()))
+    }
 }
 
 struct App {
     config: AppConfig,
+    sane_sender: SaneSender,
+    picker: Picker,
+    keys: KeyBindings,
+    theme: Theme,
     device_picker: DevicePicker,
+    device_screens: Option<DeviceScreens>,
+    help: Option<HelpPopup>,
+    error: Option<ErrorPopup>,
+    /// Asks what to do with unsaved pages before quitting
+    quit: Option<QuitPopup>,
+    /// Quit as soon as the pages are saved
+    quit_after_save: bool,
+    log: LogBuffer,
+    show_log: bool,
+    /// The profile that was applied last, shown in the status bar
+    active_profile: Option<String>,
 }
 
 impl App {
-    fn new(sane_sender: Sender<SaneQuery>) -> miette::Result<App> {
+    fn new(sane_sender: SaneSender, picker: Picker, log: LogBuffer) -> miette::Result<App> {
         let config = App::load_config()?;
+        let keys = KeyBindings::new(&config.keys)
+            .into_diagnostic()
+            .context("While reading the [keys] section of the TUI configuration")?;
+        let theme = Theme::new(&config.theme)
+            .into_diagnostic()
+            .context("While reading the [theme] section of the TUI configuration")?;
+        let device_screens = config.active_device.clone().map(|device| {
+            DeviceScreens::new(
+                sane_sender.clone(),
+                device,
+                &config,
+                picker.clone(),
+                keys.clone(),
+                theme.clone(),
+            )
+        });
         Ok(App {
             config,
-            device_picker: DevicePicker::new(sane_sender.clone()),
+            device_picker: DevicePicker::new(sane_sender.clone(), keys.clone(), theme.clone()),
+            sane_sender,
+            picker,
+            keys,
+            theme,
+            device_screens,
+            help: None,
+            error: None,
+            quit: None,
+            quit_after_save: false,
+            log,
+            show_log: false,
+            active_profile: None,
         })
     }
 
+    /// The TUI configuration, starting with the default scanner of the shared configuration on the first run
     fn load_config() -> miette::Result<AppConfig> {
-        Ok(AppConfig::default())
+        let mut config = AppConfig::load()?;
+        let shared = Config::load()?;
+        if config.active_device.is_none() {
+            config.active_device = shared.defaults.device;
+        }
+        config.ocr = shared.ocr()?;
+        Ok(config)
+    }
+
+    fn show_error(&mut self, error: &miette::Report, retry: Option<Retry>) {
+        self.error = Some(ErrorPopup::new(error, retry, &self.theme));
+    }
+
+    /// Quit, unless scanned pages would be lost, then ask first
+    fn quit(&mut self) -> Action {
+        let unsaved = self
+            .device_screens
+            .as_ref()
+            .map_or(0, |device_screens| device_screens.scan.unsaved_pages());
+        if unsaved == 0 {
+            return Action::Quit;
+        }
+
+        self.quit = Some(QuitPopup::new(unsaved, &self.theme));
+        Action::Noop
+    }
+
+    /// Show the keys of the current screen, followed by the ones that work everywhere
+    fn show_help(&mut self) {
+        let mut entries = match &mut self.device_screens {
+            Some(device_screens) => device_screens.current().keys().to_vec(),
+            None => self.device_picker.keys().to_vec(),
+        };
+        if self.device_screens.is_some() {
+            entries.push(("Tab", "switch to the next screen"));
+            entries.push(("d", "choose another scanner"));
+        }
+        entries.push(("l", "show or hide the log"));
+        entries.push(("?", "show this help"));
+        entries.push(("Esc", "quit"));
+
+        self.help = Some(HelpPopup::new(&entries, &self.keys, &self.theme));
     }
 
     fn draw(&mut self, frame: &mut Frame) -> miette::Result<()> {
         let outer_block = Block::new()
             .borders(Borders::all())
-            .title("scannrs - Scanning made easy")
+            .title(Line::styled(
+                "scannrs - Scanning made easy",
+                self.theme.title,
+            ))
             .border_type(BorderType::Thick)
+            .border_style(self.theme.border)
             .padding(Padding::uniform(2));
 
-        let rect = outer_block.inner(frame.area());
+        let inner = outer_block.inner(frame.area());
+        let [rect, log_area, status_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(if self.show_log { 10 } else { 0 }),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
 
         frame.render_widget(outer_block, frame.area());
+        self.draw_status_bar(frame, status_area);
+        if self.show_log {
+            self.log.draw(frame, log_area, &self.theme);
+        }
 
-        let Some(selected_device) = self.config.active_device.as_ref() else {
-            self.device_picker.draw(frame, rect);
-            return Ok(());
-        };
+        match &mut self.device_screens {
+            Some(device_screens) => device_screens.current().draw(frame, rect),
+            None => self.device_picker.draw(frame, rect),
+        }
 
-        frame.render_widget(selected_device, rect);
+        if let Some(help) = &mut self.help {
+            help.draw(frame, frame.area());
+        }
+
+        if let Some(quit) = &mut self.quit {
+            quit.draw(frame, frame.area());
+        }
+
+        if let Some(error) = &mut self.error {
+            error.draw(frame, frame.area());
+        }
 
         Ok(())
     }
 
+    /// The active device and profile, followed by the last thing that happened
+    fn draw_status_bar(&self, frame: &mut Frame, area: Rect) {
+        let device = self.config.active_device.as_deref().unwrap_or("none");
+        let mut spans = vec![
+            Span::styled("Device: ", self.theme.accent),
+            Span::raw(device.to_string()),
+        ];
+        if let Some(profile) = &self.active_profile {
+            spans.push(Span::raw(" │ "));
+            spans.push(Span::styled("Profile: ", self.theme.accent));
+            spans.push(Span::raw(profile.clone()));
+        }
+        if let Some(last) = self.log.last(&self.theme) {
+            spans.push(Span::raw(" │ "));
+            spans.push(last);
+        }
+
+        frame.render_widget(Line::from(spans), area);
+    }
+
+    fn tick(&mut self) -> miette::Result<Action> {
+        let actions = match &mut self.device_screens {
+            Some(device_screens) => vec![
+                device_screens.scan.tick()?,
+                device_screens.options.tick()?,
+                device_screens.preview.tick()?,
+                device_screens.history.tick()?,
+            ],
+            None => vec![self.device_picker.tick()?],
+        };
+
+        for action in actions {
+            match self.handle_action(action)? {
+                None | Some(Action::Noop) => {}
+                Some(action) => return Ok(action),
+            }
+        }
+
+        if self.quit_after_save {
+            if let Some(device_screens) = &self.device_screens {
+                if !device_screens.scan.is_saving() {
+                    // If saving failed, the scan screen shows why and the user can decide again
+                    self.quit_after_save = false;
+                    if device_screens.scan.unsaved_pages() == 0 {
+                        return Ok(Action::Quit);
+                    }
+                }
+            }
+        }
+
+        Ok(Action::Noop)
+    }
+
     fn init(&mut self) -> miette::Result<()> {
         self.device_picker.init()?;
+        if let Some(device_screens) = &mut self.device_screens {
+            device_screens.init()?;
+        }
 
         Ok(())
     }
 
     fn handle_event(&mut self, event: Event) -> miette::Result<Action> {
+        if let Some(error) = &mut self.error {
+            match error.handle_event(Some(event))? {
+                Action::DismissError => self.error = None,
+                Action::Retry(retry) => {
+                    self.error = None;
+                    match retry {
+                        Retry::Init => self.init()?,
+                        Retry::Event(event) => return self.handle_event(event),
+                    }
+                }
+                _ => {}
+            }
+
+            return Ok(Action::Noop);
+        }
+
+        // Clicking a key hint is handled as if its key was pressed
+        let event = match event {
+            Event::Mouse(MouseEvent {
+                kind: MouseEventKind::Down(MouseButton::Left),
+                column,
+                row,
+                ..
+            }) => {
+                let key = match &mut self.device_screens {
+                    Some(device_screens) => device_screens.current().key_at(column, row),
+                    None => self.device_picker.key_at(column, row),
+                };
+                key.map_or(event, |code| {
+                    Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+                })
+            }
+            _ => event,
+        };
+
+        let (captures_input, cancellable) = match &mut self.device_screens {
+            Some(device_screens) => {
+                let current = device_screens.current();
+                (current.captures_input(), current.cancellable())
+            }
+            None => (
+                self.device_picker.captures_input(),
+                self.device_picker.cancellable(),
+            ),
+        };
+
+        // Text that is typed is never remapped
+        let event = match event {
+            Event::Key(key) if !captures_input => Event::Key(KeyEvent {
+                code: self.keys.translate(key.code),
+                ..key
+            }),
+            event => event,
+        };
+
+        if let Some(help) = &mut self.help {
+            if let Action::CloseHelp = help.handle_event(Some(event))? {
+                self.help = None;
+            }
+
+            return Ok(Action::Noop);
+        }
+
+        if let Some(quit) = &mut self.quit {
+            if let Action::ChooseQuit(choice) = quit.handle_event(Some(event))? {
+                self.quit = None;
+                match choice {
+                    QuitChoice::Save => {
+                        if let Some(device_screens) = &mut self.device_screens {
+                            if !device_screens.scan.is_saving() {
+                                device_screens.scan.save();
+                            }
+                            self.quit_after_save = true;
+                        }
+                    }
+                    QuitChoice::Discard => return Ok(Action::Quit),
+                    QuitChoice::Cancel => {}
+                }
+            }
+
+            return Ok(Action::Noop);
+        }
+
         if let Event::Key(KeyEvent {
-            code: KeyCode::Esc,
+            code,
             kind: KeyEventKind::Press,
             ..
         }) = event
         {
-            return Ok(Action::Quit);
+            match code {
+                KeyCode::Esc if !captures_input && !cancellable => return Ok(self.quit()),
+                KeyCode::Char('?') if !captures_input => {
+                    self.show_help();
+                    return Ok(Action::Noop);
+                }
+                KeyCode::Char('l') if !captures_input => {
+                    self.show_log = !self.show_log;
+                    return Ok(Action::Noop);
+                }
+                KeyCode::Tab if !captures_input => {
+                    if let Some(device_screens) = &mut self.device_screens {
+                        device_screens.toggle();
+                        return Ok(Action::Noop);
+                    }
+                }
+                KeyCode::Char('d') if !captures_input && self.device_screens.is_some() => {
+                    self.device_screens = None;
+                    self.active_profile = None;
+                    if let Some(device) = self.config.active_device.take() {
+                        self.device_picker.select_device(&device);
+                    }
+                    self.config.save()?;
+                    return Ok(Action::Noop);
+                }
+                _ => {}
+            }
         }
 
-        if self.config.active_device.is_none() {
-            let action = self.device_picker.handle_event(Some(event))?;
-            if let Some(action) = self.handle_action(action) {
-                return Ok(action);
-            }
+        let action = match &mut self.device_screens {
+            Some(device_screens) => device_screens.current().handle_event(Some(event))?,
+            None => self.device_picker.handle_event(Some(event))?,
+        };
+        if let Some(action) = self.handle_action(action)? {
+            return Ok(action);
         }
 
         Ok(Action::Noop)
     }
 
-    fn handle_action(&mut self, action: Action) -> Option<Action> {
+    fn handle_action(&mut self, action: Action) -> miette::Result<Option<Action>> {
         match action {
-            Action::SetActiveDevice(device) => self.config.active_device = Some(device),
-            _ => return Some(action),
+            Action::SetActiveDevice(device) => {
+                let mut device_screens = DeviceScreens::new(
+                    self.sane_sender.clone(),
+                    device.clone(),
+                    &self.config,
+                    self.picker.clone(),
+                    self.keys.clone(),
+                    self.theme.clone(),
+                );
+                device_screens.init()?;
+                self.device_screens = Some(device_screens);
+                tracing::info!("Switched to {device}");
+                self.config.active_device = Some(device);
+                self.config.save()?;
+            }
+            Action::SetOutputPath(path) => {
+                if let Some(device_screens) = &mut self.device_screens {
+                    device_screens.profiles.set_output(path.clone());
+                }
+                tracing::info!("Saving scans to {}", path.display());
+                self.config.output_path = Some(path);
+                self.config.save()?;
+            }
+            Action::SetOptions(options) => {
+                if let Some(device_screens) = &mut self.device_screens {
+                    device_screens.preview.set_options(options.clone());
+                    device_screens.profiles.set_options(options.clone());
+                    device_screens.scan.set_options(options);
+                }
+            }
+            Action::ApplyProfile(name, profile) => {
+                if let Some(device_screens) = &mut self.device_screens {
+                    tracing::info!("Applied the profile {name}");
+                    self.active_profile = Some(name);
+                    let changes = profile.options.into_iter().collect();
+                    device_screens.options.apply(changes)?;
+
+                    if let Some(output) = profile.output {
+                        device_screens.scan.set_path(output.clone());
+                        return self.handle_action(Action::SetOutputPath(PathBuf::from(output)));
+                    }
+                }
+            }
+            Action::SetProcessing(processing) => {
+                tracing::info!("Changed the post-processing");
+                self.config.processing = processing;
+                self.config.save()?;
+            }
+            Action::ChangeOptions(changes) => {
+                if let Some(device_screens) = &mut self.device_screens {
+                    device_screens.options.apply(changes)?;
+                }
+            }
+            _ => return Ok(Some(action)),
         }
 
-        None
+        Ok(None)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DeviceScreen {
+    Scan,
+    Options,
+    Preview,
+    Profiles,
+    History,
+}
+
+/// The screens operating on the active device, switched between with Tab
+struct DeviceScreens {
+    current: DeviceScreen,
+    scan: ScanScreen,
+    options: OptionsEditor,
+    preview: PreviewScreen,
+    profiles: ProfilesScreen,
+    history: HistoryScreen,
+}
+
+impl DeviceScreens {
+    fn new(
+        sane_sender: SaneSender,
+        device: String,
+        config: &AppConfig,
+        picker: Picker,
+        keys: KeyBindings,
+        theme: Theme,
+    ) -> Self {
+        let mut scan = ScanScreen::new(
+            sane_sender.clone(),
+            device.clone(),
+            config.output_path.clone(),
+            config.processing,
+            picker.clone(),
+            keys.clone(),
+            theme.clone(),
+        );
+        let mut ocr = config.ocr.clone();
+        if let Some(language) = &config.ocr_language {
+            ocr.lang = language.clone();
+        }
+        scan.set_ocr_options(ocr);
+
+        DeviceScreens {
+            current: DeviceScreen::Scan,
+            scan,
+            options: OptionsEditor::new(
+                sane_sender.clone(),
+                device.clone(),
+                keys.clone(),
+                theme.clone(),
+            ),
+            preview: PreviewScreen::new(
+                sane_sender.clone(),
+                device,
+                picker,
+                keys.clone(),
+                theme.clone(),
+            ),
+            profiles: ProfilesScreen::new(config.output_path.clone(), keys.clone(), theme.clone()),
+            history: HistoryScreen::new(sane_sender, keys, theme),
+        }
+    }
+
+    fn init(&mut self) -> miette::Result<()> {
+        self.options.init()?;
+        self.profiles.init()?;
+        self.history.init()
+    }
+
+    fn current(&mut self) -> &mut dyn Component {
+        match self.current {
+            DeviceScreen::Scan => &mut self.scan,
+            DeviceScreen::Options => &mut self.options,
+            DeviceScreen::Preview => &mut self.preview,
+            DeviceScreen::Profiles => &mut self.profiles,
+            DeviceScreen::History => &mut self.history,
+        }
+    }
+
+    fn toggle(&mut self) {
+        self.current = match self.current {
+            DeviceScreen::Scan => DeviceScreen::Options,
+            DeviceScreen::Options => DeviceScreen::Preview,
+            DeviceScreen::Preview => DeviceScreen::Profiles,
+            DeviceScreen::Profiles => DeviceScreen::History,
+            DeviceScreen::History => DeviceScreen::Scan,
+        };
     }
 }
 
@@ -158,45 +934,75 @@ struct Tui {
 }
 
 impl Tui {
-    fn new(sane_sender: Sender<SaneQuery>) -> miette::Result<Tui> {
+    fn new(sane_sender: SaneSender, picker: Picker, log: LogBuffer) -> miette::Result<Tui> {
         Ok(Tui {
             terminal: Terminal::new(CrosstermBackend::new(stdout())).into_diagnostic()?,
-            app: App::new(sane_sender)?,
+            app: App::new(sane_sender, picker, log)?,
         })
     }
 
-    fn run(&mut self) -> miette::Result<()> {
-        self.app.init()?;
+    /// Run the interface until the user quits, errors are shown to the user instead of ending it
+    ///
+    /// The terminal is only redrawn after all events that queued up were handled, so that a burst of progress
+    /// updates does not cause a redraw each.
+    async fn run(
+        &mut self,
+        bus: EventBus,
+        mut events: UnboundedReceiver<AppEvent>,
+    ) -> miette::Result<()> {
+        events::spawn_ticker(bus.clone());
+        events::spawn_terminal_reader(bus);
+
+        if let Err(error) = self.app.init() {
+            self.app.show_error(&error, Some(Retry::Init));
+        }
         self.terminal.clear().into_diagnostic()?;
-        loop {
-            let mut should_break = None;
-            self.terminal
-                .draw(|frame| {
-                    let res = self.app.draw(frame);
-
-                    match res {
-                        Ok(()) => {}
-                        Err(e) => should_break = Some(Err(e)),
-                    }
-                })
-                .into_diagnostic()?;
+        self.draw()?;
 
-            if let Some(res) = should_break {
-                break res?;
+        while let Some(event) = events.recv().await {
+            let mut next = Some(event);
+            while let Some(event) = next {
+                if let Action::Quit = self.handle(event)? {
+                    return self.app.config.save();
+                }
+                next = events.try_recv().ok();
             }
 
-            if event::poll(Duration::from_millis(100)).into_diagnostic()? {
-                let action = match event::read().into_diagnostic()? {
-                    event::Event::Key(key) => self.app.handle_event(Event::Key(key))?,
-                    event::Event::Resize(w, h) => self.app.handle_event(Event::Resize(w, h))?,
-                    _ => Action::Noop,
-                };
+            self.draw()?;
+        }
 
-                match action {
-                    Action::Quit => break,
-                    _ => (),
-                }
-            }
+        self.app.config.save()
+    }
+
+    fn handle(&mut self, event: AppEvent) -> miette::Result<Action> {
+        match event {
+            AppEvent::Terminal(event) => match self.app.handle_event(event.clone()) {
+                Ok(Action::Quit) => return Ok(Action::Quit),
+                Ok(_) => {}
+                Err(error) => self.app.show_error(&error, Some(Retry::Event(event))),
+            },
+            AppEvent::TerminalFailed(error) => return Err(error).into_diagnostic(),
+            AppEvent::Tick | AppEvent::Sane => {}
+        }
+
+        // Answers of the SANE handler are picked up by the screens when they are ticked
+        match self.app.tick() {
+            Ok(Action::Quit) => return Ok(Action::Quit),
+            Ok(_) => {}
+            Err(error) => self.app.show_error(&error, None),
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self) -> miette::Result<()> {
+        let mut draw_error = None;
+        self.terminal
+            .draw(|frame| draw_error = self.app.draw(frame).err())
+            .into_diagnostic()?;
+
+        if let Some(error) = draw_error {
+            self.app.show_error(&error, None);
         }
 
         Ok(())
@@ -207,11 +1013,36 @@ enum Action {
     Quit,
     Noop,
     SetActiveDevice(String),
+    SetOutputPath(PathBuf),
+    SetOptions(HashMap<String, Value>),
+    /// Change options through the options editor
+    ChangeOptions(Vec<(String, Value)>),
+    DismissError,
+    Retry(Retry),
+    CloseHelp,
+    /// Apply the profile with the given name
+    ApplyProfile(String, Profile),
+    ChooseQuit(QuitChoice),
+    /// The post-processing chosen in its menu, which is closed
+    SetProcessing(PostProcessing),
+    /// The directory chosen in the file browser, or `None` if it was cancelled
+    ChooseDirectory(Option<PathBuf>),
+}
+
+/// What to do again when the user asks to retry after an error
+#[derive(Clone, Debug)]
+enum Retry {
+    Init,
+    Event(Event),
 }
 
+#[derive(Clone, Debug)]
 enum Event {
     Key(KeyEvent),
+    Mouse(MouseEvent),
     Resize(u16, u16),
+    /// Text pasted into the terminal, which arrives at once instead of as single key presses
+    Paste(String),
     Quit,
 }
 
@@ -220,10 +1051,53 @@ trait Component {
         Ok(())
     }
 
+    /// Called before every frame, to pick up results of background work
+    fn tick(&mut self) -> miette::Result<Action> {
+        Ok(Action::Noop)
+    }
+
+    /// Whether the component is taking text input, so that keys like Esc should not quit
+    fn captures_input(&self) -> bool {
+        false
+    }
+
+    /// Whether something is running that Esc cancels, instead of quitting
+    fn cancellable(&self) -> bool {
+        false
+    }
+
     fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
         let _ = event;
         Ok(Action::Noop)
     }
 
+    /// The keys of the component and what they do, with their default keys, as shown in the help overlay
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+
+    /// The key of the hint drawn at the given position, clicking it works like pressing the key
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        let _ = (column, row);
+        None
+    }
+
     fn draw(&mut self, frame: &mut Frame, rect: Rect);
 }
+
+/// Pasted text without line breaks and other control characters, as all text inputs hold a single line
+fn pasted_line(text: &str) -> String {
+    text.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// The index of the item of a list drawn without borders into `area` at the given position
+///
+/// `offset` is the index of the first item shown, as kept in the state of the list.
+fn list_item_at(area: Rect, offset: usize, len: usize, column: u16, row: u16) -> Option<usize> {
+    if !area.contains(Position::new(column, row)) {
+        return None;
+    }
+
+    let idx = offset + usize::from(row - area.y);
+    (idx < len).then_some(idx)
+}
@@ -0,0 +1,611 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+
+use miette::IntoDiagnostic;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::crossterm::event::MouseButton;
+use ratatui::crossterm::event::MouseEvent;
+use ratatui::crossterm::event::MouseEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::Clear;
+use ratatui::widgets::LineGauge;
+use ratatui::widgets::List;
+use ratatui::widgets::ListItem;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+use scannrs_core::device::OptionInfo;
+use scannrs_core::device::ValueInfo;
+use scannrs_core::value::Value;
+
+use super::constraint;
+use super::key_hints::KeyHints;
+use super::keys::KeyBindings;
+use super::list_item_at;
+use super::pasted_line;
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+use super::SaneQuery;
+use super::SaneSender;
+
+/// The answer of the SANE handler to a request for the options of a device
+pub(crate) type OptionsResponse = Result<Vec<OptionInfo>, String>;
+
+/// Group entries carry no value, they title the options following them
+fn is_group(option: &OptionInfo) -> bool {
+    option.type_ == "Group"
+}
+
+/// Lists the options of the active device and lets the user change them
+///
+/// Changes are only kept for this session and are applied to the scan started from the scan screen.
+pub struct OptionsEditor {
+    sane_sender: SaneSender,
+
+    device: String,
+    options: Option<Vec<OptionInfo>>,
+    /// The values changed by the user, by option name
+    changes: HashMap<String, Value>,
+    /// A request to the SANE handler that has not been answered yet, with the changes it applies
+    pending: Option<(Receiver<OptionsResponse>, Vec<(String, Value)>)>,
+    /// The value being typed, if the user is currently editing an option
+    editing: Option<String>,
+    /// The selection in the list of allowed values, if the user is currently choosing one
+    choosing: Option<ListState>,
+    error: Option<String>,
+    /// The titles of the groups whose options are hidden
+    collapsed: HashSet<String>,
+    /// The selection among the visible entries, see [`OptionsEditor::visible`]
+    list_state: ListState,
+    /// Where the options and the allowed values were last drawn, to find what was clicked
+    list_area: Rect,
+    choices_area: Rect,
+    hints: KeyHints,
+    theme: Theme,
+}
+
+impl OptionsEditor {
+    pub(crate) fn new(
+        sane_sender: SaneSender,
+        device: String,
+        keys: KeyBindings,
+        theme: Theme,
+    ) -> Self {
+        Self {
+            sane_sender,
+            device,
+            options: None,
+            changes: HashMap::new(),
+            pending: None,
+            editing: None,
+            choosing: None,
+            error: None,
+            collapsed: HashSet::new(),
+            list_state: ListState::default(),
+            list_area: Rect::default(),
+            choices_area: Rect::default(),
+            hints: KeyHints::new(keys),
+            theme,
+        }
+    }
+
+    /// Change several options at once, like the scan area selected in the preview
+    pub(crate) fn apply(&mut self, changes: Vec<(String, Value)>) -> miette::Result<()> {
+        self.request(changes)
+    }
+
+    /// Ask the SANE handler for the options, with the given changes applied on top of the existing ones
+    ///
+    /// The changes of a request that is still running are carried over, as its answer will be ignored.
+    fn request(&mut self, mut changes: Vec<(String, Value)>) -> miette::Result<()> {
+        if let Some((_, pending)) = self.pending.take() {
+            changes.splice(0..0, pending);
+        }
+
+        let mut options = self.changes.clone();
+        options.extend(changes.iter().cloned());
+
+        let (responder, recv) = channel();
+        self.sane_sender
+            .send(SaneQuery::ListOptions {
+                device: self.device.clone(),
+                options,
+                responder,
+            })
+            .into_diagnostic()?;
+        self.pending = Some((recv, changes));
+
+        Ok(())
+    }
+
+    /// The indices of the options that are shown, group headers are always shown but their options only if the
+    /// group is not collapsed
+    fn visible(&self) -> Vec<usize> {
+        let Some(options) = &self.options else {
+            return Vec::new();
+        };
+
+        let mut collapsed = false;
+        options
+            .iter()
+            .enumerate()
+            .filter(|(_, option)| {
+                if is_group(option) {
+                    collapsed = self.collapsed.contains(&option.title);
+                    true
+                } else {
+                    !collapsed
+                }
+            })
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    fn selected(&self) -> Option<&OptionInfo> {
+        let idx = *self.visible().get(self.list_state.selected()?)?;
+        self.options.as_ref()?.get(idx)
+    }
+
+    /// Collapse or expand the highlighted group, returns false if no group is highlighted
+    fn set_collapsed(&mut self, collapsed: Option<bool>) -> bool {
+        let Some(title) = self
+            .selected()
+            .filter(|option| is_group(option))
+            .map(|option| option.title.clone())
+        else {
+            return false;
+        };
+
+        let collapsed = collapsed.unwrap_or(!self.collapsed.contains(&title));
+        if collapsed {
+            self.collapsed.insert(title);
+        } else {
+            self.collapsed.remove(&title);
+        }
+
+        true
+    }
+
+    /// Collapse all groups, or expand them all if they already are, keeping the group of the highlighted option
+    /// highlighted
+    fn toggle_all(&mut self) {
+        let Some(options) = &self.options else {
+            return;
+        };
+        let groups = options
+            .iter()
+            .filter(|option| is_group(option))
+            .map(|option| option.title.clone())
+            .collect::<HashSet<_>>();
+
+        let selected = self
+            .list_state
+            .selected()
+            .and_then(|idx| self.visible().get(idx).copied());
+        let group = selected.and_then(|selected| {
+            options[..=selected]
+                .iter()
+                .rposition(|option| is_group(option))
+        });
+
+        if self.collapsed.is_superset(&groups) {
+            self.collapsed.clear();
+        } else {
+            self.collapsed = groups;
+        }
+
+        let position = group.and_then(|group| self.visible().iter().position(|idx| *idx == group));
+        self.list_state.select(position.or(Some(0)));
+    }
+
+    fn is_editable(option: &OptionInfo) -> bool {
+        option.active && option.settable && option.value.is_some()
+    }
+
+    fn change(&mut self, value: impl Into<Value>) -> miette::Result<Action> {
+        let Some(option) = self.selected() else {
+            return Ok(Action::Noop);
+        };
+
+        let name = option.name.clone();
+        self.request(vec![(name, value.into())])?;
+
+        Ok(Action::Noop)
+    }
+
+    /// Scrolling moves the selection, clicking the selected entry works like Enter
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> miette::Result<Action> {
+        if self.editing.is_some() {
+            return Ok(Action::Noop);
+        }
+
+        let len = match &self.choosing {
+            Some(_) => self
+                .selected()
+                .and_then(constraint::choices)
+                .map_or(0, <[_]>::len),
+            None => self.visible().len(),
+        };
+        let (area, state) = match &mut self.choosing {
+            Some(choosing) => (self.choices_area, choosing),
+            None => (self.list_area, &mut self.list_state),
+        };
+
+        match mouse.kind {
+            MouseEventKind::ScrollUp => state.select_previous(),
+            MouseEventKind::ScrollDown => state.select_next(),
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(idx) = list_item_at(area, state.offset(), len, mouse.column, mouse.row)
+                else {
+                    return Ok(Action::Noop);
+                };
+
+                if state.selected() == Some(idx) {
+                    let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+                    return self.handle_event(Some(Event::Key(enter)));
+                }
+                state.select(Some(idx));
+            }
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+}
+
+impl Component for OptionsEditor {
+    fn init(&mut self) -> miette::Result<()> {
+        self.request(vec![])
+    }
+
+    fn tick(&mut self) -> miette::Result<Action> {
+        let Some((recv, _)) = &self.pending else {
+            return Ok(Action::Noop);
+        };
+
+        let response = match recv.try_recv() {
+            Ok(response) => response,
+            Err(TryRecvError::Empty) => return Ok(Action::Noop),
+            Err(TryRecvError::Disconnected) => Err(String::from(
+                "The scanner did not answer the request for its options",
+            )),
+        };
+        let Some((_, changes)) = self.pending.take() else {
+            return Ok(Action::Noop);
+        };
+
+        match response {
+            Ok(options) => {
+                self.options = Some(options);
+                self.error = None;
+                if !changes.is_empty() {
+                    self.changes.extend(changes);
+                    return Ok(Action::SetOptions(self.changes.clone()));
+                }
+            }
+            Err(error) => self.error = Some(error),
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn captures_input(&self) -> bool {
+        self.editing.is_some() || self.choosing.is_some()
+    }
+
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("↑/↓", "highlight an option"),
+            (
+                "Enter",
+                "change the option, toggle it if it is a switch or collapse and expand a group",
+            ),
+            ("←/→", "step the value, or collapse and expand a group"),
+            ("c", "collapse or expand all groups"),
+            ("r", "reload the options from the scanner"),
+            ("Click", "highlight an option, click again to change it"),
+        ]
+    }
+
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.hints.key_at(column, row)
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let code = match event {
+            Some(Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            })) => code,
+            Some(Event::Mouse(mouse)) => return self.handle_mouse(mouse),
+            Some(Event::Paste(text)) => {
+                if let Some(editing) = &mut self.editing {
+                    editing.push_str(&pasted_line(&text));
+                }
+                return Ok(Action::Noop);
+            }
+            _ => return Ok(Action::Noop),
+        };
+
+        if let Some(editing) = &mut self.editing {
+            match code {
+                KeyCode::Char(c) => editing.push(c),
+                KeyCode::Backspace => {
+                    editing.pop();
+                }
+                KeyCode::Esc => self.editing = None,
+                KeyCode::Enter => {
+                    let Some(value) = self.editing.take() else {
+                        return Ok(Action::Noop);
+                    };
+
+                    // Typed numbers are brought into the range of the option instead of being rejected by SANE
+                    let value = Value::from(value);
+                    let value = match (
+                        self.selected().filter(|o| constraint::is_steppable(o)),
+                        &value,
+                    ) {
+                        (
+                            Some(option),
+                            Value::Number {
+                                value: number,
+                                unit,
+                            },
+                        ) => {
+                            if unit.is_some_and(|unit| unit.name() != option.unit) {
+                                let error = scannrs_core::Error::WrongUnit {
+                                    option: option.name.clone(),
+                                    value: value.to_string(),
+                                    expected: option.unit.unwrap_or("no unit"),
+                                };
+                                self.error = Some(error.to_string());
+                                return Ok(Action::Noop);
+                            }
+                            constraint::snap(option, *number).map_or(value.clone(), Value::from)
+                        }
+                        (Some(_), _) => {
+                            self.error = Some(format!("'{value}' is not a number"));
+                            return Ok(Action::Noop);
+                        }
+                        (None, _) => value,
+                    };
+
+                    return self.change(value);
+                }
+                _ => {}
+            }
+
+            return Ok(Action::Noop);
+        }
+
+        if let Some(choosing) = &mut self.choosing {
+            match code {
+                KeyCode::Up => choosing.select_previous(),
+                KeyCode::Down => choosing.select_next(),
+                KeyCode::Esc => self.choosing = None,
+                KeyCode::Enter => {
+                    let chosen = self
+                        .choosing
+                        .take()
+                        .and_then(|choosing| choosing.selected())
+                        .and_then(|idx| constraint::choices(self.selected()?)?.get(idx))
+                        .map(ToString::to_string);
+
+                    if let Some(value) = chosen {
+                        return self.change(value);
+                    }
+                }
+                _ => {}
+            }
+
+            return Ok(Action::Noop);
+        }
+
+        match code {
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Char('r') if self.pending.is_none() => self.request(vec![])?,
+            KeyCode::Char('c') => self.toggle_all(),
+            KeyCode::Left | KeyCode::Right if self.set_collapsed(Some(code == KeyCode::Left)) => {}
+            KeyCode::Enter if self.set_collapsed(None) => {}
+            KeyCode::Left | KeyCode::Right if self.pending.is_none() => {
+                let steps = if code == KeyCode::Left { -1 } else { 1 };
+                let value = self
+                    .selected()
+                    .filter(|o| Self::is_editable(o))
+                    .and_then(|option| constraint::step(option, steps));
+
+                if let Some(value) = value {
+                    return self.change(value);
+                }
+            }
+            KeyCode::Enter if self.pending.is_none() => {
+                let Some(option) = self.selected().filter(|o| Self::is_editable(o)) else {
+                    return Ok(Action::Noop);
+                };
+
+                if let Some(values) = constraint::choices(option) {
+                    let current = option.value.as_ref().map(ToString::to_string);
+                    let selected = values
+                        .iter()
+                        .position(|value| Some(value.to_string()) == current);
+                    self.choosing = Some(ListState::default().with_selected(selected.or(Some(0))));
+                    return Ok(Action::Noop);
+                }
+
+                match &option.value {
+                    Some(ValueInfo::Bool(value)) => return self.change(!value),
+                    Some(value) => self.editing = Some(value.to_string()),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [main_area, status_area, help_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(rect);
+
+        let Some(options) = self.options.as_ref() else {
+            frame.render_widget(
+                Line::from(format!("Loading the options of '{}'...", self.device)),
+                main_area,
+            );
+            return;
+        };
+
+        let [list_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main_area);
+
+        let visible = self.visible();
+        let items = visible.iter().enumerate().map(|(row, idx)| {
+            let option = &options[*idx];
+            if is_group(option) {
+                let line = if self.collapsed.contains(&option.title) {
+                    let hidden = options[idx + 1..]
+                        .iter()
+                        .take_while(|option| !is_group(option))
+                        .count();
+                    format!("▸ {} ({hidden} options)", option.title)
+                } else {
+                    format!("▾ {}", option.title)
+                };
+                return ListItem::new(Line::from(line.bold()));
+            }
+
+            let value = match (&self.editing, &option.value) {
+                (Some(editing), _) if self.list_state.selected() == Some(row) => {
+                    format!("{editing}_")
+                }
+                (_, Some(value)) => value.to_string(),
+                (_, None) => String::new(),
+            };
+            let changed = self.changes.contains_key(&option.name);
+            let line = Line::from(format!(
+                "  {}{}: {value}",
+                option.title,
+                if changed { " *" } else { "" }
+            ));
+
+            if OptionsEditor::is_editable(option) {
+                ListItem::new(line)
+            } else {
+                ListItem::new(line.dim())
+            }
+        });
+
+        let list = List::new(items)
+            .block(Block::new().borders(Borders::RIGHT))
+            .highlight_style(self.theme.highlight);
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+        self.list_area = list_area;
+
+        let selected = self
+            .list_state
+            .selected()
+            .and_then(|row| visible.get(row))
+            .and_then(|idx| options.get(*idx));
+        if let Some(option) = selected {
+            let mut details = vec![
+                Line::from(option.name.as_str().bold()),
+                Line::from(option.description.as_str()),
+                Line::default(),
+                Line::from(format!("Type: {}", option.type_)),
+            ];
+            if let Some(unit) = option.unit {
+                details.push(Line::from(format!("Unit: {unit}")));
+            }
+            if let Some(allowed) = constraint::describe(option) {
+                details.push(Line::from(format!("Allowed: {allowed}")));
+            }
+            if !option.active {
+                details.push(Line::from("Inactive".dim()));
+            } else if !option.settable {
+                details.push(Line::from("Read-only".dim()));
+            }
+
+            let details_area = details_area.inner(ratatui::layout::Margin::new(1, 0));
+            let [text_area, gauge_area] =
+                Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(details_area);
+            frame.render_widget(Paragraph::new(details).wrap(Wrap { trim: true }), text_area);
+
+            if let Some(position) = constraint::position(option) {
+                frame.render_widget(
+                    LineGauge::default()
+                        .filled_style(self.theme.accent)
+                        .ratio(position),
+                    gauge_area,
+                );
+            }
+
+            if let (Some(choosing), Some(values)) =
+                (&mut self.choosing, constraint::choices(option))
+            {
+                let height = (values.len() as u16 + 2).min(main_area.height);
+                let width = values
+                    .iter()
+                    .map(|value| value.to_string().chars().count() as u16 + 6)
+                    .max()
+                    .unwrap_or_default()
+                    .max(20)
+                    .min(main_area.width);
+                let [_, popup_area, _] = Layout::vertical([
+                    Constraint::Fill(1),
+                    Constraint::Length(height),
+                    Constraint::Fill(1),
+                ])
+                .areas(main_area);
+                let [_, popup_area, _] = Layout::horizontal([
+                    Constraint::Fill(1),
+                    Constraint::Length(width),
+                    Constraint::Fill(1),
+                ])
+                .areas(popup_area);
+
+                let block = Block::bordered().title(option.title.as_str());
+                self.choices_area = block.inner(popup_area);
+                let list = List::new(values.iter().map(ToString::to_string))
+                    .block(block)
+                    .highlight_style(self.theme.highlight);
+                frame.render_widget(Clear, popup_area);
+                frame.render_stateful_widget(list, popup_area, choosing);
+            }
+        }
+
+        if let Some(error) = &self.error {
+            frame.render_widget(Line::styled(error.as_str(), self.theme.error), status_area);
+        } else if self.pending.is_some() {
+            frame.render_widget(Line::from("Applying...".dim()), status_area);
+        }
+
+        let help = if self.editing.is_some() || self.choosing.is_some() {
+            "Enter: confirm  Esc: cancel"
+        } else {
+            "Enter: change  ←/→: adjust  c: collapse all  r: reload  Tab: next  d: device  ?: help  Esc: quit"
+        };
+        self.hints.draw(frame, help_area, help);
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+
+use miette::IntoDiagnostic;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui_image::picker::Picker;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::device::ValueInfo;
+use scannrs_core::scan::apply_options;
+use scannrs_core::scan::read_image;
+use scannrs_core::value::Value;
+
+use super::constraint;
+use super::image_view::ImageView;
+use super::key_hints::KeyHints;
+use super::keys::KeyBindings;
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+use super::SaneQuery;
+use super::SaneSender;
+use crate::commands::options::option_infos;
+use crate::commands::scan::prepare_device;
+use crate::error::ScannrsError;
+
+/// The options describing the scan area, in the order left, top, right, bottom
+const AREA_OPTIONS: [&str; 4] = ["tl-x", "tl-y", "br-x", "br-y"];
+
+/// The resolution previews are scanned at, or the closest one the scanner supports
+const PREVIEW_DPI: f64 = 75.0;
+
+/// How far the selection moves with a single key press, as a fraction of the bed
+const SELECTION_STEP: f64 = 0.02;
+
+/// A low resolution scan of the whole bed
+pub(crate) struct Preview {
+    view: ImageView,
+    /// The extent of the bed in mm, in the order left, top, right, bottom
+    bed: [f64; 4],
+    /// Whether the scanner takes the scan area in whole mm
+    integer: bool,
+}
+
+/// The answer of the SANE handler to a request for a preview
+pub(crate) type PreviewResponse = Result<Preview, String>;
+
+/// Scan the whole bed at a low resolution, ignoring the currently selected scan area
+pub(crate) fn take_preview(
+    backend: &dyn ScanBackend,
+    device: &str,
+    options: &HashMap<String, Value>,
+) -> miette::Result<Preview> {
+    let mut options = options.clone();
+    for name in AREA_OPTIONS {
+        options.remove(name);
+    }
+
+    let mut handle = prepare_device(backend, device, None, &options)?;
+    let infos = option_infos(handle.as_ref())?;
+    let find = |name: &str| {
+        infos
+            .iter()
+            .find(|o| o.name == name && o.active && o.settable)
+    };
+
+    let mut overrides = HashMap::new();
+    let mut bed = [0.0; 4];
+    let mut integer = false;
+    for (idx, name) in AREA_OPTIONS.into_iter().enumerate() {
+        let (option, (min, max)) = find(name)
+            .and_then(|option| Some((option, constraint::bounds(option)?)))
+            .ok_or_else(|| ScannrsError::ScanAreaUnsupported {
+                name: device.to_string(),
+            })
+            .into_diagnostic()?;
+
+        integer = matches!(option.value, Some(ValueInfo::Int(_)));
+        bed[idx] = if idx < 2 { min } else { max };
+        overrides.insert(name.to_string(), mm_value(bed[idx], integer));
+    }
+    if find("preview").is_some() {
+        overrides.insert(String::from("preview"), Value::Bool(true));
+    }
+    if let Some(resolution) = find("resolution").and_then(|o| constraint::closest(o, PREVIEW_DPI)) {
+        overrides.insert(String::from("resolution"), Value::from(resolution));
+    }
+    apply_options(handle.as_mut(), &overrides)?;
+
+    Ok(Preview {
+        view: ImageView::new(read_image(handle.as_mut())?),
+        bed,
+        integer,
+    })
+}
+
+/// A position on the bed as the value of a scan area option, rounded to whole or tenths of mm
+fn mm_value(value: f64, integer: bool) -> Value {
+    if integer {
+        Value::from(value.round())
+    } else {
+        Value::from((value * 10.0).round() / 10.0)
+    }
+}
+
+/// Shows a preview of the bed and lets the user select the area to scan with the keyboard
+pub struct PreviewScreen {
+    sane_sender: SaneSender,
+
+    device: String,
+    /// The options changed in the options editor
+    options: HashMap<String, Value>,
+    preview: Option<Preview>,
+    pending: Option<Receiver<PreviewResponse>>,
+    error: Option<String>,
+    /// The selected area in mm, in the order left, top, right, bottom
+    selection: [f64; 4],
+    picker: Picker,
+    hints: KeyHints,
+    theme: Theme,
+}
+
+impl PreviewScreen {
+    pub(crate) fn new(
+        sane_sender: SaneSender,
+        device: String,
+        picker: Picker,
+        keys: KeyBindings,
+        theme: Theme,
+    ) -> Self {
+        Self {
+            sane_sender,
+            device,
+            options: HashMap::new(),
+            preview: None,
+            pending: None,
+            error: None,
+            selection: [0.0; 4],
+            picker,
+            hints: KeyHints::new(keys),
+            theme,
+        }
+    }
+
+    pub(crate) fn set_options(&mut self, options: HashMap<String, Value>) {
+        self.options = options;
+    }
+
+    fn request(&mut self) -> miette::Result<()> {
+        let (responder, recv) = channel();
+        self.sane_sender
+            .send(SaneQuery::Preview {
+                device: self.device.clone(),
+                options: self.options.clone(),
+                responder,
+            })
+            .into_diagnostic()?;
+        self.pending = Some(recv);
+        self.error = None;
+
+        Ok(())
+    }
+
+    /// Move the whole selection, or only its right and bottom edges when `resize` is set
+    fn adjust(&mut self, dx: f64, dy: f64, resize: bool) {
+        let Some(preview) = &self.preview else {
+            return;
+        };
+        let [left, top, right, bottom] = preview.bed;
+        let (dx, dy) = (dx * (right - left), dy * (bottom - top));
+        let [sel_left, sel_top, sel_right, sel_bottom] = &mut self.selection;
+
+        if resize {
+            let min_width = (right - left) * SELECTION_STEP;
+            let min_height = (bottom - top) * SELECTION_STEP;
+            *sel_right = (*sel_right + dx).clamp(*sel_left + min_width, right);
+            *sel_bottom = (*sel_bottom + dy).clamp(*sel_top + min_height, bottom);
+        } else {
+            let dx = dx.clamp(left - *sel_left, right - *sel_right);
+            let dy = dy.clamp(top - *sel_top, bottom - *sel_bottom);
+            *sel_left += dx;
+            *sel_right += dx;
+            *sel_top += dy;
+            *sel_bottom += dy;
+        }
+    }
+
+    /// The selection as changes to the scan area options
+    fn selection_options(&self) -> Option<Vec<(String, Value)>> {
+        let preview = self.preview.as_ref()?;
+
+        Some(
+            AREA_OPTIONS
+                .into_iter()
+                .zip(self.selection)
+                .map(|(name, value)| (name.to_string(), mm_value(value, preview.integer)))
+                .collect(),
+        )
+    }
+}
+
+impl Component for PreviewScreen {
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("p", "take a preview"),
+            ("←↑↓→", "move the selection"),
+            ("Shift+←↑↓→", "resize the selection"),
+            ("a", "select the whole bed"),
+            ("Enter", "scan the selected area"),
+        ]
+    }
+
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.hints.key_at(column, row)
+    }
+
+    fn tick(&mut self) -> miette::Result<Action> {
+        let Some(recv) = &self.pending else {
+            return Ok(Action::Noop);
+        };
+
+        let response = match recv.try_recv() {
+            Ok(response) => response,
+            Err(TryRecvError::Empty) => return Ok(Action::Noop),
+            Err(TryRecvError::Disconnected) => {
+                Err(String::from("The preview stopped without a result"))
+            }
+        };
+        self.pending = None;
+
+        match response {
+            Ok(preview) => {
+                if self.preview.is_none() {
+                    self.selection = preview.bed;
+                }
+                self.preview = Some(preview);
+            }
+            Err(error) => self.error = Some(error),
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        let resize = modifiers.contains(KeyModifiers::SHIFT);
+        match code {
+            KeyCode::Char('p') if self.pending.is_none() => self.request()?,
+            KeyCode::Char('a') => {
+                if let Some(preview) = &self.preview {
+                    self.selection = preview.bed;
+                }
+            }
+            KeyCode::Left => self.adjust(-SELECTION_STEP, 0.0, resize),
+            KeyCode::Right => self.adjust(SELECTION_STEP, 0.0, resize),
+            KeyCode::Up => self.adjust(0.0, -SELECTION_STEP, resize),
+            KeyCode::Down => self.adjust(0.0, SELECTION_STEP, resize),
+            KeyCode::Enter => {
+                if let Some(changes) = self.selection_options() {
+                    return Ok(Action::ChangeOptions(changes));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [image_area, status_area, help_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(rect);
+
+        let help = "p: preview  ←↑↓→: move  Shift+←↑↓→: resize  a: all  Enter: use selection  Tab: next  d: device  ?: help  Esc: quit";
+        self.hints.draw(frame, help_area, help);
+
+        if let Some(error) = &self.error {
+            frame.render_widget(Line::styled(error.as_str(), self.theme.error), status_area);
+        } else if self.pending.is_some() {
+            frame.render_widget(Line::from("Scanning preview...".dim()), status_area);
+        }
+
+        let Some(preview) = &mut self.preview else {
+            if self.pending.is_none() {
+                frame.render_widget(Line::from("Press p to take a preview"), image_area);
+            }
+            return;
+        };
+
+        let area = preview.view.draw(&mut self.picker, frame, image_area);
+
+        // Map the selection from mm onto the cells the preview is drawn in
+        let [left, top, right, bottom] = preview.bed;
+        let col = |mm: f64| {
+            area.x + ((mm - left) / (right - left) * f64::from(area.width)).round() as u16
+        };
+        let row = |mm: f64| {
+            area.y + ((mm - top) / (bottom - top) * f64::from(area.height)).round() as u16
+        };
+        let [sel_left, sel_top, sel_right, sel_bottom] = self.selection;
+        let (x0, y0) = (col(sel_left), row(sel_top));
+        let (x1, y1) = (col(sel_right).max(x0 + 2), row(sel_bottom).max(y0 + 2));
+        let selection_area = Rect::new(x0, y0, x1 - x0, y1 - y0).intersection(area);
+        frame.render_widget(
+            Block::bordered().border_style(self.theme.accent),
+            selection_area,
+        );
+
+        if self.error.is_none() && self.pending.is_none() {
+            frame.render_widget(
+                Line::from(format!(
+                    "Selection: {sel_left:.1}, {sel_top:.1} to {sel_right:.1}, {sel_bottom:.1} mm"
+                )),
+                status_area,
+            );
+        }
+    }
+}
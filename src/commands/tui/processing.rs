@@ -0,0 +1,104 @@
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Flex;
+use ratatui::layout::Layout;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
+use ratatui::widgets::List;
+use ratatui::widgets::ListState;
+use scannrs_core::postprocess::PostProcessing;
+
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+
+/// Lets the user choose the post-processing applied to every scanned page
+pub struct ProcessingMenu {
+    processing: PostProcessing,
+    list_state: ListState,
+    theme: Theme,
+}
+
+impl ProcessingMenu {
+    pub(crate) fn new(processing: PostProcessing, theme: Theme) -> Self {
+        Self {
+            processing,
+            list_state: ListState::default().with_selected(Some(0)),
+            theme,
+        }
+    }
+
+    fn toggle(&mut self) {
+        let processing = &mut self.processing;
+        match self.list_state.selected() {
+            Some(0) => processing.skip_blank = !processing.skip_blank,
+            Some(1) => processing.deskew = !processing.deskew,
+            Some(2) => processing.autocrop = !processing.autocrop,
+            Some(3) => processing.rotation = processing.rotation.next(),
+            _ => {}
+        }
+    }
+}
+
+fn checkbox(checked: bool, label: &str) -> String {
+    format!("[{}] {label}", if checked { 'x' } else { ' ' })
+}
+
+impl Component for ProcessingMenu {
+    fn captures_input(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        match code {
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Enter | KeyCode::Char(' ') => self.toggle(),
+            KeyCode::Esc => return Ok(Action::SetProcessing(self.processing)),
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [area] = Layout::horizontal([Constraint::Length(50)])
+            .flex(Flex::Center)
+            .areas(rect);
+        let [area] = Layout::vertical([Constraint::Length(6)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let items = [
+            checkbox(self.processing.skip_blank, "Skip blank pages"),
+            checkbox(self.processing.deskew, "Straighten tilted pages"),
+            checkbox(self.processing.autocrop, "Crop to the content"),
+            format!("Rotation: {}", self.processing.rotation.name()),
+        ];
+
+        let block = Block::bordered()
+            .title(Line::styled(" Post-processing ", self.theme.title))
+            .title_bottom(Line::from(" Space: change  Esc: done ").dim())
+            .border_style(self.theme.border);
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(self.theme.highlight);
+
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut self.list_state);
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::List;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+use scannrs_core::value::Value;
+
+use super::key_hints::KeyHints;
+use super::keys::KeyBindings;
+use super::pasted_line;
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+use crate::config::Config;
+use crate::config::Profile;
+
+/// Lists the profiles from the configuration, applies them and saves the current options as one
+pub struct ProfilesScreen {
+    config: Config,
+    /// The options changed in the options editor
+    options: HashMap<String, Value>,
+    output: Option<PathBuf>,
+    /// The profile that was applied last
+    active: Option<String>,
+    /// The name being typed, if the user is currently creating a profile
+    naming: Option<String>,
+    list_state: ListState,
+    hints: KeyHints,
+    theme: Theme,
+}
+
+impl ProfilesScreen {
+    pub(crate) fn new(output: Option<PathBuf>, keys: KeyBindings, theme: Theme) -> Self {
+        Self {
+            config: Config::default(),
+            options: HashMap::new(),
+            output,
+            active: None,
+            naming: None,
+            list_state: ListState::default(),
+            hints: KeyHints::new(keys),
+            theme,
+        }
+    }
+
+    pub(crate) fn set_options(&mut self, options: HashMap<String, Value>) {
+        self.options = options;
+    }
+
+    pub(crate) fn set_output(&mut self, output: PathBuf) {
+        self.output = Some(output);
+    }
+
+    fn selected(&self) -> Option<(&String, &Profile)> {
+        self.config.profiles.iter().nth(self.list_state.selected()?)
+    }
+
+    /// The current options and output path as a profile
+    fn current(&self) -> Profile {
+        Profile {
+            options: self.options.clone().into_iter().collect(),
+            output: self
+                .output
+                .as_ref()
+                .map(|output| output.to_string_lossy().to_string()),
+            destinations: Vec::new(),
+            ocr: None,
+        }
+    }
+
+    /// Store the profile under the given name, replacing an existing one
+    fn save(&mut self, name: String) -> miette::Result<()> {
+        // Reload first, so that changes made outside of the TUI are kept
+        self.config = Config::load()?;
+        // Destinations and text recognition cannot be edited in the TUI, so the ones of the configuration are kept
+        let mut profile = self.current();
+        if let Some(existing) = self.config.profiles.get(&name) {
+            profile.destinations = existing.destinations.clone();
+            profile.ocr = existing.ocr.clone();
+        }
+        self.config.profiles.insert(name.clone(), profile);
+        self.config.save()?;
+
+        let position = self.config.profiles.keys().position(|n| *n == name);
+        self.list_state.select(position);
+        self.active = Some(name);
+
+        Ok(())
+    }
+
+    fn delete(&mut self) -> miette::Result<()> {
+        let Some((name, _)) = self.selected() else {
+            return Ok(());
+        };
+        let name = name.clone();
+
+        self.config = Config::load()?;
+        self.config.profiles.remove(&name);
+        self.config.save()?;
+
+        if self.active.as_ref() == Some(&name) {
+            self.active = None;
+        }
+
+        Ok(())
+    }
+}
+
+impl Component for ProfilesScreen {
+    fn init(&mut self) -> miette::Result<()> {
+        self.config = Config::load()?;
+        if !self.config.profiles.is_empty() {
+            self.list_state.select(Some(0));
+        }
+
+        Ok(())
+    }
+
+    fn captures_input(&self) -> bool {
+        self.naming.is_some()
+    }
+
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("↑/↓", "highlight a profile"),
+            ("Enter", "apply the highlighted profile"),
+            (
+                "n",
+                "create a profile from the current options and output path",
+            ),
+            (
+                "w",
+                "overwrite the highlighted profile with the current options and output path",
+            ),
+            ("x", "delete the highlighted profile"),
+        ]
+    }
+
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.hints.key_at(column, row)
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        if let (Some(naming), Some(Event::Paste(text))) = (&mut self.naming, &event) {
+            naming.push_str(&pasted_line(text));
+            return Ok(Action::Noop);
+        }
+
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        if let Some(naming) = &mut self.naming {
+            match code {
+                KeyCode::Char(c) => naming.push(c),
+                KeyCode::Backspace => {
+                    naming.pop();
+                }
+                KeyCode::Esc => self.naming = None,
+                KeyCode::Enter => {
+                    if let Some(name) = self.naming.take().filter(|n| !n.trim().is_empty()) {
+                        self.save(name.trim().to_string())?;
+                    }
+                }
+                _ => {}
+            }
+
+            return Ok(Action::Noop);
+        }
+
+        match code {
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Char('n') => self.naming = Some(String::new()),
+            KeyCode::Char('w') => {
+                if let Some(name) = self.selected().map(|(name, _)| name.clone()) {
+                    self.save(name)?;
+                }
+            }
+            KeyCode::Char('x') => self.delete()?,
+            KeyCode::Enter => {
+                let selected = self
+                    .selected()
+                    .map(|(name, profile)| (name.clone(), profile.clone()));
+                if let Some((name, profile)) = selected {
+                    self.active = Some(name.clone());
+                    return Ok(Action::ApplyProfile(name, profile));
+                }
+            }
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [main_area, status_area, help_area] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(rect);
+        let [list_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main_area);
+
+        let items = self.config.profiles.keys().map(|name| {
+            if self.active.as_ref() == Some(name) {
+                Line::from(format!("{name} (active)"))
+            } else {
+                Line::from(name.as_str())
+            }
+        });
+        let list = List::new(items)
+            .block(Block::new().borders(Borders::RIGHT))
+            .highlight_style(self.theme.highlight);
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        if self.config.profiles.is_empty() {
+            frame.render_widget(
+                Line::from("No profiles yet, press n to save the current options as one").dim(),
+                list_area,
+            );
+        }
+
+        if let Some((_, profile)) = self.selected() {
+            let mut details = vec![Line::from("Options".bold())];
+            details.extend(
+                profile
+                    .options
+                    .iter()
+                    .map(|(name, value)| Line::from(format!("  {name} = {value}"))),
+            );
+            if let Some(output) = &profile.output {
+                details.push(Line::default());
+                details.push(Line::from(vec!["Output: ".bold(), output.as_str().into()]));
+            }
+
+            let details_area = details_area.inner(ratatui::layout::Margin::new(1, 0));
+            frame.render_widget(
+                Paragraph::new(details).wrap(Wrap { trim: false }),
+                details_area,
+            );
+        }
+
+        if let Some(naming) = &self.naming {
+            frame.render_widget(
+                Line::from(vec![
+                    "Name: ".bold(),
+                    naming.as_str().into(),
+                    "_".slow_blink(),
+                ]),
+                status_area,
+            );
+        }
+
+        let help = if self.naming.is_some() {
+            "Enter: save  Esc: cancel"
+        } else {
+            "Enter: apply  n: new  w: overwrite  x: delete  Tab: next  d: device  ?: help  Esc: quit"
+        };
+        self.hints.draw(frame, help_area, help);
+    }
+}
@@ -0,0 +1,92 @@
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::layout::Constraint;
+use ratatui::layout::Flex;
+use ratatui::layout::Layout;
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::widgets::Block;
+use ratatui::widgets::Clear;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+
+/// What to do with the pages that were not saved yet when quitting
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QuitChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// Asks before quitting would lose scanned pages
+pub struct QuitPopup {
+    unsaved: usize,
+    border: Style,
+    title: Style,
+}
+
+impl QuitPopup {
+    pub(crate) fn new(unsaved: usize, theme: &Theme) -> Self {
+        QuitPopup {
+            unsaved,
+            border: theme.border,
+            title: theme.title,
+        }
+    }
+}
+
+impl Component for QuitPopup {
+    fn captures_input(&self) -> bool {
+        true
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        let Some(Event::Key(KeyEvent {
+            code,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        Ok(match code {
+            KeyCode::Char('w') => Action::ChooseQuit(QuitChoice::Save),
+            KeyCode::Char('x') => Action::ChooseQuit(QuitChoice::Discard),
+            KeyCode::Esc => Action::ChooseQuit(QuitChoice::Cancel),
+            _ => Action::Noop,
+        })
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [area] = Layout::horizontal([Constraint::Length(60)])
+            .flex(Flex::Center)
+            .areas(rect);
+        let [area] = Layout::vertical([Constraint::Length(5)])
+            .flex(Flex::Center)
+            .areas(area);
+
+        let block = Block::bordered()
+            .title(Line::styled(" Quit ", self.title))
+            .title_bottom(Line::from(" w: save and quit  x: discard and quit  Esc: cancel ").dim())
+            .border_style(self.border);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{} scanned page(s) have not been saved yet, they are lost when quitting.",
+                self.unsaved
+            ))
+            .block(block)
+            .wrap(Wrap { trim: true }),
+            area,
+        );
+    }
+}
@@ -0,0 +1,1098 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
+use std::time::Instant;
+
+use miette::IntoDiagnostic;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::crossterm::event::KeyEvent;
+use ratatui::crossterm::event::KeyEventKind;
+use ratatui::crossterm::event::KeyModifiers;
+use ratatui::layout::Constraint;
+use ratatui::layout::Layout;
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use ratatui::text::Line;
+use ratatui::text::Span;
+use ratatui::widgets::Block;
+use ratatui::widgets::Borders;
+use ratatui::widgets::Gauge;
+use ratatui::widgets::List;
+use ratatui::widgets::ListItem;
+use ratatui::widgets::ListState;
+use ratatui::widgets::Paragraph;
+use ratatui::widgets::Wrap;
+use ratatui_image::picker::Picker;
+use scannrs_core::device::OptionInfo;
+use scannrs_core::device::ValueInfo;
+use scannrs_core::ocr::OcrOptions;
+use scannrs_core::output::Format;
+use scannrs_core::output::Page;
+use scannrs_core::postprocess::PostProcessing;
+use scannrs_core::postprocess::Rotation;
+use scannrs_core::progress::ScanEvent;
+use scannrs_core::progress::Stage;
+use scannrs_core::scan::CancellationToken;
+use scannrs_core::value::Value;
+
+use super::clipboard;
+use super::file_browser::FileBrowser;
+use super::image_view::ImageView;
+use super::key_hints::KeyHints;
+use super::keys::KeyBindings;
+use super::pasted_line;
+use super::processing::ProcessingMenu;
+use super::theme::Theme;
+use super::Action;
+use super::Component;
+use super::Event;
+use super::SaneQuery;
+use super::SaneSender;
+use crate::commands::recognize_page;
+use crate::commands::scan::save_pages;
+use crate::commands::scan::ScanSource;
+use crate::commands::scan::ScanSummary;
+use crate::error::error_chain;
+use crate::i18n;
+use crate::i18n::tr;
+use crate::paths::expand_path;
+
+/// The answer of the SANE handler to a request for the sensors of a device
+pub(crate) type SensorsResponse = Result<Vec<OptionInfo>, String>;
+
+/// How often the sensors are read while no scan is running
+const SENSOR_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Sent by the SANE handler while a scan is running
+pub(crate) enum ScanUpdate {
+    Progress(ScanEvent),
+    /// A page was read, when scanning from the feeder more may follow
+    Page {
+        page: Page,
+        duration: Duration,
+    },
+    Done,
+    /// The scan was stopped on request, pages read before are kept
+    Cancelled,
+    Failed(String),
+}
+
+enum ScanState {
+    Idle,
+    Scanning {
+        updates: Receiver<ScanUpdate>,
+        read: usize,
+        total: Option<usize>,
+        /// The processing step the page is in, once it was read
+        stage: Option<Stage>,
+        cancel: CancellationToken,
+    },
+    /// The page is shown and waits to be saved
+    Scanned,
+    Saving(Receiver<Result<ScanSummary, String>>),
+    Saved {
+        summary: ScanSummary,
+        /// Whether the path was copied to the clipboard
+        copied: bool,
+    },
+    Cancelled,
+    Failed(String),
+}
+
+/// How far a page in the queue has come
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PageStatus {
+    Scanned,
+    Saving,
+    Saved,
+}
+
+/// The text recognized on a page, which tesseract reads in the background
+enum Recognition {
+    Running(Receiver<Result<String, String>>),
+    Done(String),
+    Failed(String),
+}
+
+/// The size of a cell in the thumbnail grid, including its border
+const THUMBNAIL_WIDTH: u16 = 18;
+const THUMBNAIL_HEIGHT: u16 = 11;
+
+/// A scanned page, kept in the queue until the next document is started
+struct Scanned {
+    page: Page,
+    duration: Duration,
+    view: ImageView,
+    /// A downscaled copy for the grid, so that encoding dozens of pages for the terminal stays quick
+    thumbnail: ImageView,
+    status: PageStatus,
+    /// Only recognized while text recognition is enabled
+    text: Option<Recognition>,
+}
+
+impl Scanned {
+    fn new(page: Page, duration: Duration) -> Self {
+        Scanned {
+            view: ImageView::new(page.image.clone()),
+            thumbnail: ImageView::new(page.image.thumbnail(256, 256)),
+            page,
+            duration,
+            status: PageStatus::Scanned,
+            text: None,
+        }
+    }
+}
+
+/// Lets the user scan pages of the active device, check and arrange them and save them as one document
+pub struct ScanScreen {
+    sane_sender: SaneSender,
+    picker: Picker,
+
+    device: String,
+    /// The options changed in the options editor
+    options: HashMap<String, Value>,
+    path: String,
+    /// The path being typed, if the user is currently editing it
+    editing: Option<String>,
+    /// Shown while the user chooses the directory to save in
+    browser: Option<FileBrowser>,
+    /// Applied to every page by the SANE handler as it is scanned
+    processing: PostProcessing,
+    /// Shown while the user changes the post-processing
+    processing_menu: Option<ProcessingMenu>,
+    state: ScanState,
+    /// The pages of the document being scanned, in the order they will be saved in
+    pages: Vec<Scanned>,
+    queue_state: ListState,
+    /// The last known state of the sensors, like whether a document is loaded
+    sensors: Vec<OptionInfo>,
+    sensors_pending: Option<Receiver<SensorsResponse>>,
+    sensors_read: Option<Instant>,
+    /// Whether all pages are shown as thumbnails instead of the highlighted one
+    grid: bool,
+    /// How many thumbnails fit next to each other, as of the last draw
+    grid_columns: usize,
+    /// Whether the text on the pages is recognized and shown next to them
+    ocr: bool,
+    ocr_options: OcrOptions,
+    /// How many lines of the recognized text are scrolled past
+    text_scroll: u16,
+    hints: KeyHints,
+    theme: Theme,
+}
+
+impl ScanScreen {
+    pub(crate) fn new(
+        sane_sender: SaneSender,
+        device: String,
+        path: Option<PathBuf>,
+        processing: PostProcessing,
+        picker: Picker,
+        keys: KeyBindings,
+        theme: Theme,
+    ) -> Self {
+        Self {
+            sane_sender,
+            picker,
+            device,
+            options: HashMap::new(),
+            path: path
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_else(|| String::from("scan.png")),
+            editing: None,
+            browser: None,
+            processing,
+            processing_menu: None,
+            state: ScanState::Idle,
+            pages: Vec::new(),
+            queue_state: ListState::default(),
+            sensors: Vec::new(),
+            sensors_pending: None,
+            sensors_read: None,
+            grid: false,
+            grid_columns: 1,
+            ocr: false,
+            ocr_options: OcrOptions::default(),
+            text_scroll: 0,
+            hints: KeyHints::new(keys),
+            theme,
+        }
+    }
+
+    pub(crate) fn set_options(&mut self, options: HashMap<String, Value>) {
+        self.options = options;
+    }
+
+    pub(crate) fn set_path(&mut self, path: String) {
+        self.path = path;
+    }
+
+    pub(crate) fn set_ocr_options(&mut self, options: OcrOptions) {
+        self.ocr_options = options;
+    }
+
+    /// Start recognizing the text on the pages that were not yet, and collect the finished ones
+    fn recognize_pages(&mut self) {
+        for scanned in &mut self.pages {
+            match &scanned.text {
+                None => {
+                    let page = Page {
+                        image: scanned.page.image.clone(),
+                        dpi: scanned.page.dpi,
+                    };
+                    let options = self.ocr_options.clone();
+                    let (responder, recv) = channel();
+                    std::thread::spawn(move || {
+                        let res = recognize_page(&page, &options);
+                        let _ = responder.send(res.map_err(|error| error_chain(&error)));
+                    });
+                    scanned.text = Some(Recognition::Running(recv));
+                }
+                Some(Recognition::Running(recv)) => match recv.try_recv() {
+                    Ok(Ok(text)) => scanned.text = Some(Recognition::Done(text)),
+                    Ok(Err(error)) => {
+                        tracing::warn!("Recognizing the text failed: {error}");
+                        scanned.text = Some(Recognition::Failed(error));
+                    }
+                    Err(TryRecvError::Empty) => {}
+                    Err(TryRecvError::Disconnected) => {
+                        scanned.text = Some(Recognition::Failed(String::from(
+                            "The recognition stopped without a result",
+                        )))
+                    }
+                },
+                Some(Recognition::Done(_) | Recognition::Failed(_)) => {}
+            }
+        }
+    }
+
+    /// The recognized text of the highlighted page, scrolled with PageUp and PageDown
+    fn draw_text(&self, frame: &mut ratatui::Frame, area: Rect) {
+        let block = Block::new()
+            .borders(Borders::LEFT)
+            .title(format!(" Text ({}) ", self.ocr_options.lang));
+        let text = self
+            .queue_state
+            .selected()
+            .and_then(|idx| self.pages.get(idx))
+            .and_then(|scanned| scanned.text.as_ref());
+        let paragraph = match text {
+            None => Paragraph::new(""),
+            Some(Recognition::Running(_)) => Paragraph::new(Line::from("Recognizing...").dim()),
+            Some(Recognition::Done(text)) if text.trim().is_empty() => {
+                Paragraph::new(Line::from("No text was recognized").dim())
+            }
+            Some(Recognition::Done(text)) => Paragraph::new(text.as_str()),
+            Some(Recognition::Failed(error)) => {
+                Paragraph::new(format!("Failed: {error}")).style(self.theme.error)
+            }
+        };
+
+        frame.render_widget(
+            paragraph
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((self.text_scroll, 0)),
+            area,
+        );
+    }
+
+    fn start_scan(&mut self, feeder: bool) -> miette::Result<()> {
+        // Once a document is saved, the next scan starts a new one
+        if self
+            .pages
+            .iter()
+            .all(|page| page.status == PageStatus::Saved)
+        {
+            self.pages.clear();
+            self.queue_state.select(None);
+        }
+
+        let (responder, updates) = channel();
+        let cancel = CancellationToken::default();
+        self.sane_sender
+            .send(SaneQuery::Scan {
+                device: self.device.clone(),
+                options: self.options.clone(),
+                feeder,
+                processing: self.processing,
+                cancel: cancel.clone(),
+                responder,
+            })
+            .into_diagnostic()?;
+
+        self.state = ScanState::Scanning {
+            updates,
+            read: 0,
+            total: None,
+            stage: None,
+            cancel,
+        };
+
+        Ok(())
+    }
+
+    /// Ask for the state of the sensors from time to time, but not while the scanner is busy
+    fn poll_sensors(&mut self) -> miette::Result<()> {
+        if let Some(recv) = &self.sensors_pending {
+            match recv.try_recv() {
+                Ok(response) => {
+                    self.sensors_pending = None;
+                    // Not every device can be opened while idle, then there simply is nothing to show
+                    self.sensors = response.unwrap_or_default();
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.sensors_pending = None,
+            }
+            return Ok(());
+        }
+
+        let busy = matches!(
+            self.state,
+            ScanState::Scanning { .. } | ScanState::Saving(_)
+        );
+        let due = self
+            .sensors_read
+            .is_none_or(|read| read.elapsed() >= SENSOR_INTERVAL);
+        if busy || !due {
+            return Ok(());
+        }
+
+        let (responder, recv) = channel();
+        self.sane_sender
+            .send(SaneQuery::ReadSensors {
+                device: self.device.clone(),
+                responder,
+            })
+            .into_diagnostic()?;
+        self.sensors_pending = Some(recv);
+        self.sensors_read = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// The sensors as `title: value`, with the ones standing in the way of a scan highlighted
+    fn sensor_line(&self) -> Line<'static> {
+        let mut spans = vec!["Status: ".bold()];
+        for (idx, sensor) in self.sensors.iter().enumerate() {
+            if idx > 0 {
+                spans.push("  ".into());
+            }
+
+            let (value, style) = match (&sensor.value, sensor.name.as_str()) {
+                (Some(ValueInfo::Bool(true)), "cover-open") => {
+                    ("yes".to_string(), self.theme.error)
+                }
+                (Some(ValueInfo::Bool(false)), "page-loaded") => {
+                    ("no".to_string(), self.theme.error)
+                }
+                (Some(ValueInfo::Bool(value)), _) => (
+                    if *value { "yes" } else { "no" }.to_string(),
+                    self.theme.accent,
+                ),
+                (Some(value), _) => (value.to_string(), self.theme.accent),
+                (None, _) => (String::from("unknown"), Style::new().dim()),
+            };
+            spans.push(format!("{}: ", sensor.title).into());
+            spans.push(Span::styled(value, style));
+        }
+
+        Line::from(spans)
+    }
+
+    /// The pages that would be lost when quitting
+    pub(crate) fn unsaved_pages(&self) -> usize {
+        self.pages
+            .iter()
+            .filter(|page| page.status != PageStatus::Saved)
+            .count()
+    }
+
+    pub(crate) fn is_saving(&self) -> bool {
+        matches!(self.state, ScanState::Saving(_))
+    }
+
+    /// Write the pages in the background, as encoding large pages takes a while
+    pub(crate) fn save(&mut self) {
+        if self.pages.is_empty() {
+            return;
+        }
+
+        let path = expand_path(&self.path, &self.device);
+        let pages = self
+            .pages
+            .iter()
+            .map(|scanned| Page {
+                image: scanned.page.image.clone(),
+                dpi: scanned.page.dpi,
+            })
+            .collect::<Vec<_>>();
+        let duration = self.pages.iter().map(|scanned| scanned.duration).sum();
+        self.set_status(PageStatus::Saving);
+        let device = self.device.clone();
+        let options = self.options.clone();
+        let (responder, recv) = channel();
+
+        std::thread::spawn(move || {
+            let res = save_pages(
+                &path,
+                Format::for_path(&path, None),
+                &pages,
+                ScanSource {
+                    device: &device,
+                    settings: None,
+                    options: &options,
+                    duration,
+                },
+            );
+
+            let _ = responder.send(res.map_err(|error| error_chain(&error)));
+        });
+
+        self.state = ScanState::Saving(recv);
+    }
+
+    /// Copy the absolute path of the saved document, to paste it into other applications
+    fn copy_path(&mut self) -> miette::Result<()> {
+        let ScanState::Saved { summary, copied } = &mut self.state else {
+            return Ok(());
+        };
+
+        let path = std::path::absolute(&summary.path).unwrap_or_else(|_| summary.path.clone());
+        clipboard::copy(&path.to_string_lossy())?;
+        *copied = true;
+
+        Ok(())
+    }
+
+    fn set_status(&mut self, status: PageStatus) {
+        for page in &mut self.pages {
+            page.status = status;
+        }
+    }
+
+    /// Move the highlighted page up or down in the queue
+    fn move_page(&mut self, up: bool) {
+        let Some(idx) = self.queue_state.selected() else {
+            return;
+        };
+        let target = if up {
+            idx.checked_sub(1)
+        } else {
+            Some(idx + 1)
+        };
+
+        if let Some(target) = target.filter(|target| *target < self.pages.len()) {
+            self.pages.swap(idx, target);
+            self.queue_state.select(Some(target));
+        }
+    }
+
+    /// Turn the highlighted page clockwise by a quarter
+    fn rotate_page(&mut self) {
+        let Some(scanned) = self
+            .queue_state
+            .selected()
+            .and_then(|idx| self.pages.get_mut(idx))
+        else {
+            return;
+        };
+
+        let image = scanned.page.image.rotate90();
+        *scanned = Scanned::new(
+            Page {
+                image,
+                dpi: scanned.page.dpi,
+            },
+            scanned.duration,
+        );
+    }
+
+    /// Move the highlight by the given amount of pages, stopping at the first and last one
+    fn move_highlight(&mut self, by: isize) {
+        if self.pages.is_empty() {
+            return;
+        }
+
+        let idx = self.queue_state.selected().unwrap_or(0);
+        let target = idx.saturating_add_signed(by).min(self.pages.len() - 1);
+        self.queue_state.select(Some(target));
+    }
+
+    /// Draw the pages as a grid of thumbnails, scrolled so that the highlighted one is visible
+    fn draw_grid(&mut self, frame: &mut ratatui::Frame, area: Rect) {
+        let columns = (area.width / THUMBNAIL_WIDTH).max(1);
+        let rows = (area.height / THUMBNAIL_HEIGHT).max(1);
+        self.grid_columns = usize::from(columns);
+
+        let selected = self.queue_state.selected();
+        let first_row =
+            (selected.unwrap_or(0) / usize::from(columns)).saturating_sub(usize::from(rows) - 1);
+        let first = first_row * usize::from(columns);
+
+        let shown = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .skip(first)
+            .take(usize::from(columns * rows));
+        for (idx, scanned) in shown {
+            let position = (idx - first) as u16;
+            let cell = Rect::new(
+                area.x + position % columns * THUMBNAIL_WIDTH,
+                area.y + position / columns * THUMBNAIL_HEIGHT,
+                THUMBNAIL_WIDTH,
+                THUMBNAIL_HEIGHT,
+            )
+            .intersection(area);
+
+            let border = if selected == Some(idx) {
+                self.theme.highlight
+            } else {
+                self.theme.border
+            };
+            let title = match scanned.status {
+                PageStatus::Scanned => format!(" {} ", idx + 1),
+                PageStatus::Saving => format!(" {} saving ", idx + 1),
+                PageStatus::Saved => format!(" {} saved ", idx + 1),
+            };
+            let block = Block::bordered().border_style(border).title(title);
+            let inner = block.inner(cell);
+            frame.render_widget(block, cell);
+            scanned.thumbnail.draw(&mut self.picker, frame, inner);
+        }
+    }
+
+    /// Whether the output format can hold all pages of the document
+    fn fits_format(&self) -> bool {
+        self.pages.len() <= 1
+            || Format::for_path(&expand_path(&self.path, &self.device), None)
+                .supports_multiple_pages()
+    }
+
+    fn delete_page(&mut self) {
+        let Some(idx) = self
+            .queue_state
+            .selected()
+            .filter(|idx| *idx < self.pages.len())
+        else {
+            return;
+        };
+
+        self.pages.remove(idx);
+        let selected = (!self.pages.is_empty()).then(|| idx.min(self.pages.len() - 1));
+        self.queue_state.select(selected);
+        if self.pages.is_empty() {
+            self.state = ScanState::Idle;
+        }
+    }
+}
+
+impl Component for ScanScreen {
+    fn tick(&mut self) -> miette::Result<Action> {
+        self.poll_sensors()?;
+        if self.ocr {
+            self.recognize_pages();
+        }
+
+        match &mut self.state {
+            ScanState::Scanning {
+                updates,
+                read,
+                total,
+                stage,
+                ..
+            } => loop {
+                match updates.try_recv() {
+                    Ok(ScanUpdate::Progress(event)) => match event {
+                        ScanEvent::PageStarted { .. } | ScanEvent::FrameStarted { .. } => {
+                            *read = 0;
+                            *total = None;
+                            *stage = None;
+                        }
+                        ScanEvent::Read {
+                            bytes,
+                            total: new_total,
+                            ..
+                        } => {
+                            *read = bytes;
+                            *total = new_total;
+                        }
+                        ScanEvent::Processing { stage: new_stage } => *stage = Some(new_stage),
+                        ScanEvent::PageFinished { .. } => {}
+                    },
+                    Ok(ScanUpdate::Page { page, duration }) => {
+                        self.pages.push(Scanned::new(page, duration));
+                        self.queue_state.select(Some(self.pages.len() - 1));
+                        *read = 0;
+                        *total = None;
+                        *stage = None;
+                    }
+                    Ok(ScanUpdate::Done) => {
+                        self.state = if self.pages.is_empty() {
+                            ScanState::Idle
+                        } else {
+                            ScanState::Scanned
+                        };
+                        break;
+                    }
+                    Ok(ScanUpdate::Cancelled) => {
+                        self.state = ScanState::Cancelled;
+                        break;
+                    }
+                    Ok(ScanUpdate::Failed(error)) => {
+                        self.state = ScanState::Failed(error);
+                        break;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.state =
+                            ScanState::Failed(String::from("The scan stopped without a result"));
+                        break;
+                    }
+                }
+            },
+            ScanState::Saving(recv) => match recv.try_recv() {
+                Ok(Ok(summary)) => {
+                    tracing::info!(
+                        "Saved {} page(s) to {}",
+                        summary.pages,
+                        summary.path.display()
+                    );
+                    self.set_status(PageStatus::Saved);
+                    self.state = ScanState::Saved {
+                        summary,
+                        copied: false,
+                    };
+                }
+                Ok(Err(error)) => {
+                    tracing::warn!("Saving failed: {error}");
+                    self.set_status(PageStatus::Scanned);
+                    self.state = ScanState::Failed(error);
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => {
+                    self.set_status(PageStatus::Scanned);
+                    self.state = ScanState::Failed(String::from("Saving stopped without a result"))
+                }
+            },
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn captures_input(&self) -> bool {
+        self.editing.is_some() || self.browser.is_some() || self.processing_menu.is_some()
+    }
+
+    fn cancellable(&self) -> bool {
+        matches!(self.state, ScanState::Scanning { .. })
+    }
+
+    fn keys(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            ("s", "scan a page and add it to the document"),
+            (
+                "f",
+                "scan every page in the document feeder and add them to the document",
+            ),
+            ("w", "save the scanned pages as one document"),
+            (
+                "e",
+                "edit the output path, {date}, {time}, {device} and {n} are filled in",
+            ),
+            ("o", "choose the directory to save in"),
+            (
+                "m",
+                "choose the post-processing, like skipping blank pages or straightening them",
+            ),
+            ("↑/↓", "highlight a page"),
+            (
+                "g",
+                "show all pages as thumbnails or only the highlighted one",
+            ),
+            ("←/→", "highlight a page in the thumbnail grid"),
+            ("Shift+↑/↓", "move the highlighted page"),
+            ("t", "turn the highlighted page clockwise"),
+            (
+                "r",
+                "recognize the text on the pages and show it next to them",
+            ),
+            ("PageUp/PageDown", "scroll the recognized text"),
+            ("c", "cancel the running scan, Esc works as well"),
+            ("x", "delete the highlighted page"),
+            ("y", "copy the path of the saved document to the clipboard"),
+        ]
+    }
+
+    fn key_at(&self, column: u16, row: u16) -> Option<KeyCode> {
+        self.hints.key_at(column, row)
+    }
+
+    fn handle_event(&mut self, event: Option<Event>) -> miette::Result<Action> {
+        if let Some(browser) = &mut self.browser {
+            return match browser.handle_event(event)? {
+                Action::ChooseDirectory(Some(dir)) => {
+                    self.browser = None;
+                    let file_name = Path::new(&self.path)
+                        .file_name()
+                        .map_or_else(|| OsString::from("scan.png"), ToOwned::to_owned);
+                    let path = dir.join(file_name);
+                    self.path = path.to_string_lossy().to_string();
+                    Ok(Action::SetOutputPath(path))
+                }
+                Action::ChooseDirectory(None) => {
+                    self.browser = None;
+                    Ok(Action::Noop)
+                }
+                action => Ok(action),
+            };
+        }
+
+        if let Some(menu) = &mut self.processing_menu {
+            return match menu.handle_event(event)? {
+                Action::SetProcessing(processing) => {
+                    self.processing_menu = None;
+                    self.processing = processing;
+                    Ok(Action::SetProcessing(processing))
+                }
+                action => Ok(action),
+            };
+        }
+
+        if let (Some(editing), Some(Event::Paste(text))) = (&mut self.editing, &event) {
+            editing.push_str(&pasted_line(text));
+            return Ok(Action::Noop);
+        }
+
+        let Some(Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        })) = event
+        else {
+            return Ok(Action::Noop);
+        };
+
+        if let Some(editing) = &mut self.editing {
+            match code {
+                KeyCode::Char(c) => editing.push(c),
+                KeyCode::Backspace => {
+                    editing.pop();
+                }
+                KeyCode::Esc => self.editing = None,
+                KeyCode::Enter => {
+                    if let Some(path) = self.editing.take().filter(|p| !p.trim().is_empty()) {
+                        self.path = path.clone();
+                        return Ok(Action::SetOutputPath(PathBuf::from(path)));
+                    }
+                }
+                _ => {}
+            }
+
+            return Ok(Action::Noop);
+        }
+
+        let busy = matches!(
+            self.state,
+            ScanState::Scanning { .. } | ScanState::Saving(_)
+        );
+        let shift = modifiers.contains(KeyModifiers::SHIFT);
+        if let ScanState::Scanning { cancel, .. } = &self.state {
+            if matches!(code, KeyCode::Char('c') | KeyCode::Esc) {
+                cancel.cancel();
+            }
+        }
+        if matches!(
+            code,
+            KeyCode::Up | KeyCode::Down | KeyCode::Left | KeyCode::Right
+        ) {
+            self.text_scroll = 0;
+        }
+        match code {
+            KeyCode::Char('e') if !busy => self.editing = Some(self.path.clone()),
+            KeyCode::Char('o') if !busy => {
+                let dir = Path::new(&self.path)
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+                self.browser = Some(FileBrowser::new(dir, self.theme.clone()));
+            }
+            KeyCode::Char('m') if !busy => {
+                self.processing_menu =
+                    Some(ProcessingMenu::new(self.processing, self.theme.clone()));
+            }
+            KeyCode::Char('s') if !busy => self.start_scan(false)?,
+            KeyCode::Char('f') if !busy => self.start_scan(true)?,
+            KeyCode::Char('w') if !busy && !self.pages.is_empty() => self.save(),
+            KeyCode::Up | KeyCode::Down if shift && !busy => self.move_page(code == KeyCode::Up),
+            KeyCode::Char('g') => self.grid = !self.grid,
+            KeyCode::Up if self.grid => self.move_highlight(-(self.grid_columns as isize)),
+            KeyCode::Down if self.grid => self.move_highlight(self.grid_columns as isize),
+            KeyCode::Left if self.grid => self.move_highlight(-1),
+            KeyCode::Right if self.grid => self.move_highlight(1),
+            KeyCode::Up => self.queue_state.select_previous(),
+            KeyCode::Down => self.queue_state.select_next(),
+            KeyCode::Char('t') if !busy => self.rotate_page(),
+            KeyCode::Char('r') => self.ocr = !self.ocr,
+            KeyCode::Char('y') => self.copy_path()?,
+            KeyCode::PageUp => self.text_scroll = self.text_scroll.saturating_sub(5),
+            KeyCode::PageDown => self.text_scroll = self.text_scroll.saturating_add(5),
+            KeyCode::Char('x') | KeyCode::Delete if !busy => self.delete_page(),
+            _ => {}
+        }
+
+        Ok(Action::Noop)
+    }
+
+    fn draw(&mut self, frame: &mut ratatui::Frame, rect: ratatui::prelude::Rect) {
+        let [device_area, sensors_area, path_area, status_area, image_area, help_area] =
+            Layout::vertical([
+                Constraint::Length(1),
+                Constraint::Length(u16::from(!self.sensors.is_empty())),
+                Constraint::Length(2),
+                Constraint::Length(2),
+                Constraint::Fill(1),
+                Constraint::Length(1),
+            ])
+            .areas(rect);
+
+        let mut steps = Vec::new();
+        if self.processing.skip_blank {
+            steps.push("skip blank");
+        }
+        if self.processing.deskew {
+            steps.push("straighten");
+        }
+        if self.processing.autocrop {
+            steps.push("crop");
+        }
+        let rotation = format!("rotate {}", self.processing.rotation.name());
+        if self.processing.rotation != Rotation::None {
+            steps.push(&rotation);
+        }
+        let steps = if steps.is_empty() {
+            String::from("none")
+        } else {
+            steps.join(", ")
+        };
+        let mut device = vec![
+            "Device: ".bold(),
+            self.device.as_str().into(),
+            "  Post-processing: ".bold(),
+            steps.into(),
+        ];
+        if self.ocr {
+            device.push("  Text recognition: ".bold());
+            device.push(self.ocr_options.lang.as_str().into());
+        }
+        frame.render_widget(Line::from(device), device_area);
+        if !self.sensors.is_empty() {
+            frame.render_widget(self.sensor_line(), sensors_area);
+        }
+
+        let path = match &self.editing {
+            Some(editing) => vec![
+                Line::from(vec![
+                    "Output: ".bold(),
+                    editing.as_str().into(),
+                    "_".slow_blink(),
+                ]),
+                Line::from("{date}, {time}, {device} and {n} are filled in when saving").dim(),
+            ],
+            None if self.path.contains('{') => vec![
+                Line::from(vec!["Output: ".bold(), self.path.as_str().into()]),
+                Line::from(format!(
+                    "Saved as {}",
+                    expand_path(&self.path, &self.device).display()
+                ))
+                .dim(),
+            ],
+            None => vec![Line::from(vec![
+                "Output: ".bold(),
+                self.path.as_str().into(),
+            ])],
+        };
+        frame.render_widget(Paragraph::new(path), path_area);
+
+        match &self.state {
+            ScanState::Idle => {}
+            ScanState::Scanning { cancel, .. } if cancel.is_cancelled() => {
+                frame.render_widget(Line::from(tr!("progress-cancelling")), status_area)
+            }
+            ScanState::Scanning {
+                stage: Some(stage), ..
+            } => frame.render_widget(
+                Line::from(tr!("progress-processing", stage = i18n::stage(*stage))),
+                status_area,
+            ),
+            ScanState::Scanning {
+                read,
+                total: Some(total),
+                ..
+            } if *total > 0 => {
+                let ratio = (*read as f64 / *total as f64).clamp(0.0, 1.0);
+                let [gauge_area, _] =
+                    Layout::vertical([Constraint::Length(1), Constraint::Fill(1)])
+                        .areas(status_area);
+                frame.render_widget(
+                    Gauge::default()
+                        .label(tr!(
+                            "progress-percent",
+                            percent = format!("{:.0}", ratio * 100.0)
+                        ))
+                        .ratio(ratio),
+                    gauge_area,
+                );
+            }
+            ScanState::Scanning { read, .. } => frame.render_widget(
+                Line::from(tr!("progress-read", kib = read / 1024)),
+                status_area,
+            ),
+            ScanState::Scanned if !self.fits_format() => frame.render_widget(
+                Paragraph::new(tr!("tui-pages-do-not-fit", pages = self.pages.len()))
+                    .style(self.theme.error)
+                    .wrap(Wrap { trim: true }),
+                status_area,
+            ),
+            ScanState::Scanned => frame.render_widget(
+                Line::from(tr!("tui-pages-scanned", pages = self.pages.len())),
+                status_area,
+            ),
+            ScanState::Saving(_) => {
+                frame.render_widget(Line::from(tr!("progress-saving")), status_area)
+            }
+            ScanState::Saved { summary, copied } => frame.render_widget(
+                Paragraph::new(format!(
+                    "Saved {}x{} pixels at {} DPI to {}, {}",
+                    summary.width,
+                    summary.height,
+                    summary.dpi,
+                    summary.path.display(),
+                    if *copied {
+                        "the path was copied to the clipboard"
+                    } else {
+                        "press y to copy the path"
+                    }
+                ))
+                .style(self.theme.success)
+                .wrap(Wrap { trim: true }),
+                status_area,
+            ),
+            ScanState::Cancelled if self.pages.is_empty() => {
+                frame.render_widget(Line::from("The scan was cancelled"), status_area)
+            }
+            ScanState::Cancelled => frame.render_widget(
+                Line::from(format!(
+                    "The scan was cancelled, press w to save the {} page(s) scanned before",
+                    self.pages.len()
+                )),
+                status_area,
+            ),
+            ScanState::Failed(error) => frame.render_widget(
+                Paragraph::new(format!("Failed: {error}"))
+                    .style(self.theme.error)
+                    .wrap(Wrap { trim: true }),
+                status_area,
+            ),
+        }
+
+        let scanning = match &self.state {
+            ScanState::Scanning { stage: Some(_), .. } => Some(String::from("processing")),
+            ScanState::Scanning { read, total, .. } => Some(match total {
+                Some(total) if *total > 0 => {
+                    format!("{:.0}%", *read as f64 / *total as f64 * 100.0)
+                }
+                _ => format!("{} KiB", read / 1024),
+            }),
+            _ => None,
+        };
+        let image_area = if self.grid && !self.pages.is_empty() {
+            self.draw_grid(frame, image_area);
+            None
+        } else if self.pages.is_empty() && scanning.is_none() {
+            Some(image_area)
+        } else {
+            let [image_area, queue_area] =
+                Layout::horizontal([Constraint::Fill(1), Constraint::Length(26)]).areas(image_area);
+
+            let mut items = self
+                .pages
+                .iter()
+                .enumerate()
+                .map(|(idx, scanned)| {
+                    let status = match scanned.status {
+                        PageStatus::Scanned => "scanned",
+                        PageStatus::Saving => "saving",
+                        PageStatus::Saved => "saved",
+                    };
+                    ListItem::new(format!("Page {:<3} {status}", idx + 1))
+                })
+                .collect::<Vec<_>>();
+            if let Some(scanning) = scanning {
+                items.push(ListItem::new(
+                    Line::from(format!(
+                        "Page {:<3} scanning {scanning}",
+                        self.pages.len() + 1
+                    ))
+                    .dim(),
+                ));
+            }
+
+            let queue = List::new(items)
+                .block(Block::new().borders(Borders::LEFT).title(" Pages "))
+                .highlight_style(self.theme.highlight);
+            frame.render_stateful_widget(queue, queue_area, &mut self.queue_state);
+
+            if self.ocr {
+                let [image_area, text_area] =
+                    Layout::horizontal([Constraint::Fill(1), Constraint::Percentage(40)])
+                        .areas(image_area);
+                self.draw_text(frame, text_area);
+                Some(image_area)
+            } else {
+                Some(image_area)
+            }
+        };
+
+        let shown = self
+            .queue_state
+            .selected()
+            .and_then(|idx| self.pages.get_mut(idx));
+        if let (Some(scanned), Some(image_area)) = (shown, image_area) {
+            scanned.view.draw(&mut self.picker, frame, image_area);
+        }
+
+        let help = match (&self.editing, &self.state) {
+            (Some(_), _) => "Enter: confirm  Esc: cancel",
+            (None, ScanState::Scanning { .. }) => "c: cancel",
+            (None, ScanState::Saving(_)) => "Esc: quit",
+            (None, ScanState::Saved { .. }) => {
+                "y: copy path  s: scan another  f: feeder  g: grid  r: text  m: processing  e: edit output path  o: directory  Tab: next  d: device  ?: help  Esc: quit"
+            }
+            (None, _) if !self.pages.is_empty() => {
+                "s: scan another  f: feeder  w: save  t: turn  x: delete  g: grid  r: text  m: processing  e: edit output path  o: directory  Tab: next  d: device  ?: help  Esc: quit"
+            }
+            (None, _) => {
+                "s: scan  f: feeder  m: processing  e: edit output path  o: directory  Tab: next  d: device  ?: help  Esc: quit"
+            }
+        };
+        self.hints.draw(frame, help_area, help);
+
+        if let Some(browser) = &mut self.browser {
+            browser.draw(frame, rect);
+        }
+
+        if let Some(menu) = &mut self.processing_menu {
+            menu.draw(frame, rect);
+        }
+    }
+}
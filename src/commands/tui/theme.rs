@@ -0,0 +1,113 @@
+//! The colors of the TUI, which can be changed in the `[theme]` section of its configuration
+
+use std::str::FromStr;
+
+use ratatui::style::Color;
+use ratatui::style::Style;
+use ratatui::style::Stylize;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::ScannrsError;
+
+/// The theme as it is written in the configuration, colors are names like `red` or hex codes like `#ff8800`
+#[derive(Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ThemeConfig {
+    /// Do not use any colors, for terminals without them or when more contrast is needed
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    monochrome: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    highlight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    border: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// Used for the selected scan area and gauges
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    success: Option<String>,
+}
+
+impl ThemeConfig {
+    pub(crate) fn is_default(&self) -> bool {
+        *self == ThemeConfig::default()
+    }
+}
+
+/// The styles the screens are drawn with
+#[derive(Clone, Debug)]
+pub(crate) struct Theme {
+    pub(crate) highlight: Style,
+    pub(crate) border: Style,
+    pub(crate) title: Style,
+    pub(crate) accent: Style,
+    pub(crate) error: Style,
+    pub(crate) success: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            highlight: Style::new().reversed(),
+            border: Style::new(),
+            title: Style::new(),
+            accent: Style::new().yellow().bold(),
+            error: Style::new().red(),
+            success: Style::new().green(),
+        }
+    }
+}
+
+impl Theme {
+    /// Use the colors from the configuration, and the defaults for all others
+    pub(crate) fn new(config: &ThemeConfig) -> Result<Theme, ScannrsError> {
+        if config.monochrome {
+            return Ok(Theme {
+                highlight: Style::new().reversed(),
+                border: Style::new(),
+                title: Style::new().bold(),
+                accent: Style::new().bold(),
+                error: Style::new().bold().underlined(),
+                success: Style::new().bold(),
+            });
+        }
+
+        let mut theme = Theme::default();
+        if let Some(color) = parse_color(&config.highlight)? {
+            // Reversed, so that the color ends up as the background
+            theme.highlight = Style::new().reversed().fg(color);
+        }
+        if let Some(color) = parse_color(&config.border)? {
+            theme.border = Style::new().fg(color);
+        }
+        if let Some(color) = parse_color(&config.title)? {
+            theme.title = Style::new().fg(color);
+        }
+        if let Some(color) = parse_color(&config.accent)? {
+            theme.accent = Style::new().fg(color).bold();
+        }
+        if let Some(color) = parse_color(&config.error)? {
+            theme.error = Style::new().fg(color);
+        }
+        if let Some(color) = parse_color(&config.success)? {
+            theme.success = Style::new().fg(color);
+        }
+
+        Ok(theme)
+    }
+}
+
+fn parse_color(color: &Option<String>) -> Result<Option<Color>, ScannrsError> {
+    color
+        .as_deref()
+        .map(|color| {
+            Color::from_str(color).map_err(|_| ScannrsError::InvalidColor {
+                color: color.to_string(),
+            })
+        })
+        .transpose()
+}
@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use crate::cli::UnitService;
+
+/// How often systemd expects to hear from the service, the watchdog is fed twice as often
+const WATCHDOG_SEC: u64 = 30;
+
+/// Print the systemd unit running `service`, or the socket unit activating it with `socket`
+pub fn systemd_unit(service: UnitService, socket: bool) -> miette::Result<()> {
+    let exe = std::env::current_exe()
+        .into_diagnostic()
+        .context("Could not find the path of scannrs for the unit")?;
+    print!("{}", unit(service, &exe, socket));
+
+    Ok(())
+}
+
+/// The service unit running `service` with the binary at `exe`, or the socket unit activating it with `socket`
+fn unit(service: UnitService, exe: &Path, socket: bool) -> String {
+    let (description, command, listen) = match service {
+        UnitService::Daemon => ("scannrs scan queue", "daemon", "%t/scannrs.sock"),
+        UnitService::Serve => ("scannrs HTTP API", "serve", "127.0.0.1:8080"),
+    };
+
+    if socket {
+        return format!(
+            "[Unit]\nDescription=Socket of the {description}\n\n\
+             [Socket]\nListenStream={listen}\n\n\
+             [Install]\nWantedBy=sockets.target\n"
+        );
+    }
+
+    format!(
+        "[Unit]\nDescription={description}\nAfter=network-online.target\nWants=network-online.target\n\n\
+         [Service]\nType=notify\nExecStart={} {command}\nWatchdogSec={WATCHDOG_SEC}\nRestart=on-failure\n\n\
+         [Install]\nWantedBy=default.target\n",
+        exe.display()
+    )
+}
@@ -0,0 +1,591 @@
+//! The configuration shared by all subcommands and the TUI
+//!
+//! Settings are taken from these layers, each replacing the ones before:
+//!
+//! 1. the `[defaults]` and `[devices]` sections of the configuration file, which is read from `--config`,
+//!    `$SCANNRS_CONFIG` or `$XDG_CONFIG_HOME/scannrs/config.toml`
+//! 2. the profile selected by `--profile`, `$SCANNRS_PROFILE` or `profile` in the `[defaults]`
+//! 3. the environment variables `SCANNRS_DEVICE`, `SCANNRS_FORMAT`, `SCANNRS_RESOLUTION` and `SCANNRS_OUTPUT_DIR`,
+//!    standing in for the arguments of the same name
+//! 4. the flags and arguments on the command line
+//!
+//! Option values go through the same layers, with a settings file given by `--settings` coming after the profile.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use miette::Context;
+use miette::IntoDiagnostic;
+use miette::NamedSource;
+use scannrs_core::backend::Unit;
+use scannrs_core::ocr::OcrOptions;
+use scannrs_core::output::Format;
+use scannrs_core::value::Value;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::cli::parse_dpi;
+use crate::error::ScannrsError;
+
+/// The path of the configuration file, if it is not at the default location
+const CONFIG_VAR: &str = "SCANNRS_CONFIG";
+/// The profile to use, if none is given on the command line
+const PROFILE_VAR: &str = "SCANNRS_PROFILE";
+const DEVICE_VAR: &str = "SCANNRS_DEVICE";
+const FORMAT_VAR: &str = "SCANNRS_FORMAT";
+const RESOLUTION_VAR: &str = "SCANNRS_RESOLUTION";
+const OUTPUT_DIR_VAR: &str = "SCANNRS_OUTPUT_DIR";
+
+static FLAGS: OnceLock<ConfigFlags> = OnceLock::new();
+
+/// The global flags of the command line that change which configuration is used
+#[derive(Default, Debug)]
+pub(crate) struct ConfigFlags {
+    pub(crate) config: Option<PathBuf>,
+    pub(crate) profile: Option<String>,
+}
+
+/// Use the flags for every configuration loaded from now on, only the first call has an effect
+pub(crate) fn set_flags(flags: ConfigFlags) {
+    let _ = FLAGS.set(flags);
+}
+
+fn flags() -> &'static ConfigFlags {
+    FLAGS.get_or_init(ConfigFlags::default)
+}
+
+/// Parse a TOML file, pointing at the place in the file that is wrong if it cannot be parsed
+///
+/// Unknown keys, values of the wrong type and values that are not allowed all point at their place in the file.
+pub(crate) fn parse_toml<T: DeserializeOwned>(path: &Path, contents: String) -> miette::Result<T> {
+    toml::from_str(&contents)
+        .map_err(|error| ScannrsError::InvalidToml {
+            message: error.message().to_string(),
+            span: error.span().map(Into::into),
+            contents: NamedSource::new(path.display().to_string(), contents),
+        })
+        .into_diagnostic()
+}
+
+/// A variable of the environment, treating an empty one like an unset one
+pub(crate) fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// The configuration file shared by all subcommands, see the [module documentation](self) for how it is found
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Config {
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub(crate) defaults: Defaults,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) devices: BTreeMap<String, DeviceConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) profiles: BTreeMap<String, Profile>,
+    /// Post-processors applied to every scanned page by their name, in order, as listed by `scannrs about`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) post_processors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) paperless: Option<PaperlessConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) webdav: Option<WebdavConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) s3: Option<S3Config>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) sftp: Option<SftpConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) email: Option<EmailConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) mqtt: Option<MqttConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) webhooks: Vec<WebhookConfig>,
+    /// How text is recognized by `ocr` and the TUI, unless they are told otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ocr: Option<OcrOptions>,
+    /// Where scans go by the text on them, see [`crate::rules`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) rules: Vec<RuleConfig>,
+    /// What the buttons of the scanners do while the daemon runs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) buttons: Vec<ButtonConfig>,
+}
+
+/// Used when the command line does not say otherwise
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Defaults {
+    /// The scanner to use when none is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) device: Option<String>,
+    /// The profile applied to every scan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) profile: Option<String>,
+    /// The format to save scans in when the path does not tell
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) format: Option<Format>,
+    /// Where relative paths of scans are saved, instead of the current directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output_dir: Option<PathBuf>,
+    /// For how many seconds the list of scanners is reused instead of enumerating them again, `0` to always enumerate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) device_cache: Option<u64>,
+}
+
+impl Defaults {
+    fn is_empty(&self) -> bool {
+        *self == Defaults::default()
+    }
+}
+
+/// Settings specific to a single scanner
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DeviceConfig {
+    /// Option values applied before every scan, as set with `options set`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) options: BTreeMap<String, Value>,
+}
+
+/// A named set of option values to switch between, like `photo` or `document`
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Profile {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) options: BTreeMap<String, Value>,
+    /// Where scans with this profile are saved, may contain the placeholders of the TUI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<String>,
+    /// Where saved scans are sent on to by `scan`, in addition to the ones given on the command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) destinations: Vec<DestinationConfig>,
+    /// How text is recognized with this profile, instead of the `[ocr]` section
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) ocr: Option<OcrOptions>,
+}
+
+/// A rule sorting scans by their text, as listed in `[[rules]]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RuleConfig {
+    /// The regular expression searched for in the text
+    pub(crate) pattern: String,
+    /// Where matching scans are saved instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) output: Option<String>,
+    /// Added to the tags of the scan in paperless-ngx
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) tags: Vec<String>,
+    /// Who the scan is from in paperless-ngx, unless the destination says otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) correspondent: Option<String>,
+}
+
+/// The scan a button of the scanner starts, as listed in `[[buttons]]`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ButtonConfig {
+    /// The name of the sensor option of the button, like `scan`, `copy`, `email` or `file`
+    pub(crate) button: String,
+    /// The number the function selector has to show, any if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) function: Option<i32>,
+    /// The scanner with the button, the default one if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) device: Option<String>,
+    /// The profile to scan with, the default one if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) profile: Option<String>,
+    /// Where the scan is sent, in addition to the destinations of the profile
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) destinations: Vec<DestinationConfig>,
+}
+
+/// A place saved scans are sent to, as listed in `[[profiles.<name>.destinations]]` by its `type`
+///
+/// `{name}` is filled in with the name of the saved file, `{path}` with its absolute path and `{date}`, `{time}` and
+/// `{device}` like in the TUI.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) enum DestinationConfig {
+    /// Copy the scan to another path
+    File { path: String },
+    /// Write the scan to standard output, to pipe it into other programs
+    Stdout,
+    /// POST the scan to a URL, with the headers given
+    Http {
+        url: String,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        headers: BTreeMap<String, String>,
+    },
+    /// Upload the scan to the server of the [webdav] section, at another remote path than the one there if given
+    Webdav {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<String>,
+    },
+    /// Upload the scan to an `s3://`, `sftp://` or `scp://` URL
+    Upload { url: String },
+    /// Email the scan with the account of the [email] section
+    Email { to: Vec<String> },
+    /// Upload the scan to the paperless-ngx of the [paperless] section
+    Paperless {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        tags: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        correspondent: Option<String>,
+    },
+    /// Run a program, given as the program followed by its arguments, like `["lpr", "{path}"]`
+    Command { command: Vec<String> },
+}
+
+/// The paperless-ngx instance scans are uploaded to with `--paperless`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PaperlessConfig {
+    /// Where paperless-ngx is reached, like `https://paperless.example.com`
+    pub(crate) url: String,
+    /// An API token, as created in the profile settings of paperless-ngx
+    pub(crate) token: String,
+    /// Tags given to every uploaded document, by their name or id
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) tags: Vec<String>,
+}
+
+/// The WebDAV server scans are uploaded to with `--webdav`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WebdavConfig {
+    /// The collection uploads go into, like `https://cloud.example.com/remote.php/dav/files/alice/` for Nextcloud
+    pub(crate) url: String,
+    pub(crate) user: String,
+    /// The password, only for when the keyring cannot be used as `scannrs login webdav` stores it there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) password: Option<String>,
+    /// Where below the URL scans are put, `{name}` is the name of the saved file and `{date}`, `{time}` and `{device}`
+    /// are filled in like in the TUI
+    #[serde(default = "WebdavConfig::default_path")]
+    pub(crate) path: String,
+}
+
+impl WebdavConfig {
+    fn default_path() -> String {
+        String::from("{name}")
+    }
+}
+
+/// Where `--upload s3://...` uploads to, when it is not AWS itself or the region is not configured for the AWS CLI
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct S3Config {
+    /// The endpoint of S3-compatible storage, like `http://localhost:9000` for MinIO
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) region: Option<String>,
+    /// Address buckets as part of the path instead of the host name, as MinIO needs by default
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) path_style: bool,
+}
+
+/// How `--upload sftp://...` and `scp://...` log in
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SftpConfig {
+    /// The user to log in as when the URL does not name one, instead of the current one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    /// The private key to log in with when the SSH agent has none, instead of the default keys in `~/.ssh`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) identity: Option<PathBuf>,
+}
+
+/// The SMTP account `--email` sends scans with
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct EmailConfig {
+    /// The SMTP server, like `smtp.example.com`
+    pub(crate) server: String,
+    /// The port of the server, if it is not the usual one of `security`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) port: Option<u16>,
+    #[serde(default)]
+    pub(crate) security: SmtpSecurity,
+    /// The user to log in as, servers that need no login are used without
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    /// The password, only for when the keyring cannot be used as `scannrs login email` stores it there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) password: Option<String>,
+    /// The sender, like `Scanner <scanner@example.com>`
+    pub(crate) from: String,
+    /// The subject, `{name}` is the name of the saved file and `{date}`, `{time}` and `{device}` are filled in like
+    /// in the TUI
+    #[serde(default = "EmailConfig::default_subject")]
+    pub(crate) subject: String,
+    /// The text of the email, with the same placeholders as the subject
+    #[serde(default = "EmailConfig::default_body")]
+    pub(crate) body: String,
+}
+
+impl EmailConfig {
+    fn default_subject() -> String {
+        String::from("Scan {name}")
+    }
+
+    fn default_body() -> String {
+        String::from("Scanned with {device} on {date} at {time}.")
+    }
+}
+
+/// The MQTT broker `daemon` publishes its events to, for home automation like Home Assistant
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MqttConfig {
+    /// Where the broker is reached, like `mqtt://homeassistant.local` or `mqtts://broker.example.com:8883`
+    pub(crate) broker: String,
+    /// The user to log in as, brokers that need no login are used without
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) user: Option<String>,
+    /// The password, only for when the keyring cannot be used as `scannrs login mqtt` stores it there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) password: Option<String>,
+    /// Which client scannrs connects as, has to be unique on the broker
+    #[serde(default = "MqttConfig::default_client_id")]
+    pub(crate) client_id: String,
+    /// The prefix of all topics, events are published to `<topic>/event/<name>` and the state to `<topic>/status`
+    #[serde(default = "MqttConfig::default_topic")]
+    pub(crate) topic: String,
+    /// Scan with the profile named in messages to `<topic>/scan`, the default profile for empty ones
+    #[serde(default)]
+    pub(crate) commands: bool,
+}
+
+impl MqttConfig {
+    /// The name of the configured account in the keyring
+    pub(crate) fn account(&self) -> Option<String> {
+        self.user
+            .as_ref()
+            .map(|user| format!("mqtt:{user}@{}", self.broker))
+    }
+
+    fn default_client_id() -> String {
+        String::from("scannrs")
+    }
+
+    fn default_topic() -> String {
+        String::from("scannrs")
+    }
+}
+
+/// A URL told about every finished scan, listed as `[[webhooks]]`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct WebhookConfig {
+    pub(crate) url: String,
+    /// Sent with every request, like `Authorization`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) headers: BTreeMap<String, String>,
+}
+
+/// How the connection to the SMTP server is encrypted
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SmtpSecurity {
+    /// Upgrade the connection with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// Connect with TLS right away, usually on port 465
+    Tls,
+    /// Send unencrypted, only for servers on the same machine or network, usually on port 25
+    None,
+}
+
+impl Config {
+    pub(crate) fn path() -> miette::Result<PathBuf> {
+        if let Some(path) = &flags().config {
+            return Ok(path.clone());
+        }
+        if let Some(path) = env_var(CONFIG_VAR) {
+            return Ok(PathBuf::from(path));
+        }
+
+        Ok(crate::paths::config_dir()?.join("config.toml"))
+    }
+
+    /// Load the configuration, a missing file is the same as an empty one
+    pub(crate) fn load() -> miette::Result<Config> {
+        let path = Config::path()?;
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => parse_toml(&path, contents)
+                .with_context(|| format!("While reading the configuration at {}", path.display())),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(error) => Err(error).into_diagnostic(),
+        }
+    }
+
+    pub(crate) fn save(&self) -> miette::Result<()> {
+        let path = Config::path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        std::fs::write(&path, toml::to_string_pretty(self).into_diagnostic()?)
+            .into_diagnostic()
+            .with_context(|| format!("While writing the configuration to {}", path.display()))
+    }
+
+    /// The persistent option values of the given scanner
+    pub(crate) fn device_options(&self, device: &str) -> impl Iterator<Item = (&String, &Value)> {
+        self.devices
+            .get(device)
+            .into_iter()
+            .flat_map(|device| device.options.iter())
+    }
+
+    /// The selected profile with its name, if one is selected
+    pub(crate) fn profile(&self) -> miette::Result<Option<(&str, &Profile)>> {
+        let name = flags()
+            .profile
+            .clone()
+            .or_else(|| env_var(PROFILE_VAR))
+            .or_else(|| self.defaults.profile.clone());
+        let Some(name) = name else {
+            return Ok(None);
+        };
+
+        self.profile_named(&name).map(Some)
+    }
+
+    /// How text is recognized, as set by the profile or else the `[ocr]` section
+    pub(crate) fn ocr(&self) -> miette::Result<OcrOptions> {
+        let profile = self.profile()?.and_then(|(_, profile)| profile.ocr.clone());
+        Ok(profile.or_else(|| self.ocr.clone()).unwrap_or_default())
+    }
+
+    /// The profile with the given name, which has to exist
+    pub(crate) fn profile_named(&self, name: &str) -> miette::Result<(&str, &Profile)> {
+        self.profiles
+            .get_key_value(name)
+            .map(|(name, profile)| (name.as_str(), profile))
+            .ok_or_else(|| ScannrsError::ProfileNotFound {
+                available: self.profiles.keys().cloned().collect::<Vec<_>>().join(", "),
+                name: name.to_string(),
+            })
+            .into_diagnostic()
+    }
+
+    /// The option values of the scanner from the configuration, the ones of the profile replacing the persistent ones
+    pub(crate) fn options_for(&self, device: &str) -> miette::Result<Vec<(String, Value)>> {
+        let profile = self.profile()?.map(|(_, profile)| profile);
+        Ok(self.options_with(device, profile))
+    }
+
+    /// The option values of the scanner from the configuration, the ones of `profile` replacing the persistent ones
+    pub(crate) fn options_with(
+        &self,
+        device: &str,
+        profile: Option<&Profile>,
+    ) -> Vec<(String, Value)> {
+        self.device_options(device)
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .chain(
+                profile
+                    .into_iter()
+                    .flat_map(|profile| profile.options.clone()),
+            )
+            .collect()
+    }
+
+    /// The paperless-ngx instance to upload to, which has to be configured
+    pub(crate) fn paperless(&self) -> miette::Result<&PaperlessConfig> {
+        self.paperless
+            .as_ref()
+            .ok_or(ScannrsError::PaperlessNotConfigured)
+            .into_diagnostic()
+    }
+
+    /// The WebDAV server to upload to, which has to be configured
+    pub(crate) fn webdav(&self) -> miette::Result<&WebdavConfig> {
+        self.webdav
+            .as_ref()
+            .ok_or(ScannrsError::WebdavNotConfigured)
+            .into_diagnostic()
+    }
+
+    /// The SMTP account to send emails with, which has to be configured
+    pub(crate) fn email(&self) -> miette::Result<&EmailConfig> {
+        self.email
+            .as_ref()
+            .ok_or(ScannrsError::EmailNotConfigured)
+            .into_diagnostic()
+    }
+
+    /// The MQTT broker to publish to, which has to be configured
+    pub(crate) fn mqtt(&self) -> miette::Result<&MqttConfig> {
+        self.mqtt
+            .as_ref()
+            .ok_or(ScannrsError::MqttNotConfigured)
+            .into_diagnostic()
+    }
+
+    /// The scanner given on the command line, in the environment or the default one
+    pub(crate) fn device(&self, name: Option<String>) -> miette::Result<String> {
+        name.or_else(|| env_var(DEVICE_VAR))
+            .or_else(|| self.defaults.device.clone())
+            .ok_or(ScannrsError::NoDevice)
+            .into_diagnostic()
+    }
+
+    /// The format given on the command line, in the environment or the default one
+    pub(crate) fn format(&self, format: Option<Format>) -> miette::Result<Option<Format>> {
+        if format.is_some() {
+            return Ok(format);
+        }
+
+        match env_var(FORMAT_VAR) {
+            Some(value) => Format::from_str(&value, true)
+                .map(Some)
+                .map_err(|_| ScannrsError::InvalidEnvVar {
+                    name: FORMAT_VAR,
+                    value,
+                    expected: "one of jpeg, png, tiff or pdf",
+                })
+                .into_diagnostic(),
+            None => Ok(self.defaults.format),
+        }
+    }
+
+    /// The resolution given on the command line or in the environment, as the value of the `resolution` option
+    pub(crate) fn resolution(&self, dpi: Option<f32>) -> miette::Result<Option<Value>> {
+        let dpi = match dpi {
+            Some(dpi) => Some(dpi),
+            None => env_var(RESOLUTION_VAR)
+                .map(|value| {
+                    parse_dpi(&value).with_context(|| format!("While reading ${RESOLUTION_VAR}"))
+                })
+                .transpose()?,
+        };
+
+        Ok(dpi.map(|dpi| Value::with_unit(f64::from(dpi), Unit::Dpi)))
+    }
+
+    /// Where to save a scan given on the command line, relative paths go into the output directory if there is one
+    ///
+    /// The output directory is taken from the command line, the environment or the defaults.
+    pub(crate) fn output_path(&self, output_dir: Option<&Path>, path: &Path) -> PathBuf {
+        let output_dir = output_dir
+            .map(Path::to_path_buf)
+            .or_else(|| env_var(OUTPUT_DIR_VAR).map(PathBuf::from))
+            .or_else(|| self.defaults.output_dir.clone());
+
+        match output_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+}
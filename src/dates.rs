@@ -0,0 +1,158 @@
+//! Finding the date of a document in its recognized text, for the `{doc_date}` placeholder
+//!
+//! Dates are found as `2024-03-15`, `15.03.2024`, `15/03/24`, `15 March 2024`, `15. März 2024` and `March 15th, 2024`,
+//! with the names of the months in English, German, French, Spanish, Italian and Dutch. The first date in the text
+//! wins, which is the one in the letterhead for most letters and invoices. Dates like `03/04/2024` are read day first,
+//! unless the locale is `en_US`.
+
+use std::sync::OnceLock;
+
+use chrono::NaiveDate;
+use regex::Captures;
+use regex::Regex;
+
+/// The placeholder filled in with the date of the document
+pub(crate) const DOC_DATE: &str = "{doc_date}";
+
+/// The names of the months, any prefix of three or more letters also stands for the month
+const MONTHS: &[(&str, u32)] = &[
+    ("january", 1),
+    ("januar", 1),
+    ("janvier", 1),
+    ("enero", 1),
+    ("gennaio", 1),
+    ("januari", 1),
+    ("jänner", 1),
+    ("february", 2),
+    ("februar", 2),
+    ("février", 2),
+    ("febrero", 2),
+    ("febbraio", 2),
+    ("februari", 2),
+    ("march", 3),
+    ("märz", 3),
+    ("maerz", 3),
+    ("mars", 3),
+    ("marzo", 3),
+    ("maart", 3),
+    ("april", 4),
+    ("avril", 4),
+    ("abril", 4),
+    ("aprile", 4),
+    ("may", 5),
+    ("mai", 5),
+    ("mayo", 5),
+    ("maggio", 5),
+    ("mei", 5),
+    ("june", 6),
+    ("juni", 6),
+    ("juin", 6),
+    ("junio", 6),
+    ("giugno", 6),
+    ("july", 7),
+    ("juli", 7),
+    ("juillet", 7),
+    ("julio", 7),
+    ("luglio", 7),
+    ("august", 8),
+    ("août", 8),
+    ("agosto", 8),
+    ("augustus", 8),
+    ("september", 9),
+    ("septembre", 9),
+    ("septiembre", 9),
+    ("settembre", 9),
+    ("october", 10),
+    ("oktober", 10),
+    ("octobre", 10),
+    ("octubre", 10),
+    ("ottobre", 10),
+    ("november", 11),
+    ("novembre", 11),
+    ("noviembre", 11),
+    ("december", 12),
+    ("dezember", 12),
+    ("décembre", 12),
+    ("diciembre", 12),
+    ("dicembre", 12),
+];
+
+/// The patterns of dates, with the parts named `year`, `month`, `day` and `name` for a written month
+const PATTERNS: &[&str] = &[
+    r"\b(?P<year>\d{4})-(?P<month>\d{1,2})-(?P<day>\d{1,2})\b",
+    r"\b(?P<first>\d{1,2})(?P<separator>[./-])(?P<second>\d{1,2})[./-](?P<year>\d{4}|\d{2})\b",
+    r"(?i)\b(?P<day>\d{1,2})(?:st|nd|rd|th|\.)?\s+(?:of\s+|de\s+)?(?P<name>\p{L}{3,})\.?,?\s+(?:de\s+)?(?P<year>\d{4})\b",
+    r"(?i)\b(?P<name>\p{L}{3,})\.?\s+(?P<day>\d{1,2})(?:st|nd|rd|th)?,?\s+(?P<year>\d{4})\b",
+];
+
+fn patterns() -> &'static [Regex] {
+    static COMPILED: OnceLock<Vec<Regex>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        PATTERNS
+            .iter()
+            .filter_map(|pattern| Regex::new(pattern).ok())
+            .collect()
+    })
+}
+
+/// The first date in the text
+pub(crate) fn document_date(text: &str) -> Option<NaiveDate> {
+    let month_first = month_first();
+    patterns()
+        .iter()
+        .flat_map(|pattern| pattern.captures_iter(text))
+        .filter_map(|captures| {
+            let start = captures.get(0)?.start();
+            Some((start, date(&captures, month_first)?))
+        })
+        .min_by_key(|(start, _)| *start)
+        .map(|(_, date)| date)
+}
+
+fn date(captures: &Captures<'_>, month_first: bool) -> Option<NaiveDate> {
+    let number = |name: &str| captures.name(name)?.as_str().parse::<u32>().ok();
+
+    let year = number("year")?;
+    let year = if year < 100 { 2000 + year } else { year };
+    let (month, day) = match (number("first"), number("second")) {
+        (Some(first), Some(second)) => {
+            // Only slashes are written month first, where that is the custom or if the day cannot be a month
+            let slash = captures.name("separator").map(|s| s.as_str()) == Some("/");
+            if second > 12 || (slash && month_first && first <= 12) {
+                (first, second)
+            } else {
+                (second, first)
+            }
+        }
+        _ => {
+            let month = match captures.name("name") {
+                Some(name) => month(name.as_str())?,
+                None => number("month")?,
+            };
+            (month, number("day")?)
+        }
+    };
+
+    if !(1900..=2100).contains(&year) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, day)
+}
+
+/// The number of a month by its name or the abbreviation of its name
+fn month(name: &str) -> Option<u32> {
+    let name = name.to_lowercase();
+    MONTHS
+        .iter()
+        .find(|(month, _)| month.starts_with(name.as_str()))
+        .map(|(_, number)| *number)
+}
+
+/// Whether dates like `03/04/2024` are written month first in the locale of the user
+fn month_first() -> bool {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|value| value.starts_with("en_US"))
+}
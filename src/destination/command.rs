@@ -0,0 +1,53 @@
+//! Running a program with the scan, for anything scannrs does not know how to send to itself
+
+use std::process::Command;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use super::Destination;
+use super::SavedScan;
+use crate::error::ScannrsError;
+
+/// A program and its arguments, run without a shell once the placeholders are filled in
+pub(crate) struct RunCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl RunCommand {
+    pub(crate) fn new(command: &[String]) -> miette::Result<RunCommand> {
+        let (program, args) = command
+            .split_first()
+            .ok_or(ScannrsError::EmptyCommand)
+            .into_diagnostic()?;
+
+        Ok(RunCommand {
+            program: program.clone(),
+            args: args.to_vec(),
+        })
+    }
+}
+
+impl Destination for RunCommand {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let status = Command::new(&self.program)
+            .args(self.args.iter().map(|arg| scan.fill(arg)))
+            .status()
+            .into_diagnostic()
+            .with_context(|| format!("Could not run {}", self.program))?;
+        if !status.success() {
+            return Err(ScannrsError::CommandFailed {
+                program: self.program.clone(),
+                status: status.to_string(),
+            })
+            .into_diagnostic();
+        }
+
+        Ok(self.program.clone())
+    }
+}
@@ -0,0 +1,137 @@
+//! Sending scans as email attachments through SMTP, like the "scan to email" of office printers
+//!
+//! The account is configured in the [email] section, its password is kept in the keyring of the system by
+//! `scannrs login email`, unless one is in the configuration.
+
+use lettre::message::header::ContentType;
+use lettre::message::Attachment;
+use lettre::message::Mailbox;
+use lettre::message::MultiPart;
+use lettre::message::SinglePart;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::Message;
+use lettre::SmtpTransport;
+use lettre::Transport;
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::output::Format;
+
+use super::stored_password;
+use super::Destination;
+use super::SavedScan;
+use crate::config::EmailConfig;
+use crate::config::SmtpSecurity;
+use crate::error::ScannrsError;
+
+/// The name of the configured account in the keyring
+pub(crate) fn account(config: &EmailConfig) -> Option<String> {
+    config
+        .user
+        .as_ref()
+        .map(|user| format!("smtp:{user}@{}", config.server))
+}
+
+/// The user and password to log in with, if the server needs a login
+pub(crate) fn credentials(config: &EmailConfig) -> miette::Result<Option<Credentials>> {
+    let (Some(user), Some(account)) = (&config.user, account(config)) else {
+        return Ok(None);
+    };
+    let password = match &config.password {
+        Some(password) => password.clone(),
+        None => stored_password(&account)?
+            .ok_or_else(|| ScannrsError::EmailNoPassword { user: user.clone() })
+            .into_diagnostic()?,
+    };
+
+    Ok(Some(Credentials::new(user.clone(), password)))
+}
+
+/// Parse an address like `alice@example.com` or `Alice <alice@example.com>`
+pub(crate) fn mailbox(address: &str) -> miette::Result<Mailbox> {
+    address
+        .parse()
+        .map_err(|_| ScannrsError::InvalidEmailAddress {
+            address: address.to_string(),
+        })
+        .into_diagnostic()
+}
+
+/// An SMTP server with the addresses to send to and the login already looked up
+pub(crate) struct Email {
+    config: EmailConfig,
+    to: Vec<Mailbox>,
+    credentials: Option<Credentials>,
+}
+
+impl Email {
+    pub(crate) fn new(config: &EmailConfig, to: &[String]) -> miette::Result<Email> {
+        // Checked here so that a wrong address fails before anything is scanned
+        mailbox(&config.from)?;
+
+        Ok(Email {
+            config: config.clone(),
+            to: to
+                .iter()
+                .map(|address| mailbox(address))
+                .collect::<miette::Result<_>>()?,
+            credentials: credentials(config)?,
+        })
+    }
+}
+
+impl Destination for Email {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    /// Send the scan as an attachment to the addresses
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let config = &self.config;
+        let content_type =
+            Format::from_path(scan.path).map_or("application/octet-stream", Format::mime_type);
+
+        let mut message = Message::builder()
+            .from(mailbox(&config.from)?)
+            .subject(scan.fill(&config.subject));
+        for address in &self.to {
+            message = message.to(address.clone());
+        }
+        let message = message
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(scan.fill(&config.body)))
+                    .singlepart(Attachment::new(scan.name()).body(
+                        scan.read()?,
+                        ContentType::parse(content_type).into_diagnostic()?,
+                    )),
+            )
+            .into_diagnostic()?;
+
+        let mut transport = match config.security {
+            SmtpSecurity::Starttls => {
+                SmtpTransport::starttls_relay(&config.server).into_diagnostic()?
+            }
+            SmtpSecurity::Tls => SmtpTransport::relay(&config.server).into_diagnostic()?,
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.server),
+        };
+        if let Some(port) = config.port {
+            transport = transport.port(port);
+        }
+        if let Some(credentials) = &self.credentials {
+            transport = transport.credentials(credentials.clone());
+        }
+
+        transport
+            .build()
+            .send(&message)
+            .into_diagnostic()
+            .with_context(|| format!("Could not send the email through {}", config.server))?;
+
+        Ok(self
+            .to
+            .iter()
+            .map(|address| address.email.to_string())
+            .collect::<Vec<_>>()
+            .join(", "))
+    }
+}
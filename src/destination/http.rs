@@ -0,0 +1,48 @@
+//! Posting scans to an HTTP endpoint, for services without their own destination
+
+use std::collections::BTreeMap;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::output::Format;
+
+use super::Destination;
+use super::SavedScan;
+
+/// An URL the scan is posted to as the body of the request
+pub(crate) struct HttpPost {
+    pub(crate) url: String,
+    /// Sent with the request, like `Authorization`
+    pub(crate) headers: BTreeMap<String, String>,
+}
+
+impl Destination for HttpPost {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let url = scan.fill(&self.url);
+        let content_type =
+            Format::from_path(scan.path).map_or("application/octet-stream", Format::mime_type);
+
+        let mut request = ureq::AgentBuilder::new()
+            .user_agent(concat!("scannrs/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .post(&url)
+            .set("Content-Type", content_type)
+            .set(
+                "Content-Disposition",
+                &format!("attachment; filename=\"{}\"", scan.name().replace('"', "")),
+            );
+        for (name, value) in &self.headers {
+            request = request.set(name, &scan.fill(value));
+        }
+        request
+            .send_bytes(&scan.read()?)
+            .into_diagnostic()
+            .with_context(|| format!("Could not post the scan to {url}"))?;
+
+        Ok(url)
+    }
+}
@@ -0,0 +1,63 @@
+//! Destinations on this machine: another file, or stdout for piping into other programs
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use super::Destination;
+use super::SavedScan;
+
+/// A copy of the scan at a path filled in from a template, in directories created as needed
+pub(crate) struct CopyFile {
+    pub(crate) template: String,
+}
+
+impl Destination for CopyFile {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let mut target = PathBuf::from(scan.fill(&self.template));
+        // Like `cp`, a directory gets a file of the same name in it
+        if self.template.ends_with('/') || target.is_dir() {
+            target.push(scan.name());
+        }
+
+        if let Some(parent) = target
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .into_diagnostic()
+                .with_context(|| format!("Could not create the directory {}", parent.display()))?;
+        }
+        std::fs::copy(scan.path, &target)
+            .into_diagnostic()
+            .with_context(|| format!("Could not copy the scan to {}", target.display()))?;
+
+        Ok(target.display().to_string())
+    }
+}
+
+/// The contents of the scan written to stdout
+pub(crate) struct Stdout;
+
+impl Destination for Stdout {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(&scan.read()?)
+            .and_then(|()| stdout.flush())
+            .into_diagnostic()
+            .context("Could not write the scan to stdout")?;
+
+        Ok(String::from("stdout"))
+    }
+}
@@ -0,0 +1,331 @@
+//! Sending saved scans on to other places, like another directory, a WebDAV server or an email address
+//!
+//! Every place is a [`Destination`], built from a [`DestinationConfig`] of the selected profile or of the command
+//! line. Scans are sent to all of them, one that fails does not keep the scan from the others.
+
+use std::path::Path;
+
+use chrono::Local;
+use miette::Context;
+use miette::IntoDiagnostic;
+
+use crate::config::Config;
+use crate::config::DestinationConfig;
+use crate::config::Profile;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::events::Event;
+use crate::i18n::tr;
+
+mod command;
+pub(crate) mod email;
+mod http;
+mod local;
+pub(crate) mod paperless;
+#[cfg(feature = "s3")]
+mod s3;
+#[cfg(feature = "sftp")]
+mod sftp;
+pub(crate) mod webdav;
+
+/// The service the passwords are stored under in the keyring
+const KEYRING_SERVICE: &str = "scannrs";
+
+/// A place saved scans are sent to
+pub(crate) trait Destination {
+    /// A short name for messages and events, like `webdav`
+    fn name(&self) -> &'static str;
+
+    /// Send the scan, returning where it ended up to tell the user
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String>;
+}
+
+/// A scan saved to a file, about to be sent on
+pub(crate) struct SavedScan<'a> {
+    pub(crate) path: &'a Path,
+    pub(crate) device: &'a str,
+    /// Tags the rules gave the scan, for destinations that know tags
+    pub(crate) tags: &'a [String],
+    /// Who the rules found the scan to be from
+    pub(crate) correspondent: Option<&'a str>,
+}
+
+impl SavedScan<'_> {
+    /// The name of the saved file
+    fn name(&self) -> String {
+        self.path.file_name().map_or_else(
+            || String::from("scan"),
+            |name| name.to_string_lossy().to_string(),
+        )
+    }
+
+    /// Fill in the placeholders of a template, as described for [`DestinationConfig`]
+    pub(crate) fn fill(&self, template: &str) -> String {
+        let now = Local::now();
+        let device = self
+            .device
+            .replace(|c: char| !c.is_alphanumeric() && c != '-', "_");
+        let path = std::path::absolute(self.path).unwrap_or_else(|_| self.path.to_path_buf());
+
+        template
+            .replace("{date}", &now.format("%Y-%m-%d").to_string())
+            .replace("{time}", &now.format("%H-%M-%S").to_string())
+            .replace("{device}", &device)
+            .replace("{name}", &self.name())
+            .replace("{path}", &path.to_string_lossy())
+    }
+
+    fn read(&self) -> miette::Result<Vec<u8>> {
+        std::fs::read(self.path)
+            .into_diagnostic()
+            .with_context(|| format!("Tried to read the scan at {}", self.path.display()))
+    }
+}
+
+/// Build a destination, checking that it is configured and can log in so that this fails before anything is scanned
+pub(crate) fn destination(
+    config: &Config,
+    destination: &DestinationConfig,
+) -> miette::Result<Box<dyn Destination>> {
+    Ok(match destination {
+        DestinationConfig::File { path } => Box::new(local::CopyFile {
+            template: path.clone(),
+        }),
+        DestinationConfig::Stdout => Box::new(local::Stdout),
+        DestinationConfig::Http { url, headers } => Box::new(http::HttpPost {
+            url: url.clone(),
+            headers: headers.clone(),
+        }),
+        DestinationConfig::Webdav { path } => {
+            Box::new(webdav::Webdav::new(config.webdav()?, path.as_deref())?)
+        }
+        DestinationConfig::Upload { url } => match parse_upload_target(url)? {
+            UploadTarget::S3(location) => s3_destination(config, location)?,
+            UploadTarget::Sftp(location) => ssh_destination(config, location, false)?,
+            UploadTarget::Scp(location) => ssh_destination(config, location, true)?,
+        },
+        DestinationConfig::Email { to } => Box::new(email::Email::new(config.email()?, to)?),
+        DestinationConfig::Paperless {
+            title,
+            tags,
+            correspondent,
+        } => Box::new(paperless::Paperless {
+            config: config.paperless()?.clone(),
+            document: paperless::PaperlessDocument {
+                title: title.clone(),
+                tags: tags.clone(),
+                correspondent: correspondent.clone(),
+            },
+        }),
+        DestinationConfig::Command { command } => Box::new(command::RunCommand::new(command)?),
+    })
+}
+
+/// Everywhere a scan is sent to
+pub(crate) struct Destinations(Vec<Box<dyn Destination>>);
+
+impl Destinations {
+    /// The destinations of the profile followed by the given ones, see [`destination`]
+    pub(crate) fn new(
+        config: &Config,
+        profile: Option<&Profile>,
+        given: &[DestinationConfig],
+    ) -> miette::Result<Destinations> {
+        profile
+            .into_iter()
+            .flat_map(|profile| &profile.destinations)
+            .chain(given)
+            .map(|given| destination(config, given))
+            .collect::<miette::Result<Vec<_>>>()
+            .map(Destinations)
+    }
+
+    /// Send the scan to every destination, reporting each one that fails and failing if any did
+    pub(crate) fn send(&self, scan: &SavedScan<'_>) -> miette::Result<()> {
+        let mut failed = 0;
+        for destination in &self.0 {
+            match destination.send(scan) {
+                Ok(target) => {
+                    events::emit(&Event::Uploaded {
+                        destination: destination.name(),
+                        path: scan.path,
+                    });
+                    events::status(tr!("upload-done", target = target));
+                }
+                Err(error) => {
+                    failed += 1;
+                    let message = error_chain(&error);
+                    if events::enabled() {
+                        events::emit(&Event::UploadFailed {
+                            destination: destination.name(),
+                            message,
+                        });
+                    } else {
+                        events::warning(tr!(
+                            "upload-failed",
+                            destination = destination.name(),
+                            message = message
+                        ));
+                    }
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(ScannrsError::DestinationsFailed {
+                failed,
+                total: self.0.len(),
+                path: scan.path.to_path_buf(),
+            })
+            .into_diagnostic();
+        }
+        Ok(())
+    }
+}
+
+/// A place to upload to, given by its URL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum UploadTarget {
+    /// `s3://bucket/prefix/`, needs the `s3` feature
+    S3(S3Location),
+    /// `sftp://user@host:port/path/`, needs the `sftp` feature
+    Sftp(SshLocation),
+    /// `scp://user@host:port/path/`, for servers without SFTP, needs the `sftp` feature
+    Scp(SshLocation),
+}
+
+/// Where in an S3 bucket a scan is put
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct S3Location {
+    pub(crate) bucket: String,
+    /// The key of the object, or the prefix of it if it is empty or ends in `/`
+    pub(crate) key: String,
+}
+
+/// A file or directory on a server reached over SSH
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SshLocation {
+    /// The user to log in as, the one of the configuration or the current one if not given
+    pub(crate) user: Option<String>,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    /// The path of the file, or of the directory it goes into if it ends in `/`, relative to the home directory if it
+    /// starts with `/~/`
+    pub(crate) path: String,
+}
+
+impl SshLocation {
+    fn parse(rest: &str) -> Option<SshLocation> {
+        let (authority, path) = rest.split_once('/')?;
+        let (user, address) = match authority.rsplit_once('@') {
+            Some((user, address)) => (Some(user.to_string()), address),
+            None => (None, authority),
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => (host, port.parse().ok()?),
+            _ => (address, 22),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return None;
+        }
+
+        Some(SshLocation {
+            user,
+            host: host.to_string(),
+            port,
+            path: format!("/{path}"),
+        })
+    }
+}
+
+/// Parse the URL of a place to upload to, like `s3://bucket/prefix/`
+pub(crate) fn parse_upload_target(url: &str) -> miette::Result<UploadTarget> {
+    let unsupported = || ScannrsError::UnsupportedUpload {
+        url: url.to_string(),
+    };
+
+    match url.split_once("://") {
+        Some(("s3", rest)) => {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            if bucket.is_empty() {
+                return Err(unsupported()).into_diagnostic();
+            }
+            Ok(UploadTarget::S3(S3Location {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            }))
+        }
+        Some(("sftp", rest)) => SshLocation::parse(rest)
+            .map(UploadTarget::Sftp)
+            .ok_or_else(unsupported)
+            .into_diagnostic(),
+        Some(("scp", rest)) => SshLocation::parse(rest)
+            .map(UploadTarget::Scp)
+            .ok_or_else(unsupported)
+            .into_diagnostic(),
+        _ => Err(unsupported()).into_diagnostic(),
+    }
+}
+
+/// The keyring entry holding the password of an account, named like `webdav:alice@https://cloud.example.com/`
+pub(crate) fn keyring_entry(account: &str) -> miette::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, account)
+        .into_diagnostic()
+        .context("Could not access the keyring of the system")
+}
+
+/// The password of the account in the keyring, if one was stored
+pub(crate) fn stored_password(account: &str) -> miette::Result<Option<String>> {
+    match keyring_entry(account)?.get_password() {
+        Ok(password) => Ok(Some(password)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(error) => Err(error)
+            .into_diagnostic()
+            .context("Could not read the password from the keyring"),
+    }
+}
+
+#[cfg(feature = "s3")]
+fn s3_destination(config: &Config, location: S3Location) -> miette::Result<Box<dyn Destination>> {
+    Ok(Box::new(s3::S3 {
+        config: config.s3.clone(),
+        location,
+    }))
+}
+
+#[cfg(not(feature = "s3"))]
+fn s3_destination(_config: &Config, _location: S3Location) -> miette::Result<Box<dyn Destination>> {
+    Err(ScannrsError::UploadNotBuilt {
+        service: "S3",
+        feature: "s3",
+    })
+    .into_diagnostic()
+}
+
+#[cfg(feature = "sftp")]
+fn ssh_destination(
+    config: &Config,
+    location: SshLocation,
+    scp: bool,
+) -> miette::Result<Box<dyn Destination>> {
+    Ok(Box::new(sftp::Ssh {
+        config: config.sftp.clone(),
+        location,
+        scp,
+    }))
+}
+
+#[cfg(not(feature = "sftp"))]
+fn ssh_destination(
+    _config: &Config,
+    _location: SshLocation,
+    _scp: bool,
+) -> miette::Result<Box<dyn Destination>> {
+    Err(ScannrsError::UploadNotBuilt {
+        service: "SFTP",
+        feature: "sftp",
+    })
+    .into_diagnostic()
+}
@@ -0,0 +1,203 @@
+//! Uploading scans to paperless-ngx through its consumption API
+//!
+//! Documents are posted to `/api/documents/post_document/`, where paperless-ngx queues them for consumption and
+//! answers with the id of the task. Tags and correspondents are given by their name or id, names are looked up first.
+
+use std::path::Path;
+use std::time::SystemTime;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use serde::Deserialize;
+
+use super::Destination;
+use super::SavedScan;
+use crate::config::PaperlessConfig;
+use crate::error::ScannrsError;
+
+/// paperless-ngx, with what it should know about the documents sent to it
+pub(crate) struct Paperless {
+    pub(crate) config: PaperlessConfig,
+    pub(crate) document: PaperlessDocument,
+}
+
+impl Destination for Paperless {
+    fn name(&self) -> &'static str {
+        "paperless"
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let document = PaperlessDocument {
+            title: self.document.title.clone(),
+            tags: self
+                .document
+                .tags
+                .iter()
+                .chain(scan.tags)
+                .cloned()
+                .collect(),
+            correspondent: self
+                .document
+                .correspondent
+                .clone()
+                .or_else(|| scan.correspondent.map(str::to_string)),
+        };
+        let task = upload(&self.config, scan.path, &document)?;
+        Ok(format!("paperless-ngx, consumed in task {task}"))
+    }
+}
+
+/// What paperless-ngx should know about the uploaded document, as given on the command line or in the profile
+#[derive(Default, Debug)]
+pub(crate) struct PaperlessDocument {
+    /// The title, paperless-ngx uses the name of the file if there is none
+    pub(crate) title: Option<String>,
+    /// Tags given in addition to the ones of the configuration
+    pub(crate) tags: Vec<String>,
+    pub(crate) correspondent: Option<String>,
+}
+
+/// A page of the results of a list endpoint, of which only the ids are needed
+#[derive(Deserialize)]
+struct Listing {
+    results: Vec<Named>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+    id: u64,
+}
+
+/// Upload the file at `path`, returning the id of the task paperless-ngx consumes it in
+fn upload(
+    config: &PaperlessConfig,
+    path: &Path,
+    document: &PaperlessDocument,
+) -> miette::Result<String> {
+    let client = Client::new(config);
+
+    let mut fields = Vec::new();
+    if let Some(title) = &document.title {
+        fields.push(("title", title.clone()));
+    }
+    if let Some(correspondent) = &document.correspondent {
+        fields.push((
+            "correspondent",
+            client.id("correspondents", correspondent)?.to_string(),
+        ));
+    }
+    for tag in config.tags.iter().chain(&document.tags) {
+        fields.push(("tags", client.id("tags", tag)?.to_string()));
+    }
+
+    let contents = std::fs::read(path)
+        .into_diagnostic()
+        .with_context(|| format!("Tried to read the scan at {}", path.display()))?;
+    let file_name = path.file_name().map_or_else(
+        || String::from("scan"),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let boundary = format!(
+        "scannrs-{:x}",
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos()
+    );
+
+    client
+        .request("POST", "documents/post_document/")
+        .set(
+            "Content-Type",
+            &format!("multipart/form-data; boundary={boundary}"),
+        )
+        .send_bytes(&multipart(&boundary, &fields, &file_name, &contents))
+        .map_err(|error| client.error(error))?
+        .into_json::<String>()
+        .into_diagnostic()
+        .context("paperless-ngx did not answer with the id of the consumption task")
+}
+
+struct Client<'a> {
+    agent: ureq::Agent,
+    config: &'a PaperlessConfig,
+}
+
+impl<'a> Client<'a> {
+    fn new(config: &'a PaperlessConfig) -> Client<'a> {
+        Client {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(concat!("scannrs/", env!("CARGO_PKG_VERSION")))
+                .build(),
+            config,
+        }
+    }
+
+    fn request(&self, method: &str, endpoint: &str) -> ureq::Request {
+        let url = format!("{}/api/{endpoint}", self.config.url.trim_end_matches('/'));
+        self.agent
+            .request(method, &url)
+            .set("Authorization", &format!("Token {}", self.config.token))
+            .set("Accept", "application/json")
+    }
+
+    /// The id of the tag or correspondent with the given name, or the given id itself
+    fn id(&self, kind: &'static str, name: &str) -> miette::Result<u64> {
+        if let Ok(id) = name.parse() {
+            return Ok(id);
+        }
+
+        let listing = self
+            .request("GET", &format!("{kind}/"))
+            .query("name__iexact", name)
+            .call()
+            .map_err(|error| self.error(error))?
+            .into_json::<Listing>()
+            .into_diagnostic()
+            .with_context(|| format!("paperless-ngx sent an invalid list of {kind}"))?;
+
+        listing
+            .results
+            .first()
+            .map(|named| named.id)
+            .ok_or_else(|| ScannrsError::PaperlessNotFound {
+                kind,
+                name: name.to_string(),
+            })
+            .into_diagnostic()
+    }
+
+    fn error(&self, error: ureq::Error) -> miette::Report {
+        match error {
+            ureq::Error::Status(401 | 403, _) => ScannrsError::PaperlessTokenRejected.into(),
+            error => miette::Report::from_err(error).wrap_err(format!(
+                "Could not reach paperless-ngx at {}",
+                self.config.url
+            )),
+        }
+    }
+}
+
+/// A `multipart/form-data` body with the fields and the file as `document`
+fn multipart(boundary: &str, fields: &[(&str, String)], file_name: &str, file: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+            )
+            .as_bytes(),
+        );
+    }
+
+    let file_name = file_name.replace('"', "");
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"document\"; filename=\"{file_name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(file);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
@@ -0,0 +1,92 @@
+//! Uploading scans to S3 and compatible object storage, like MinIO
+//!
+//! Credentials and the region are found like the AWS CLI finds them, in the `AWS_*` variables, `~/.aws/config` and
+//! `~/.aws/credentials`, or from the instance or container the command runs in. The [s3] section of the
+//! configuration points at other endpoints.
+
+use std::path::Path;
+
+use aws_config::BehaviorVersion;
+use aws_config::Region;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::output::Format;
+
+use super::Destination;
+use super::S3Location;
+use super::SavedScan;
+use crate::config::S3Config;
+use crate::error::ScannrsError;
+
+/// A bucket, or a prefix in it, to upload to
+pub(crate) struct S3 {
+    pub(crate) config: Option<S3Config>,
+    pub(crate) location: S3Location,
+}
+
+impl Destination for S3 {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        upload(self.config.as_ref(), &self.location, scan.path)
+    }
+}
+
+/// Upload the file at `path`, returning the `s3://` URL it was uploaded to
+fn upload(config: Option<&S3Config>, location: &S3Location, path: &Path) -> miette::Result<String> {
+    let name = path.file_name().map_or_else(
+        || String::from("scan"),
+        |name| name.to_string_lossy().to_string(),
+    );
+    // A key ending in `/` is a prefix, like a directory
+    let key = if location.key.is_empty() || location.key.ends_with('/') {
+        format!("{}{name}", location.key)
+    } else {
+        location.key.clone()
+    };
+    let content_type =
+        Format::from_path(path).map_or("application/octet-stream", Format::mime_type);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .into_diagnostic()?;
+    runtime.block_on(async {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(region) = config.and_then(|config| config.region.clone()) {
+            loader = loader.region(Region::new(region));
+        }
+        if let Some(endpoint) = config.and_then(|config| config.endpoint.as_deref()) {
+            loader = loader.endpoint_url(endpoint);
+        }
+        let shared = loader.load().await;
+        let client = aws_sdk_s3::Client::from_conf(
+            aws_sdk_s3::config::Builder::from(&shared)
+                .force_path_style(config.is_some_and(|config| config.path_style))
+                .build(),
+        );
+
+        let body = ByteStream::from_path(path)
+            .await
+            .into_diagnostic()
+            .with_context(|| format!("Tried to read the scan at {}", path.display()))?;
+        client
+            .put_object()
+            .bucket(&location.bucket)
+            .key(&key)
+            .content_type(content_type)
+            .body(body)
+            .send()
+            .await
+            .map_err(|error| ScannrsError::S3 {
+                message: DisplayErrorContext(error).to_string(),
+            })
+            .into_diagnostic()?;
+
+        Ok(format!("s3://{}/{key}", location.bucket))
+    })
+}
@@ -0,0 +1,185 @@
+//! Uploading scans to servers over SSH, with SFTP or SCP
+//!
+//! Servers have to be known in `~/.ssh/known_hosts`, as after connecting with `ssh` once. Logging in is tried with the
+//! keys of the SSH agent first, then with the key of the [sftp] section of the configuration or the default keys in
+//! `~/.ssh`. Passwords are not supported.
+
+use std::fs::File;
+use std::io::Write;
+use std::net::TcpStream;
+use std::path::Path;
+use std::path::PathBuf;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+use ssh2::CheckResult;
+use ssh2::KnownHostFileKind;
+use ssh2::Session;
+
+use super::Destination;
+use super::SavedScan;
+use super::SshLocation;
+use crate::config::SftpConfig;
+use crate::error::ScannrsError;
+
+/// The keys tried when none is configured, in the order `ssh` tries them
+const DEFAULT_IDENTITIES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// A server reached over SSH, with SFTP or with SCP for servers without it
+pub(crate) struct Ssh {
+    pub(crate) config: Option<SftpConfig>,
+    pub(crate) location: SshLocation,
+    pub(crate) scp: bool,
+}
+
+impl Destination for Ssh {
+    fn name(&self) -> &'static str {
+        if self.scp {
+            "scp"
+        } else {
+            "sftp"
+        }
+    }
+
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        upload(self.config.as_ref(), &self.location, self.scp, scan.path)
+    }
+}
+
+/// Upload the file at `path`, with SCP instead of SFTP if `scp` is set, returning the URL it was uploaded to
+fn upload(
+    config: Option<&SftpConfig>,
+    location: &SshLocation,
+    scp: bool,
+    path: &Path,
+) -> miette::Result<String> {
+    let user = location
+        .user
+        .clone()
+        .or_else(|| config.and_then(|config| config.user.clone()))
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .ok_or(ScannrsError::SshNoUser)?;
+    let name = path.file_name().map_or_else(
+        || String::from("scan"),
+        |name| name.to_string_lossy().to_string(),
+    );
+    let remote = remote_path(&location.path, &name);
+
+    let session = connect(location)?;
+    authenticate(&session, &user, config)?;
+
+    let mut file = File::open(path)
+        .into_diagnostic()
+        .with_context(|| format!("Tried to read the scan at {}", path.display()))?;
+    if scp {
+        let size = file.metadata().into_diagnostic()?.len();
+        let mut channel = session
+            .scp_send(Path::new(&remote), 0o644, size, None)
+            .into_diagnostic()?;
+        std::io::copy(&mut file, &mut channel).into_diagnostic()?;
+        // The file is only complete once the server saw the end of it
+        channel.send_eof().into_diagnostic()?;
+        channel.wait_eof().into_diagnostic()?;
+        channel.close().into_diagnostic()?;
+        channel.wait_close().into_diagnostic()?;
+    } else {
+        let sftp = session.sftp().into_diagnostic()?;
+        let mut target = sftp
+            .create(Path::new(&remote))
+            .into_diagnostic()
+            .with_context(|| format!("Could not create {remote} on {}", location.host))?;
+        std::io::copy(&mut file, &mut target).into_diagnostic()?;
+        target.flush().into_diagnostic()?;
+    }
+
+    let scheme = if scp { "scp" } else { "sftp" };
+    let remote = if remote.starts_with('/') {
+        remote
+    } else {
+        format!("/~/{remote}")
+    };
+    Ok(format!(
+        "{scheme}://{user}@{}:{}{remote}",
+        location.host, location.port
+    ))
+}
+
+/// Connect to the server, refusing it unless its host key is known
+fn connect(location: &SshLocation) -> miette::Result<Session> {
+    let tcp = TcpStream::connect((location.host.as_str(), location.port))
+        .into_diagnostic()
+        .with_context(|| format!("Could not connect to {}:{}", location.host, location.port))?;
+    let mut session = Session::new().into_diagnostic()?;
+    session.set_tcp_stream(tcp);
+    session.handshake().into_diagnostic()?;
+
+    let mut known_hosts = session.known_hosts().into_diagnostic()?;
+    if let Some(file) = ssh_dir().map(|dir| dir.join("known_hosts")) {
+        if file.exists() {
+            known_hosts
+                .read_file(&file, KnownHostFileKind::OpenSSH)
+                .into_diagnostic()
+                .with_context(|| format!("Could not read {}", file.display()))?;
+        }
+    }
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| ScannrsError::SshUnknownHost {
+            host: location.host.clone(),
+        })?;
+
+    match known_hosts.check_port(&location.host, location.port, key) {
+        CheckResult::Match => Ok(session),
+        CheckResult::Mismatch => Err(ScannrsError::SshHostKeyChanged {
+            host: location.host.clone(),
+        }
+        .into()),
+        CheckResult::NotFound | CheckResult::Failure => Err(ScannrsError::SshUnknownHost {
+            host: location.host.clone(),
+        }
+        .into()),
+    }
+}
+
+/// Log in with the keys of the agent, or with the configured or default key files
+fn authenticate(session: &Session, user: &str, config: Option<&SftpConfig>) -> miette::Result<()> {
+    if session.userauth_agent(user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+
+    let identities = match config.and_then(|config| config.identity.clone()) {
+        Some(identity) => vec![identity],
+        None => ssh_dir()
+            .map(|dir| DEFAULT_IDENTITIES.map(|name| dir.join(name)).to_vec())
+            .unwrap_or_default(),
+    };
+    for identity in identities.iter().filter(|identity| identity.exists()) {
+        if session
+            .userauth_pubkey_file(user, None, identity, None)
+            .is_ok()
+            && session.authenticated()
+        {
+            return Ok(());
+        }
+    }
+
+    Err(ScannrsError::SshAuthFailed {
+        user: user.to_string(),
+    }
+    .into())
+}
+
+fn ssh_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh"))
+}
+
+/// The path of the file on the server, paths starting with `/~/` are relative to the home directory
+fn remote_path(path: &str, name: &str) -> String {
+    let path = path.strip_prefix("/~/").unwrap_or(path);
+    if path.is_empty() || path.ends_with('/') {
+        format!("{path}{name}")
+    } else {
+        path.to_string()
+    }
+}
@@ -0,0 +1,123 @@
+//! Uploading scans to a WebDAV server, like Nextcloud or ownCloud
+//!
+//! The remote path is a template relative to the configured URL, the collections it lies in are created as needed.
+//! Passwords are kept in the keyring of the system by `scannrs login webdav`, unless one is in the configuration.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use super::stored_password;
+use super::Destination;
+use super::SavedScan;
+use crate::config::WebdavConfig;
+use crate::error::ScannrsError;
+
+/// The name of the configured account in the keyring
+pub(crate) fn account(config: &WebdavConfig) -> String {
+    format!("webdav:{}@{}", config.user, config.url)
+}
+
+/// The password from the configuration, or the one stored in the keyring
+pub(crate) fn password(config: &WebdavConfig) -> miette::Result<String> {
+    if let Some(password) = &config.password {
+        return Ok(password.clone());
+    }
+
+    stored_password(&account(config))?
+        .ok_or_else(|| ScannrsError::WebdavNoPassword {
+            user: config.user.clone(),
+        })
+        .map_err(miette::Report::from)
+}
+
+/// A WebDAV server with the password already looked up
+pub(crate) struct Webdav {
+    config: WebdavConfig,
+    authorization: String,
+}
+
+impl Webdav {
+    /// Upload to the configured server, to `path` instead of the configured path if given
+    pub(crate) fn new(config: &WebdavConfig, path: Option<&str>) -> miette::Result<Webdav> {
+        let authorization = format!(
+            "Basic {}",
+            STANDARD.encode(format!("{}:{}", config.user, password(config)?))
+        );
+        let mut config = config.clone();
+        if let Some(path) = path {
+            config.path = path.to_string();
+        }
+
+        Ok(Webdav {
+            config,
+            authorization,
+        })
+    }
+}
+
+impl Destination for Webdav {
+    fn name(&self) -> &'static str {
+        "webdav"
+    }
+
+    /// Upload the scan, returning the URL it was uploaded to
+    fn send(&self, scan: &SavedScan<'_>) -> miette::Result<String> {
+        let remote = remote_path(&scan.fill(&self.config.path));
+        let agent = ureq::AgentBuilder::new()
+            .user_agent(concat!("scannrs/", env!("CARGO_PKG_VERSION")))
+            .build();
+        let base = self.config.url.trim_end_matches('/');
+
+        // Collections have to exist before anything can be put into them, existing ones are answered with 405
+        let segments = remote.split('/').collect::<Vec<_>>();
+        for depth in 1..segments.len() {
+            let collection = format!("{base}/{}/", segments[..depth].join("/"));
+            match agent
+                .request("MKCOL", &collection)
+                .set("Authorization", &self.authorization)
+                .call()
+            {
+                Ok(_) | Err(ureq::Error::Status(405, _)) => {}
+                Err(error) => return Err(request_error(error, &collection)),
+            }
+        }
+
+        let url = format!("{base}/{remote}");
+        agent
+            .put(&url)
+            .set("Authorization", &self.authorization)
+            .set("Content-Type", "application/octet-stream")
+            .send_bytes(&scan.read()?)
+            .map_err(|error| request_error(error, &url))?;
+
+        Ok(url)
+    }
+}
+
+fn request_error(error: ureq::Error, url: &str) -> miette::Report {
+    match error {
+        ureq::Error::Status(401 | 403, _) => ScannrsError::WebdavRejected.into(),
+        error => miette::Report::from_err(error).wrap_err(format!("Could not upload to {url}")),
+    }
+}
+
+/// Percent-encode each segment of the remote path
+fn remote_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(encode)
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn encode(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,135 @@
+//! Remembering the scanners found by enumerating them, which takes many seconds with SANE's network backends
+//!
+//! The list is kept in the cache directory with the backend that found it and when, and is handed out to `list` instead
+//! of enumerating again until it is older than `device_cache` seconds of the `[defaults]` in the configuration.
+//! `--refresh` enumerates again regardless, and `device_cache = 0` turns the cache off. Long-running commands like the
+//! TUI and `serve` always enumerate, as they are expected to notice scanners plugged in while they run.
+
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use miette::IntoDiagnostic;
+use scannrs_core::backend::ScanBackend;
+use scannrs_core::backend::ScanDevice;
+use scannrs_core::device::DeviceInfo;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::events;
+use crate::i18n::tr;
+use crate::paths;
+
+/// How long the list is used if the configuration does not say
+const DEFAULT_TTL: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct CachedDevices {
+    /// The version of the backend, a list found by another backend is not used
+    backend: String,
+    /// When the list was made, in seconds since the Unix epoch
+    found: u64,
+    devices: Vec<DeviceInfo>,
+}
+
+/// A backend listing the scanners from the cache while it is fresh
+pub(crate) struct DeviceCache {
+    backend: Box<dyn ScanBackend>,
+    /// Whether the next listing enumerates again in any case
+    refresh: AtomicBool,
+}
+
+impl DeviceCache {
+    pub(crate) fn new(backend: Box<dyn ScanBackend>, refresh: bool) -> DeviceCache {
+        DeviceCache {
+            backend,
+            refresh: AtomicBool::new(refresh),
+        }
+    }
+}
+
+fn path() -> miette::Result<PathBuf> {
+    Ok(paths::cache_dir()?.join("devices.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// The cached list, if there is one from this backend that is younger than `ttl`
+fn read(backend: &str, ttl: Duration) -> Option<Vec<DeviceInfo>> {
+    let cached: CachedDevices = serde_json::from_slice(&std::fs::read(path().ok()?).ok()?).ok()?;
+    let age = now().checked_sub(cached.found)?;
+
+    (cached.backend == backend && age < ttl.as_secs()).then_some(cached.devices)
+}
+
+fn write(cached: &CachedDevices) -> miette::Result<()> {
+    let path = path()?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).into_diagnostic()?;
+    }
+    std::fs::write(path, serde_json::to_vec(cached).into_diagnostic()?).into_diagnostic()
+}
+
+impl ScanBackend for DeviceCache {
+    fn version(&self) -> String {
+        self.backend.version()
+    }
+
+    fn devices(&self) -> miette::Result<Vec<DeviceInfo>> {
+        let mut devices = Vec::new();
+        self.devices_streamed(&mut |found| devices.extend(found))?;
+        Ok(devices)
+    }
+
+    /// The cached list is handed over at once, otherwise the scanners are handed over as the backend finds them
+    fn devices_streamed(&self, found: &mut dyn FnMut(Vec<DeviceInfo>)) -> miette::Result<()> {
+        let ttl = Config::load()?
+            .defaults
+            .device_cache
+            .map_or(DEFAULT_TTL, Duration::from_secs);
+        if ttl.is_zero() {
+            return self.backend.devices_streamed(found);
+        }
+
+        let backend = self.backend.version();
+        if !self.refresh.swap(false, Ordering::Relaxed) {
+            if let Some(devices) = read(&backend, ttl) {
+                found(devices);
+                return Ok(());
+            }
+        }
+
+        let mut devices = Vec::new();
+        self.backend.devices_streamed(&mut |group| {
+            devices.extend(group.iter().cloned());
+            found(group);
+        })?;
+        // A cache that cannot be written only makes the next listing slow
+        let cached = CachedDevices {
+            backend,
+            found: now(),
+            devices,
+        };
+        if let Err(error) = write(&cached) {
+            events::warning(tr!("device-cache-failed", message = error.to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn open(&self, name: &str) -> miette::Result<Box<dyn ScanDevice>> {
+        self.backend.open(name)
+    }
+
+    fn claims(&self, name: &str) -> bool {
+        self.backend.claims(name)
+    }
+}
@@ -1,28 +1,299 @@
+use std::path::PathBuf;
+
 use miette::Diagnostic;
+use miette::NamedSource;
+use miette::SourceSpan;
 use thiserror::Error;
 
 #[derive(Debug, Error, Diagnostic)]
 pub(crate) enum ScannrsError {
-    #[error("Could not find scanner with name: '{}'", .name)]
-    CouldNotFindScanner { name: String },
-
-    #[error("An error occured while communicating with the scanner: {}", .error)]
-    Sane {
-        #[from]
-        error: sane_scan::Error,
-    },
-    #[error("The given option '{}' does not exist for scanner '{}'", .option, .name)]
-    OptionNotFound { name: String, option: String },
+    #[error("The option '{}' of scanner '{}' is inactive or cannot be set", .option, .name)]
+    #[diagnostic(help(
+        "Options can depend on others, like the depth on the mode, see `scannrs options {} describe {}`",
+        .name,
+        .option
+    ))]
+    OptionNotSettable { name: String, option: String },
 
     #[error("The given option is not formatted correctly. Please use `key=value`")]
     InvalidOption,
 
-    #[error("The scanner gave nonsensical values, or there is a bug. It was reported: {width}x{height}pixels with a\
-        bitdepth of {pixel_size} to fit into {buffer_size}. If the values make sense, please report it as a bug")]
-    InvalidImageSize {
-        width: u32,
-        height: u32,
-        buffer_size: usize,
-        pixel_size: u32,
+    #[error("The thread communicating with the scanners has stopped unexpectedly")]
+    SaneHandlerStopped,
+
+    #[error("The resolution needs to be a positive number of dots per inch")]
+    InvalidDpi,
+
+    #[error("Could not determine the home directory of the current user")]
+    NoHomeDirectory,
+
+    #[cfg(unix)]
+    #[error("A daemon is already listening on '{}'", .socket.display())]
+    DaemonAlreadyRunning { socket: std::path::PathBuf },
+
+    #[cfg(unix)]
+    #[error("Could not connect to the daemon at '{}'", .socket.display())]
+    #[diagnostic(help(
+        "Start the daemon with `scannrs daemon`, or point to its socket with `--socket`"
+    ))]
+    DaemonNotRunning { socket: std::path::PathBuf },
+
+    #[cfg(unix)]
+    #[error("The daemon reported an error: {}", .message)]
+    Daemon { message: String },
+
+    #[error(
+        "The duration is not valid, use a number followed by s, m, h or d, like `30s` or `5m`"
+    )]
+    InvalidDuration,
+
+    #[cfg(unix)]
+    #[error("The time is not valid, use the 24-hour `HH:MM` format")]
+    InvalidTime,
+
+    #[error("'{}' does not refer to a scan, use its id or `last`", .value)]
+    InvalidHistoryRef { value: String },
+
+    #[error("There is no such scan in the history")]
+    #[diagnostic(help("Past scans and their ids are listed by `scannrs history`"))]
+    HistoryEntryNotFound,
+
+    #[error("The file '{}' does not exist anymore", .path.display())]
+    FileMissing { path: std::path::PathBuf },
+
+    #[error("The scanner '{}' does not allow selecting a scan area", .name)]
+    ScanAreaUnsupported { name: String },
+
+    #[error("A batch is already in progress")]
+    #[diagnostic(help(
+        "Continue it with `scannrs batch --resume`, or delete '{}' to start over",
+        .state.display()
+    ))]
+    BatchInProgress { state: std::path::PathBuf },
+
+    #[error("There is no interrupted batch to resume")]
+    NoBatchToResume,
+
+    #[error("There is no job with id {}", .id)]
+    JobNotFound { id: u64 },
+
+    #[error("The scanner returned an empty image")]
+    EmptyImage,
+
+    #[error("{}", .message)]
+    InvalidToml {
+        message: String,
+        #[source_code]
+        contents: NamedSource<String>,
+        #[label("here")]
+        span: Option<SourceSpan>,
+    },
+
+    #[error("The value '{}' of ${} is not valid, expected {}", .value, .name, .expected)]
+    InvalidEnvVar {
+        name: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+
+    #[error("No scanner was given")]
+    #[diagnostic(help(
+        "Name the scanner on the command line, or set a default one as `device` in the [defaults] of the configuration"
+    ))]
+    NoDevice,
+
+    #[error("There is no profile named '{}'", .name)]
+    #[diagnostic(help("The profiles in the configuration are: {}", .available))]
+    ProfileNotFound { name: String, available: String },
+
+    #[error("There is no post-processor named '{}'", .name)]
+    #[diagnostic(help("The available post-processors are: {}", .available))]
+    UnknownPostProcessor { name: String, available: String },
+
+    #[error("paperless-ngx is not configured")]
+    #[diagnostic(help(
+        "Add a [paperless] section with the `url` of paperless-ngx and an API `token` to the configuration"
+    ))]
+    PaperlessNotConfigured,
+
+    #[error("paperless-ngx did not accept the API token")]
+    #[diagnostic(help(
+        "Create a token in the profile settings of paperless-ngx and set it as `token` in the [paperless] section"
+    ))]
+    PaperlessTokenRejected,
+
+    #[error("There are no {} named '{}' in paperless-ngx", .kind, .name)]
+    #[diagnostic(help("Create them in paperless-ngx first, or give their id instead"))]
+    PaperlessNotFound { kind: &'static str, name: String },
+
+    #[error("WebDAV is not configured")]
+    #[diagnostic(help(
+        "Add a [webdav] section with the `url` of the server and the `user` to the configuration"
+    ))]
+    WebdavNotConfigured,
+
+    #[error("There is no WebDAV password for '{}'", .user)]
+    #[diagnostic(help("Store it in the keyring with `scannrs login webdav`"))]
+    WebdavNoPassword { user: String },
+
+    #[error("The WebDAV server did not accept the user and password")]
+    #[diagnostic(help(
+        "Store the right password with `scannrs login webdav`, Nextcloud needs an app password with two-factor authentication"
+    ))]
+    WebdavRejected,
+
+    #[error("Email is not configured")]
+    #[diagnostic(help(
+        "Add an [email] section with the `server` and the `from` address to the configuration"
+    ))]
+    EmailNotConfigured,
+
+    #[error("There is no SMTP password for '{}'", .user)]
+    #[diagnostic(help("Store it in the keyring with `scannrs login email`"))]
+    EmailNoPassword { user: String },
+
+    #[error("The email account has no user to log in as")]
+    #[diagnostic(help("Set the `user` in the [email] section of the configuration"))]
+    EmailNoUser,
+
+    #[error("'{}' is not an email address", .address)]
+    #[diagnostic(help("Use an address like `alice@example.com` or `Alice <alice@example.com>`"))]
+    InvalidEmailAddress { address: String },
+
+    #[error("Cannot upload to '{}'", .url)]
+    #[diagnostic(help(
+        "Give the place to upload to as `s3://bucket/prefix/`, `sftp://user@host/path/` or `scp://user@host/path/`"
+    ))]
+    UnsupportedUpload { url: String },
+
+    #[error("This build of scannrs cannot upload to {}", .service)]
+    #[diagnostic(help("Build it with the `{}` feature", .feature))]
+    UploadNotBuilt {
+        service: &'static str,
+        feature: &'static str,
+    },
+
+    #[error("The upload to S3 failed: {}", .message)]
+    #[diagnostic(help(
+        "Credentials are read from $AWS_ACCESS_KEY_ID and $AWS_SECRET_ACCESS_KEY or ~/.aws/credentials, other endpoints like MinIO are set in the [s3] section of the configuration"
+    ))]
+    S3 { message: String },
+
+    #[error("The host key of '{}' is not known", .host)]
+    #[diagnostic(help(
+        "Connect once with `ssh {}` to check its key and add it to ~/.ssh/known_hosts",
+        .host
+    ))]
+    SshUnknownHost { host: String },
+
+    #[error("The host key of '{}' does not match the one in ~/.ssh/known_hosts", .host)]
+    #[diagnostic(help(
+        "Someone could be intercepting the connection, if the server was set up anew remove the old key with `ssh-keygen -R {}`",
+        .host
+    ))]
+    SshHostKeyChanged { host: String },
+
+    #[error("Could not log in as '{}' with any SSH key", .user)]
+    #[diagnostic(help(
+        "Add the key to the SSH agent, or set its path as `identity` in the [sftp] section of the configuration"
+    ))]
+    SshAuthFailed { user: String },
+
+    #[error("Could not determine the user to log in as over SSH")]
+    #[diagnostic(help("Give it in the URL, like `sftp://user@host/path/`"))]
+    SshNoUser,
+
+    #[error("A command destination needs at least the program to run")]
+    #[diagnostic(help("Give it as a list, like `command = [\"lp\", \"{path}\"]`"))]
+    EmptyCommand,
+
+    #[error("'{}' is not the address of an MQTT broker", .url)]
+    #[diagnostic(help(
+        "Give it like `mqtt://homeassistant.local` or `mqtts://broker.example.com:8883`"
+    ))]
+    InvalidBroker { url: String },
+
+    #[error("There is no MQTT password for '{}'", .user)]
+    #[diagnostic(help("Store it in the keyring with `scannrs login mqtt`"))]
+    MqttNoPassword { user: String },
+
+    #[error("MQTT is not configured")]
+    #[diagnostic(help("Add an [mqtt] section with the `broker` to the configuration"))]
+    MqttNotConfigured,
+
+    #[error("The MQTT broker has no user to log in as")]
+    #[diagnostic(help("Set the `user` in the [mqtt] section of the configuration"))]
+    MqttNoUser,
+
+    #[cfg(feature = "dbus")]
+    #[error("Could not offer the D-Bus service: {}", .error)]
+    #[diagnostic(help(
+        "Check that a D-Bus session is running, or that the system bus allows owning `org.scannrs` with `--system`"
+    ))]
+    Dbus { error: zbus::Error },
+
+    #[error("This build of scannrs cannot connect to MQTT brokers")]
+    #[diagnostic(help(
+        "Build it with the `mqtt` feature, or remove the [mqtt] section of the configuration"
+    ))]
+    MqttNotBuilt,
+
+    #[error("The rule '{}' is not a valid regular expression: {}", .pattern, .message)]
+    InvalidRule { pattern: String, message: String },
+
+    #[error("This build of scannrs cannot show desktop notifications")]
+    #[diagnostic(help("Build it with the `notifications` feature, or leave out `--notify`"))]
+    NotificationsNotBuilt,
+
+    #[error("This build of scannrs cannot serve gRPC")]
+    #[diagnostic(help("Build it with the `grpc` feature, or leave out `--grpc`"))]
+    GrpcNotBuilt,
+
+    #[error("'{}' failed with {}", .program, .status)]
+    CommandFailed { program: String, status: String },
+
+    #[error("The printer {} did not accept the job, it answered with IPP status {}", .printer, .status)]
+    #[diagnostic(help("Check the URI of the printer, and that it can print PDF documents"))]
+    PrintFailed { printer: String, status: String },
+
+    #[error("Sending the scan to {} of {} destinations failed", .failed, .total)]
+    #[diagnostic(help("The scan is still saved at {}", .path.display()))]
+    DestinationsFailed {
+        failed: usize,
+        total: usize,
+        path: PathBuf,
     },
+
+    #[error("The post-processing dropped the page")]
+    PageDropped,
+
+    #[error("{} of {} self-tests failed", .failed, .total)]
+    SelfTestFailed { failed: usize, total: usize },
+
+    #[error("'{}' is not a key, use a single character or one of Enter, Esc, Tab, Backspace, Space or F1 to F12", .key)]
+    InvalidKey { key: String },
+
+    #[error("'{}' is not a color, use a name like `red` or `lightblue`, or a hex code like `#ff8800`", .color)]
+    InvalidColor { color: String },
+
+    #[error("The TUI needs an interactive terminal, but {} is not one", .stream)]
+    #[diagnostic(help(
+        "Use `scannrs scan` or `scannrs batch` in scripts, cron jobs and pipelines"
+    ))]
+    NotATerminal { stream: &'static str },
+
+    #[error("scannrs was built without a backend to talk to scanners")]
+    #[diagnostic(help(
+        "Build it with the `sane`, `escl` or `wsd` feature, or the `wia` feature on Windows"
+    ))]
+    NoBackend,
+}
+
+/// Render an error and all its causes on a single line
+pub(crate) fn error_chain(error: &miette::Report) -> String {
+    error
+        .chain()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
 }
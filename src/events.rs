@@ -0,0 +1,141 @@
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::i18n::tr;
+
+/// Whether `--json-events` was given
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Everything else events are passed to, like an MQTT broker
+static SINKS: Mutex<Vec<Sink>> = Mutex::new(Vec::new());
+
+type Sink = Box<dyn Fn(&Event<'_>) + Send>;
+
+/// What happened during a command, written as one JSON object per line to stderr with `--json-events`
+///
+/// The `event` field holds the name of the event, like `page-saved`. Events and fields may be added, but existing
+/// ones keep their meaning.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub(crate) enum Event<'a> {
+    /// The daemon started a job from its queue
+    #[cfg(unix)]
+    JobStarted {
+        job: u64,
+        device: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        profile: Option<&'a str>,
+    },
+    /// The daemon finished a job, with the error if it failed
+    #[cfg(unix)]
+    JobFinished {
+        job: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    DeviceOpened {
+        device: &'a str,
+    },
+    ScanStarted {
+        device: &'a str,
+        page: usize,
+    },
+    Progress {
+        /// How much of the current frame was read, if the scanner knows its size
+        #[serde(skip_serializing_if = "Option::is_none")]
+        percent: Option<u32>,
+        /// The data read so far, if the scanner does not know the size of the frame
+        #[serde(skip_serializing_if = "Option::is_none")]
+        kib: Option<usize>,
+        /// The post-processing step the page is in
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stage: Option<&'static str>,
+    },
+    /// A batch waits for the user to press Enter on stdin before scanning the page
+    WaitingForPage {
+        page: usize,
+    },
+    PageSaved {
+        page: usize,
+        path: &'a Path,
+    },
+    /// The saved document was sent on to another service, like `paperless`
+    Uploaded {
+        destination: &'static str,
+        path: &'a Path,
+    },
+    /// Sending the saved document to a destination failed, the others are still sent to
+    UploadFailed {
+        destination: &'static str,
+        message: String,
+    },
+    Warning {
+        message: String,
+    },
+    Done {
+        pages: usize,
+        outputs: Vec<PathBuf>,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Write events from now on, instead of the messages meant for people
+pub(crate) fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Pass every event emitted from now on to `sink`, whether events are enabled or not
+pub(crate) fn forward(sink: impl Fn(&Event<'_>) + Send + 'static) {
+    SINKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(Box::new(sink));
+}
+
+/// Write the event if events are enabled, and pass it to the sinks
+pub(crate) fn emit(event: &Event<'_>) {
+    for sink in SINKS.lock().unwrap_or_else(|e| e.into_inner()).iter() {
+        sink(event);
+    }
+
+    if !enabled() {
+        return;
+    }
+
+    // Nothing sensible can be done when stderr is gone, and the event is not worth failing the command for
+    if let Ok(line) = serde_json::to_string(event) {
+        let mut stderr = std::io::stderr().lock();
+        let _ = writeln!(stderr, "{line}");
+        let _ = stderr.flush();
+    }
+}
+
+/// Show a message on stderr, unless events are written instead
+pub(crate) fn status(message: impl std::fmt::Display) {
+    if !enabled() {
+        eprintln!("{message}");
+    }
+}
+
+/// Warn about something that did not stop the command, as an event or on stderr
+pub(crate) fn warning(message: impl std::fmt::Display) {
+    if enabled() {
+        emit(&Event::Warning {
+            message: message.to_string(),
+        });
+    } else {
+        eprintln!("{}", tr!("warning", message = message.to_string()));
+    }
+}
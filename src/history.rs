@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::Utc;
+use miette::Context;
+use miette::IntoDiagnostic;
+use scannrs_core::output::Format;
+use scannrs_core::value::Value;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::ScannrsError;
+
+/// A completed scan
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct HistoryEntry {
+    pub(crate) id: u64,
+    pub(crate) finished_at: DateTime<Utc>,
+    pub(crate) device: String,
+    #[serde(default)]
+    pub(crate) options: BTreeMap<String, Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) settings: Option<PathBuf>,
+    pub(crate) outputs: Vec<PathBuf>,
+    pub(crate) format: Format,
+    pub(crate) pages: usize,
+    pub(crate) duration_ms: u64,
+}
+
+/// Refers to an entry of the history, either by its id or as `last`
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum HistoryRef {
+    Last,
+    Id(u64),
+}
+
+impl FromStr for HistoryRef {
+    type Err = ScannrsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last" => Ok(HistoryRef::Last),
+            id => id
+                .parse()
+                .map(HistoryRef::Id)
+                .map_err(|_| ScannrsError::InvalidHistoryRef {
+                    value: id.to_string(),
+                }),
+        }
+    }
+}
+
+/// The scan history, stored as one JSON object per line at `$XDG_DATA_HOME/scannrs/history.jsonl`
+pub(crate) struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub(crate) fn open() -> miette::Result<History> {
+        Ok(History {
+            path: crate::paths::data_dir()?.join("history.jsonl"),
+        })
+    }
+
+    pub(crate) fn entries(&self) -> miette::Result<Vec<HistoryEntry>> {
+        let contents = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error).into_diagnostic(),
+        };
+
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(idx, line)| {
+                serde_json::from_str(line)
+                    .into_diagnostic()
+                    .with_context(|| {
+                        format!(
+                            "While reading line {} of the history at {}",
+                            idx + 1,
+                            self.path.display()
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    pub(crate) fn get(&self, entry: HistoryRef) -> miette::Result<HistoryEntry> {
+        let entries = self.entries()?;
+        let found = match entry {
+            HistoryRef::Last => entries.into_iter().last(),
+            HistoryRef::Id(id) => entries.into_iter().find(|e| e.id == id),
+        };
+
+        found
+            .ok_or(ScannrsError::HistoryEntryNotFound)
+            .into_diagnostic()
+    }
+
+    /// Append an entry, its id is assigned automatically
+    pub(crate) fn record(&self, mut entry: HistoryEntry) -> miette::Result<HistoryEntry> {
+        entry.id = self.last_id()? + 1;
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).into_diagnostic()?;
+        }
+
+        let mut line = serde_json::to_string(&entry).into_diagnostic()?;
+        line.push('\n');
+
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .into_diagnostic()
+            .with_context(|| format!("While writing to the history at {}", self.path.display()))?;
+
+        Ok(entry)
+    }
+
+    /// The id of the last entry, or 0 for an empty history
+    ///
+    /// Only the last line is read, from the end of the file, as the history grows with every scan.
+    fn last_id(&self) -> miette::Result<u64> {
+        #[derive(Deserialize)]
+        struct Id {
+            id: u64,
+        }
+
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(error) => return Err(error).into_diagnostic(),
+        };
+        let mut start = file.seek(SeekFrom::End(0)).into_diagnostic()?;
+        let mut tail: Vec<u8> = Vec::new();
+        let (from, to) = loop {
+            let to = tail.trim_ascii_end().len();
+            if let Some(newline) = tail[..to].iter().rposition(|byte| *byte == b'\n') {
+                break (newline + 1, to);
+            }
+            if start == 0 {
+                break (0, to);
+            }
+
+            let read = start;
+            start = start.saturating_sub(4096);
+            let mut chunk = vec![0; (read - start) as usize];
+            file.seek(SeekFrom::Start(start)).into_diagnostic()?;
+            file.read_exact(&mut chunk).into_diagnostic()?;
+            chunk.append(&mut tail);
+            tail = chunk;
+        };
+        let line = &tail[from..to];
+
+        if line.trim_ascii().is_empty() {
+            return Ok(0);
+        }
+        serde_json::from_slice::<Id>(line)
+            .map(|last| last.id)
+            .into_diagnostic()
+            .with_context(|| {
+                format!(
+                    "While reading the last line of the history at {}",
+                    self.path.display()
+                )
+            })
+    }
+}
@@ -0,0 +1,80 @@
+# Meldungen von scannrs auf Deutsch
+
+## Fortschritt
+
+progress-scanning = Scanne...
+progress-bar = Scanne [{ $bar }] { $percent }%
+progress-percent = Scanne... { $percent }%
+progress-read = Scanne... { $kib } KiB gelesen
+progress-processing = Bearbeite die Seite: { $stage }...
+progress-cancelling = Breche ab...
+progress-saving = Speichere...
+
+stage-calibration = Kalibrierung
+stage-blank-detection = Erkennung leerer Seiten
+stage-deskew = Begradigen
+stage-autocrop = Zuschneiden
+stage-rotation = Drehen
+
+## Befehle
+
+warning = Warnung: { $message }
+scan-saved = Scan gespeichert unter { $path }
+share-url = Der Scan wird bis { $until } unter { $url } geteilt, Strg+C beendet das Teilen
+upload-done = Scan an { $target } gesendet
+upload-failed = Scan konnte nicht an { $destination } gesendet werden: { $message }
+login-password = Passwort für { $user } bei { $url }:{" "}
+login-stored = Passwort im Schlüsselbund gespeichert
+notification-failed = Die Benachrichtigung konnte nicht angezeigt werden: { $message }
+doc-date-missing = Im Text des Scans wurde kein Datum gefunden, stattdessen wird das Datum des Scans verwendet
+rules-failed = Der Scan konnte nicht nach seinem Text einsortiert werden: { $message }
+rules-routed = Scan nach seinem Text in { $path } einsortiert
+device-cache-failed = Die Liste der Scanner konnte nicht gespeichert werden: { $message }
+webhook-failed = Webhook { $url } konnte nicht aufgerufen werden: { $message }
+button-pressed = Die Taste { $button } hat Auftrag { $job } eingereiht
+button-scan-failed = Der Scan der Taste { $button } konnte nicht eingereiht werden: { $message }
+mqtt-connected = Ereignisse werden an { $broker } veröffentlicht
+mqtt-disconnected = Verbindung zu { $broker } verloren: { $message }
+mqtt-scan-failed = Über MQTT angeforderter Scan konnte nicht eingereiht werden: { $message }
+batch-resuming = Setze den Stapel auf '{ $device }' bei Seite { $page } fort
+batch-page-scanned = Seite { $page } gescannt
+batch-ask-for-page = Enter drücken, um Seite { $page } zu scannen, oder `done` eingeben, um abzuschließen:{" "}
+batch-notify-done = Stapel auf { $device } ist fertig
+batch-notify-failed = Stapel auf { $device } ist fehlgeschlagen
+copy-printed = Scan an { $printer } gesendet, um ihn { $copies }-mal zu drucken
+batch-done = { $pages } Seite(n) gescannt nach { $outputs }
+calibration-removed = Die gespeicherte Kalibrierung von '{ $name }' wurde entfernt
+calibration-done = Der Scanner '{ $name }' wurde kalibriert
+calibration-software = Der Scanner '{ $name }' bietet keine Kalibrierung an, es wird in Software kalibriert
+calibration-place-sheet = Ein weißes Blatt Papier auf den Scanner legen und Enter drücken, um fortzufahren
+calibration-saved = Die Kalibrierung wurde unter { $path } gespeichert
+history-empty = Noch keine Scans
+list-empty = Keine Scanner gefunden
+queue-empty = Die Warteschlange ist leer
+
+## Oberfläche
+
+tui-pages-scanned = { $pages } Seite(n) gescannt, w drücken, um sie als ein Dokument zu speichern, oder s, um weiter zu scannen
+tui-pages-do-not-fit = { $pages } Seiten gescannt, aber das Ausgabeformat fasst nur eine Seite, als .pdf oder .tiff speichern, um alle zu behalten
+
+## Fehler
+
+error-scanner-not-found = Der Scanner '{ $name }' wurde nicht gefunden
+error-scanner-not-found-help = `scannrs list` zeigt die gefundenen Scanner
+error-device-busy = Der Scanner ist belegt
+error-device-busy-help = Warten, bis das andere Programm den Scanner freigibt, oder es schließen
+error-access-denied = Der Zugriff auf den Scanner wurde verweigert
+error-access-denied-help = Prüfen, ob der Benutzer den Scanner verwenden darf, meist über die Gruppe `scanner` oder `lp`
+error-feeder-jammed = Im Einzug steckt Papier fest
+error-feeder-jammed-help = Das feststeckende Papier entfernen und erneut versuchen
+error-feeder-empty = Der Einzug ist leer
+error-feeder-empty-help = Papier in den Einzug legen oder vom Flachbett scannen
+error-cover-open = Der Deckel des Scanners ist offen
+error-cover-open-help = Den Deckel schließen und erneut versuchen
+error-device-io = Keine Verbindung zum Scanner
+error-device-io-help = Das Kabel oder die Netzwerkverbindung des Scanners prüfen
+error-scan-cancelled = Der Scan wurde abgebrochen
+error-no-device = Es wurde kein Scanner angegeben
+error-no-device-help = Den Namen eines Scanners angeben oder ein Standardgerät in der Konfiguration festlegen
+error-no-batch = Es gibt keinen Stapel zum Fortsetzen
+error-page-dropped = Die Seite wurde bei der Nachbearbeitung verworfen
@@ -0,0 +1,80 @@
+# Messages of scannrs in English, the fallback for all other languages
+
+## Progress
+
+progress-scanning = Scanning...
+progress-bar = Scanning [{ $bar }] { $percent }%
+progress-percent = Scanning... { $percent }%
+progress-read = Scanning... { $kib } KiB read
+progress-processing = Processing the page: { $stage }...
+progress-cancelling = Cancelling...
+progress-saving = Saving...
+
+stage-calibration = calibration
+stage-blank-detection = blank page detection
+stage-deskew = deskewing
+stage-autocrop = cropping
+stage-rotation = rotation
+
+## Commands
+
+warning = Warning: { $message }
+scan-saved = Saved scan to { $path }
+share-url = Sharing the scan at { $url } until { $until }, press Ctrl+C to stop
+upload-done = Sent the scan to { $target }
+upload-failed = Could not send the scan to { $destination }: { $message }
+login-password = Password for { $user } at { $url }:{" "}
+login-stored = Stored the password in the keyring
+notification-failed = Could not show the notification: { $message }
+doc-date-missing = Found no date in the text of the scan, using the date it was scanned instead
+rules-failed = Could not sort the scan by its text: { $message }
+rules-routed = Sorted the scan to { $path } by its text
+device-cache-failed = Could not remember the list of scanners: { $message }
+webhook-failed = Could not call the webhook { $url }: { $message }
+button-pressed = The { $button } button queued job { $job }
+button-scan-failed = Could not queue the scan of the { $button } button: { $message }
+mqtt-connected = Publishing events to { $broker }
+mqtt-disconnected = Lost the connection to { $broker }: { $message }
+mqtt-scan-failed = Could not queue the scan asked for over MQTT: { $message }
+batch-resuming = Resuming batch on '{ $device }' at page { $page }
+batch-page-scanned = Scanned page { $page }
+batch-ask-for-page = Press Enter to scan page { $page }, or type `done` to finish:{" "}
+batch-notify-done = Batch scan on { $device } is done
+batch-notify-failed = Batch scan on { $device } failed
+copy-printed = Sent the scan to { $printer } to be printed { $copies } time(s)
+batch-done = Scanned { $pages } page(s) to { $outputs }
+calibration-removed = Removed the stored calibration of '{ $name }'
+calibration-done = The scanner '{ $name }' has been calibrated
+calibration-software = The scanner '{ $name }' does not offer calibration, falling back to a software calibration
+calibration-place-sheet = Place a white sheet of paper on the scanner and press Enter to continue
+calibration-saved = Saved the calibration to { $path }
+history-empty = No scans yet
+list-empty = No scanners found
+queue-empty = The queue is empty
+
+## Interface
+
+tui-pages-scanned = { $pages } page(s) scanned, press w to save them as one document or s to scan another
+tui-pages-do-not-fit = { $pages } pages scanned, but the output format holds a single page, save as .pdf or .tiff to keep them all
+
+## Errors
+
+error-scanner-not-found = Could not find scanner '{ $name }'
+error-scanner-not-found-help = Run `scannrs list` to see the scanners that were found
+error-device-busy = The scanner is busy
+error-device-busy-help = Wait for the other program using the scanner to finish, or close it
+error-access-denied = Access to the scanner was denied
+error-access-denied-help = Check that your user may use the scanner, often by being in the `scanner` or `lp` group
+error-feeder-jammed = The document feeder is jammed
+error-feeder-jammed-help = Remove the stuck paper and try again
+error-feeder-empty = The document feeder is empty
+error-feeder-empty-help = Put paper in the document feeder, or scan from the flatbed
+error-cover-open = The cover of the scanner is open
+error-cover-open-help = Close the cover and try again
+error-device-io = Could not talk to the scanner
+error-device-io-help = Check the cable or the network connection of the scanner
+error-scan-cancelled = The scan was cancelled
+error-no-device = No scanner was given
+error-no-device-help = Pass the name of a scanner, or set a default device in the configuration
+error-no-batch = There is no batch to resume
+error-page-dropped = The page was dropped by the post-processing
@@ -0,0 +1,190 @@
+//! Translations of the messages shown to people, using Fluent
+//!
+//! The language is taken from `$SCANNRS_LANG`, or the usual `$LC_ALL`, `$LC_MESSAGES` and `$LANG`. Messages missing
+//! in a catalog fall back to English, so only English has to be complete. Output meant for scripts, like JSON, the
+//! porcelain formats and the names of options, is never translated.
+
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::FluentArgs;
+use fluent_bundle::FluentResource;
+use fluent_bundle::FluentValue;
+use miette::Diagnostic;
+use scannrs_core::progress::Stage;
+use thiserror::Error;
+use unic_langid::LanguageIdentifier;
+
+use crate::error::ScannrsError;
+
+/// The catalogs, by their language, English first as the fallback
+const CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("en.ftl")),
+    ("de", include_str!("de.ftl")),
+];
+
+/// Set to a language like `de` to use it instead of the one of the locale
+const LANG_VAR: &str = "SCANNRS_LANG";
+
+struct Catalogs {
+    selected: Option<FluentBundle<FluentResource>>,
+    english: FluentBundle<FluentResource>,
+}
+
+static CATALOGS_LOADED: OnceLock<Catalogs> = OnceLock::new();
+
+/// Translate a message, with arguments given as `name = value`
+///
+/// ```ignore
+/// println!("{}", tr!("calibration-saved", path = path.display().to_string()));
+/// ```
+macro_rules! tr {
+    ($id:literal) => {
+        $crate::i18n::message($id, &[])
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::message(
+            $id,
+            &[$((stringify!($name), fluent_bundle::FluentValue::from($value))),+],
+        )
+    };
+}
+pub(crate) use tr;
+
+/// The message with the id in the language of the user, see [`tr!`]
+pub(crate) fn message(id: &str, args: &[(&str, FluentValue<'_>)]) -> String {
+    let catalogs = CATALOGS_LOADED.get_or_init(load);
+    let args = args.iter().cloned().collect::<FluentArgs<'_>>();
+
+    catalogs
+        .selected
+        .iter()
+        .chain([&catalogs.english])
+        .find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+            let text = bundle.format_pattern(pattern, Some(&args), &mut errors);
+            errors.is_empty().then(|| text.into_owned())
+        })
+        .unwrap_or_else(|| id.to_string())
+}
+
+/// The language of the user, like `de` for `de_DE.UTF-8`
+fn language() -> Option<String> {
+    [LANG_VAR, "LC_ALL", "LC_MESSAGES", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .split(['_', '.', '@', '-'])
+                .next()
+                .unwrap_or_default()
+                .to_lowercase()
+        })
+}
+
+fn bundle(language: &str, source: &str) -> Option<FluentBundle<FluentResource>> {
+    let id = language.parse::<LanguageIdentifier>().ok()?;
+    let resource = FluentResource::try_new(source.to_string()).ok()?;
+
+    let mut bundle = FluentBundle::new_concurrent(vec![id]);
+    // The marks isolating arguments show up as garbage on many terminals
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).ok()?;
+    Some(bundle)
+}
+
+fn load() -> Catalogs {
+    let language = language();
+    let selected = CATALOGS
+        .iter()
+        .skip(1)
+        .find(|(name, _)| Some(*name) == language.as_deref())
+        .and_then(|(name, source)| bundle(name, source));
+
+    let (name, source) = CATALOGS[0];
+    let english = bundle(name, source).unwrap_or_else(|| FluentBundle::new_concurrent(vec![]));
+
+    Catalogs { selected, english }
+}
+
+/// The name of a processing stage, for progress messages
+pub(crate) fn stage(stage: Stage) -> String {
+    match stage {
+        Stage::Calibration => tr!("stage-calibration"),
+        Stage::BlankDetection => tr!("stage-blank-detection"),
+        Stage::Deskew => tr!("stage-deskew"),
+        Stage::Autocrop => tr!("stage-autocrop"),
+        Stage::Rotation => tr!("stage-rotation"),
+        Stage::Other(name) => name.to_string(),
+    }
+}
+
+/// An error in the language of the user, keeping the original error as its cause
+#[derive(Debug, Error, Diagnostic)]
+#[error("{message}")]
+pub(crate) struct Localized {
+    message: String,
+    #[help]
+    help: Option<String>,
+    #[source]
+    cause: Box<dyn std::error::Error + Send + Sync>,
+}
+
+/// Translate the errors people run into when scanning, other errors are kept as they are
+///
+/// Nothing is changed for English, where the error already reads as intended.
+pub(crate) fn localize(error: miette::Report) -> miette::Report {
+    if CATALOGS_LOADED.get_or_init(load).selected.is_none() {
+        return error;
+    }
+
+    let (message, help) = if let Some(error) = error.downcast_ref::<scannrs_core::Error>() {
+        match error {
+            scannrs_core::Error::CouldNotFindScanner { name } => (
+                tr!("error-scanner-not-found", name = name.as_str()),
+                Some(tr!("error-scanner-not-found-help")),
+            ),
+            scannrs_core::Error::DeviceBusy => (
+                tr!("error-device-busy"),
+                Some(tr!("error-device-busy-help")),
+            ),
+            scannrs_core::Error::AccessDenied => (
+                tr!("error-access-denied"),
+                Some(tr!("error-access-denied-help")),
+            ),
+            scannrs_core::Error::FeederJammed => (
+                tr!("error-feeder-jammed"),
+                Some(tr!("error-feeder-jammed-help")),
+            ),
+            scannrs_core::Error::FeederEmpty => (
+                tr!("error-feeder-empty"),
+                Some(tr!("error-feeder-empty-help")),
+            ),
+            scannrs_core::Error::CoverOpen => {
+                (tr!("error-cover-open"), Some(tr!("error-cover-open-help")))
+            }
+            scannrs_core::Error::DeviceIo => {
+                (tr!("error-device-io"), Some(tr!("error-device-io-help")))
+            }
+            scannrs_core::Error::ScanCancelled => (tr!("error-scan-cancelled"), None),
+            _ => return error,
+        }
+    } else if let Some(error) = error.downcast_ref::<ScannrsError>() {
+        match error {
+            ScannrsError::NoDevice => (tr!("error-no-device"), Some(tr!("error-no-device-help"))),
+            ScannrsError::NoBatchToResume => (tr!("error-no-batch"), None),
+            ScannrsError::PageDropped => (tr!("error-page-dropped"), None),
+            _ => return error,
+        }
+    } else {
+        return error;
+    };
+
+    miette::Report::new(Localized {
+        message,
+        help,
+        cause: error.into(),
+    })
+}
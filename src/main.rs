@@ -1,34 +1,239 @@
 use clap::Parser;
-use miette::IntoDiagnostic;
-use sane_scan::Sane;
+#[cfg(feature = "escl")]
+use scannrs_core::backend::escl::EsclBackend;
+use scannrs_core::backend::mock::MockBackend;
+#[cfg(feature = "sane")]
+use scannrs_core::backend::sane::SaneBackend;
+#[cfg(all(windows, feature = "wia"))]
+use scannrs_core::backend::wia::WiaBackend;
+#[cfg(feature = "wsd")]
+use scannrs_core::backend::wsd::WsdBackend;
+use scannrs_core::backend::Backends;
+use scannrs_core::backend::ScanBackend;
 
+mod calibration;
 mod cli;
 mod commands;
+mod config;
+mod dates;
+mod destination;
+mod device_cache;
 mod error;
+mod events;
+mod history;
+mod i18n;
+mod metrics;
+mod notification;
+mod paths;
+mod progress;
+mod rules;
+mod share;
+#[cfg(unix)]
+mod systemd;
+mod webhook;
+
+/// Set to `mock` to use made up scanners, for tests and trying out scannrs without a scanner
+///
+/// When both SANE and WIA are built in, `sane` picks SANE, WIA is used otherwise. Scanners named `escl:<url>` and
+/// `wsd:<url>` are always opened over eSCL and WS-Scan.
+const BACKEND_VAR: &str = "SCANNRS_BACKEND";
+
+/// The way of the system to talk to scanners, if one was built in
+fn system_backend() -> miette::Result<Option<Box<dyn ScanBackend>>> {
+    Ok(match std::env::var(BACKEND_VAR).as_deref() {
+        #[cfg(all(feature = "sane", windows, feature = "wia"))]
+        Ok("sane") => Some(Box::new(SaneBackend::init()?)),
+        #[cfg(all(windows, feature = "wia"))]
+        _ => Some(Box::new(WiaBackend::init()?)),
+        #[cfg(all(feature = "sane", not(all(windows, feature = "wia"))))]
+        _ => Some(Box::new(SaneBackend::init()?)),
+        #[cfg(not(any(feature = "sane", all(windows, feature = "wia"))))]
+        _ => None,
+    })
+}
+
+fn mock() -> bool {
+    std::env::var(BACKEND_VAR).as_deref() == Ok("mock")
+}
+
+fn backend() -> miette::Result<Box<dyn ScanBackend>> {
+    if mock() {
+        return Ok(Box::new(MockBackend::default()));
+    }
+
+    let backends = system_backend()?
+        .into_iter()
+        .chain([
+            // Network scanners are talked to directly, next to the backend of the system
+            #[cfg(feature = "escl")]
+            (Box::new(EsclBackend::default()) as Box<dyn ScanBackend>),
+            #[cfg(feature = "wsd")]
+            (Box::new(WsdBackend::default()) as Box<dyn ScanBackend>),
+        ])
+        .collect::<Vec<_>>();
+    if backends.is_empty() {
+        return Err(error::ScannrsError::NoBackend.into());
+    }
+    Ok(Box::new(Backends::new(backends)))
+}
 
 fn main() -> miette::Result<()> {
     human_panic::setup_panic!();
 
     let args = cli::Cli::parse();
+    if args.json_events {
+        events::enable();
+    }
+    config::set_flags(config::ConfigFlags {
+        config: args.config.clone(),
+        profile: args.profile.clone(),
+    });
 
-    let sane = Sane::init_1_0().into_diagnostic()?;
+    let result = run(args);
+    if let Err(error) = &result {
+        events::emit(&events::Event::Error {
+            message: error::error_chain(error),
+        });
+    }
 
+    result.map_err(i18n::localize)
+}
+
+fn run(args: cli::Cli) -> miette::Result<()> {
+    // Only the commands talking to scanners set up the backend, the others also work without SANE or WIA
     match args.command {
-        cli::Command::List => {
-            commands::list(sane)?;
+        cli::Command::About => commands::about(&backend()?, args.output)?,
+        cli::Command::List {
+            vendor,
+            model,
+            type_,
+            porcelain,
+        } => {
+            let filter = commands::ListFilter {
+                vendor,
+                model,
+                type_,
+            };
+            // Only a one-off listing uses the cache, the TUI and the servers always look for new scanners
+            let backend = device_cache::DeviceCache::new(backend()?, args.refresh);
+            // Made up scanners are not mixed with real ones from the network
+            commands::list(&backend, filter, porcelain, !mock(), args.output)?;
         }
         cli::Command::Options { name, command } => {
-            commands::options(sane, name, command)?;
+            commands::options(&backend()?, name, command, args.output)?;
         }
         cli::Command::Scan {
             name,
+            resolution,
+            output_dir,
             path,
+            format,
+            settings,
             options,
+            share,
+            uploads,
         } => {
-            commands::scan(sane, name, path, options)?;
+            let target = commands::ScanTarget {
+                name,
+                resolution,
+                output_dir,
+                path,
+                format,
+            };
+            commands::scan(
+                &backend()?,
+                target,
+                settings,
+                options,
+                uploads.destinations(),
+                share,
+                args.output,
+            )?;
         }
 
-        cli::Command::Tui => commands::tui(sane)?,
+        cli::Command::Batch {
+            name,
+            resolution,
+            output_dir,
+            options,
+            path,
+            format,
+            settings,
+            pages,
+            start,
+            notify,
+            resume,
+        } => {
+            let new = match (resume, path) {
+                (false, Some(path)) => Some(commands::NewBatch {
+                    name,
+                    resolution,
+                    output_dir,
+                    path,
+                    format,
+                    settings,
+                    options,
+                    pages,
+                    start,
+                }),
+                _ => None,
+            };
+            commands::batch(&backend()?, new, notify, args.output)?;
+        }
+        cli::Command::Rerun { entry, path } => {
+            commands::rerun(&backend()?, entry, path, args.output)?
+        }
+        cli::Command::Tui => commands::tui(&backend()?)?,
+        cli::Command::Calibrate {
+            name,
+            software,
+            reset,
+            options,
+        } => commands::calibrate(&backend()?, name, software, reset, options)?,
+        cli::Command::Copy {
+            name,
+            resolution,
+            options,
+            settings,
+            printer,
+            copies,
+        } => commands::copy(
+            &backend()?,
+            name,
+            resolution,
+            options,
+            settings,
+            printer,
+            copies,
+        )?,
+        cli::Command::Merge {
+            output,
+            pages,
+            dpi,
+            format,
+        } => commands::merge(output, pages, dpi, format)?,
+        cli::Command::Ocr {
+            input,
+            lang,
+            psm,
+            engine,
+            format,
+            path,
+        } => commands::ocr(input, lang, psm, engine, format, path)?,
+        cli::Command::Selftest { device } => commands::selftest(&backend()?, device)?,
+        cli::Command::Login { service } => commands::login(service)?,
+        cli::Command::Serve { listen, escl, grpc } => {
+            commands::serve(&backend()?, listen, escl, grpc)?
+        }
+        #[cfg(unix)]
+        cli::Command::SystemdUnit { service, socket } => commands::systemd_unit(service, socket)?,
+        #[cfg(feature = "dbus")]
+        cli::Command::Dbus { system } => commands::dbus(&backend()?, system)?,
+        cli::Command::History { command } => commands::history(command, args.output)?,
+        #[cfg(unix)]
+        cli::Command::Queue { socket, command } => commands::queue(socket, command, args.output)?,
+        #[cfg(unix)]
+        cli::Command::Daemon { socket, metrics } => commands::daemon(&backend()?, socket, metrics)?,
     }
 
     Ok(())
@@ -0,0 +1,186 @@
+//! Counters and histograms of the scans done by `serve` and `daemon`, for monitoring a shared scan server
+//!
+//! They are rendered in the text format of Prometheus, which `serve` offers at `/metrics` and `daemon` with
+//! `--metrics`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use miette::Context;
+use miette::IntoDiagnostic;
+
+/// The content type of the text format
+pub(crate) const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The upper bounds of the buckets of the scan duration in seconds, a page takes from seconds to minutes
+const DURATION_BUCKETS: [f64; 9] = [1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0, 300.0];
+
+/// The upper bounds of the buckets of the pages per scan, for feeders
+const PAGE_BUCKETS: [f64; 7] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0];
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    scans: BTreeMap::new(),
+    errors: BTreeMap::new(),
+    pages: 0,
+    bytes: 0,
+    durations: Histogram::new(DURATION_BUCKETS),
+    pages_per_scan: Histogram::new(PAGE_BUCKETS),
+});
+
+struct Metrics {
+    /// Completed scans by device
+    scans: BTreeMap<String, u64>,
+    /// Failed scans by the status of the error
+    errors: BTreeMap<&'static str, u64>,
+    pages: u64,
+    /// Bytes of the saved or sent scans
+    bytes: u64,
+    durations: Histogram<9>,
+    pages_per_scan: Histogram<7>,
+}
+
+struct Histogram<const N: usize> {
+    /// The upper bounds of the buckets
+    bounds: [f64; N],
+    /// The observations in each bucket, not yet accumulated
+    buckets: [u64; N],
+    sum: f64,
+    count: u64,
+}
+
+impl<const N: usize> Histogram<N> {
+    const fn new(bounds: [f64; N]) -> Histogram<N> {
+        Histogram {
+            bounds,
+            buckets: [0; N],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum {}", self.sum);
+        let _ = writeln!(out, "{name}_count {}", self.count);
+    }
+}
+
+fn metrics() -> std::sync::MutexGuard<'static, Metrics> {
+    METRICS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Count a scan of `pages` pages with the device that took `duration` and resulted in `bytes`
+pub(crate) fn scan_finished(device: &str, pages: usize, bytes: u64, duration: Duration) {
+    let mut metrics = metrics();
+    *metrics.scans.entry(device.to_string()).or_default() += 1;
+    metrics.pages += pages as u64;
+    metrics.bytes += bytes;
+    metrics.durations.observe(duration.as_secs_f64());
+    metrics.pages_per_scan.observe(pages as f64);
+}
+
+/// Count a failed scan by the status of the error, see [`scannrs_core::Error::status`]
+pub(crate) fn scan_failed(error: &miette::Report) {
+    let status = error
+        .downcast_ref::<scannrs_core::Error>()
+        .map_or("other", scannrs_core::Error::status);
+    *metrics().errors.entry(status).or_default() += 1;
+}
+
+/// All metrics in the text format of Prometheus
+pub(crate) fn render() -> String {
+    let metrics = metrics();
+    let mut out = String::new();
+
+    out.push_str("# HELP scannrs_scans_total Scans that were completed.\n");
+    out.push_str("# TYPE scannrs_scans_total counter\n");
+    for (device, count) in &metrics.scans {
+        let device = device.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = writeln!(out, "scannrs_scans_total{{device=\"{device}\"}} {count}");
+    }
+
+    out.push_str(
+        "# HELP scannrs_scan_errors_total Scans that failed, by the SANE status of the error.\n",
+    );
+    out.push_str("# TYPE scannrs_scan_errors_total counter\n");
+    for (status, count) in &metrics.errors {
+        let _ = writeln!(
+            out,
+            "scannrs_scan_errors_total{{status=\"{status}\"}} {count}"
+        );
+    }
+
+    out.push_str("# HELP scannrs_pages_total Pages that were scanned.\n");
+    out.push_str("# TYPE scannrs_pages_total counter\n");
+    let _ = writeln!(out, "scannrs_pages_total {}", metrics.pages);
+
+    out.push_str("# HELP scannrs_output_bytes_total Bytes of the saved scans.\n");
+    out.push_str("# TYPE scannrs_output_bytes_total counter\n");
+    let _ = writeln!(out, "scannrs_output_bytes_total {}", metrics.bytes);
+
+    out.push_str("# HELP scannrs_scan_duration_seconds How long scans took.\n");
+    out.push_str("# TYPE scannrs_scan_duration_seconds histogram\n");
+    metrics
+        .durations
+        .render(&mut out, "scannrs_scan_duration_seconds");
+
+    out.push_str("# HELP scannrs_scan_pages How many pages scans had.\n");
+    out.push_str("# TYPE scannrs_scan_pages histogram\n");
+    metrics
+        .pages_per_scan
+        .render(&mut out, "scannrs_scan_pages");
+
+    out
+}
+
+/// Answer `GET /metrics` on `listen` in the background, for commands without an HTTP server of their own
+pub(crate) fn serve(listen: SocketAddr) -> miette::Result<()> {
+    let listener = TcpListener::bind(listen)
+        .into_diagnostic()
+        .with_context(|| format!("While trying to listen on {listen}"))?;
+    eprintln!("Serving metrics on http://{listen}/metrics");
+
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+
+            let response = if request.starts_with("GET /metrics ") {
+                let body = render();
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {CONTENT_TYPE}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                String::from(
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Desktop notifications for scans that run while nobody watches the terminal
+//!
+//! Shown through the notification service of the desktop, over D-Bus on Linux and the BSDs. A notification that
+//! cannot be shown is warned about, it never fails the scan.
+
+use crate::error::ScannrsError;
+#[cfg(feature = "notifications")]
+use crate::events;
+#[cfg(feature = "notifications")]
+use crate::i18n::tr;
+
+/// Fail right away if this build cannot show notifications, instead of once the scan is done
+pub(crate) fn supported() -> miette::Result<()> {
+    if cfg!(feature = "notifications") {
+        Ok(())
+    } else {
+        Err(ScannrsError::NotificationsNotBuilt.into())
+    }
+}
+
+/// Show a notification with the `summary` as its title
+#[cfg(feature = "notifications")]
+pub(crate) fn show(summary: &str, body: &str) {
+    let shown = notify_rust::Notification::new()
+        .appname("scannrs")
+        .icon("scanner")
+        .summary(summary)
+        .body(body)
+        .show();
+    if let Err(error) = shown {
+        events::warning(tr!("notification-failed", message = error.to_string()));
+    }
+}
+
+#[cfg(not(feature = "notifications"))]
+pub(crate) fn show(_summary: &str, _body: &str) {}
@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use chrono::Local;
+use miette::IntoDiagnostic;
+
+use crate::error::ScannrsError;
+
+/// The directory persistent state like the job queue is kept in
+pub(crate) fn state_dir() -> miette::Result<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("scannrs"))
+        .ok_or(ScannrsError::NoHomeDirectory)
+        .into_diagnostic()
+}
+
+/// The directory user data like the scan history is kept in
+pub(crate) fn data_dir() -> miette::Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("scannrs"))
+        .ok_or(ScannrsError::NoHomeDirectory)
+        .into_diagnostic()
+}
+
+/// The directory data that can be found again, like the list of scanners, is kept in
+pub(crate) fn cache_dir() -> miette::Result<PathBuf> {
+    dirs::cache_dir()
+        .map(|dir| dir.join("scannrs"))
+        .ok_or(ScannrsError::NoHomeDirectory)
+        .into_diagnostic()
+}
+
+/// The directory the configuration file is kept in
+pub(crate) fn config_dir() -> miette::Result<PathBuf> {
+    dirs::config_dir()
+        .map(|dir| dir.join("scannrs"))
+        .ok_or(ScannrsError::NoHomeDirectory)
+        .into_diagnostic()
+}
+
+/// The default location of the daemon socket
+#[cfg(unix)]
+pub(crate) fn daemon_socket() -> miette::Result<PathBuf> {
+    match dirs::runtime_dir() {
+        Some(dir) => Ok(dir.join("scannrs.sock")),
+        None => Ok(state_dir()?.join("scannrs.sock")),
+    }
+}
+
+/// Fill in the placeholders of an output path, as the TUI and profiles use them
+///
+/// `{n}` is replaced by the first number for which the file does not exist yet.
+pub(crate) fn expand_path(template: &str, device: &str) -> PathBuf {
+    let now = Local::now();
+    let device = device.replace(|c: char| !c.is_alphanumeric() && c != '-', "_");
+    let path = template
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H-%M-%S").to_string())
+        .replace("{device}", &device);
+
+    if !path.contains("{n}") {
+        return PathBuf::from(path);
+    }
+
+    (1..)
+        .map(|n| PathBuf::from(path.replace("{n}", &n.to_string())))
+        .find(|path| !path.exists())
+        .unwrap_or_default()
+}
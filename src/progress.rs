@@ -0,0 +1,109 @@
+use std::io::IsTerminal;
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use scannrs_core::progress::ScanEvent;
+
+use crate::events;
+use crate::events::Event;
+use crate::i18n;
+use crate::i18n::tr;
+
+/// The amount of characters the bar itself takes up
+const BAR_WIDTH: usize = 30;
+
+/// Shows the progress of a scan on a single line of stderr, which is cleared again once the bar is dropped
+///
+/// Nothing is shown unless stderr is a terminal, so that logs and scripts do not fill up with progress lines. With
+/// `--json-events` the progress is written as events instead.
+pub(crate) struct ProgressBar {
+    enabled: bool,
+    json: bool,
+    /// What is currently shown, to only redraw when it changes
+    shown: Option<String>,
+}
+
+impl ProgressBar {
+    pub(crate) fn new() -> ProgressBar {
+        let json = events::enabled();
+        ProgressBar {
+            enabled: json || std::io::stderr().is_terminal(),
+            json,
+            shown: None,
+        }
+    }
+
+    /// Show the event, meant to be used as the progress callback of a scan
+    pub(crate) fn update(&mut self, event: ScanEvent) -> ControlFlow<()> {
+        if !self.enabled {
+            return ControlFlow::Continue(());
+        }
+
+        let line = match event {
+            ScanEvent::PageStarted { .. } | ScanEvent::FrameStarted { .. } => {
+                tr!("progress-scanning")
+            }
+            ScanEvent::Read { bytes, .. } => match event.fraction() {
+                Some(fraction) => {
+                    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+                    tr!(
+                        "progress-bar",
+                        bar = format!("{}{}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled)),
+                        percent = format!("{:3.0}", fraction * 100.0)
+                    )
+                }
+                None => tr!("progress-read", kib = bytes / 1024),
+            },
+            ScanEvent::Processing { stage } => {
+                tr!("progress-processing", stage = i18n::stage(stage))
+            }
+            ScanEvent::PageFinished { .. } => {
+                self.clear();
+                return ControlFlow::Continue(());
+            }
+        };
+
+        if self.shown.as_ref() != Some(&line) {
+            if self.json {
+                events::emit(&progress_event(event));
+            } else {
+                eprint!("\r\x1b[2K{line}");
+                let _ = std::io::stderr().flush();
+            }
+            self.shown = Some(line);
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn clear(&mut self) {
+        if self.shown.take().is_some() && !self.json {
+            eprint!("\r\x1b[2K");
+            let _ = std::io::stderr().flush();
+        }
+    }
+}
+
+/// The event for a change of the line, only called for the events that show progress
+fn progress_event(event: ScanEvent) -> Event<'static> {
+    let (percent, kib, stage) = match event {
+        ScanEvent::Read { bytes, .. } => match event.fraction() {
+            Some(fraction) => (Some((fraction * 100.0).round() as u32), None, None),
+            None => (None, Some(bytes / 1024), None),
+        },
+        ScanEvent::Processing { stage } => (None, None, Some(stage.name())),
+        _ => (Some(0), None, None),
+    };
+
+    Event::Progress {
+        percent,
+        kib,
+        stage,
+    }
+}
+
+impl Drop for ProgressBar {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
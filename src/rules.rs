@@ -0,0 +1,151 @@
+//! Sorting scans by the text on them, like invoices into their own folder
+//!
+//! Every rule of `[[rules]]` has a regular expression that is searched for in the recognized text of a scanned page.
+//! Matching rules add their tags for paperless-ngx, and the first matching one with an `output` or a `correspondent`
+//! decides where the scan is saved or who it is from. Named groups of the expression, like `(?P<vendor>\w+)`, fill in
+//! the placeholders of the same name, next to the ones of the destinations and `{doc_date}`, the date found in the text
+//! by [`crate::dates`]:
+//!
+//! ```toml
+//! [[rules]]
+//! pattern = "(?i)invoice from (?P<vendor>\\w+)"
+//! output = "~/Documents/invoices/{date}-{vendor}.pdf"
+//! tags = ["invoice"]
+//! correspondent = "{vendor}"
+//! ```
+//!
+//! The text is only recognized if there are rules, or if the path of the scan has a `{doc_date}` as well. A scan whose
+//! text cannot be recognized is saved where it would have been without rules, with the date of the scan for
+//! `{doc_date}`.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use chrono::Local;
+use miette::IntoDiagnostic;
+use regex::Regex;
+use scannrs_core::output::Page;
+
+use crate::commands::recognize_page;
+use crate::config::Config;
+use crate::config::RuleConfig;
+use crate::dates::document_date;
+use crate::dates::DOC_DATE;
+use crate::destination::SavedScan;
+use crate::error::error_chain;
+use crate::error::ScannrsError;
+use crate::events;
+use crate::i18n::tr;
+
+/// What the matching rules decided for a scan
+#[derive(Default, Debug)]
+pub(crate) struct Routing {
+    pub(crate) output: Option<PathBuf>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) correspondent: Option<String>,
+}
+
+/// Whether routing a scan that would be saved at `path` reads its page, otherwise it is routed nowhere
+pub(crate) fn reads_page(config: &Config, path: &Path) -> bool {
+    !config.rules.is_empty() || path.to_string_lossy().contains(DOC_DATE)
+}
+
+/// Match the text on the page against the rules of the configuration, for a scan that would be saved at `path`
+///
+/// A `path` with `{doc_date}` is also saved elsewhere, with the placeholder filled in.
+pub(crate) fn route(config: &Config, page: &Page, device: &str, path: &Path) -> Routing {
+    if !reads_page(config, path) {
+        return Routing::default();
+    }
+    let dated = path.to_string_lossy().contains(DOC_DATE);
+
+    let scan = SavedScan {
+        path,
+        device,
+        tags: &[],
+        correspondent: None,
+    };
+    let text = config
+        .ocr()
+        .and_then(|options| recognize_page(page, &options));
+    let date = match text.as_deref().ok().and_then(document_date) {
+        Some(date) => date,
+        None => {
+            if dated && text.is_ok() {
+                events::warning(tr!("doc-date-missing"));
+            }
+            Local::now().date_naive()
+        }
+    };
+    let date = date.format("%Y-%m-%d").to_string();
+
+    let routing = text.and_then(|text| route_text(&config.rules, &text, &scan, &date));
+    let mut routing = match routing {
+        Ok(routing) => routing,
+        Err(error) => {
+            events::warning(tr!("rules-failed", message = error_chain(&error)));
+            Routing::default()
+        }
+    };
+    if dated && routing.output.is_none() {
+        routing.output = Some(PathBuf::from(
+            path.to_string_lossy().replace(DOC_DATE, &date),
+        ));
+    }
+
+    routing
+}
+
+fn route_text(
+    rules: &[RuleConfig],
+    text: &str,
+    scan: &SavedScan<'_>,
+    date: &str,
+) -> miette::Result<Routing> {
+    let mut routing = Routing::default();
+    for rule in rules {
+        let regex = Regex::new(&rule.pattern)
+            .map_err(|error| ScannrsError::InvalidRule {
+                pattern: rule.pattern.clone(),
+                message: error.to_string(),
+            })
+            .into_diagnostic()?;
+        let Some(captures) = regex.captures(text) else {
+            continue;
+        };
+
+        let fill = |template: &str| {
+            let mut filled = template.to_string();
+            for name in regex.capture_names().flatten() {
+                let value = captures.name(name).map_or("", |value| value.as_str());
+                filled = filled.replace(&format!("{{{name}}}"), &sanitize(value));
+            }
+            scan.fill(&filled.replace(DOC_DATE, date))
+        };
+
+        routing.tags.extend(rule.tags.iter().map(|tag| fill(tag)));
+        if routing.output.is_none() {
+            routing.output = rule.output.as_deref().map(|output| home(&fill(output)));
+        }
+        if routing.correspondent.is_none() {
+            routing.correspondent = rule.correspondent.as_deref().map(fill);
+        }
+    }
+
+    Ok(routing)
+}
+
+/// A matched piece of text as a part of a path, which the text cannot leave
+fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .replace(|c: char| matches!(c, '/' | '\\') || c.is_control(), "_")
+}
+
+/// Replace a leading `~` with the home directory
+fn home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
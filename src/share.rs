@@ -0,0 +1,113 @@
+//! Handing a scan to a phone over the local network, with `scan --share`
+//!
+//! The file is served on a random port under a random path for a while, and the link to it is printed as a QR code.
+//! Anyone on the network who knows the link can download the file until then, or until Ctrl+C is pressed.
+
+use std::hash::BuildHasher;
+use std::hash::RandomState;
+use std::net::IpAddr;
+use std::net::Ipv4Addr;
+use std::net::UdpSocket;
+use std::path::Path;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use axum::body::Bytes;
+use axum::http::header;
+use axum::routing::get;
+use axum::Router;
+use chrono::Local;
+use chrono::TimeDelta;
+use miette::Context;
+use miette::IntoDiagnostic;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+use scannrs_core::output::Format;
+
+use crate::events;
+use crate::i18n::tr;
+
+/// Serve the scan at `path` for `duration`, printing the link to it
+pub(crate) fn share(path: &Path, format: Format, duration: Duration) -> miette::Result<()> {
+    let data = Bytes::from(
+        std::fs::read(path)
+            .into_diagnostic()
+            .with_context(|| format!("While reading the scan at {}", path.display()))?,
+    );
+    let file_name = path.file_name().map_or_else(
+        || String::from("scan"),
+        |name| name.to_string_lossy().replace('"', ""),
+    );
+    let headers = [
+        (header::CONTENT_TYPE, format.mime_type().to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        ),
+    ];
+    let token = token();
+    let router = Router::new().route(
+        &format!("/{token}"),
+        get(move || {
+            let response = (headers.clone(), data.clone());
+            async move { response }
+        }),
+    );
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .into_diagnostic()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0))
+            .await
+            .into_diagnostic()
+            .context("While trying to listen for the shared scan")?;
+        let port = listener.local_addr().into_diagnostic()?.port();
+        let url = format!("http://{}:{port}/{token}", local_address());
+
+        let code = QrCode::new(&url).into_diagnostic()?;
+        // Inverted, as most terminals are dark
+        eprintln!(
+            "{}",
+            code.render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .build()
+        );
+        let until = TimeDelta::from_std(duration)
+            .ok()
+            .and_then(|delta| Local::now().checked_add_signed(delta))
+            .map_or_else(String::new, |until| until.format("%H:%M").to_string());
+        events::status(tr!("share-url", url = url.as_str(), until = until));
+
+        axum::serve(listener, router)
+            .with_graceful_shutdown(async move {
+                tokio::select! {
+                    _ = tokio::time::sleep(duration) => {}
+                    _ = tokio::signal::ctrl_c() => {}
+                }
+            })
+            .await
+            .into_diagnostic()
+    })
+}
+
+/// A path that cannot be guessed by others on the network
+fn token() -> String {
+    let state = RandomState::new();
+    (0..2)
+        .map(|part| format!("{:016x}", state.hash_one((part, SystemTime::now()))))
+        .collect()
+}
+
+/// The address of this machine towards the local network, for the link to reach it from a phone
+fn local_address() -> IpAddr {
+    // Connecting a UDP socket sends nothing, it only picks the interface packets to that address would leave through
+    UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9))?;
+            socket.local_addr()
+        })
+        .map_or(IpAddr::V4(Ipv4Addr::LOCALHOST), |address| address.ip())
+}
@@ -0,0 +1,67 @@
+//! Running `daemon` and `serve` as systemd services
+//!
+//! Both tell systemd once they are ready, for units with `Type=notify`, and listen on the socket of a socket unit if
+//! they were started by one. With `WatchdogSec=` its watchdog is only fed while the service shows it still works: the
+//! daemon from its job loop, the servers whenever the thread owning the backend answers, and all of them while scans
+//! make progress. Outside of systemd all of this does nothing. `scannrs systemd-unit` prints units set up for this.
+
+use std::os::fd::FromRawFd;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use scannrs_core::driver::Driver;
+use sd_notify::NotifyState;
+
+/// The first socket systemd passed on, if the service was started by a socket unit
+pub(crate) fn activated_socket<T: FromRawFd>() -> Option<T> {
+    let mut fds = sd_notify::listen_fds().ok()?;
+    // SAFETY: systemd passed the descriptor to this process for it to own, and the variables telling about it are
+    // unset so that it is not taken twice
+    fds.next().map(|fd| unsafe { T::from_raw_fd(fd) })
+}
+
+/// Tell systemd the service is ready
+pub(crate) fn ready() {
+    // Failing to reach systemd only matters to systemd, which restarts the service if it waits for this
+    let _ = sd_notify::notify(false, &[NotifyState::Ready]);
+    alive();
+}
+
+/// How often the watchdog wants to hear from the service, half of `WatchdogSec=`, if the service has one
+pub(crate) fn watchdog() -> Option<Duration> {
+    static INTERVAL: OnceLock<Option<Duration>> = OnceLock::new();
+    *INTERVAL.get_or_init(|| {
+        let mut usec = 0;
+        sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec) / 2)
+    })
+}
+
+/// Feed the watchdog, at most once a second as scans call this for every bit they read
+pub(crate) fn alive() {
+    static FED: Mutex<Option<Instant>> = Mutex::new(None);
+    if watchdog().is_none() {
+        return;
+    }
+
+    let mut fed = FED.lock().unwrap_or_else(|e| e.into_inner());
+    if fed.is_some_and(|fed| fed.elapsed() < Duration::from_secs(1)) {
+        return;
+    }
+    *fed = Some(Instant::now());
+    let _ = sd_notify::notify(false, &[NotifyState::Watchdog]);
+}
+
+/// Feed the watchdog whenever the thread owning the backend answers, a scan holding it up feeds it by its progress
+pub(crate) async fn watch(driver: Driver) {
+    let Some(interval) = watchdog() else {
+        return;
+    };
+    loop {
+        if driver.run(|_| ()).await.is_ok() {
+            alive();
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
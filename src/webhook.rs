@@ -0,0 +1,110 @@
+//! Telling other services about finished scans, like n8n or Zapier-style automations
+//!
+//! Every URL in `[[webhooks]]` gets a JSON object POSTed after each scan of `scan` and `daemon`, whether it worked or
+//! not, and after each finished `batch`, which can be resumed when a page fails. A webhook that cannot be reached is
+//! warned about, it never fails the scan.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::commands::ScanSummary;
+use crate::config::Config;
+use crate::error::error_chain;
+use crate::events;
+use crate::i18n::tr;
+
+/// How long a webhook may take to answer before it is given up on
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What is POSTed to the webhooks
+#[derive(Serialize, Debug)]
+pub(crate) struct JobReport<'a> {
+    /// The command that scanned, `scan`, `batch` or `daemon`
+    pub(crate) command: &'static str,
+    /// The id of the job of the daemon, or of the scan in the history for `batch`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) job: Option<u64>,
+    pub(crate) device: &'a str,
+    /// The saved files, sent with absolute paths
+    #[serde(serialize_with = "absolute")]
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) pages: usize,
+    pub(crate) duration_ms: u64,
+    /// Why the scan failed, if it did
+    pub(crate) error: Option<String>,
+}
+
+impl<'a> JobReport<'a> {
+    /// The report of a scan saved as described by `summary`
+    pub(crate) fn saved(
+        command: &'static str,
+        job: Option<u64>,
+        summary: &'a ScanSummary,
+        duration: Duration,
+    ) -> JobReport<'a> {
+        JobReport {
+            command,
+            job,
+            device: &summary.device,
+            files: vec![summary.path.clone()],
+            pages: summary.pages,
+            duration_ms: duration.as_millis() as u64,
+            error: None,
+        }
+    }
+
+    /// The report of a scan that failed with `error`
+    pub(crate) fn failed(
+        command: &'static str,
+        job: Option<u64>,
+        device: &'a str,
+        duration: Duration,
+        error: &miette::Report,
+    ) -> JobReport<'a> {
+        JobReport {
+            command,
+            job,
+            device,
+            files: Vec::new(),
+            pages: 0,
+            duration_ms: duration.as_millis() as u64,
+            error: Some(error_chain(error)),
+        }
+    }
+}
+
+/// POST the report to every configured webhook, warning about the ones that fail
+pub(crate) fn notify(config: &Config, report: &JobReport<'_>) {
+    if config.webhooks.is_empty() {
+        return;
+    }
+
+    let agent = ureq::AgentBuilder::new()
+        .user_agent(concat!("scannrs/", env!("CARGO_PKG_VERSION")))
+        .timeout(TIMEOUT)
+        .build();
+    for webhook in &config.webhooks {
+        let mut request = agent.post(&webhook.url);
+        for (name, value) in &webhook.headers {
+            request = request.set(name, value);
+        }
+        if let Err(error) = request.send_json(report) {
+            events::warning(tr!(
+                "webhook-failed",
+                url = webhook.url.clone(),
+                message = error.to_string()
+            ));
+        }
+    }
+}
+
+fn absolute<S: Serializer>(files: &[PathBuf], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        files
+            .iter()
+            .map(|file| std::path::absolute(file).unwrap_or_else(|_| file.clone())),
+    )
+}
@@ -0,0 +1,1135 @@
+//! The output of the command line interface, run against the mock backend
+//!
+//! Changed output shows up as a failing snapshot, review it with `cargo insta review`.
+
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::process::Output;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use assert_cmd::Command;
+use insta::assert_snapshot;
+use tempfile::TempDir;
+
+/// Run scannrs with the mock backend, keeping its config, history and state inside `home`
+fn scannrs(home: &TempDir) -> Command {
+    let dir = |name: &str| home.path().join(name);
+
+    let mut command = Command::cargo_bin("scannrs").expect("the binary is built for tests");
+    for (name, _) in std::env::vars() {
+        if name.starts_with("SCANNRS_") || name == "LC_ALL" || name == "LC_MESSAGES" {
+            command.env_remove(name);
+        }
+    }
+    command
+        .current_dir(home.path())
+        .env("SCANNRS_BACKEND", "mock")
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", dir("config"))
+        .env("XDG_DATA_HOME", dir("data"))
+        .env("XDG_STATE_HOME", dir("state"))
+        .env("XDG_CACHE_HOME", dir("cache"))
+        .env("XDG_RUNTIME_DIR", dir("runtime"))
+        .env("LANG", "C")
+        .env("NO_COLOR", "1")
+        .env("NO_GRAPHICS", "1");
+    command
+}
+
+fn run(args: &[&str]) -> String {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    stdout(scannrs(&home).args(args).assert().success().get_output())
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).expect("the output is UTF-8")
+}
+
+/// The message of the error a failing command reports, without its causes
+fn error(args: &[&str]) -> String {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(args)
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("Error: "))
+        .expect("the error is reported")
+        .trim()
+        .to_string()
+}
+
+#[test]
+fn list() {
+    assert_snapshot!(run(&["list"]));
+}
+
+#[test]
+fn list_porcelain() {
+    assert_snapshot!(run(&["list", "--porcelain"]));
+}
+
+#[test]
+fn list_json() {
+    assert_snapshot!(run(&["--output", "json", "list"]));
+}
+
+#[test]
+fn list_filtered_out() {
+    assert_snapshot!(run(&["list", "--vendor", "acme"]));
+}
+
+#[test]
+fn options_list() {
+    assert_snapshot!(run(&["options", "mock:0"]));
+}
+
+#[test]
+fn options_describe() {
+    assert_snapshot!(run(&["options", "mock:0", "describe", "resolution"]));
+}
+
+#[test]
+fn options_describe_json() {
+    assert_snapshot!(run(&[
+        "--output", "json", "options", "mock:0", "describe", "br-x"
+    ]));
+}
+
+#[test]
+fn options_show() {
+    assert_snapshot!(run(&["options", "mock:0", "show", "depth"]));
+}
+
+#[test]
+fn options_export() {
+    assert_snapshot!(run(&["options", "mock:0", "export"]));
+}
+
+#[test]
+fn scan_json() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["--output", "json", "scan", "mock:0", "-p", "scan.png"])
+        .args(["-o", "resolution=50"])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    assert_snapshot!(stdout(&output));
+
+    let image = image::open(home.path().join("scan.png")).expect("the scan is a valid PNG");
+    assert_eq!((image.width(), image.height()), (425, 550));
+}
+
+#[test]
+fn scan_text_is_quiet() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["scan", "mock:0", "-p", "scan.png"])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    assert_eq!(stdout(&output), "");
+    assert!(home.path().join("scan.png").exists());
+}
+
+#[test]
+fn scan_json_events() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["--json-events", "scan", "mock:0", "-p", "scan.png"])
+        .args(["-o", "resolution=50"])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+    let events = stderr
+        .lines()
+        .map(|line| {
+            let event: serde_json::Value =
+                serde_json::from_str(line).expect("every line is a JSON event");
+            event["event"]
+                .as_str()
+                .expect("events are named")
+                .to_string()
+        })
+        .filter(|event| event != "progress")
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        events,
+        ["device-opened", "scan-started", "page-saved", "done"]
+    );
+}
+
+#[test]
+fn scan_with_configured_defaults() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::create_dir(home.path().join("scans")).expect("the output directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        r#"
+[defaults]
+device = "mock:0"
+output_dir = "scans"
+
+[devices."mock:0".options]
+resolution = 100
+
+[profiles.draft.options]
+resolution = 50
+"#,
+    )
+    .expect("the configuration can be written");
+
+    scannrs(&home)
+        .args(["--profile", "draft", "scan", "-p", "scan.png"])
+        .assert()
+        .success();
+
+    let image = image::open(home.path().join("scans/scan.png")).expect("the scan is a valid PNG");
+    assert_eq!((image.width(), image.height()), (425, 550));
+}
+
+#[test]
+fn scan_configured_by_the_environment() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    std::fs::create_dir(home.path().join("scans")).expect("the output directory can be created");
+
+    scannrs(&home)
+        .env("SCANNRS_DEVICE", "mock:0")
+        .env("SCANNRS_RESOLUTION", "50")
+        .env("SCANNRS_FORMAT", "png")
+        .env("SCANNRS_OUTPUT_DIR", "scans")
+        .args(["scan", "-p", "scan"])
+        .assert()
+        .success();
+
+    let image = image::ImageReader::open(home.path().join("scans/scan"))
+        .and_then(|reader| reader.with_guessed_format())
+        .expect("the scan can be read")
+        .decode()
+        .expect("the scan is a valid PNG");
+    assert_eq!(image.color(), image::ColorType::Rgb8);
+    assert_eq!((image.width(), image.height()), (425, 550));
+}
+
+#[test]
+fn flags_replace_the_environment() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+
+    scannrs(&home)
+        .env("SCANNRS_DEVICE", "mock:1")
+        .env("SCANNRS_RESOLUTION", "50")
+        .args(["scan", "mock:0", "--resolution", "100", "-p", "scan.png"])
+        .assert()
+        .success();
+
+    let image = image::open(home.path().join("scan.png")).expect("the scan is a valid PNG");
+    assert_eq!((image.width(), image.height()), (850, 1100));
+}
+
+#[test]
+fn invalid_config_points_at_the_key() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        "[defaults]\ndevice = \"mock:0\"\nresolution = 300\n",
+    )
+    .expect("the configuration can be written");
+
+    let output = scannrs(&home)
+        .args(["scan", "-p", "scan.png"])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(stderr.contains("unknown field `resolution`"), "{stderr}");
+    assert!(stderr.contains("resolution = 300"), "the line is shown: {stderr}");
+}
+
+#[test]
+fn paperless_needs_configuration() {
+    assert_eq!(
+        error(&["scan", "mock:0", "-p", "scan.pdf", "--paperless"]),
+        "paperless-ngx is not configured"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn systemd_units_for_the_daemon() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["systemd-unit", "daemon"])
+        .output()
+        .expect("scannrs runs");
+    let service = String::from_utf8(output.stdout).expect("the output is UTF-8");
+    assert!(service.contains("Type=notify\n"), "{service}");
+    assert!(service.contains("scannrs daemon\n"), "{service}");
+
+    let output = scannrs(&home)
+        .args(["systemd-unit", "daemon", "--socket"])
+        .output()
+        .expect("scannrs runs");
+    let socket = String::from_utf8(output.stdout).expect("the output is UTF-8");
+    assert!(socket.contains("ListenStream=%t/scannrs.sock\n"), "{socket}");
+}
+
+#[test]
+#[cfg(not(feature = "notifications"))]
+fn batch_notifications_need_the_feature() {
+    assert_eq!(
+        error(&[
+            "batch",
+            "mock:0",
+            "-p",
+            "page-{n}.png",
+            "--pages",
+            "1",
+            "--notify"
+        ]),
+        "This build of scannrs cannot show desktop notifications"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "grpc"))]
+fn serve_grpc_needs_the_feature() {
+    assert_eq!(
+        error(&["serve", "--listen", "127.0.0.1:0", "--grpc", "127.0.0.1:0"]),
+        "This build of scannrs cannot serve gRPC"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "tesseract"))]
+fn ocr_library_needs_the_feature() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let image = home.path().join("page.png");
+    std::fs::write(&image, b"not looked at").expect("the image can be written");
+    let output = scannrs(&home)
+        .args(["ocr", "--engine", "library", "--lang", "deu+eng"])
+        .arg(&image)
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+    assert!(
+        stderr.contains("This build cannot run tesseract as a library"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn ocr_tells_how_to_install_tesseract() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let image = home.path().join("page.png");
+    std::fs::write(&image, b"not looked at").expect("the image can be written");
+    let output = scannrs(&home)
+        .env("PATH", home.path())
+        .args(["ocr", "--engine", "executable"])
+        .arg(&image)
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+    assert!(stderr.contains("help: Install tesseract"), "{stderr}");
+}
+
+#[test]
+fn ocr_profile_sends_to_a_remote_service() {
+    let (url, requests) = serve_http(|_| "Invoice 42");
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!(
+            "[profiles.fast.ocr]\nlang = \"deu+eng\"\nengine = \"remote\"\nremote = {{ url = \"{url}/ocr\", token = \"secret\" }}\n"
+        ),
+    )
+    .expect("the configuration can be written");
+    let image = home.path().join("page.png");
+    std::fs::write(&image, b"an image").expect("the image can be written");
+
+    let assert = scannrs(&home)
+        .args(["--profile", "fast", "ocr"])
+        .arg(&image)
+        .assert();
+    if cfg!(feature = "remote-ocr") {
+        assert.success().stdout("Invoice 42");
+        let requests = requests.lock().expect("the lock is not poisoned");
+        let [request] = requests.as_slice() else {
+            panic!("the service is called once: {requests:?}");
+        };
+        assert!(
+            request.starts_with("POST /ocr?lang=deu%2Beng&format=txt "),
+            "{request}"
+        );
+        assert!(
+            request.contains("Authorization: Bearer secret"),
+            "{request}"
+        );
+        assert!(request.ends_with("an image"), "{request}");
+    } else {
+        let stderr = String::from_utf8(assert.failure().get_output().stderr.clone())
+            .expect("the output is UTF-8");
+        assert!(
+            stderr.contains("cannot send images to a remote OCR service"),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn scanners_are_listed_from_the_cache() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let list = |refresh: bool| {
+        let mut command = scannrs(&home);
+        command.args(["--output", "json", "list"]);
+        if refresh {
+            command.arg("--refresh");
+        }
+        stdout(command.assert().success().get_output())
+    };
+    assert!(list(false).contains("mock:0"));
+
+    let path = home.path().join("cache/scannrs/devices.json");
+    let cached = std::fs::read_to_string(&path).expect("the list of scanners is cached");
+    std::fs::write(&path, cached.replace("mock:0", "cached:0")).expect("the cache can be written");
+    assert!(list(false).contains("cached:0"));
+    assert!(!list(true).contains("cached:0"));
+}
+
+#[test]
+fn scan_is_shared_with_a_link() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args([
+            "scan", "mock:0", "-r", "50", "-p", "scan.png", "--share", "0",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+    assert!(stderr.contains("Sharing the scan at http://"), "{stderr}");
+    assert!(
+        stderr.contains('█'),
+        "the link is shown as a QR code: {stderr}"
+    );
+}
+
+#[test]
+fn copy_prints_over_ipp() {
+    // Version 2.0, status successful-ok, request 1, no attributes
+    let (url, requests) = serve_http(|_| "\u{2}\0\0\0\0\0\0\u{1}\u{3}");
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let printer = format!("{}/printers/office", url.replace("http://", "ipp://"));
+
+    scannrs(&home)
+        .args(["copy", "mock:0", "--printer", &printer, "-n", "2"])
+        .assert()
+        .success();
+
+    let requests = requests.lock().expect("the lock is not poisoned");
+    let [request] = requests.as_slice() else {
+        panic!("the printer is called once: {requests:?}");
+    };
+    assert!(request.starts_with("POST /printers/office "), "{request}");
+    assert!(
+        request.contains("Content-Type: application/ipp"),
+        "{request}"
+    );
+    assert!(request.contains(&printer), "{request}");
+    assert!(request.contains("application/pdf"), "{request}");
+    assert!(request.contains("%PDF"), "{request}");
+}
+
+#[test]
+fn scans_are_sorted_by_their_text() {
+    let (url, _) = serve_http(|_| "Invoice from ACME/Corp\n");
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!(
+            r#"
+[ocr]
+engine = "remote"
+remote = {{ url = "{url}" }}
+
+[[rules]]
+pattern = "Receipt"
+output = "receipts/{{name}}"
+
+[[rules]]
+pattern = "Invoice from (?P<vendor>\\S+)"
+output = "invoices/{{vendor}}.png"
+tags = ["invoice"]
+"#
+        ),
+    )
+    .expect("the configuration can be written");
+
+    let output = scannrs(&home)
+        .args([
+            "--output", "json", "scan", "mock:0", "-r", "50", "-p", "scan.png",
+        ])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    let stdout = String::from_utf8(output.stdout).expect("the output is UTF-8");
+
+    if cfg!(feature = "remote-ocr") {
+        assert!(home.path().join("invoices/ACME_Corp.png").exists());
+        assert!(!home.path().join("scan.png").exists());
+        assert!(stdout.contains(r#""invoice""#), "{stdout}");
+    } else {
+        // Without the text the scan is saved where it would have been without rules
+        assert!(home.path().join("scan.png").exists());
+        let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+        assert!(
+            stderr.contains("Could not sort the scan by its text"),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn doc_date_is_found_in_the_text() {
+    let (url, _) = serve_http(|_| "ACME GmbH\nBerlin, den 15. März 2024\n\nRechnung 12/2024\n");
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!("[ocr]\nengine = \"remote\"\nremote = {{ url = \"{url}\" }}\n"),
+    )
+    .expect("the configuration can be written");
+
+    scannrs(&home)
+        .args(["scan", "mock:0", "-r", "50", "-p", "letter-{doc_date}.png"])
+        .assert()
+        .success();
+
+    let names = std::fs::read_dir(home.path())
+        .expect("the directory can be read")
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with("letter-"))
+        .collect::<Vec<_>>();
+    if cfg!(feature = "remote-ocr") {
+        assert_eq!(names, ["letter-2024-03-15.png"]);
+    } else {
+        // Without the text the date of the scan is used
+        assert_eq!(names.len(), 1, "{names:?}");
+        assert!(!names[0].contains("{doc_date}"), "{names:?}");
+    }
+}
+
+#[test]
+fn mqtt_login_needs_configuration() {
+    assert_eq!(error(&["login", "mqtt"]), "MQTT is not configured");
+}
+
+/// Serve HTTP with a body picked by `respond` for every request, returning the address and the requests it got
+fn serve_http(respond: fn(&str) -> &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a local port is free");
+    let url = format!(
+        "http://{}",
+        listener.local_addr().expect("the port is bound")
+    );
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let received = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().expect("the stream can be cloned"));
+
+            let mut request = String::new();
+            let mut length = 0;
+            reader.read_line(&mut request).expect("the request is read");
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).expect("the headers are read");
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        length = value.trim().parse().expect("the length is a number");
+                    }
+                }
+                request.push_str(&header);
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).expect("the body is read");
+            request.push_str(&String::from_utf8_lossy(&body));
+
+            let response = respond(&request);
+            received
+                .lock()
+                .expect("the lock is not poisoned")
+                .push(request);
+
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{response}",
+                response.len()
+            );
+        }
+    });
+
+    (url, requests)
+}
+
+#[test]
+fn scan_uploads_to_paperless() {
+    let (url, requests) = serve_http(|request| {
+        if request.starts_with("GET /api/tags/?name__iexact=inbox ") {
+            r#"{"count":1,"results":[{"id":7,"name":"Inbox"}]}"#
+        } else if request.starts_with("POST /api/documents/post_document/ ") {
+            r#""a1b2c3""#
+        } else {
+            r#"{"count":0,"results":[]}"#
+        }
+    });
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!("[paperless]\nurl = \"{url}\"\ntoken = \"secret\"\ntags = [\"inbox\"]\n"),
+    )
+    .expect("the configuration can be written");
+
+    let output = scannrs(&home)
+        .args(["scan", "mock:0", "-r", "50", "-p", "scan.pdf"])
+        .args(["--paperless", "--title", "Invoice", "--tag", "3"])
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+    assert!(stderr.contains("task a1b2c3"), "{stderr}");
+
+    let requests = requests.lock().expect("the lock is not poisoned");
+    let upload = requests
+        .iter()
+        .find(|request| request.starts_with("POST"))
+        .expect("the scan is uploaded");
+    assert!(upload.contains("Authorization: Token secret"), "{upload}");
+    assert!(
+        upload.contains("name=\"title\"\r\n\r\nInvoice\r\n"),
+        "{upload}"
+    );
+    assert!(upload.contains("name=\"tags\"\r\n\r\n7\r\n"), "{upload}");
+    assert!(upload.contains("name=\"tags\"\r\n\r\n3\r\n"), "{upload}");
+    assert!(upload.contains("filename=\"scan.pdf\""), "{upload}");
+    assert!(upload.contains("%PDF"), "{upload}");
+}
+
+#[test]
+fn scan_uploads_to_webdav() {
+    let (url, requests) = serve_http(|_| "");
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!(
+            "[webdav]\nurl = \"{url}/dav/\"\nuser = \"alice\"\npassword = \"secret\"\npath = \"Scans/{{device}}/{{name}}\"\n"
+        ),
+    )
+    .expect("the configuration can be written");
+
+    scannrs(&home)
+        .args(["scan", "mock:0", "-r", "50", "-p", "scan.png", "--webdav"])
+        .assert()
+        .success();
+
+    let requests = requests.lock().expect("the lock is not poisoned");
+    let lines = requests
+        .iter()
+        .filter_map(|request| request.lines().next())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        [
+            "MKCOL /dav/Scans/ HTTP/1.1",
+            "MKCOL /dav/Scans/mock_0/ HTTP/1.1",
+            "PUT /dav/Scans/mock_0/scan.png HTTP/1.1",
+        ]
+    );
+    // alice:secret
+    assert!(
+        requests[2].contains("Authorization: Basic YWxpY2U6c2VjcmV0"),
+        "{}",
+        requests[2]
+    );
+}
+
+#[test]
+fn scan_is_reported_to_webhooks() {
+    let (url, requests) = serve_http(|_| "");
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!("[[webhooks]]\nurl = \"{url}/hook\"\nheaders = {{ X-Token = \"secret\" }}\n"),
+    )
+    .expect("the configuration can be written");
+
+    scannrs(&home)
+        .args(["scan", "mock:0", "-r", "50", "-p", "scan.png"])
+        .assert()
+        .success();
+
+    let requests = requests.lock().expect("the lock is not poisoned");
+    let [hook] = requests.as_slice() else {
+        panic!("the webhook is called once: {requests:?}");
+    };
+    assert!(hook.starts_with("POST /hook "), "{hook}");
+    assert!(hook.contains("X-Token: secret"), "{hook}");
+    assert!(hook.contains(r#""command":"scan""#), "{hook}");
+    assert!(hook.contains(r#""device":"mock:0""#), "{hook}");
+    assert!(hook.contains(r#""error":null"#), "{hook}");
+}
+
+#[test]
+fn scan_is_sent_to_the_destinations_of_the_profile() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        r#"
+[[profiles.archive.destinations]]
+type = "file"
+path = "copies/{device}/{name}"
+
+[[profiles.archive.destinations]]
+type = "stdout"
+"#,
+    )
+    .expect("the configuration can be written");
+
+    let output = scannrs(&home)
+        .args([
+            "--profile",
+            "archive",
+            "scan",
+            "mock:0",
+            "-r",
+            "50",
+            "-p",
+            "scan.png",
+        ])
+        .output()
+        .expect("scannrs runs");
+    assert!(output.status.success(), "{output:?}");
+
+    let scan = std::fs::read(home.path().join("scan.png")).expect("the scan is saved");
+    let copy =
+        std::fs::read(home.path().join("copies/mock_0/scan.png")).expect("the scan is copied");
+    assert_eq!(copy, scan);
+    assert_eq!(output.stdout, scan);
+}
+
+#[test]
+fn failed_destinations_do_not_stop_the_others() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        r#"
+[[profiles.archive.destinations]]
+type = "file"
+path = "scan.png/copy.png"
+
+[[profiles.archive.destinations]]
+type = "file"
+path = "copy.png"
+"#,
+    )
+    .expect("the configuration can be written");
+
+    let output = scannrs(&home)
+        .args([
+            "--profile",
+            "archive",
+            "scan",
+            "mock:0",
+            "-r",
+            "50",
+            "-p",
+            "scan.png",
+        ])
+        .output()
+        .expect("scannrs runs");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+    assert!(
+        stderr.contains("Sending the scan to 1 of 2 destinations failed"),
+        "{stderr}"
+    );
+
+    assert!(home.path().join("scan.png").is_file());
+    assert!(home.path().join("copy.png").is_file());
+}
+
+/// Accept a single email over SMTP, returning the port and the received message once it arrived
+fn serve_smtp() -> (u16, std::thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("a local port is free");
+    let port = listener.local_addr().expect("the port is bound").port();
+
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().expect("the client connects");
+        let mut reader = BufReader::new(stream.try_clone().expect("the stream can be cloned"));
+        let mut received = String::new();
+        write!(stream, "220 localhost ESMTP\r\n").expect("the greeting is sent");
+
+        let mut data = false;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).expect("the line is read") == 0 {
+                break;
+            }
+            received.push_str(&line);
+
+            let reply = match line.trim_end() {
+                "." if data => {
+                    data = false;
+                    "250 queued"
+                }
+                _ if data => continue,
+                "DATA" => {
+                    data = true;
+                    "354 go ahead"
+                }
+                "QUIT" => {
+                    let _ = write!(stream, "221 bye\r\n");
+                    break;
+                }
+                _ => "250 ok",
+            };
+            write!(stream, "{reply}\r\n").expect("the reply is sent");
+        }
+        received
+    });
+
+    (port, server)
+}
+
+#[test]
+fn scan_is_emailed() {
+    let (port, server) = serve_smtp();
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let config = home.path().join("config/scannrs");
+    std::fs::create_dir_all(&config).expect("the config directory can be created");
+    std::fs::write(
+        config.join("config.toml"),
+        format!(
+            "[email]\nserver = \"127.0.0.1\"\nport = {port}\nsecurity = \"none\"\nfrom = \"scanner@example.com\"\nsubject = \"Scan from {{device}}\"\n"
+        ),
+    )
+    .expect("the configuration can be written");
+
+    scannrs(&home)
+        .args(["scan", "mock:0", "-r", "50", "-p", "scan.png"])
+        .args(["--email", "alice@example.com"])
+        .assert()
+        .success();
+
+    let received = server.join().expect("the server does not panic");
+    assert!(
+        received.contains("RCPT TO:<alice@example.com>"),
+        "{received}"
+    );
+    assert!(received.contains("Subject: Scan from mock_0"), "{received}");
+    assert!(
+        received.contains("Content-Disposition: attachment; filename=\"scan.png\""),
+        "{received}"
+    );
+}
+
+#[test]
+fn unknown_profile() {
+    assert_snapshot!(
+        error(&["--profile", "photo", "scan", "mock:0", "-p", "scan.png"]),
+        @"There is no profile named 'photo'"
+    );
+}
+
+#[test]
+fn unknown_scanner() {
+    assert_snapshot!(error(&["options", "mock:1"]), @"Could not find scanner with name: 'mock:1'");
+}
+
+#[test]
+fn damaged_calibration_leaves_the_scan_as_it_is() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    scannrs(&home)
+        .args(["scan", "mock:0", "-p", "plain.png", "-o", "resolution=50"])
+        .assert()
+        .success();
+
+    let calibration = home.path().join("state/scannrs/calibration");
+    std::fs::create_dir_all(&calibration).expect("the calibration directory can be created");
+    std::fs::write(
+        calibration.join("mock_0.json"),
+        r#"{"width":0,"channels":3,"gains":[]}"#,
+    )
+    .expect("the calibration can be written");
+    scannrs(&home)
+        .args([
+            "scan",
+            "mock:0",
+            "-p",
+            "calibrated.png",
+            "-o",
+            "resolution=50",
+        ])
+        .assert()
+        .success();
+
+    let plain = image::open(home.path().join("plain.png")).expect("the scan is a valid PNG");
+    let calibrated =
+        image::open(home.path().join("calibrated.png")).expect("the scan is a valid PNG");
+    assert_eq!(plain, calibrated);
+}
+
+#[test]
+fn selftest_passes_on_the_mock_scanner() {
+    let output = run(&["selftest", "--device", "mock:0"]);
+    let mut lines = output.lines();
+
+    assert_eq!(lines.next_back(), Some("All 7 self-tests passed"));
+    assert!(lines.all(|line| line.starts_with("ok ")), "{output}");
+}
+
+#[test]
+fn selftest_fails_without_the_scanner() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["selftest", "--device", "mock:1"])
+        .assert()
+        .code(1)
+        .get_output()
+        .clone();
+    let output = stdout(&output);
+
+    assert_eq!(
+        output
+            .lines()
+            .filter(|line| line.starts_with("FAILED "))
+            .count(),
+        7,
+        "{output}"
+    );
+    assert_snapshot!(
+        error(&["selftest", "--device", "mock:1"]),
+        @"7 of 7 self-tests failed"
+    );
+}
+
+#[test]
+fn errors_show_their_help() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["options", "mock:1"])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(
+        stderr.contains("help: Check that the scanner is switched on"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn errors_follow_the_locale() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .env("LANG", "de_DE.UTF-8")
+        .args(["options", "mock:1"])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(
+        stderr.contains("Der Scanner 'mock:1' wurde nicht gefunden"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Could not find scanner with name: 'mock:1'"),
+        "the original error is kept as the cause: {stderr}"
+    );
+}
+
+#[test]
+fn unknown_option() {
+    assert_snapshot!(
+        error(&["options", "mock:0", "show", "gamma"]),
+        @"The given option 'gamma' does not exist for scanner 'mock:0'"
+    );
+}
+
+#[test]
+fn scan_with_unknown_option() {
+    assert_snapshot!(
+        error(&["scan", "mock:0", "-p", "scan.png", "-o", "gamma=2"]),
+        @"The given option 'gamma' does not exist for scanner 'mock:0'"
+    );
+}
+
+#[test]
+fn scan_with_invalid_value() {
+    assert_snapshot!(
+        error(&["scan", "mock:0", "-p", "scan.png", "-o", "hand-scanner=maybe"]),
+        @"The value 'maybe' given for 'hand-scanner' is not a boolean, use one of true/false, yes/no, on/off or 1/0"
+    );
+}
+
+#[test]
+fn the_tui_needs_a_terminal() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .arg("tui")
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(
+        stderr.contains("help: Use `scannrs scan` or `scannrs batch` in scripts"),
+        "{stderr}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn queueing_needs_the_daemon() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args(["queue", "list"])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(
+        stderr.contains("help: Start the daemon with `scannrs daemon`"),
+        "{stderr}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn delays_too_long_to_represent_are_invalid() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let output = scannrs(&home)
+        .args([
+            "queue",
+            "add",
+            "mock:0",
+            "scan.png",
+            "--delay",
+            "99999999999999999d",
+        ])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(stderr.contains("The duration is not valid"), "{stderr}");
+}
+
+#[test]
+fn batches_do_not_replace_an_interrupted_one() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let state = home.path().join("state/scannrs");
+    std::fs::create_dir_all(&state).expect("the state directory can be created");
+    std::fs::write(
+        state.join("batch.json"),
+        r#"{"device":"mock:0","options":{},"settings":null,"template":"page-{n}.png","format":"png","start":1,"counter":2,"limit":null,"started_at":"2024-01-01T00:00:00Z","pages":[],"outputs":[]}"#,
+    )
+    .expect("the batch state can be written");
+
+    let output = scannrs(&home)
+        .args(["batch", "mock:0", "-p", "page-{n}.png", "--pages", "1"])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).expect("the output is UTF-8");
+
+    assert!(
+        stderr.contains("help: Continue it with `scannrs batch --resume`"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn history_ids_follow_the_last_entry() {
+    let home = TempDir::new().expect("a temporary directory can be created");
+    let data = home.path().join("data/scannrs");
+    std::fs::create_dir_all(&data).expect("the data directory can be created");
+    // Longer than what is read from the end of the file at once
+    let device = "x".repeat(5000);
+    let last = format!(
+        r#"{{"id":41,"finished_at":"2024-01-01T00:00:00Z","device":"{device}","outputs":[],"format":"png","pages":1,"duration_ms":1}}"#
+    );
+    std::fs::write(
+        data.join("history.jsonl"),
+        format!("an entry of an older version\n{last}\n"),
+    )
+    .expect("the history can be written");
+
+    scannrs(&home)
+        .args(["scan", "mock:0", "-r", "50", "-p", "scan.png"])
+        .assert()
+        .success();
+
+    let history =
+        std::fs::read_to_string(data.join("history.jsonl")).expect("the history can be read");
+    let recorded = history.lines().last().expect("the scan is recorded");
+    assert!(recorded.starts_with(r#"{"id":42,"#), "{recorded}");
+}